@@ -11,3 +11,17 @@ CREATE TABLE compliance (
     duration float not null,
     uploaded datetime not null
 );
+
+-- reports older than the retention period, rolled up per branch and day
+DROP TABLE IF EXISTS compliance_daily;
+CREATE TABLE compliance_daily (
+    branch string not null,
+    day date not null,
+    reports integer not null,
+    errors integer not null,
+    failed integer not null,
+    skipped integer not null,
+    passed integer not null,
+    duration float not null,
+    primary key (branch, day)
+);
@@ -1,10 +1,25 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use eyre::WrapErr;
 use futures::TryStreamExt;
+use serde::Serialize;
 use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
 
 use crate::ComplianceReport;
 
+/// Totals of the reports submitted for a branch on one day
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyAggregate {
+    day: NaiveDate,
+    reports: i64,
+    errors: i64,
+    failed: i64,
+    skipped: i64,
+    passed: i64,
+    /// mean duration of the test runs
+    duration: f64,
+}
+
 #[derive(Clone)]
 pub struct Database {
     conn: SqlitePool,
@@ -48,8 +63,9 @@ impl Database {
         &self,
         branch: &str,
     ) -> eyre::Result<(Vec<DateTime<Utc>>, Vec<f64>)> {
-        let mut stream = sqlx::query("SELECT uploaded, passed * 100.0 / (passed + errors + failed + skipped) FROM compliance WHERE branch = $1")
-            .bind(&branch)
+        // rolled up days are plotted at midnight
+        let mut stream = sqlx::query("SELECT datetime(day) AS uploaded, passed * 100.0 / (passed + errors + failed + skipped) FROM compliance_daily WHERE branch = $1 UNION ALL SELECT uploaded, passed * 100.0 / (passed + errors + failed + skipped) FROM compliance WHERE branch = $1 ORDER BY uploaded")
+            .bind(branch)
             .map(|row: SqliteRow| (row.get(0), row.get(1)))
             .fetch(&self.conn);
 
@@ -63,4 +79,45 @@ impl Database {
 
         Ok((x, y))
     }
+
+    pub(crate) async fn fetch_daily_aggregates(
+        &self,
+        branch: &str,
+    ) -> eyre::Result<Vec<DailyAggregate>> {
+        let rows = sqlx::query("SELECT day, reports, errors, failed, skipped, passed, duration / reports FROM compliance_daily WHERE branch = $1 ORDER BY day")
+            .bind(branch)
+            .map(|row: SqliteRow| DailyAggregate {
+                day: row.get(0),
+                reports: row.get(1),
+                errors: row.get(2),
+                failed: row.get(3),
+                skipped: row.get(4),
+                passed: row.get(5),
+                duration: row.get(6),
+            })
+            .fetch_all(&self.conn)
+            .await
+            .wrap_err("fetching daily aggregates")?;
+        Ok(rows)
+    }
+
+    /// Roll up the reports uploaded before `cutoff` into daily aggregates, and delete them
+    ///
+    /// Returns the number of reports rolled up.
+    pub(crate) async fn roll_up(&self, cutoff: DateTime<Utc>) -> eyre::Result<u64> {
+        let mut tx = self.conn.begin().await.wrap_err("starting transaction")?;
+        sqlx::query("INSERT INTO compliance_daily (branch, day, reports, errors, failed, skipped, passed, duration) SELECT branch, date(uploaded), count(*), sum(errors), sum(failed), sum(skipped), sum(passed), sum(duration) FROM compliance WHERE uploaded < $1 GROUP BY branch, date(uploaded) ON CONFLICT (branch, day) DO UPDATE SET reports = reports + excluded.reports, errors = errors + excluded.errors, failed = failed + excluded.failed, skipped = skipped + excluded.skipped, passed = passed + excluded.passed, duration = duration + excluded.duration")
+            .bind(cutoff)
+            .execute(&mut tx)
+            .await
+            .wrap_err("aggregating reports")?;
+        let deleted = sqlx::query("DELETE FROM compliance WHERE uploaded < $1")
+            .bind(cutoff)
+            .execute(&mut tx)
+            .await
+            .wrap_err("deleting aggregated reports")?
+            .rows_affected();
+        tx.commit().await.wrap_err("committing roll up")?;
+        Ok(deleted)
+    }
 }
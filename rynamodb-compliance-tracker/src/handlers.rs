@@ -71,6 +71,21 @@ pub(crate) async fn branch(
     .into_response()
 }
 
+// GET /branches/:branch/daily
+
+pub(crate) async fn daily_aggregates(
+    Path(branch): Path<String>,
+    State(crate::AppState { db, .. }): State<crate::AppState>,
+) -> impl IntoResponse {
+    match db.fetch_daily_aggregates(&branch).await {
+        Ok(aggregates) => Json(aggregates).into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "error fetching daily aggregates");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
 // POST /submit
 pub(crate) async fn submit_compliance_report(
     State(crate::AppState { db, auth_token }): State<crate::AppState>,
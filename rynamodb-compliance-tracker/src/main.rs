@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Duration};
 
 use axum::{
     routing::{get, post},
@@ -21,6 +21,14 @@ struct Args {
 
     #[clap(short, long, default_value = "9050")]
     port: u16,
+
+    /// Reports older than this many days are rolled up into daily aggregates
+    #[clap(long, default_value = "30")]
+    retention_days: i64,
+
+    /// Time in seconds between roll ups of old reports
+    #[clap(long, default_value = "3600")]
+    roll_up_interval_secs: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -54,6 +62,11 @@ async fn main() {
         .await
         .expect("could not connect to database");
     let db = Database::new(conn);
+    spawn_roll_up(
+        db.clone(),
+        chrono::Duration::days(args.retention_days),
+        Duration::from_secs(args.roll_up_interval_secs),
+    );
     let state = AppState {
         db,
         auth_token: std::env::var("RYNAMODB_AUTH_TOKEN").expect("no auth token specified"),
@@ -62,6 +75,7 @@ async fn main() {
     let app = Router::new()
         .route("/", get(handlers::index))
         .route("/branches/:branch", get(handlers::branch))
+        .route("/branches/:branch/daily", get(handlers::daily_aggregates))
         .route("/submit", post(handlers::submit_compliance_report))
         .with_state(state);
 
@@ -72,3 +86,18 @@ async fn main() {
         .await
         .expect("running server");
 }
+
+// periodically roll up reports older than the retention period, so that the database does not
+// grow with every submitted report
+fn spawn_roll_up(db: Database, retention: chrono::Duration, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match db.roll_up(Utc::now() - retention).await {
+                Ok(count) => tracing::info!(%count, "rolled up old reports"),
+                Err(e) => tracing::warn!(error = %e, "error rolling up old reports"),
+            }
+        }
+    });
+}
@@ -0,0 +1,9 @@
+//! Key condition expressions, with the placeholders used in the tests defined
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|expression: &str| {
+    let (names, values) = rynamodb::fuzzing::default_placeholders();
+    rynamodb::fuzzing::query(expression, Some(names), Some(values));
+});
@@ -0,0 +1,51 @@
+//! Key condition expressions with arbitrary expression attribute names and values
+#![no_main]
+
+use std::collections::HashMap;
+
+use libfuzzer_sys::{arbitrary::Arbitrary, fuzz_target};
+use serde_dynamo::AttributeValue;
+
+#[derive(Arbitrary, Debug)]
+enum Value {
+    S(String),
+    N(String),
+    B(Vec<u8>),
+    Bool(bool),
+    Null,
+    Ss(Vec<String>),
+    L(Vec<String>),
+}
+
+impl From<Value> for AttributeValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::S(s) => AttributeValue::S(s),
+            Value::N(n) => AttributeValue::N(n),
+            Value::B(b) => AttributeValue::B(b),
+            Value::Bool(b) => AttributeValue::Bool(b),
+            Value::Null => AttributeValue::Null(true),
+            Value::Ss(values) => AttributeValue::Ss(values),
+            Value::L(values) => {
+                AttributeValue::L(values.into_iter().map(AttributeValue::S).collect())
+            }
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    expression: String,
+    names: Option<HashMap<String, String>>,
+    values: Option<HashMap<String, Value>>,
+}
+
+fuzz_target!(|input: Input| {
+    let values = input.values.map(|values| {
+        values
+            .into_iter()
+            .map(|(placeholder, value)| (placeholder, value.into()))
+            .collect()
+    });
+    rynamodb::fuzzing::query(&input.expression, input.names, values);
+});
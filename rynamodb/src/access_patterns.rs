@@ -0,0 +1,223 @@
+//! Report of the attributes which Query and Scan operations read, compared with those they return
+//!
+//! DynamoDB charges reads for the full size of every item examined, regardless of how much of it
+//! is returned. Comparing the two shows how many read capacity units could be saved by keeping
+//! the attributes which are actually used in a narrower index, projecting only those attributes.
+//! Recording is disabled by default, as it needs the size of every item read.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use serde::Serialize;
+use serde_dynamo::AttributeValue;
+
+use crate::table::{names::AttributeName, size};
+
+/// size of a read capacity unit for a strongly consistent read
+const READ_UNIT_BYTES: u64 = 4096;
+
+type Item<K = String> = HashMap<K, AttributeValue>;
+
+/// Number and total size of one side of an operation, either the items read or returned
+#[derive(Debug, Default)]
+pub struct Measurement {
+    items: u64,
+    bytes: u64,
+    attributes: HashMap<String, u64>,
+}
+
+impl Measurement {
+    fn new<'a, K: AttributeName + 'a>(items: impl IntoIterator<Item = &'a Item<K>>) -> Self {
+        let mut measurement = Self::default();
+        for item in items {
+            measurement.items += 1;
+            for (name, value) in item {
+                let name = name.borrow();
+                let bytes = size::attribute_size(name, value) as u64;
+                measurement.bytes += bytes;
+                *measurement.attributes.entry(name.to_string()).or_default() += bytes;
+            }
+        }
+        measurement
+    }
+
+    /// Read capacity units consumed by an operation reading this much, which are rounded up per
+    /// operation rather than per item
+    fn read_units(&self) -> u64 {
+        self.bytes.div_ceil(READ_UNIT_BYTES)
+    }
+}
+
+#[derive(Debug, Default)]
+struct TableAccess {
+    operations: u64,
+    items_read: u64,
+    items_returned: u64,
+    bytes_read: u64,
+    bytes_returned: u64,
+    read_units: u64,
+    returned_read_units: u64,
+    attributes: HashMap<String, AttributeAccess>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeAccess {
+    pub bytes_read: u64,
+    pub bytes_returned: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableAccessReport {
+    pub operations: u64,
+    pub items_read: u64,
+    pub items_returned: u64,
+    pub bytes_read: u64,
+    pub bytes_returned: u64,
+    /// read capacity units consumed, assuming strongly consistent reads
+    pub read_capacity_units: u64,
+    /// read capacity units which would have been consumed if only the returned items and
+    /// attributes had been read
+    pub returned_read_capacity_units: u64,
+    pub estimated_read_capacity_unit_savings: u64,
+    /// attributes which were read but never returned
+    pub unused_attributes: Vec<String>,
+    pub attributes: BTreeMap<String, AttributeAccess>,
+}
+
+#[derive(Debug, Default)]
+pub struct AccessPatterns {
+    enabled: bool,
+    tables: Mutex<HashMap<String, TableAccess>>,
+}
+
+impl AccessPatterns {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    /// Measure the items read by an operation, before they are filtered or projected. Returns
+    /// `None` when recording is disabled, so that items are not measured needlessly.
+    pub fn measure<'a, K: AttributeName + 'a>(
+        &self,
+        items: impl IntoIterator<Item = &'a Item<K>>,
+    ) -> Option<Measurement> {
+        self.enabled.then(|| Measurement::new(items))
+    }
+
+    /// Record an operation which read the `read` items and returned `returned`
+    pub fn record(&self, table_name: &str, read: Option<Measurement>, returned: &[Item]) {
+        let Some(read) = read else {
+            return;
+        };
+        let returned = Measurement::new(returned);
+
+        let mut tables = self.tables.lock().unwrap();
+        let access = tables.entry(table_name.to_string()).or_default();
+        access.operations += 1;
+        access.items_read += read.items;
+        access.items_returned += returned.items;
+        access.bytes_read += read.bytes;
+        access.bytes_returned += returned.bytes;
+        access.read_units += read.read_units();
+        access.returned_read_units += returned.read_units();
+        for (name, bytes) in read.attributes {
+            access.attributes.entry(name).or_default().bytes_read += bytes;
+        }
+        for (name, bytes) in returned.attributes {
+            access.attributes.entry(name).or_default().bytes_returned += bytes;
+        }
+    }
+
+    pub fn report(&self) -> BTreeMap<String, TableAccessReport> {
+        let tables = self.tables.lock().unwrap();
+        tables
+            .iter()
+            .map(|(table_name, access)| {
+                let attributes: BTreeMap<_, _> = access
+                    .attributes
+                    .iter()
+                    .map(|(name, attribute)| (name.clone(), *attribute))
+                    .collect();
+                let report = TableAccessReport {
+                    operations: access.operations,
+                    items_read: access.items_read,
+                    items_returned: access.items_returned,
+                    bytes_read: access.bytes_read,
+                    bytes_returned: access.bytes_returned,
+                    read_capacity_units: access.read_units,
+                    returned_read_capacity_units: access.returned_read_units,
+                    estimated_read_capacity_unit_savings: access
+                        .read_units
+                        .saturating_sub(access.returned_read_units),
+                    unused_attributes: attributes
+                        .iter()
+                        .filter(|(_, attribute)| attribute.bytes_returned == 0)
+                        .map(|(name, _)| name.clone())
+                        .collect(),
+                    attributes,
+                };
+                (table_name.clone(), report)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(payload_size: usize) -> Item {
+        [
+            ("pk".to_string(), AttributeValue::S("abc".to_string())),
+            (
+                "payload".to_string(),
+                AttributeValue::S("x".repeat(payload_size)),
+            ),
+        ]
+        .into()
+    }
+
+    fn project(item: &Item) -> Item {
+        item.iter()
+            .filter(|(name, _)| *name == "pk")
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn disabled() {
+        let access = AccessPatterns::new(false);
+        let items = vec![item(10)];
+        access.record("a", access.measure(&items), &items);
+        assert!(access.report().is_empty());
+    }
+
+    #[test]
+    fn savings() {
+        let access = AccessPatterns::new(true);
+        let items = vec![item(5000), item(5000)];
+        let read = access.measure(&items);
+        let returned: Vec<_> = items.iter().map(project).collect();
+        access.record("a", read, &returned);
+
+        let report = access.report();
+        let table = &report["a"];
+        assert_eq!(table.operations, 1);
+        assert_eq!(table.items_read, 2);
+        assert_eq!(table.items_returned, 2);
+        assert_eq!(table.bytes_read, 2 * (5 + 7 + 5000));
+        assert_eq!(table.bytes_returned, 2 * 5);
+        assert_eq!(table.read_capacity_units, 3);
+        assert_eq!(table.returned_read_capacity_units, 1);
+        assert_eq!(table.estimated_read_capacity_unit_savings, 2);
+        assert_eq!(table.unused_attributes, vec!["payload".to_string()]);
+        assert_eq!(table.attributes["pk"].bytes_returned, 10);
+    }
+}
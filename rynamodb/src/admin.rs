@@ -0,0 +1,569 @@
+//! Endpoints for inspecting and manipulating the state of the emulator, served under
+//! `/_rynamodb`
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_dynamo::AttributeValue;
+
+use crate::{
+    access_patterns::TableAccessReport,
+    connections::ConnectionsReport,
+    dax::DaxReport,
+    error_log::ErrorSummary,
+    errors::ErrorResponse,
+    extractors::{AwsJson, Namespace},
+    hot_keys::HotKeysReport,
+    operations::OperationsReport,
+    scheduler::JobReport,
+    settings::{CurrentSettings, SettingsUpdate},
+    slow_log::SlowOperation,
+    table::{cost::CostEstimate, key::Key, schema::TableSchema, stats::AttributeStatistics, Table},
+    types,
+    unimplemented::UnimplementedReport,
+    AppState,
+};
+
+/// number of items sampled for attribute statistics, unless the request specifies otherwise
+const DEFAULT_SAMPLE_SIZE: usize = 1000;
+
+/// longest a barrier waits, unless the request specifies otherwise
+const DEFAULT_BARRIER_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub async fn slow_operations(State(state): State<AppState>) -> Json<Vec<SlowOperation>> {
+    Json(state.slow_operations.entries())
+}
+
+/// Bytes read and returned by Query and Scan operations on each table, when recording them is
+/// enabled
+pub async fn access_patterns(
+    State(state): State<AppState>,
+) -> Json<BTreeMap<String, TableAccessReport>> {
+    Json(state.access_patterns.report())
+}
+
+/// Write rates of the busiest partition keys of each table, when recording them is enabled
+pub async fn hot_keys(State(state): State<AppState>) -> Json<HotKeysReport> {
+    Json(state.hot_keys.report())
+}
+
+/// Which DynamoDB operations the emulator implements
+pub async fn operations() -> Json<OperationsReport> {
+    Json(crate::operations::report())
+}
+
+/// Counts of the errors returned to clients, and the most recent errors
+pub async fn errors(State(state): State<AppState>) -> Json<ErrorSummary> {
+    Json(state.errors.summary())
+}
+
+/// Operation, item size and connection metrics in the Prometheus text format
+pub async fn metrics(State(state): State<AppState>) -> Result<impl IntoResponse, ErrorResponse> {
+    let item_sizes = {
+        let unlocked_manager = state
+            .manager
+            .read()
+            .map_err(|_| ErrorResponse::MutexUnlock)?;
+        state
+            .metrics
+            .render_item_sizes(unlocked_manager.active_tables())
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render()
+            + &item_sizes
+            + &state.connections.render()
+            + &state.unimplemented.render()
+            + &state.dax.map(|dax| dax.render()).unwrap_or_default(),
+    ))
+}
+
+/// Features not implemented by the emulator which requests have needed
+pub async fn unimplemented(State(state): State<AppState>) -> Json<UnimplementedReport> {
+    Json(state.unimplemented.report())
+}
+
+/// Forget the unimplemented features needed so far, e.g. at the start of a test run
+pub async fn clear_unimplemented(State(state): State<AppState>) -> StatusCode {
+    state.unimplemented.clear();
+    StatusCode::NO_CONTENT
+}
+
+/// Hit and miss counts of the simulated DAX caches, if DAX is simulated
+pub async fn dax(State(state): State<AppState>) -> Result<Json<DaxReport>, StatusCode> {
+    state
+        .dax
+        .map(|dax| Json(dax.report()))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Empty the simulated DAX caches and reset their counts, e.g. at the start of a test run
+pub async fn clear_dax(State(state): State<AppState>) -> StatusCode {
+    match state.dax {
+        Some(dax) => {
+            dax.clear();
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Periodic background jobs, such as time to live sweeps, and when they last ran
+pub async fn jobs(State(state): State<AppState>) -> Json<Vec<JobReport>> {
+    Json(state.scheduler.report())
+}
+
+/// Open connections and the requests made on each, with counts of the connections opened and
+/// rejected
+pub async fn connections(State(state): State<AppState>) -> Json<ConnectionsReport> {
+    Json(state.connections.report())
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributesQuery {
+    sample_size: Option<usize>,
+}
+
+/// Names, types and fill rates of the attributes of a sample of the items in a table
+pub async fn table_attributes(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(table_name): Path<String>,
+    Query(query): Query<AttributesQuery>,
+) -> Result<Json<AttributeStatistics>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    // read the sample after releasing the lock, as it may be large
+    let snapshot = {
+        let unlocked_manager = state
+            .manager
+            .read()
+            .map_err(|_| ErrorResponse::MutexUnlock)?;
+        unlocked_manager
+            .get_table(&table_name)
+            .ok_or(ErrorResponse::ResourceNotFound {
+                name: Some(table_name),
+            })?
+            .snapshot()
+    };
+    Ok(Json(snapshot.attribute_statistics(
+        query.sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE),
+    )))
+}
+
+/// Keys, indexes and time to live attribute of a table
+pub async fn table_schema(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(table_name): Path<String>,
+) -> Result<Json<TableSchema>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let unlocked_manager = state
+        .manager
+        .read()
+        .map_err(|_| ErrorResponse::MutexUnlock)?;
+    unlocked_manager
+        .get_table(&table_name)
+        .map(|table| Json(table.schema()))
+        .ok_or(ErrorResponse::ResourceNotFound {
+            name: Some(table_name),
+        })
+}
+
+/// Estimate what a Query or Scan request would cost on DynamoDB, given the items currently in its
+/// table, without running it
+pub async fn estimate(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(operation): Path<String>,
+    body: axum::body::Bytes,
+) -> Result<Json<CostEstimate>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let unlocked_manager = state
+        .manager
+        .read()
+        .map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = |table_name: &str| {
+        unlocked_manager
+            .get_active_table(table_name)
+            .ok_or_else(|| ErrorResponse::ResourceNotFound {
+                name: Some(table_name.to_string()),
+            })
+    };
+    let estimate = match operation.as_str() {
+        "Query" => {
+            let AwsJson(mut input) = AwsJson::<types::QueryInput>::from_bytes(&body)?;
+            input.exclusive_start_key = input
+                .exclusive_start_key
+                .take()
+                .map(|key| {
+                    state.page_tokens.exclusive_start_key(
+                        &input.table_name,
+                        input.index_name.as_deref(),
+                        key,
+                    )
+                })
+                .transpose()?;
+            table(&input.table_name)?.estimate_query(&input, &unlocked_manager.limits)?
+        }
+        "Scan" => {
+            let AwsJson(input) = AwsJson::<types::ScanInput>::from_bytes(&body)?;
+            table(&input.table_name)?.estimate_scan(&input, &unlocked_manager.limits)
+        }
+        _ => {
+            return Err(ErrorResponse::ValidationError(format!(
+                "Only Query and Scan costs can be estimated, not {operation}"
+            )))
+        }
+    };
+    Ok(Json(estimate))
+}
+
+/// Key attributes of a table, which come first in CSV exports
+fn key_attributes(table: &Table) -> Vec<String> {
+    std::iter::once(table.partition_key())
+        .chain(table.sort_key())
+        .map(str::to_string)
+        .collect()
+}
+
+fn csv_response(file_name: &str, contents: String) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}.csv\""),
+            ),
+        ],
+        contents,
+    )
+}
+
+/// Download every item of a table as CSV, ordered by key, see [`crate::csv`]
+pub async fn table_csv(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(table_name): Path<String>,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    // write the items after releasing the lock, as there may be many
+    let (snapshot, key_attributes) = {
+        let unlocked_manager = state
+            .manager
+            .read()
+            .map_err(|_| ErrorResponse::MutexUnlock)?;
+        let table = unlocked_manager.get_table(&table_name).ok_or_else(|| {
+            ErrorResponse::ResourceNotFound {
+                name: Some(table_name.clone()),
+            }
+        })?;
+        (table.snapshot(), key_attributes(table))
+    };
+    let mut items: Vec<_> = snapshot.items().collect();
+    items.sort_by_cached_key(|item| {
+        key_attributes
+            .iter()
+            .map(|name| item.get(name.as_str()).and_then(Key::from_value))
+            .collect::<Vec<_>>()
+    });
+    let key_attributes: Vec<&str> = key_attributes.iter().map(String::as_str).collect();
+    Ok(csv_response(
+        &table_name,
+        crate::csv::to_csv(&items, &key_attributes),
+    ))
+}
+
+/// Run a Query or Scan request and download the items it returns as CSV
+pub async fn csv(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(operation): Path<String>,
+    body: axum::body::Bytes,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let (table_name, output) = match operation.as_str() {
+        "Query" => {
+            let input = AwsJson::<types::QueryInput>::from_bytes(&body)?;
+            let table_name = input.0.table_name.clone();
+            (
+                table_name,
+                crate::handle_query(State(state.clone()), input).await?,
+            )
+        }
+        "Scan" => {
+            let input = AwsJson::<types::ScanInput>::from_bytes(&body)?;
+            let table_name = input.0.table_name.clone();
+            (
+                table_name,
+                crate::handle_scan(State(state.clone()), input).await?,
+            )
+        }
+        _ => {
+            return Err(ErrorResponse::ValidationError(format!(
+                "Only the results of Query and Scan can be exported, not {operation}"
+            )))
+        }
+    };
+    let key_attributes = state
+        .manager
+        .read()
+        .map_err(|_| ErrorResponse::MutexUnlock)?
+        .get_table(&table_name)
+        .map(key_attributes)
+        .unwrap_or_default();
+    let key_attributes: Vec<&str> = key_attributes.iter().map(String::as_str).collect();
+    let items: Vec<_> = output.items.iter().collect();
+    Ok(csv_response(
+        &table_name,
+        crate::csv::to_csv(&items, &key_attributes),
+    ))
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepResult {
+    expired_items: usize,
+}
+
+/// Delete every item whose time to live has passed now, rather than waiting for a background
+/// sweep
+pub async fn sweep_expired_items(
+    State(state): State<AppState>,
+    namespace: Namespace,
+) -> Result<Json<SweepResult>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let mut unlocked_manager = state
+        .manager
+        .write()
+        .map_err(|_| ErrorResponse::MutexUnlock)?;
+    Ok(Json(SweepResult {
+        expired_items: unlocked_manager.expire_items(),
+    }))
+}
+
+/// Fault injection, limits and log level currently in effect
+pub async fn settings(State(state): State<AppState>) -> Json<CurrentSettings> {
+    Json(state.settings.current())
+}
+
+/// Change some of the settings, keeping the others
+pub async fn update_settings(
+    State(state): State<AppState>,
+    Json(update): Json<SettingsUpdate>,
+) -> Result<Json<CurrentSettings>, ErrorResponse> {
+    Ok(Json(state.settings.update(update)?))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrierQuery {
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrierResult {
+    waited_ms: f64,
+}
+
+/// Wait until every write acknowledged so far is visible to reads, including through indexes
+/// which are still backfilling, and tables have finished being created or deleted, so that tests
+/// do not need to sleep for the simulated delays
+pub async fn barrier(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Query(query): Query<BarrierQuery>,
+) -> Result<Json<BarrierResult>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let start = std::time::Instant::now();
+    let timeout = query
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_BARRIER_TIMEOUT);
+    loop {
+        // taking the lock also waits for writes which are in progress
+        let settles_at = state
+            .manager
+            .read()
+            .map_err(|_| ErrorResponse::MutexUnlock)?
+            .settles_at();
+        let Some(settles_at) = settles_at else {
+            break;
+        };
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(ErrorResponse::InternalServerError(format!(
+                "Pending writes did not become visible within {}ms",
+                timeout.as_millis()
+            )));
+        }
+        let wait = (settles_at - state.clock.now())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(wait.min(remaining)).await;
+    }
+    Ok(Json(BarrierResult {
+        waited_ms: start.elapsed().as_secs_f64() * 1000.0,
+    }))
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactionResult {
+    reclaimed_bytes: usize,
+}
+
+/// Release the memory left unused by removed items now, rather than waiting for a background
+/// compaction
+pub async fn compact(
+    State(state): State<AppState>,
+    namespace: Namespace,
+) -> Result<Json<CompactionResult>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let reclaimed_bytes = state
+        .manager
+        .write()
+        .map_err(|_| ErrorResponse::MutexUnlock)?
+        .compact();
+    state.metrics.record_compaction(reclaimed_bytes);
+    Ok(Json(CompactionResult { reclaimed_bytes }))
+}
+
+/// Download the active tables as a DynamoDB Local database file
+#[cfg(feature = "dynamodb-local")]
+pub async fn export_dynamodb_local(
+    State(state): State<AppState>,
+    namespace: Namespace,
+) -> Result<impl IntoResponse, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let tables: Vec<_> = state
+        .manager
+        .read()
+        .map_err(|_| ErrorResponse::MutexUnlock)?
+        .active_tables()
+        .cloned()
+        .collect();
+
+    // SQLite can only write to files
+    let path = std::env::temp_dir().join(format!("rynamodb-export-{}.db", uuid::Uuid::new_v4()));
+    let exported = crate::dynamodb_local::export(&path, &tables).await;
+    let contents = exported.and_then(|_| Ok(std::fs::read(&path)?));
+    let _ = std::fs::remove_file(&path);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.sqlite3"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"shared-local-instance.db\"",
+            ),
+        ],
+        contents?,
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchItemRequest {
+    key: HashMap<String, AttributeValue>,
+    /// JSON merge patch of DynamoDB JSON attribute values
+    patch: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchItemResponse {
+    item: HashMap<String, AttributeValue>,
+}
+
+/// Change the attributes of a stored item directly, without expressions or conditions, so tests
+/// can set up states which are awkward to reach through the DynamoDB API
+pub async fn patch_item(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(table_name): Path<String>,
+    Json(request): Json<PatchItemRequest>,
+) -> Result<Json<PatchItemResponse>, ErrorResponse> {
+    let state = state.in_namespace(&namespace);
+    let mut unlocked_manager = state
+        .manager
+        .write()
+        .map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = unlocked_manager.get_active_table_mut(&table_name).ok_or(
+        ErrorResponse::ResourceNotFound {
+            name: Some(table_name),
+        },
+    )?;
+    let item = table.patch_item(request.key, &request.patch)?;
+    Ok(Json(PatchItemResponse { item }))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyTableRequest {
+    target_table_name: String,
+}
+
+/// Duplicate a table including its items and indexes
+pub async fn copy_table(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(table_name): Path<String>,
+    Json(request): Json<CopyTableRequest>,
+) -> Result<Json<types::TableDescription>, ErrorResponse> {
+    copy(
+        state.in_namespace(&namespace),
+        &table_name,
+        &request.target_table_name,
+        false,
+    )
+}
+
+/// Rename a table, keeping its items and indexes
+pub async fn rename_table(
+    State(state): State<AppState>,
+    namespace: Namespace,
+    Path(table_name): Path<String>,
+    Json(request): Json<CopyTableRequest>,
+) -> Result<Json<types::TableDescription>, ErrorResponse> {
+    copy(
+        state.in_namespace(&namespace),
+        &table_name,
+        &request.target_table_name,
+        true,
+    )
+}
+
+fn copy(
+    state: AppState,
+    source_name: &str,
+    target_name: &str,
+    remove_source: bool,
+) -> Result<Json<types::TableDescription>, ErrorResponse> {
+    let mut unlocked_manager = state
+        .manager
+        .write()
+        .map_err(|_| ErrorResponse::MutexUnlock)?;
+    if unlocked_manager.get_table(target_name).is_some() {
+        return Err(ErrorResponse::TableAlreadyExists(target_name.to_string()));
+    }
+    if !remove_source {
+        unlocked_manager.check_limits(true)?;
+    }
+
+    let table = unlocked_manager
+        .copy_table(source_name, target_name, remove_source)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(source_name.to_string()),
+        })?;
+    Ok(Json(table.description()))
+}
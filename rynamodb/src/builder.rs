@@ -0,0 +1,424 @@
+//! Builder for embedding the emulator in another application
+//!
+//! Embedders can wrap the router in their own tower layers, and register hooks which see every
+//! operation before it is handled. A hook may return a response to short circuit the request,
+//! which allows faults to be injected programmatically.
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, Request},
+    response::{IntoResponse, Response},
+    routing::{any, get, patch, post, Route},
+    Router,
+};
+use serde::de::DeserializeOwned;
+use serde_dynamo::AttributeValue;
+use tower::{Layer, Service};
+
+use crate::{
+    access_patterns::AccessPatterns,
+    admin,
+    connections::Connections,
+    dax::Dax,
+    events::TableEvent,
+    handler,
+    hot_keys::HotKeys,
+    journal::Journal,
+    metrics::Metrics,
+    namespaces::Namespaces,
+    page_tokens::PageTokens,
+    providers::{Clock, IdProvider, Providers},
+    scheduler::Scheduler,
+    settings::{LogLevelReloader, Settings},
+    slow_log::SlowOperationLog,
+    table::Table,
+    table_manager::TableManager,
+    types,
+    unimplemented::Unimplemented,
+    AppState, OperationType, RynamodbConfig,
+};
+
+/// Details of an incoming operation, passed to hooks registered with
+/// [`RynamodbBuilder::on_operation`]
+#[derive(Debug)]
+pub struct OperationRequest<'a> {
+    pub operation: OperationType,
+    pub headers: &'a HeaderMap,
+    /// Raw JSON request body
+    pub body: &'a str,
+}
+
+/// Callback invoked for every operation
+///
+/// Returning `Some(response)` sends that response to the client instead of handling the
+/// operation.
+pub type OperationHook = Arc<dyn Fn(&OperationRequest<'_>) -> Option<Response> + Send + Sync>;
+
+type RouterLayer = Box<dyn FnOnce(Router) -> Router>;
+
+/// Read access to the tables of an emulator, and to its runtime settings, returned by
+/// [`RynamodbBuilder::build`]
+///
+/// ```no_run
+/// let (router, tables) = rynamodb::Rynamodb::builder().build();
+/// // ... serve the router and create a table
+/// let count = tables.with_table("my-table", |table| table.iter().count());
+/// ```
+#[derive(Clone)]
+pub struct Tables {
+    manager: Arc<RwLock<TableManager>>,
+    settings: Settings,
+}
+
+impl Tables {
+    /// Call `f` with the table named `name`, if it exists
+    ///
+    /// Requests which write to the emulator are blocked while `f` runs.
+    pub fn with_table<R>(&self, name: &str, f: impl FnOnce(&Table) -> R) -> Option<R> {
+        let manager = self.manager.read().unwrap_or_else(|e| e.into_inner());
+        manager.get_table(name).map(f)
+    }
+
+    /// Copies of all the tables which can serve reads and writes, e.g. to export them
+    ///
+    /// Copying a table does not copy its items until either copy is written to.
+    pub fn all(&self) -> Vec<Table> {
+        let manager = self.manager.read().unwrap_or_else(|e| e.into_inner());
+        manager.active_tables().cloned().collect()
+    }
+
+    /// Receive the changes made to the tables from now on, e.g. to react to writes
+    ///
+    /// Tables in namespaces are not included.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<TableEvent> {
+        let manager = self.manager.read().unwrap_or_else(|e| e.into_inner());
+        manager.subscribe()
+    }
+
+    /// Handle to change fault injection, limits and the log level while the emulator is running
+    pub fn settings(&self) -> Settings {
+        self.settings.clone()
+    }
+}
+
+/// Entry point for building a customised emulator
+///
+/// ```no_run
+/// let router = rynamodb::Rynamodb::builder()
+///     .on_operation(|request| {
+///         println!("{:?}", request.operation);
+///         None
+///     })
+///     .build_router();
+/// ```
+pub struct Rynamodb;
+
+impl Rynamodb {
+    pub fn builder() -> RynamodbBuilder {
+        RynamodbBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct RynamodbBuilder {
+    config: RynamodbConfig,
+    layers: Vec<RouterLayer>,
+    operation_hooks: Vec<OperationHook>,
+    tables: Vec<Table>,
+    reload_log_level: Option<LogLevelReloader>,
+    journal: Option<Journal>,
+    providers: Providers,
+}
+
+impl RynamodbBuilder {
+    pub fn config(mut self, config: RynamodbConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Wrap the router in a tower layer
+    ///
+    /// Layers are applied in the order they are added, so the last layer added is the outermost.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<Route> + Clone + Send + 'static,
+        L::Service: Service<Request<Body>> + Clone + Send + 'static,
+        <L::Service as Service<Request<Body>>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request<Body>>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+    {
+        self.layers
+            .push(Box::new(move |router: Router| router.layer(layer)));
+        self
+    }
+
+    /// Start with these tables, e.g. ones imported from DynamoDB Local
+    pub fn tables(mut self, tables: impl IntoIterator<Item = Table>) -> Self {
+        self.tables.extend(tables);
+        self
+    }
+
+    /// Append every operation which changes the tables to a journal, so that they can be
+    /// replayed with [`crate::journal::replay`]
+    pub fn journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Take timestamps and time simulated delays with this clock rather than the wall clock, see
+    /// [`crate::FixedClock`]
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.providers.clock = clock;
+        self
+    }
+
+    /// Generate request, table and KMS key ids with this provider rather than randomly, see
+    /// [`crate::SequentialIds`]
+    pub fn id_provider(mut self, ids: Arc<dyn IdProvider>) -> Self {
+        self.providers.ids = ids;
+        self
+    }
+
+    /// Allow the log level to be changed at runtime, see [`crate::Settings`]
+    ///
+    /// The emulator does not install a tracing subscriber, so the embedder must supply the
+    /// function which changes the subscriber's filter, e.g. through a
+    /// `tracing_subscriber::reload::Handle`.
+    pub fn log_level_reloader<F>(mut self, reload: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.reload_log_level = Some(Arc::new(reload));
+        self
+    }
+
+    /// Register a hook which is invoked before every operation is handled
+    ///
+    /// Hooks run in the order they are registered, and the first hook to return a response wins.
+    pub fn on_operation<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&OperationRequest<'_>) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.operation_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Observe every `PutItem` request, with the table name and item being written
+    ///
+    /// Observers are called before the operation is handled, so they also see requests which
+    /// subsequently fail.
+    pub fn on_put_item<F>(self, observer: F) -> Self
+    where
+        F: Fn(&str, &HashMap<String, AttributeValue>) + Send + Sync + 'static,
+    {
+        self.observe(OperationType::PutItem, move |input: types::PutItemInput| {
+            observer(&input.table_name, &input.item)
+        })
+    }
+
+    /// Observe every `Query` request, with the table name and full query input
+    pub fn on_query<F>(self, observer: F) -> Self
+    where
+        F: Fn(&str, &types::QueryInput) + Send + Sync + 'static,
+    {
+        self.observe(OperationType::Query, move |input: types::QueryInput| {
+            observer(&input.table_name, &input)
+        })
+    }
+
+    // register a hook which never intercepts the request, and passes the parsed input of a single
+    // operation type to the observer. Unparseable requests are skipped, as the handler will
+    // reject them anyway.
+    fn observe<I, F>(self, operation: OperationType, observer: F) -> Self
+    where
+        I: DeserializeOwned + 'static,
+        F: Fn(I) + Send + Sync + 'static,
+    {
+        self.on_operation(move |request| {
+            if request.operation == operation {
+                if let Ok(input) = serde_json::from_str(request.body) {
+                    observer(input);
+                }
+            }
+            None
+        })
+    }
+
+    /// Build the router
+    ///
+    /// If a cluster, time to live sweeps, compaction or namespace expiry are configured, this
+    /// schedules background jobs so it must be called within a tokio runtime.
+    pub fn build_router(self) -> Router {
+        self.build().0
+    }
+
+    /// Build the router, along with a handle to read the tables it serves
+    ///
+    /// If a cluster, time to live sweeps, compaction or namespace expiry are configured, this
+    /// schedules background jobs so it must be called within a tokio runtime.
+    pub fn build(self) -> (Router, Tables) {
+        let config = self.config;
+        let metrics = Arc::new(Metrics::new(
+            config.account_id.clone(),
+            config.region,
+            config.metrics,
+        ));
+        let mut manager = TableManager {
+            per_account: Default::default(),
+            account_id: config.account_id,
+            region: config.region,
+            unprocessed_simulation: config.unprocessed_simulation,
+            lifecycle: config.lifecycle,
+            limits: config.limits,
+            defaults: config.table_defaults,
+            idempotent_create_table: config.idempotent_create_table,
+            events: Default::default(),
+            providers: self.providers.clone(),
+        };
+        for table in self.tables {
+            if let Err(e) = manager.insert_table(table) {
+                tracing::warn!(error = %e, "could not add table");
+            }
+        }
+        let manager = Arc::new(RwLock::new(manager));
+        let namespaces = Arc::new(Namespaces::new(config.namespace_ttl));
+        let scheduler = Arc::new(Scheduler::new(Arc::clone(&self.providers.clock)));
+        if config.namespace_ttl.is_some() {
+            crate::namespaces::schedule_reaper(&scheduler, Arc::clone(&namespaces));
+        }
+        if let Some(sweep_interval) = config.time_to_live.sweep_interval {
+            crate::table_manager::schedule_expiry(
+                &scheduler,
+                Arc::clone(&manager),
+                Arc::clone(&namespaces),
+                sweep_interval,
+            );
+        }
+        if let Some(compaction_interval) = config.compaction_interval {
+            crate::table_manager::schedule_compaction(
+                &scheduler,
+                Arc::clone(&manager),
+                Arc::clone(&namespaces),
+                Arc::clone(&metrics),
+                compaction_interval,
+            );
+        }
+        let connection_faults = Arc::new(RwLock::new(config.connection_faults));
+        let connections = Arc::new(Connections::new(config.max_connections));
+        let settings = Settings {
+            manager: Arc::clone(&manager),
+            namespaces: Arc::clone(&namespaces),
+            connection_faults: Arc::clone(&connection_faults),
+            reload_log_level: self.reload_log_level,
+            log_level: Arc::new(Mutex::new(None)),
+        };
+        let state = AppState {
+            manager: Arc::clone(&manager),
+            settings: settings.clone(),
+            operation_hooks: self.operation_hooks.into(),
+            slow_operations: Arc::new(SlowOperationLog::new(config.slow_operations)),
+            access_patterns: Arc::new(AccessPatterns::new(config.access_patterns)),
+            hot_keys: Arc::new(HotKeys::new(config.hot_keys)),
+            errors: Default::default(),
+            metrics,
+            connections: Arc::clone(&connections),
+            unimplemented: Arc::new(Unimplemented::new(config.fail_fast)),
+            tail: Default::default(),
+            journal: self.journal.map(Arc::new),
+            namespaces,
+            request_timeout: config.request_timeout,
+            region: config.region,
+            latency: config.latency,
+            page_jitter: config.page_jitter,
+            scan_shuffle_seed: config.scan_shuffle_seed,
+            page_tokens: Arc::new(PageTokens::default().in_region(config.region)),
+            ids: self.providers.ids,
+            clock: self.providers.clock,
+            validate_schemas: config.validate_schemas,
+            dax: config.dax.map(|dax| Arc::new(Dax::new(dax))),
+            scheduler: Arc::clone(&scheduler),
+        };
+
+        let router = Router::new()
+            .route("/_health", get(|| async { "ok" }))
+            .route("/_rynamodb/slow-operations", get(admin::slow_operations))
+            .route("/_rynamodb/access-patterns", get(admin::access_patterns))
+            .route("/_rynamodb/hot-keys", get(admin::hot_keys))
+            .route("/_rynamodb/estimate/:operation", post(admin::estimate))
+            .route("/_rynamodb/csv/:operation", post(admin::csv))
+            .route("/_rynamodb/errors", get(admin::errors))
+            .route("/_rynamodb/metrics", get(admin::metrics))
+            .route("/_rynamodb/connections", get(admin::connections))
+            .route("/_rynamodb/operations", get(admin::operations))
+            .route(
+                "/_rynamodb/unimplemented",
+                get(admin::unimplemented).delete(admin::clear_unimplemented),
+            )
+            .route("/_rynamodb/dax", get(admin::dax).delete(admin::clear_dax))
+            .route("/_rynamodb/tail", get(crate::tail::tail))
+            .route("/_rynamodb/jobs", get(admin::jobs))
+            .route(
+                "/_rynamodb/tables/:name/attributes",
+                get(admin::table_attributes),
+            )
+            .route("/_rynamodb/tables/:name/schema", get(admin::table_schema))
+            .route("/_rynamodb/tables/:name/items", patch(admin::patch_item))
+            .route("/_rynamodb/tables/:name/csv", get(admin::table_csv))
+            .route("/_rynamodb/tables/:name/copy", post(admin::copy_table))
+            .route("/_rynamodb/tables/:name/rename", post(admin::rename_table))
+            .route("/_rynamodb/ttl/sweep", post(admin::sweep_expired_items))
+            .route("/_rynamodb/compact", post(admin::compact))
+            .route("/_rynamodb/barrier", post(admin::barrier))
+            .route(
+                "/_rynamodb/config",
+                get(admin::settings).put(admin::update_settings),
+            );
+        #[cfg(feature = "dynamodb-local")]
+        let router = router.route(
+            "/_rynamodb/export/dynamodb-local",
+            get(admin::export_dynamodb_local),
+        );
+        let router = router.fallback(any(handler)).with_state(state);
+
+        #[cfg(feature = "cluster")]
+        let router = match config.cluster {
+            Some(cluster_config) => {
+                let cluster = Arc::new(crate::cluster::Cluster::new(cluster_config));
+                Arc::clone(&cluster).schedule_gossip(&scheduler, Arc::clone(&manager));
+                router
+                    .merge(
+                        Router::new()
+                            .route("/_rynamodb/cluster", get(crate::cluster::members))
+                            .route("/_rynamodb/cluster/gossip", post(crate::cluster::gossip))
+                            .with_state(Arc::clone(&cluster)),
+                    )
+                    .layer(axum::middleware::from_fn_with_state(
+                        cluster,
+                        crate::cluster::forward,
+                    ))
+            }
+            None => router,
+        };
+
+        let router = router.layer(axum::middleware::from_fn_with_state(
+            connection_faults,
+            crate::faults::inject,
+        ));
+        let router = router.layer(axum::middleware::from_fn_with_state(
+            connections,
+            crate::connections::track,
+        ));
+        let router = config.edge.apply(router);
+        let router = self
+            .layers
+            .into_iter()
+            .fold(router, |router, layer| layer(router));
+        (router, Tables { manager, settings })
+    }
+}
@@ -0,0 +1,250 @@
+//! Experimental simulation of a multi-node deployment
+//!
+//! Several emulator processes are started with the same list of nodes. Each table is owned by
+//! one node, chosen by rendezvous hashing of the table name, and operations on a table received
+//! by any other node are forwarded to its owner. Stopping or slowing down one process therefore
+//! affects only the tables it owns, much like a slow or unavailable partition.
+//!
+//! Nodes also periodically gossip the names of their tables to each other. The membership view
+//! is served on `/_rynamodb/cluster` to help debug the simulation.
+//!
+//! Batch operations and `ListTables` are always handled by the node receiving them.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, State},
+    http::{HeaderName, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::ErrorResponse, scheduler::Scheduler, table_manager::TableManager};
+
+/// set on forwarded requests, so that they are never forwarded again
+static FORWARDED_HEADER: HeaderName = HeaderName::from_static("x-rynamodb-forwarded");
+
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    /// base URL at which the other nodes reach this node, e.g. `http://127.0.0.1:3050`
+    pub node_url: String,
+    /// base URLs of the other nodes
+    pub peers: Vec<String>,
+    pub gossip_interval: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Gossip {
+    pub node: String,
+    pub tables: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Member {
+    pub node: String,
+    pub tables: Vec<String>,
+    pub last_seen: DateTime<Utc>,
+}
+
+pub struct Cluster {
+    config: ClusterConfig,
+    members: Mutex<HashMap<String, Member>>,
+    client: reqwest::Client,
+}
+
+impl Cluster {
+    pub fn new(config: ClusterConfig) -> Self {
+        Self {
+            config,
+            members: Default::default(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Node which owns a table
+    fn owner(&self, table_name: &str) -> &str {
+        std::iter::once(&self.config.node_url)
+            .chain(&self.config.peers)
+            .max_by_key(|node| mix(fnv1a(format!("{node}/{table_name}").as_bytes())))
+            .expect("the cluster always contains this node")
+    }
+
+    fn record(&self, gossip: Gossip) {
+        let member = Member {
+            node: gossip.node.clone(),
+            tables: gossip.tables,
+            last_seen: Utc::now(),
+        };
+        self.members.lock().unwrap().insert(gossip.node, member);
+    }
+
+    pub fn members(&self) -> Vec<Member> {
+        let mut members: Vec<_> = self.members.lock().unwrap().values().cloned().collect();
+        members.sort_by(|a, b| a.node.cmp(&b.node));
+        members
+    }
+
+    /// Periodically send the names of the tables on this node to the other nodes
+    pub fn schedule_gossip(
+        self: Arc<Self>,
+        scheduler: &Scheduler,
+        manager: Arc<RwLock<TableManager>>,
+    ) {
+        let gossip_interval = self.config.gossip_interval;
+        scheduler.every("cluster-gossip", gossip_interval, move || {
+            let cluster = Arc::clone(&self);
+            let tables = manager.read().ok().map(|manager| manager.table_names());
+            async move {
+                let Some(tables) = tables else {
+                    return;
+                };
+                let gossip = Gossip {
+                    node: cluster.config.node_url.clone(),
+                    tables,
+                };
+                for peer in &cluster.config.peers {
+                    let res = cluster
+                        .client
+                        .post(format!("{peer}/_rynamodb/cluster/gossip"))
+                        .json(&gossip)
+                        .send()
+                        .await;
+                    if let Err(e) = res {
+                        tracing::debug!(%peer, error = %e, "could not gossip with peer");
+                    }
+                }
+            }
+        });
+    }
+}
+
+// stable across processes, unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// FNV leaves the high bits, which decide the owner, depending little on the last bytes hashed,
+// so they are mixed with the finaliser of MurmurHash3
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+pub async fn gossip(State(cluster): State<Arc<Cluster>>, Json(gossip): Json<Gossip>) {
+    cluster.record(gossip);
+}
+
+pub async fn members(State(cluster): State<Arc<Cluster>>) -> Json<Vec<Member>> {
+    Json(cluster.members())
+}
+
+/// Middleware forwarding operations on tables owned by other nodes
+pub async fn forward(
+    State(cluster): State<Arc<Cluster>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if request.uri().path().starts_with("/_") || request.headers().contains_key(&FORWARDED_HEADER) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match Bytes::from_request(Request::new(body), &()).await {
+        Ok(body) => body,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let table_name = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|input| input.get("TableName")?.as_str().map(ToString::to_string));
+    let owner = table_name
+        .as_deref()
+        .map(|table_name| cluster.owner(table_name))
+        .filter(|owner| *owner != cluster.config.node_url);
+    let Some(owner) = owner else {
+        return next.run(Request::from_parts(parts, Body::from(body))).await;
+    };
+
+    tracing::debug!(?table_name, %owner, "forwarding request");
+    let mut headers = parts.headers;
+    headers.remove(axum::http::header::HOST);
+    headers.insert(FORWARDED_HEADER.clone(), "1".parse().unwrap());
+    let res = cluster
+        .client
+        .post(format!("{owner}{}", parts.uri.path()))
+        .headers(headers)
+        .body(body)
+        .send()
+        .await;
+
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            tracing::warn!(%owner, error = %e, "could not forward request");
+            return ErrorResponse::InternalServerError(format!(
+                "The node owning the table is unavailable: {owner}"
+            ))
+            .into_response();
+        }
+    };
+    let status = res.status();
+    let headers = res.headers().clone();
+    match res.bytes().await {
+        Ok(body) => (status, headers, body).into_response(),
+        Err(e) => ErrorResponse::InternalServerError(format!(
+            "Reading the response of {owner} failed: {e}"
+        ))
+        .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_node_agrees_on_the_owner() {
+        let nodes = ["http://a", "http://b", "http://c"];
+        let clusters: Vec<_> = nodes
+            .iter()
+            .map(|node| {
+                Cluster::new(ClusterConfig {
+                    node_url: node.to_string(),
+                    peers: nodes
+                        .iter()
+                        .filter(|peer| peer != &node)
+                        .map(ToString::to_string)
+                        .collect(),
+                    gossip_interval: Duration::from_secs(1),
+                })
+            })
+            .collect();
+
+        let mut owners = HashMap::new();
+        for i in 0..100 {
+            let table_name = format!("table-{i}");
+            let owner = clusters[0].owner(&table_name).to_string();
+            for cluster in &clusters {
+                assert_eq!(cluster.owner(&table_name), owner);
+            }
+            *owners.entry(owner).or_insert(0) += 1;
+        }
+        // tables are spread over all of the nodes
+        assert_eq!(owners.len(), nodes.len());
+    }
+}
@@ -0,0 +1,132 @@
+//! Configuration of the emulator, shared by the command line server and embedders
+
+use std::time::Duration;
+
+use crate::{
+    dax::DaxSimulation,
+    edge::EdgeRouting,
+    faults::ConnectionFaults,
+    hot_keys::HotKeyConfig,
+    latency::LatencySimulation,
+    limits::Limits,
+    metrics::MetricsConfig,
+    slow_log::SlowOperationThresholds,
+    table::pagination::PageJitter,
+    table_manager::{
+        LifecycleSimulation, Region, TableDefaults, TimeToLiveSimulation, UnprocessedSimulation,
+    },
+};
+
+/// Options controlling the behaviour of the emulator, passed to [`crate::router`] or
+/// [`crate::RynamodbBuilder::config`]
+///
+/// ```no_run
+/// let router = rynamodb::router(rynamodb::RynamodbConfig {
+///     region: rynamodb::Region::EuWest1,
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct RynamodbConfig {
+    /// account which owns the tables, used in table ARNs
+    pub account_id: String,
+    /// region the tables are created in and looked up in, used in table ARNs. Ignored for signed
+    /// requests, which include every request from the AWS SDKs, as they use the region they are
+    /// signed for. Each region has its own tables, so tables in different regions can share a
+    /// name. The `/_rynamodb` endpoints always use this region.
+    pub region: Region,
+    pub unprocessed_simulation: UnprocessedSimulation,
+    pub lifecycle: LifecycleSimulation,
+    /// limits of the account and of requests, which tests can shrink to exercise their handling
+    pub limits: Limits,
+    pub table_defaults: TableDefaults,
+    /// return the existing table from `CreateTable` if it has the requested schema, rather than
+    /// failing with `ResourceInUseException` as DynamoDB does, so that test suites can share
+    /// fixtures
+    pub idempotent_create_table: bool,
+    pub time_to_live: TimeToLiveSimulation,
+    /// release the memory left unused by removed items this often, for long running instances.
+    /// Compaction can also be requested at `/_rynamodb/compact`
+    pub compaction_interval: Option<Duration>,
+    pub slow_operations: SlowOperationThresholds,
+    /// record the attributes read and returned by Query and Scan operations, reported at
+    /// `/_rynamodb/access-patterns` with an estimate of the read capacity projections could save
+    pub access_patterns: bool,
+    /// count the write capacity units written to each partition key, reported at
+    /// `/_rynamodb/hot-keys`, to check that writes are spread across keys
+    pub hot_keys: HotKeyConfig,
+    pub metrics: MetricsConfig,
+    /// refuse connections opened beyond this many, so that clients leaking connections fail
+    /// quickly. Open connections are reported at `/_rynamodb/connections`
+    pub max_connections: Option<usize>,
+    /// give up on operations which take longer than this, so a pathological scan cannot hold
+    /// a table lock indefinitely
+    pub request_timeout: Option<Duration>,
+    /// slow successful operations down in proportion to their size and the number of items they
+    /// return, so that local performance tests reflect payload sizes
+    pub latency: LatencySimulation,
+    /// end each page of query results after a random number of items, at most the request's
+    /// `Limit`, to exercise the client's pagination
+    pub page_jitter: Option<PageJitter>,
+    /// return the partitions of each table scanned in a random order derived from this seed and
+    /// the table name, rather than the order they happen to be stored in, to flush out clients
+    /// which depend on scan order
+    pub scan_shuffle_seed: Option<u64>,
+    /// answer eventually consistent reads from a cache, as DynamoDB Accelerator would, to
+    /// estimate hit rates and test tolerance of stale reads. Cache hits and misses are reported
+    /// at `/_rynamodb/dax`
+    pub dax: Option<DaxSimulation>,
+    /// close connections, truncate responses or stall part way through them, to exercise the
+    /// client's handling of network failures
+    pub connection_faults: ConnectionFaults,
+    /// remove namespaces, with all of their tables, once they have not been used for this long,
+    /// unless they set their own time to live
+    pub namespace_ttl: Option<Duration>,
+    /// routing of requests when running behind an edge proxy shared with other services
+    pub edge: EdgeRouting,
+    /// answer requests needing behaviour the emulator does not implement with a
+    /// `NotImplementedByRynamodb` error naming the feature, rather than the error DynamoDB
+    /// returns for invalid requests. The features needed are reported at
+    /// `/_rynamodb/unimplemented` in either mode
+    pub fail_fast: bool,
+    /// check requests and responses against the schemas of their operations, logging requests
+    /// which do not match and failing responses which do not with an `InternalServerError`, to
+    /// catch differences from DynamoDB's response shapes. Enabled by default in debug builds
+    pub validate_schemas: bool,
+    /// run as one node of a simulated cluster, rather than on its own
+    #[cfg(feature = "cluster")]
+    pub cluster: Option<crate::cluster::ClusterConfig>,
+}
+
+impl Default for RynamodbConfig {
+    fn default() -> Self {
+        Self {
+            account_id: crate::DEFAULT_ACCOUNT_ID.to_string(),
+            region: Region::default(),
+            unprocessed_simulation: Default::default(),
+            lifecycle: Default::default(),
+            limits: Default::default(),
+            table_defaults: Default::default(),
+            idempotent_create_table: false,
+            time_to_live: Default::default(),
+            compaction_interval: None,
+            slow_operations: Default::default(),
+            access_patterns: false,
+            hot_keys: Default::default(),
+            metrics: Default::default(),
+            max_connections: None,
+            request_timeout: None,
+            latency: Default::default(),
+            page_jitter: None,
+            scan_shuffle_seed: None,
+            dax: None,
+            connection_faults: Default::default(),
+            namespace_ttl: None,
+            edge: Default::default(),
+            fail_fast: false,
+            validate_schemas: cfg!(debug_assertions),
+            #[cfg(feature = "cluster")]
+            cluster: None,
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Open connections, the requests made on each, and an optional limit on their number
+//!
+//! Clients which leak connections, e.g. by building a new SDK client for every test, only run
+//! out of file descriptors after a long soak test, and usually on a CI machine with lower limits
+//! than a laptop. With a limit, connections opened beyond it are answered with
+//! `ServiceUnavailable` and closed, so a leak fails the test quickly.
+//!
+//! Connections are tracked when the router is served with [`ConnectionInfo`], as
+//! [`crate::run_server`] does, from their first request until they are closed.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use axum::{
+    body::Body,
+    extract::{connect_info::Connected, ConnectInfo, State},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use hyper::server::conn::AddrStream;
+use serde::Serialize;
+
+use crate::errors::ErrorResponse;
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Information about the connection a request was made on, attached to each request by the server
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    remote_addr: SocketAddr,
+    connection: Arc<Connection>,
+}
+
+impl Connected<&AddrStream> for ConnectionInfo {
+    fn connect_info(target: &AddrStream) -> Self {
+        Self {
+            remote_addr: target.remote_addr(),
+            connection: Arc::new(Connection {
+                id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+                tracker: OnceLock::new(),
+            }),
+        }
+    }
+}
+
+/// Held by the server for as long as the connection is open, and by its requests in flight
+#[derive(Debug)]
+struct Connection {
+    id: u64,
+    /// set by the first request made on the connection
+    tracker: OnceLock<Arc<Connections>>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(tracker) = self.tracker.get() {
+            tracker.close(self.id);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Connections {
+    max_connections: Option<usize>,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    open: BTreeMap<u64, OpenConnection>,
+    opened: u64,
+    rejected: u64,
+}
+
+#[derive(Debug)]
+struct OpenConnection {
+    remote_addr: SocketAddr,
+    opened_at: DateTime<Utc>,
+    requests: u64,
+    /// opened beyond the limit, so its requests are refused
+    rejected: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionsReport {
+    pub max_connections: Option<usize>,
+    /// connections opened since the server started, excluding rejected ones
+    pub opened: u64,
+    pub rejected: u64,
+    pub open: Vec<OpenConnectionReport>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenConnectionReport {
+    pub remote_addr: SocketAddr,
+    pub opened_at: DateTime<Utc>,
+    pub requests: u64,
+}
+
+impl Connections {
+    pub fn new(max_connections: Option<usize>) -> Self {
+        Self {
+            max_connections,
+            ..Default::default()
+        }
+    }
+
+    /// Count a request on a connection, starting to track the connection on its first request.
+    /// Returns whether the connection was accepted.
+    fn record_request(self: &Arc<Self>, info: &ConnectionInfo) -> bool {
+        let is_new = info.connection.tracker.set(Arc::clone(self)).is_ok();
+        let mut inner = self.inner.lock().unwrap();
+        if is_new {
+            let accepted = inner.open.values().filter(|c| !c.rejected).count();
+            let rejected = self.max_connections.is_some_and(|max| accepted >= max);
+            if rejected {
+                inner.rejected += 1;
+                tracing::warn!(
+                    remote_addr = %info.remote_addr,
+                    max_connections = self.max_connections,
+                    "rejecting connection beyond the limit"
+                );
+            } else {
+                inner.opened += 1;
+            }
+            inner.open.insert(
+                info.connection.id,
+                OpenConnection {
+                    remote_addr: info.remote_addr,
+                    opened_at: Utc::now(),
+                    requests: 0,
+                    rejected,
+                },
+            );
+        }
+        match inner.open.get_mut(&info.connection.id) {
+            Some(connection) => {
+                connection.requests += 1;
+                !connection.rejected
+            }
+            None => true,
+        }
+    }
+
+    fn close(&self, id: u64) {
+        self.inner.lock().unwrap().open.remove(&id);
+    }
+
+    pub fn report(&self) -> ConnectionsReport {
+        let inner = self.inner.lock().unwrap();
+        ConnectionsReport {
+            max_connections: self.max_connections,
+            opened: inner.opened,
+            rejected: inner.rejected,
+            open: inner
+                .open
+                .values()
+                .filter(|connection| !connection.rejected)
+                .map(|connection| OpenConnectionReport {
+                    remote_addr: connection.remote_addr,
+                    opened_at: connection.opened_at,
+                    requests: connection.requests,
+                })
+                .collect(),
+        }
+    }
+
+    /// Connection metrics in the Prometheus text exposition format, served with the operation
+    /// metrics
+    pub fn render(&self) -> String {
+        let report = self.report();
+        let requests: u64 = report.open.iter().map(|c| c.requests).sum();
+        let max_requests = report.open.iter().map(|c| c.requests).max().unwrap_or(0);
+        let metrics = [
+            (
+                "rynamodb_open_connections",
+                "gauge",
+                "Connections currently open",
+                report.open.len() as u64,
+            ),
+            (
+                "rynamodb_connections_total",
+                "counter",
+                "Connections accepted",
+                report.opened,
+            ),
+            (
+                "rynamodb_rejected_connections_total",
+                "counter",
+                "Connections rejected for exceeding the maximum number of connections",
+                report.rejected,
+            ),
+            (
+                "rynamodb_open_connection_requests",
+                "gauge",
+                "Requests made on the connections currently open",
+                requests,
+            ),
+            (
+                "rynamodb_open_connection_max_requests",
+                "gauge",
+                "Most requests made on one of the connections currently open",
+                max_requests,
+            ),
+        ];
+        let mut out = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        out
+    }
+}
+
+/// Middleware tracking the connection of each request, and refusing the requests on connections
+/// beyond the limit. Admin endpoints are always served, so the connections can be inspected.
+pub async fn track(
+    State(connections): State<Arc<Connections>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let Some(ConnectInfo(info)) = request
+        .extensions()
+        .get::<ConnectInfo<ConnectionInfo>>()
+        .cloned()
+    else {
+        return next.run(request).await;
+    };
+    let accepted = connections.record_request(&info);
+    if !accepted && !request.uri().path().starts_with("/_") {
+        return ErrorResponse::ServiceUnavailable(format!(
+            "rynamodb is limited to {} connections, check that clients are reused",
+            connections.max_connections.unwrap_or_default()
+        ))
+        .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(port: u16) -> ConnectionInfo {
+        ConnectionInfo {
+            remote_addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            connection: Arc::new(Connection {
+                id: NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed),
+                tracker: OnceLock::new(),
+            }),
+        }
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_are_rejected() {
+        let connections = Arc::new(Connections::new(Some(2)));
+        let a = connect(1);
+        let b = connect(2);
+        assert!(connections.record_request(&a));
+        assert!(connections.record_request(&a));
+        assert!(connections.record_request(&b));
+
+        let c = connect(3);
+        assert!(!connections.record_request(&c));
+        assert!(!connections.record_request(&c));
+        drop(c);
+
+        // closing a connection makes room for another
+        drop(a);
+        let d = connect(4);
+        assert!(connections.record_request(&d));
+
+        let report = connections.report();
+        assert_eq!(report.opened, 3);
+        assert_eq!(report.rejected, 1);
+        let open: Vec<_> = report
+            .open
+            .iter()
+            .map(|c| (c.remote_addr.port(), c.requests))
+            .collect();
+        assert_eq!(open, [(2, 1), (4, 1)]);
+
+        let rendered = connections.render();
+        for line in [
+            "rynamodb_open_connections 2",
+            "rynamodb_connections_total 3",
+            "rynamodb_rejected_connections_total 1",
+            "rynamodb_open_connection_requests 2",
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {line}");
+        }
+    }
+
+    #[test]
+    fn unlimited() {
+        let connections = Arc::new(Connections::new(None));
+        let infos: Vec<_> = (0..10).map(connect).collect();
+        for info in &infos {
+            assert!(connections.record_request(info));
+        }
+        assert_eq!(connections.report().open.len(), 10);
+    }
+}
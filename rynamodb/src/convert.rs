@@ -0,0 +1,164 @@
+//! Conversions between plain JSON documents and DynamoDB attribute values
+//!
+//! This allows fixtures to be written as natural JSON, e.g. `{"pk": "abc", "count": 3}` rather
+//! than the typed DynamoDB encoding `{"pk": {"S": "abc"}, "count": {"N": "3"}}`.
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_dynamo::AttributeValue;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConversionError {
+    #[error("expected a JSON object at the top level of an item")]
+    NotAnObject,
+}
+
+/// Convert an arbitrary JSON value into the equivalent DynamoDB attribute value
+pub fn json_to_attribute_value(value: Value) -> AttributeValue {
+    match value {
+        Value::Null => AttributeValue::Null(true),
+        Value::Bool(b) => AttributeValue::Bool(b),
+        Value::Number(n) => AttributeValue::N(n.to_string()),
+        Value::String(s) => AttributeValue::S(s),
+        Value::Array(values) => {
+            AttributeValue::L(values.into_iter().map(json_to_attribute_value).collect())
+        }
+        Value::Object(map) => AttributeValue::M(
+            map.into_iter()
+                .map(|(k, v)| (k, json_to_attribute_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a DynamoDB attribute value into plain JSON
+///
+/// Sets are converted into arrays, and binary values are base64 encoded strings, matching the
+/// DynamoDB wire format.
+pub fn attribute_value_to_json(value: AttributeValue) -> Value {
+    match value {
+        AttributeValue::Null(_) => Value::Null,
+        AttributeValue::Bool(b) => Value::Bool(b),
+        AttributeValue::N(n) => number_to_json(n),
+        AttributeValue::S(s) => Value::String(s),
+        AttributeValue::B(b) => Value::String(STANDARD.encode(b)),
+        AttributeValue::Ss(ss) => Value::Array(ss.into_iter().map(Value::String).collect()),
+        AttributeValue::Ns(ns) => Value::Array(ns.into_iter().map(number_to_json).collect()),
+        AttributeValue::Bs(bs) => Value::Array(
+            bs.into_iter()
+                .map(|b| Value::String(STANDARD.encode(b)))
+                .collect(),
+        ),
+        AttributeValue::L(values) => {
+            Value::Array(values.into_iter().map(attribute_value_to_json).collect())
+        }
+        AttributeValue::M(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k, attribute_value_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a JSON object into a DynamoDB item
+pub fn json_to_item(value: Value) -> Result<HashMap<String, AttributeValue>, ConversionError> {
+    match value {
+        Value::Object(map) => Ok(map
+            .into_iter()
+            .map(|(k, v)| (k, json_to_attribute_value(v)))
+            .collect()),
+        _ => Err(ConversionError::NotAnObject),
+    }
+}
+
+/// Convert a DynamoDB item into a JSON object
+pub fn item_to_json(item: HashMap<String, AttributeValue>) -> Value {
+    Value::Object(
+        item.into_iter()
+            .map(|(k, v)| (k, attribute_value_to_json(v)))
+            .collect(),
+    )
+}
+
+// DynamoDB numbers are arbitrary precision strings, so fall back to a string if the number
+// cannot be represented as a JSON number
+fn number_to_json(n: String) -> Value {
+    match serde_json::from_str::<Value>(&n) {
+        Ok(v @ Value::Number(_)) => v,
+        _ => Value::String(n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn json_to_item_conversion() {
+        let item = json_to_item(json!({
+            "pk": "abc",
+            "count": 3,
+            "enabled": true,
+            "missing": null,
+            "tags": ["a", 1],
+            "nested": {"value": 1.5},
+        }))
+        .unwrap();
+
+        assert_eq!(item["pk"], AttributeValue::S("abc".to_string()));
+        assert_eq!(item["count"], AttributeValue::N("3".to_string()));
+        assert_eq!(item["enabled"], AttributeValue::Bool(true));
+        assert_eq!(item["missing"], AttributeValue::Null(true));
+        assert_eq!(
+            item["tags"],
+            AttributeValue::L(vec![
+                AttributeValue::S("a".to_string()),
+                AttributeValue::N("1".to_string()),
+            ])
+        );
+        assert_eq!(
+            item["nested"],
+            AttributeValue::M(
+                [("value".to_string(), AttributeValue::N("1.5".to_string()))]
+                    .into_iter()
+                    .collect()
+            )
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let doc = json!({
+            "pk": "abc",
+            "count": 3,
+            "nested": {"values": [1, "two", false, null]},
+        });
+        let item = json_to_item(doc.clone()).unwrap();
+        assert_eq!(item_to_json(item), doc);
+    }
+
+    #[test]
+    fn sets_and_binary_to_json() {
+        assert_eq!(
+            attribute_value_to_json(AttributeValue::Ns(vec!["1".to_string(), "2".to_string()])),
+            json!([1, 2])
+        );
+        assert_eq!(
+            attribute_value_to_json(AttributeValue::B(b"abc".to_vec())),
+            json!("YWJj")
+        );
+    }
+
+    #[test]
+    fn non_object_item() {
+        assert!(matches!(
+            json_to_item(json!([1, 2])),
+            Err(ConversionError::NotAnObject)
+        ));
+    }
+}
@@ -0,0 +1,101 @@
+//! Flattened CSV of items, for inspecting the contents of the emulator in a spreadsheet
+//!
+//! Every top level attribute gets its own column, with the key attributes first and the others
+//! ordered by name. Strings, numbers, binary values (base64 encoded) and booleans are written as
+//! they are, and every other value, including `NULL`, as DynamoDB JSON such as
+//! `{"L":[{"S":"a"}]}`. Items without an attribute leave its cell empty.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_dynamo::AttributeValue;
+
+use crate::table::names::AttributeName;
+
+/// Write `items` as CSV, with a header row naming the attributes
+pub fn to_csv<'a, K: AttributeName + 'a>(
+    items: &[&'a HashMap<K, AttributeValue>],
+    key_attributes: &[&str],
+) -> String {
+    let others: BTreeSet<&str> = items
+        .iter()
+        .flat_map(|item| item.keys().map(|name| name.borrow()))
+        .filter(|name| !key_attributes.contains(name))
+        .collect();
+    let columns: Vec<&str> = key_attributes.iter().copied().chain(others).collect();
+
+    let mut out = String::new();
+    write_row(&mut out, columns.iter().map(|name| name.to_string()));
+    for item in items {
+        write_row(
+            &mut out,
+            columns
+                .iter()
+                .map(|name| item.get(*name).map(cell).unwrap_or_default()),
+        );
+    }
+    out
+}
+
+fn cell(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::S(s) | AttributeValue::N(s) => s.clone(),
+        AttributeValue::B(b) => STANDARD.encode(b),
+        AttributeValue::Bool(b) => b.to_string(),
+        value => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// Write one row, quoting fields as described in RFC 4180
+fn write_row(out: &mut String, fields: impl Iterator<Item = String>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            let _ = write!(out, "\"{}\"", field.replace('"', "\"\""));
+        } else {
+            out.push_str(&field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flattens_items() {
+        let s = |s: &str| AttributeValue::S(s.to_string());
+        let items: Vec<HashMap<String, AttributeValue>> = vec![
+            [
+                ("pk".to_string(), s("a")),
+                ("sk".to_string(), AttributeValue::N("1".to_string())),
+                ("note".to_string(), s("says \"hi\", twice")),
+                ("tags".to_string(), AttributeValue::L(vec![s("x")])),
+            ]
+            .into_iter()
+            .collect(),
+            [
+                ("pk".to_string(), s("b")),
+                ("sk".to_string(), AttributeValue::N("2".to_string())),
+                ("active".to_string(), AttributeValue::Bool(true)),
+                ("missing".to_string(), AttributeValue::Null(true)),
+            ]
+            .into_iter()
+            .collect(),
+        ];
+        let items: Vec<_> = items.iter().collect();
+
+        assert_eq!(
+            to_csv(&items, &["pk", "sk"]),
+            "pk,sk,active,missing,note,tags\r\n\
+            a,1,,,\"says \"\"hi\"\", twice\",\"{\"\"L\"\":[{\"\"S\"\":\"\"x\"\"}]}\"\r\n\
+            b,2,true,\"{\"\"NULL\"\":true}\",,\r\n"
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Read-through cache in front of the tables, like DynamoDB Accelerator (DAX)
+//!
+//! Like DAX, eventually consistent `GetItem` results are kept in an item cache and `Query` and
+//! `Scan` results in a query cache, each for a time to live. Writes through the emulator update
+//! the item cache by evicting the items they write, unless write invalidation is disabled to
+//! simulate writers which bypass DAX, but never the query cache, so queries can return stale
+//! results until they expire, as they do with DAX. Strongly consistent reads bypass the caches.
+//!
+//! Hits and misses are reported at `/_rynamodb/dax` and in the metrics, to estimate the hit rate
+//! an application would see. Requests in namespaces are not cached.
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_dynamo::AttributeValue;
+use serde_json::Value;
+
+use crate::{table_manager::Region, OperationType};
+
+/// Caching of reads. DAX caches items and queries for five minutes by default.
+#[derive(Debug, Clone, Copy)]
+pub struct DaxSimulation {
+    pub item_ttl: Duration,
+    pub query_ttl: Duration,
+    /// evict the items written through the emulator from the item cache
+    pub invalidate_on_write: bool,
+}
+
+impl Default for DaxSimulation {
+    fn default() -> Self {
+        Self {
+            item_ttl: Duration::from_secs(300),
+            query_ttl: Duration::from_secs(300),
+            invalidate_on_write: true,
+        }
+    }
+}
+
+/// Request which may be answered from the cache
+pub struct CacheKey {
+    kind: CacheKind,
+    table: String,
+    request: String,
+    /// key of the item read by `GetItem`, to evict it when it is written
+    item_key: Option<HashMap<String, AttributeValue>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheKind {
+    Item,
+    Query,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatistics {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    /// fraction of reads served from the cache, if there were any
+    pub hit_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaxReport {
+    pub item_ttl_ms: u64,
+    pub query_ttl_ms: u64,
+    pub invalidate_on_write: bool,
+    pub item_cache: CacheStatistics,
+    pub query_cache: CacheStatistics,
+}
+
+struct Entry {
+    table: String,
+    item_key: Option<HashMap<String, AttributeValue>>,
+    output: Value,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, Entry>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Cache {
+    fn statistics(&self) -> CacheStatistics {
+        let reads = self.hits + self.misses;
+        CacheStatistics {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+            hit_rate: (reads > 0).then(|| self.hits as f64 / reads as f64),
+        }
+    }
+}
+
+pub struct Dax {
+    config: DaxSimulation,
+    items: Mutex<Cache>,
+    queries: Mutex<Cache>,
+}
+
+/// Fields of the read and write requests the cache looks at
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Request {
+    table_name: Option<String>,
+    consistent_read: Option<bool>,
+    key: Option<HashMap<String, AttributeValue>>,
+    item: Option<HashMap<String, AttributeValue>>,
+    request_items: Option<HashMap<String, Vec<WriteRequest>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WriteRequest {
+    put_request: Option<PutRequest>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PutRequest {
+    item: HashMap<String, AttributeValue>,
+}
+
+/// Tables of the same name in different regions are different tables
+fn table_id(region: Region, table_name: &str) -> String {
+    format!("{region}/{table_name}")
+}
+
+impl Dax {
+    pub fn new(config: DaxSimulation) -> Self {
+        Self {
+            config,
+            items: Default::default(),
+            queries: Default::default(),
+        }
+    }
+
+    fn cache(&self, kind: CacheKind) -> &Mutex<Cache> {
+        match kind {
+            CacheKind::Item => &self.items,
+            CacheKind::Query => &self.queries,
+        }
+    }
+
+    /// Key to cache the output of a request under, if it can be cached
+    pub fn cache_key(
+        &self,
+        operation: OperationType,
+        region: Region,
+        body: &[u8],
+    ) -> Option<CacheKey> {
+        let kind = match operation {
+            OperationType::GetItem => CacheKind::Item,
+            OperationType::Query | OperationType::Scan => CacheKind::Query,
+            _ => return None,
+        };
+        let request: Value = serde_json::from_slice(body).ok()?;
+        let fields: Request = serde_json::from_value(request.clone()).ok()?;
+        if fields.consistent_read == Some(true) {
+            return None;
+        }
+        Some(CacheKey {
+            kind,
+            table: table_id(region, &fields.table_name?),
+            // object members are ordered by name, so equal requests serialise the same
+            request: request.to_string(),
+            item_key: fields.key,
+        })
+    }
+
+    /// Cached output of a request, counting the hit or miss
+    pub fn get(&self, key: &CacheKey) -> Option<Value> {
+        let mut cache = self.cache(key.kind).lock().unwrap();
+        let now = Instant::now();
+        let output = match cache.entries.get(&key.request) {
+            Some(entry) if entry.expires_at > now => Some(entry.output.clone()),
+            Some(_) => {
+                cache.entries.remove(&key.request);
+                None
+            }
+            None => None,
+        };
+        match output {
+            Some(_) => cache.hits += 1,
+            None => cache.misses += 1,
+        }
+        output
+    }
+
+    pub fn insert(&self, key: CacheKey, output: Value) {
+        let ttl = match key.kind {
+            CacheKind::Item => self.config.item_ttl,
+            CacheKind::Query => self.config.query_ttl,
+        };
+        let mut cache = self.cache(key.kind).lock().unwrap();
+        let now = Instant::now();
+        cache.entries.retain(|_, entry| entry.expires_at > now);
+        cache.entries.insert(
+            key.request,
+            Entry {
+                table: key.table,
+                item_key: key.item_key,
+                output,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    /// Evict the entries a successful write makes out of date
+    pub fn invalidate(&self, operation: OperationType, region: Region, body: &[u8]) {
+        let Ok(request) = serde_json::from_slice::<Request>(body) else {
+            return;
+        };
+        match operation {
+            // DAX keeps serving the results of deleted tables until they expire, but a table
+            // recreated under the same name should not see them
+            OperationType::CreateTable | OperationType::DeleteTable => {
+                if let Some(table_name) = &request.table_name {
+                    let table = table_id(region, table_name);
+                    for kind in [CacheKind::Item, CacheKind::Query] {
+                        let mut cache = self.cache(kind).lock().unwrap();
+                        cache.entries.retain(|_, entry| entry.table != table);
+                    }
+                }
+            }
+            OperationType::PutItem if self.config.invalidate_on_write => {
+                if let (Some(table_name), Some(item)) = (&request.table_name, &request.item) {
+                    self.evict_item(&table_id(region, table_name), item);
+                }
+            }
+            OperationType::UpdateItem if self.config.invalidate_on_write => {
+                if let (Some(table_name), Some(key)) = (&request.table_name, &request.key) {
+                    self.evict_item(&table_id(region, table_name), key);
+                }
+            }
+            OperationType::BatchWriteItem if self.config.invalidate_on_write => {
+                for (table_name, writes) in request.request_items.iter().flatten() {
+                    let table = table_id(region, table_name);
+                    for put_request in writes.iter().filter_map(|w| w.put_request.as_ref()) {
+                        self.evict_item(&table, &put_request.item);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn evict_item(&self, table: &str, item: &HashMap<String, AttributeValue>) {
+        let mut cache = self.items.lock().unwrap();
+        cache.entries.retain(|_, entry| {
+            let written = entry.table == table
+                && entry.item_key.as_ref().is_some_and(|key| {
+                    key.iter()
+                        .all(|(name, value)| item.get(name) == Some(value))
+                });
+            !written
+        });
+    }
+
+    /// Forget the cached results and counts, e.g. between test runs
+    pub fn clear(&self) {
+        for kind in [CacheKind::Item, CacheKind::Query] {
+            *self.cache(kind).lock().unwrap() = Cache::default();
+        }
+    }
+
+    pub fn report(&self) -> DaxReport {
+        DaxReport {
+            item_ttl_ms: self.config.item_ttl.as_millis() as u64,
+            query_ttl_ms: self.config.query_ttl.as_millis() as u64,
+            invalidate_on_write: self.config.invalidate_on_write,
+            item_cache: self.items.lock().unwrap().statistics(),
+            query_cache: self.queries.lock().unwrap().statistics(),
+        }
+    }
+
+    /// Cache hits and misses in the Prometheus text exposition format, served with the
+    /// operation metrics
+    pub fn render(&self) -> String {
+        let report = self.report();
+        let mut out = String::new();
+        let (items, queries) = (report.item_cache, report.query_cache);
+        for (name, help, item_count, query_count) in [
+            (
+                "rynamodb_dax_cache_hits_total",
+                "Reads answered from the simulated DAX cache",
+                items.hits,
+                queries.hits,
+            ),
+            (
+                "rynamodb_dax_cache_misses_total",
+                "Cacheable reads not found in the simulated DAX cache",
+                items.misses,
+                queries.misses,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name}{{cache=\"item\"}} {item_count}");
+            let _ = writeln!(out, "{name}{{cache=\"query\"}} {query_count}");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_item(value: &str) -> String {
+        format!(r#"{{"TableName": "t", "Key": {{"pk": {{"S": "{value}"}}}}}}"#)
+    }
+
+    #[test]
+    fn caches_reads_until_written() {
+        let dax = Dax::new(DaxSimulation::default());
+        let key = || {
+            dax.cache_key(
+                OperationType::GetItem,
+                Region::default(),
+                get_item("a").as_bytes(),
+            )
+            .unwrap()
+        };
+        assert_eq!(dax.get(&key()), None);
+        dax.insert(key(), serde_json::json!({"Item": {}}));
+        assert!(dax.get(&key()).is_some());
+
+        let query = br#"{"TableName": "t", "KeyConditionExpression": "pk = :a"}"#;
+        let query_key = || {
+            dax.cache_key(OperationType::Query, Region::default(), query)
+                .unwrap()
+        };
+        dax.insert(query_key(), serde_json::json!({"Items": []}));
+
+        // writing another item keeps the cached item
+        let put = |value: &str| {
+            format!(
+                r#"{{"TableName": "t", "Item": {{"pk": {{"S": "{value}"}}, "v": {{"N": "1"}}}}}}"#
+            )
+        };
+        dax.invalidate(
+            OperationType::PutItem,
+            Region::default(),
+            put("b").as_bytes(),
+        );
+        assert!(dax.get(&key()).is_some());
+        dax.invalidate(
+            OperationType::PutItem,
+            Region::default(),
+            put("a").as_bytes(),
+        );
+        assert_eq!(dax.get(&key()), None);
+        // queries are only evicted when they expire
+        assert!(dax.get(&query_key()).is_some());
+
+        let report = dax.report();
+        assert_eq!((report.item_cache.hits, report.item_cache.misses), (2, 2));
+        assert_eq!(report.item_cache.hit_rate, Some(0.5));
+        assert_eq!(report.query_cache.hits, 1);
+
+        let consistent = br#"{"TableName": "t", "Key": {}, "ConsistentRead": true}"#;
+        assert!(dax
+            .cache_key(OperationType::GetItem, Region::default(), consistent)
+            .is_none());
+        assert!(dax
+            .cache_key(
+                OperationType::PutItem,
+                Region::default(),
+                put("a").as_bytes()
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn entries_expire() {
+        let dax = Dax::new(DaxSimulation {
+            item_ttl: Duration::ZERO,
+            ..Default::default()
+        });
+        let key = || {
+            dax.cache_key(
+                OperationType::GetItem,
+                Region::default(),
+                get_item("a").as_bytes(),
+            )
+            .unwrap()
+        };
+        dax.insert(key(), serde_json::json!({}));
+        assert_eq!(dax.get(&key()), None);
+    }
+}
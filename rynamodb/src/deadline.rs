@@ -0,0 +1,51 @@
+//! Per-request deadlines
+//!
+//! Reading a table is synchronous, so a request cannot be cancelled from outside while it scans
+//! the items. Instead the reading loops check the deadline as they go, and give up (releasing any
+//! locks they hold) once it has passed.
+
+use std::time::{Duration, Instant};
+
+use crate::table::{Result, TableError};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deadline {
+    /// `None` if requests may run for as long as they need
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    /// Deadline for a request which started at `start`, with an optional timeout
+    pub fn new(start: Instant, timeout: Option<Duration>) -> Self {
+        Self {
+            at: timeout.map(|timeout| start + timeout),
+        }
+    }
+
+    /// Fail with [`TableError::DeadlineExceeded`] if the deadline has passed
+    pub fn check(&self) -> Result<()> {
+        match self.at {
+            Some(at) if Instant::now() >= at => Err(TableError::DeadlineExceeded),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passed_deadlines_fail() {
+        let start = Instant::now();
+        assert!(Deadline::default().check().is_ok());
+        assert!(Deadline::new(start, None).check().is_ok());
+        assert!(Deadline::new(start, Some(Duration::from_secs(3600)))
+            .check()
+            .is_ok());
+        assert!(matches!(
+            Deadline::new(start, Some(Duration::ZERO)).check(),
+            Err(TableError::DeadlineExceeded)
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+//! Import and export of DynamoDB Local's SQLite database files
+//!
+//! DynamoDB Local keeps its tables in a single `.db` file, with a `dm` table holding the metadata
+//! of each table, and one SQL table per DynamoDB table holding its items as DynamoDB JSON in the
+//! `ObjectJSON` column. Importing reads the key schema, attribute definitions and global
+//! secondary indexes of each table, and all of its items, so that seeded data can be carried
+//! over without rewriting fixtures.
+//!
+//! Exported files use the same layout. DynamoDB Local's internal encoding of the key columns is
+//! not reproduced; they hold the key values themselves.
+
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serde_dynamo::AttributeValue;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePool, SqliteRow},
+    Row,
+};
+
+use crate::{
+    error::{Error, Result},
+    table::{names::Item, size, Table, TableOptions},
+    table_manager::Region,
+    types,
+};
+
+/// Metadata of a table, stored as JSON in the `TableInfo` column of `dm`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct TableInfo {
+    #[serde(default)]
+    attributes: Vec<types::AttributeDefinition>,
+    key_schema: Vec<types::KeySchema>,
+    #[serde(default, rename = "GSIList")]
+    gsi_list: Vec<IndexInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    table_status: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct IndexInfo {
+    index_name: String,
+    key_schema: Vec<types::KeySchema>,
+    #[serde(default)]
+    projection: types::Projection,
+}
+
+/// Read every table in a DynamoDB Local database, creating them in the given account and region
+pub async fn import(
+    path: impl AsRef<Path>,
+    region: Region,
+    account_id: &str,
+) -> Result<Vec<Table>> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = SqlitePool::connect_with(options).await?;
+
+    let rows = sqlx::query("SELECT TableName, CAST(TableInfo AS BLOB) AS TableInfo FROM dm")
+        .fetch_all(&pool)
+        .await?;
+    let mut tables = Vec::with_capacity(rows.len());
+    for row in rows {
+        let name: String = row.try_get("TableName")?;
+        let info: Vec<u8> = row.try_get("TableInfo")?;
+        let info: TableInfo = serde_json::from_slice(&info)
+            .map_err(|e| Error::InvalidDynamodbLocal(format!("metadata of table {name}: {e}")))?;
+
+        let mut table = Table::new(region, account_id, table_options(name.clone(), info));
+        let items = sqlx::query(&format!(
+            "SELECT CAST(ObjectJSON AS BLOB) AS ObjectJSON FROM {}",
+            quote_identifier(&name)
+        ))
+        .map(|row: SqliteRow| row.try_get::<Vec<u8>, _>("ObjectJSON"))
+        .fetch_all(&pool)
+        .await?;
+        for item in items {
+            let item: HashMap<String, AttributeValue> = serde_json::from_slice(&item?)
+                .map_err(|e| Error::InvalidDynamodbLocal(format!("item of table {name}: {e}")))?;
+            table.insert(item)?;
+        }
+        tracing::debug!(table_name = %name, items = table.iter().count(), "imported table");
+        tables.push(table);
+    }
+    pool.close().await;
+    Ok(tables)
+}
+
+fn table_options(name: String, info: TableInfo) -> TableOptions {
+    types::CreateTableInput {
+        table_name: name,
+        attribute_definitions: info.attributes,
+        key_schema: info.key_schema,
+        sse_specification: None,
+        on_demand_throughput: None,
+        warm_throughput: None,
+        global_secondary_indexes: Some(
+            info.gsi_list
+                .into_iter()
+                .map(|index| types::GlobalSecondaryIndex {
+                    index_name: index.index_name,
+                    key_schema: index.key_schema,
+                    projection: index.projection,
+                })
+                .collect(),
+        ),
+    }
+    .into()
+}
+
+/// Write tables to a new DynamoDB Local database
+pub async fn export(path: impl AsRef<Path>, tables: &[Table]) -> Result<()> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await?;
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS dm (TableName TEXT, CreationDateTime INTEGER, \
+         LastDecreaseDate INTEGER, LastIncreaseDate INTEGER, NumberOfDecreasesToday INTEGER, \
+         ReadCapacityUnits INTEGER, WriteCapacityUnits INTEGER, TableInfo BLOB, \
+         BillingMode INTEGER DEFAULT 0, PayPerRequestDateTime INTEGER DEFAULT 0, \
+         PRIMARY KEY(TableName))",
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    for table in tables {
+        let schema = table.schema();
+        let key_schema = schema.key_schema();
+        let info = TableInfo {
+            key_schema: key_schema.clone(),
+            gsi_list: schema
+                .global_secondary_indexes
+                .iter()
+                .map(|index| IndexInfo {
+                    index_name: index.index_name.clone(),
+                    key_schema: index.key_schema(),
+                    projection: index.projection.clone(),
+                })
+                .collect(),
+            attributes: schema.attribute_definitions,
+            table_status: Some("ACTIVE".to_string()),
+        };
+        let info = serde_json::to_vec(&info).map_err(|e| {
+            Error::InvalidDynamodbLocal(format!("metadata of table {}: {e}", table.name))
+        })?;
+
+        // capacity is not enforced by either emulator, so it is not carried over
+        sqlx::query(
+            "INSERT OR REPLACE INTO dm (TableName, CreationDateTime, LastDecreaseDate, \
+             LastIncreaseDate, NumberOfDecreasesToday, ReadCapacityUnits, WriteCapacityUnits, \
+             TableInfo) VALUES ($1, $2, 0, 0, 0, 0, 0, $3)",
+        )
+        .bind(&table.name)
+        .bind(table.created_at.timestamp_millis())
+        .bind(info)
+        .execute(&mut transaction)
+        .await?;
+
+        let items_table = quote_identifier(&table.name);
+        sqlx::query(&format!("DROP TABLE IF EXISTS {items_table}"))
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query(&format!(
+            "CREATE TABLE {items_table} (hashKey BLOB, rangeKey BLOB, hashValue BLOB NOT NULL, \
+             rangeValue BLOB NOT NULL, itemSize INTEGER DEFAULT 0, ObjectJSON BLOB NOT NULL, \
+             PRIMARY KEY(hashKey, rangeKey))"
+        ))
+        .execute(&mut transaction)
+        .await?;
+
+        let key_value = |item: &Item, key_type: types::KeyType| {
+            key_schema
+                .iter()
+                .find(|key| key.key_type == key_type)
+                .and_then(|key| item.get(key.attribute_name.as_str()))
+                .map(key_bytes)
+                .unwrap_or_default()
+        };
+        for item in table.iter() {
+            let hash_key = key_value(item, types::KeyType::HASH);
+            let range_key = key_value(item, types::KeyType::RANGE);
+            let object = serde_json::to_vec(item).map_err(|e| {
+                Error::InvalidDynamodbLocal(format!("item of table {}: {e}", table.name))
+            })?;
+            sqlx::query(&format!(
+                "INSERT INTO {items_table} (hashKey, rangeKey, hashValue, rangeValue, itemSize, \
+                 ObjectJSON) VALUES ($1, $2, $1, $2, $3, $4)"
+            ))
+            .bind(hash_key)
+            .bind(range_key)
+            .bind(size::item_size(item) as i64)
+            .bind(object)
+            .execute(&mut transaction)
+            .await?;
+        }
+        tracing::debug!(table_name = %table.name, "exported table");
+    }
+
+    transaction.commit().await?;
+    pool.close().await;
+    Ok(())
+}
+
+fn key_bytes(value: &AttributeValue) -> Vec<u8> {
+    match value {
+        AttributeValue::S(s) | AttributeValue::N(s) => s.as_bytes().to_vec(),
+        AttributeValue::B(b) => b.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Quote a table name for use as an SQL identifier
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_trip() {
+        let path = std::env::temp_dir().join(format!("rynamodb-{}.db", uuid::Uuid::new_v4()));
+
+        let info: TableInfo = serde_json::from_str(
+            r#"{
+                "Attributes": [
+                    {"AttributeName": "pk", "AttributeType": "S"},
+                    {"AttributeName": "sk", "AttributeType": "N"}
+                ],
+                "KeySchema": [
+                    {"AttributeName": "pk", "KeyType": "HASH"},
+                    {"AttributeName": "sk", "KeyType": "RANGE"}
+                ],
+                "GSIList": [],
+                "TableStatus": "ACTIVE"
+            }"#,
+        )
+        .unwrap();
+        let mut table = Table::new(
+            Region::UsEast1,
+            crate::DEFAULT_ACCOUNT_ID,
+            table_options("fixtures".to_string(), info),
+        );
+        for sk in ["1", "2"] {
+            table
+                .insert(
+                    [
+                        ("pk".to_string(), AttributeValue::S("abc".to_string())),
+                        ("sk".to_string(), AttributeValue::N(sk.to_string())),
+                    ]
+                    .into(),
+                )
+                .unwrap();
+        }
+
+        export(&path, &[table]).await.unwrap();
+        let tables = import(&path, Region::EuWest1, crate::DEFAULT_ACCOUNT_ID)
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].name, "fixtures");
+        assert_eq!(tables[0].iter().count(), 2);
+        let description = tables[0].description();
+        assert_eq!(description.key_schema.unwrap().len(), 2);
+        assert_eq!(
+            description.table_arn.as_deref(),
+            Some("arn:aws:dynamodb:eu-west-1:000000000000:table/fixtures")
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Support for running behind a LocalStack style edge proxy
+//!
+//! Edge proxies serve many AWS services from one port, and route each request either by the
+//! service named in its `Host` header, e.g. `dynamodb.us-east-1.localhost.localstack.cloud`, or
+//! by a path prefix such as `/dynamodb/`.
+
+use std::net::IpAddr;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+
+/// Host name labels which address this emulator: the service itself, DynamoDB streams, and the
+/// generic LocalStack host names which route by the request contents instead
+const SERVICE_LABELS: &[&str] = &["dynamodb", "streams", "localhost"];
+
+/// How requests are routed to the emulator when it shares a port with other services
+#[derive(Debug, Clone, Default)]
+pub struct EdgeRouting {
+    /// serve the emulator under this path, e.g. `/dynamodb`, instead of at the root
+    pub path_prefix: Option<String>,
+    /// reject requests whose `Host` header names a service other than DynamoDB, e.g.
+    /// `s3.localhost.localstack.cloud`. Hosts without a service name, such as `localhost` or IP
+    /// addresses, are always accepted.
+    pub host_routing: bool,
+}
+
+impl EdgeRouting {
+    pub(crate) fn apply(self, router: Router) -> Router {
+        let prefix = self
+            .path_prefix
+            .as_deref()
+            .map(|prefix| prefix.trim_matches('/'))
+            .filter(|prefix| !prefix.is_empty());
+        let router = match prefix {
+            // unlike `nest`, `nest_service` also matches the prefix with a trailing slash, which
+            // is where the SDKs send requests when given an endpoint with a path
+            Some(prefix) => Router::new().nest_service(&format!("/{prefix}"), router),
+            None => router,
+        };
+        if self.host_routing {
+            router.layer(middleware::from_fn(route_by_host))
+        } else {
+            router
+        }
+    }
+}
+
+async fn route_by_host(request: Request<Body>, next: Next<Body>) -> Response {
+    // HTTP/2 requests carry the host in the URI rather than a header
+    let host = request.uri().host().or_else(|| {
+        request
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+    });
+    if host.is_none_or(is_service_host) {
+        next.run(request).await
+    } else {
+        tracing::debug!(?host, "rejecting request for another service");
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
+
+fn is_service_host(host: &str) -> bool {
+    // IPv6 addresses are bracketed, and never name a service
+    if host.starts_with('[') {
+        return true;
+    }
+    let host = host.split_once(':').map_or(host, |(host, _port)| host);
+    if host.parse::<IpAddr>().is_ok() {
+        return true;
+    }
+    match host.split_once('.') {
+        Some((label, _)) => SERVICE_LABELS.contains(&label.to_ascii_lowercase().as_str()),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_hosts() {
+        for host in [
+            "localhost",
+            "localhost:4566",
+            "127.0.0.1:4566",
+            "[::1]:4566",
+            "rynamodb",
+            "dynamodb.us-east-1.localhost.localstack.cloud:4566",
+            "DynamoDB.eu-west-1.amazonaws.com",
+            "streams.dynamodb.us-east-1.amazonaws.com",
+            "localhost.localstack.cloud:4566",
+        ] {
+            assert!(is_service_host(host), "{host} should be accepted");
+        }
+
+        for host in [
+            "s3.localhost.localstack.cloud:4566",
+            "sqs.us-east-1.amazonaws.com",
+        ] {
+            assert!(!is_service_host(host), "{host} should be rejected");
+        }
+    }
+}
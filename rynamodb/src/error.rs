@@ -0,0 +1,32 @@
+//! Errors returned by the library API
+//!
+//! These are what embedders see when calling into the emulator directly. Errors returned to
+//! clients over HTTP are converted into `ErrorResponse`s instead.
+
+use thiserror::Error;
+
+use crate::{table::TableError, table_manager::LimitExceeded};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Table(#[from] TableError),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+    #[error("reading or writing a file")]
+    Io(#[from] std::io::Error),
+    #[error("invalid settings: {0}")]
+    InvalidSettings(String),
+    #[error("invalid journal: {0}")]
+    InvalidJournal(String),
+    #[error("server shut down incorrectly")]
+    Server(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "dynamodb-local")]
+    #[error("accessing DynamoDB Local database")]
+    DynamodbLocal(#[from] sqlx::Error),
+    #[cfg(feature = "dynamodb-local")]
+    #[error("invalid DynamoDB Local database: {0}")]
+    InvalidDynamodbLocal(String),
+}
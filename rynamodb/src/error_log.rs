@@ -0,0 +1,131 @@
+//! Record of the errors returned to clients, to help debug requests rejected by the emulator
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{errors::ErrorResponse, OperationType};
+
+/// only keep the most recent entries
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorEntry {
+    /// `None` if the operation could not be determined
+    pub operation: Option<String>,
+    pub table_name: Option<String>,
+    /// error type without the namespace, e.g. `ValidationException`
+    pub error_type: String,
+    pub message: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorSummary {
+    /// number of errors returned of each type, since the emulator started
+    pub counts: BTreeMap<String, u64>,
+    /// most recent errors, oldest first
+    pub recent: Vec<ErrorEntry>,
+}
+
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    counts: BTreeMap<String, u64>,
+    entries: VecDeque<ErrorEntry>,
+}
+
+impl ErrorLog {
+    pub fn record(
+        &self,
+        operation: Option<OperationType>,
+        table_name: Option<String>,
+        error: &ErrorResponse,
+    ) {
+        let (error_type, message) = describe(error);
+        let entry = ErrorEntry {
+            operation: operation.map(|operation| format!("{operation:?}")),
+            table_name,
+            error_type,
+            message,
+            at: Utc::now(),
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        *inner.counts.entry(entry.error_type.clone()).or_default() += 1;
+        if inner.entries.len() == MAX_ENTRIES {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(entry);
+    }
+
+    pub fn summary(&self) -> ErrorSummary {
+        let inner = self.inner.lock().unwrap();
+        ErrorSummary {
+            counts: inner.counts.clone(),
+            recent: inner.entries.iter().cloned().collect(),
+        }
+    }
+}
+
+// use the error as it is sent to the client, so that the log matches what the client saw
+pub(crate) fn describe(error: &ErrorResponse) -> (String, Option<String>) {
+    let value = serde_json::to_value(error).unwrap_or_default();
+    let error_type = match value.get("__type").and_then(|t| t.as_str()) {
+        Some(error_type) => error_type
+            .rsplit_once('#')
+            .map_or(error_type, |(_, name)| name)
+            .to_string(),
+        None => "InternalServerError".to_string(),
+    };
+    let message = ["message", "Message", "error"]
+        .iter()
+        .find_map(|key| value.get(key)?.as_str())
+        .map(ToString::to_string);
+    (error_type, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_recent_errors() {
+        let log = ErrorLog::default();
+        for i in 0..MAX_ENTRIES + 5 {
+            log.record(
+                Some(OperationType::PutItem),
+                Some(format!("table-{i}")),
+                &ErrorResponse::ValidationError("bad".to_string()),
+            );
+        }
+        log.record(None, None, &ErrorResponse::InvalidOperation(String::new()));
+
+        let summary = log.summary();
+        assert_eq!(
+            summary.counts["ValidationException"],
+            MAX_ENTRIES as u64 + 5
+        );
+        assert_eq!(summary.counts["UnknownOperationException"], 1);
+        assert_eq!(summary.recent.len(), MAX_ENTRIES);
+
+        let first = &summary.recent[0];
+        assert_eq!(first.operation.as_deref(), Some("PutItem"));
+        assert_eq!(first.table_name.as_deref(), Some("table-6"));
+        assert_eq!(first.message.as_deref(), Some("bad"));
+
+        let last = summary.recent.last().unwrap();
+        assert_eq!(last.error_type, "UnknownOperationException");
+        assert_eq!(last.operation, None);
+    }
+}
@@ -7,13 +7,86 @@ use axum::{
 };
 use serde::ser::SerializeMap;
 
+use crate::{table::TableError, table_manager::LimitExceeded, Error};
+
 #[derive(Debug)]
 pub enum ErrorResponse {
-    ResourceNotFound { name: Option<String> },
-    SerializationError,
-    RynamodbError(Box<dyn std::error::Error>),
+    ResourceNotFound {
+        name: Option<String>,
+    },
+    SerializationError {
+        message: Option<String>,
+    },
+    RynamodbError(Box<dyn std::error::Error + Send + Sync>),
     MutexUnlock,
     InvalidOperation(String),
+    /// a DynamoDB operation which the emulator does not implement
+    NotImplemented(String),
+    /// behaviour which the emulator does not implement, named by the feature, see
+    /// [`crate::unimplemented`]
+    NotImplementedByRynamodb(String),
+    MissingAuthenticationToken,
+    ValidationError(String),
+    TableAlreadyExists(String),
+    SourceTableNotFound(String),
+    PointInTimeRecoveryUnavailable(String),
+    InvalidRestoreTime,
+    ResourceInUse(String),
+    LimitExceeded(String),
+    InternalServerError(String),
+    /// returned, and the connection closed, when the server cannot take more connections
+    ServiceUnavailable(String),
+}
+
+impl From<TableError> for ErrorResponse {
+    fn from(e: TableError) -> Self {
+        match e {
+            // expression placeholders are only used in key conditions for now
+            TableError::NoAttributeName(_) | TableError::NoAttributeValue(_) => {
+                Self::ValidationError(format!("Invalid KeyConditionExpression: {e}"))
+            }
+            TableError::ParseError(source) => {
+                Self::ValidationError(format!("Invalid KeyConditionExpression: {source}"))
+            }
+            TableError::InvalidKey
+            | TableError::InvalidCondition(_)
+            | TableError::MissingKeyCondition(_)
+            | TableError::UnsupportedKeyCondition
+            | TableError::InvalidParameter(_)
+            | TableError::InvalidTimeToLive(_)
+            | TableError::InvalidNumber
+            | TableError::EmptyIndexKey { .. }
+            | TableError::IndexNotFound(_)
+            | TableError::InvalidKeyConditionExpression(_)
+            | TableError::InvalidStartKey(_)
+            | TableError::InvalidUpdateExpression(_) => Self::ValidationError(e.to_string()),
+            TableError::PointInTimeRecoveryUnavailable(name) => {
+                Self::PointInTimeRecoveryUnavailable(name)
+            }
+            TableError::InvalidRestoreTime => Self::InvalidRestoreTime,
+            TableError::TableAlreadyExists(name) => Self::TableAlreadyExists(name),
+            TableError::DeadlineExceeded => Self::InternalServerError(e.to_string()),
+            TableError::NotImplemented(feature) => Self::NotImplementedByRynamodb(feature),
+            e => Self::RynamodbError(Box::new(e)),
+        }
+    }
+}
+
+impl From<Error> for ErrorResponse {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Table(e) => e.into(),
+            Error::LimitExceeded(e) => e.into(),
+            Error::InvalidSettings(_) => Self::ValidationError(e.to_string()),
+            e => Self::RynamodbError(Box::new(e)),
+        }
+    }
+}
+
+impl From<LimitExceeded> for ErrorResponse {
+    fn from(e: LimitExceeded) -> Self {
+        Self::LimitExceeded(e.to_string())
+    }
 }
 
 // How to encode the errors
@@ -38,8 +111,12 @@ impl serde::Serialize for ErrorResponse {
                     map.serialize_entry("message", "Requested resource not found")?;
                 }
             }
-            Self::SerializationError => {
+            Self::SerializationError { message } => {
                 map.serialize_entry("__type", "com.amazon.coral.service#SerializationException")?;
+                // unlike the DynamoDB errors, coral service errors capitalise the message key
+                if let Some(message) = message {
+                    map.serialize_entry("Message", message)?;
+                }
             }
             Self::RynamodbError(inner) => {
                 map.serialize_entry("error", &inner.to_string())?;
@@ -47,17 +124,122 @@ impl serde::Serialize for ErrorResponse {
             Self::MutexUnlock => {
                 map.serialize_entry("error", "corrupted internal state")?;
             }
-            Self::InvalidOperation(name) => {
-                map.serialize_entry("error", &format!("invalid response: {name}"))?;
+            Self::InvalidOperation(_) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazon.coral.service#UnknownOperationException",
+                )?;
+            }
+            Self::NotImplemented(operation) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazon.coral.service#UnknownOperationException",
+                )?;
+                map.serialize_entry(
+                    "message",
+                    &format!(
+                        "Operation {operation} is not implemented by rynamodb, please request it at {}",
+                        crate::operations::ISSUES_URL
+                    ),
+                )?;
+            }
+            Self::NotImplementedByRynamodb(feature) => {
+                map.serialize_entry("__type", "rynamodb#NotImplementedByRynamodb")?;
+                map.serialize_entry(
+                    "message",
+                    &format!(
+                        "{feature} is not implemented by rynamodb, please request it at {}",
+                        crate::operations::ISSUES_URL
+                    ),
+                )?;
+            }
+            Self::MissingAuthenticationToken => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazon.coral.service#MissingAuthenticationTokenException",
+                )?;
+                map.serialize_entry("Message", "Missing Authentication Token")?;
+            }
+            Self::ValidationError(message) => {
+                map.serialize_entry("__type", "com.amazon.coral.validate#ValidationException")?;
+                map.serialize_entry("message", message)?;
+            }
+            Self::TableAlreadyExists(name) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#TableAlreadyExistsException",
+                )?;
+                map.serialize_entry("message", &format!("Table already exists: {name}"))?;
+            }
+            Self::SourceTableNotFound(name) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#SourceTableNotFoundException",
+                )?;
+                map.serialize_entry("message", &format!("Source table not found: {name}"))?;
+            }
+            Self::PointInTimeRecoveryUnavailable(name) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#PointInTimeRecoveryUnavailableException",
+                )?;
+                map.serialize_entry(
+                    "message",
+                    &format!("Point in time recovery is not enabled for table '{name}'"),
+                )?;
+            }
+            Self::InvalidRestoreTime => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#InvalidRestoreTimeException",
+                )?;
+                map.serialize_entry(
+                    "message",
+                    "Restore time must be between the earliest and latest restorable date times",
+                )?;
+            }
+            Self::ResourceInUse(reason) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#ResourceInUseException",
+                )?;
+                map.serialize_entry(
+                    "message",
+                    &format!("Attempt to change a resource which is still in use: {reason}"),
+                )?;
+            }
+            Self::LimitExceeded(message) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#LimitExceededException",
+                )?;
+                map.serialize_entry("message", message)?;
+            }
+            Self::InternalServerError(message) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#InternalServerError",
+                )?;
+                map.serialize_entry("message", message)?;
+            }
+            Self::ServiceUnavailable(message) => {
+                map.serialize_entry(
+                    "__type",
+                    "com.amazonaws.dynamodb.v20120810#ServiceUnavailable",
+                )?;
+                map.serialize_entry("message", message)?;
             }
         }
         map.end()
     }
 }
 
+/// Content type of all responses to DynamoDB operations, successful or not
+pub const AWS_JSON_CONTENT_TYPE: &str = "application/x-amz-json-1.0";
+
 impl IntoResponse for ErrorResponse {
     fn into_response(self) -> axum::response::Response {
-        match self {
+        let mut response = match self {
             ErrorResponse::ResourceNotFound { .. } => {
                 let request_id = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
                 let mut headers = HeaderMap::new();
@@ -65,21 +247,39 @@ impl IntoResponse for ErrorResponse {
                     header::HeaderName::from_static("x-amzn-requestid"),
                     request_id.parse().unwrap(),
                 );
-                headers.insert(
-                    header::CONTENT_TYPE,
-                    HeaderValue::from_static("application/x-amz-json-1.0"),
-                );
                 headers.insert(header::CONNECTION, HeaderValue::from_static("keep-alive"));
 
                 (StatusCode::BAD_REQUEST, headers, Json(self)).into_response()
             }
-            ErrorResponse::SerializationError | ErrorResponse::InvalidOperation(_) => {
+            ErrorResponse::SerializationError { .. }
+            | ErrorResponse::InvalidOperation(_)
+            | ErrorResponse::NotImplemented(_)
+            | ErrorResponse::NotImplementedByRynamodb(_)
+            | ErrorResponse::MissingAuthenticationToken
+            | ErrorResponse::ValidationError(_)
+            | ErrorResponse::TableAlreadyExists(_)
+            | ErrorResponse::SourceTableNotFound(_)
+            | ErrorResponse::PointInTimeRecoveryUnavailable(_)
+            | ErrorResponse::InvalidRestoreTime
+            | ErrorResponse::ResourceInUse(_)
+            | ErrorResponse::LimitExceeded(_) => {
                 (StatusCode::BAD_REQUEST, Json(self)).into_response()
             }
-            ErrorResponse::RynamodbError(_) | ErrorResponse::MutexUnlock => {
+            ErrorResponse::RynamodbError(_)
+            | ErrorResponse::MutexUnlock
+            | ErrorResponse::InternalServerError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, Json(self)).into_response()
             }
-        }
+            ErrorResponse::ServiceUnavailable(_) => {
+                let headers = [(header::CONNECTION, HeaderValue::from_static("close"))];
+                (StatusCode::SERVICE_UNAVAILABLE, headers, Json(self)).into_response()
+            }
+        };
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(AWS_JSON_CONTENT_TYPE),
+        );
+        response
     }
 }
 
@@ -94,4 +294,17 @@ mod tests {
         let val = serde_json::to_string(&e).unwrap();
         assert_eq!(val, r#"{"error":"bad"}"#);
     }
+
+    #[test]
+    fn undefined_placeholder_is_validation_error() {
+        let e: ErrorResponse = TableError::NoAttributeValue(":v".to_string()).into();
+        let val = serde_json::to_value(&e).unwrap();
+        assert_eq!(
+            val,
+            serde_json::json!({
+                "__type": "com.amazon.coral.validate#ValidationException",
+                "message": "Invalid KeyConditionExpression: An expression attribute value used in expression is not defined; attribute value: :v",
+            })
+        );
+    }
 }
@@ -0,0 +1,81 @@
+//! Changes to tables and their items, published to subscribers
+//!
+//! Features which react to writes, and embedders, subscribe with [`crate::Tables::subscribe`]
+//! rather than hooking each operation handler. Events are published as the changes are applied,
+//! whichever operation or background task made them, so a subscriber sees batch writes, copies
+//! and time to live expiry like any other change. Changes are only described while there is a
+//! subscriber.
+//!
+//! Subscribers which fall behind by more than [`CAPACITY`] events miss the oldest of them, see
+//! [`broadcast::error::RecvError::Lagged`].
+
+use std::collections::HashMap;
+
+use serde_dynamo::AttributeValue;
+use tokio::sync::broadcast;
+
+use crate::table_manager::Region;
+
+/// events buffered for each subscriber
+pub const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableEvent {
+    TableCreated {
+        table_name: String,
+        region: Region,
+    },
+    /// deletion of the table was requested
+    TableDeleted {
+        table_name: String,
+        region: Region,
+    },
+    /// an item was written, replacing `old_item` if the table already held an item with its key
+    ItemPut {
+        table_name: String,
+        item: HashMap<String, AttributeValue>,
+        old_item: Option<HashMap<String, AttributeValue>>,
+    },
+    ItemDeleted {
+        table_name: String,
+        /// the item as it was before it was deleted
+        old_item: HashMap<String, AttributeValue>,
+        reason: DeletionReason,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletionReason {
+    /// removed by a time to live sweep
+    Expired,
+}
+
+/// Sender shared by a table manager and its tables
+#[derive(Debug, Clone)]
+pub struct Events {
+    sender: broadcast::Sender<TableEvent>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Events {
+    pub fn subscribe(&self) -> broadcast::Receiver<TableEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Whether anyone is subscribed, so that events are only built when they will be received
+    pub fn is_subscribed(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn send(&self, event: TableEvent) {
+        // sending only fails when there are no subscribers
+        let _ = self.sender.send(event);
+    }
+}
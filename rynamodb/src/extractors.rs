@@ -1,18 +1,54 @@
+use std::{convert::Infallible, time::Duration};
+
 use axum::{
     async_trait,
     body::{Bytes, HttpBody},
     extract::{FromRequest, FromRequestParts},
-    http::{request::Parts, HeaderName, HeaderValue, Request},
+    http::{request::Parts, HeaderName, Request},
     BoxError,
 };
 use serde::de::DeserializeOwned;
 
-// JSON type that accepts aws content-type
-//
-// Copied directly from the axum source code
+use crate::{
+    errors::ErrorResponse,
+    namespaces::{NAMESPACE_HEADER, NAMESPACE_TTL_HEADER},
+    table_manager::Region,
+    validation,
+};
+
+/// JSON body of an operation
+///
+/// Unlike `axum::Json`, this does not require the `Content-Type: application/json` header, which
+/// the SDK does not send, and rejects invalid input with the errors DynamoDB returns.
 #[derive(Debug, Clone, Copy, Default)]
 pub struct AwsJson<T>(pub T);
 
+impl<T> AwsJson<T>
+where
+    T: DeserializeOwned,
+{
+    /// Deserialize the input of an operation
+    ///
+    /// An empty body is an operation without any input parameters. Inputs which fail to parse
+    /// because of a malformed attribute value are reported with the location of that value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ErrorResponse> {
+        let bytes = if bytes.trim_ascii().is_empty() {
+            b"{}"
+        } else {
+            bytes
+        };
+        serde_json::from_slice(bytes).map(AwsJson).map_err(|e| {
+            tracing::debug!(error = %e, "invalid input");
+            match serde_json::from_slice(bytes) {
+                Ok(value) => validation::check_attribute_values(&value)
+                    .err()
+                    .unwrap_or(ErrorResponse::SerializationError { message: None }),
+                Err(_) => ErrorResponse::SerializationError { message: None },
+            }
+        })
+    }
+}
+
 #[async_trait]
 impl<T, S, B> FromRequest<S, B> for AwsJson<T>
 where
@@ -22,45 +58,71 @@ where
     B::Error: Into<BoxError>,
     S: Send + Sync,
 {
-    type Rejection = String;
+    type Rejection = ErrorResponse;
 
     async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
-        // TODO check content-type header
-
-        let bytes = Bytes::from_request(req, state)
-            .await
-            .map_err(|e| format!("fetching body bytes: {e:?}"))?;
-        let res =
-            serde_json::from_slice(&bytes).map_err(|e| format!("deserializing body: {e:?}"))?;
-
-        Ok(AwsJson(res))
+        let bytes = Bytes::from_request(req, state).await.map_err(|e| {
+            tracing::debug!(error = %e, "fetching body bytes");
+            ErrorResponse::SerializationError { message: None }
+        })?;
+        Self::from_bytes(&bytes)
     }
 }
 
 /// Extractor for dynamodb operation
+///
+/// The operation is named by the `x-amz-target` header, normally prefixed by the service and API
+/// version as in `DynamoDB_20120810.PutItem`. Some clients send the bare operation name instead,
+/// with the API version in the `x-amz-api-version` header.
 #[derive(Debug)]
 pub struct Operation {
     pub version: String,
     pub name: crate::OperationType,
 }
 
-impl TryFrom<&HeaderValue> for Operation {
-    // error does not matter because we map it away anyway
-    type Error = String;
+/// The only API version of DynamoDB
+pub const API_VERSION: &str = "20120810";
 
-    fn try_from(value: &HeaderValue) -> std::result::Result<Self, Self::Error> {
-        let s = value
-            .to_str()
-            .map_err(|e| format!("converting to string: {e:?}"))?;
-        let mut parts = s.splitn(2, '.');
-        let version = parts.next().ok_or("invalid number of parts".to_string())?;
-        let operation = parts.next().ok_or("invalid number of parts".to_string())?;
+const SERVICE: &str = "DynamoDB";
 
+impl Operation {
+    fn parse(target: &str, api_version: Option<&str>) -> Result<Self, ErrorResponse> {
+        let (version, operation) = match target.split_once('.') {
+            Some((prefix, operation)) => {
+                let Some(version) = prefix
+                    .strip_prefix(SERVICE)
+                    .and_then(|rest| rest.strip_prefix('_'))
+                else {
+                    // DynamoDB cannot tell which service the request is for, so cannot
+                    // authenticate it
+                    return Err(ErrorResponse::MissingAuthenticationToken);
+                };
+                if let Some(api_version) = api_version.filter(|v| *v != version) {
+                    return Err(ErrorResponse::InvalidOperation(format!(
+                        "target version {version} does not match API version {api_version}"
+                    )));
+                }
+                (version, operation)
+            }
+            None => (api_version.unwrap_or(API_VERSION), target),
+        };
+
+        if version != API_VERSION {
+            return Err(ErrorResponse::InvalidOperation(format!(
+                "unsupported API version {version}"
+            )));
+        }
+
+        let name = operation.parse().map_err(|e| {
+            if crate::operations::is_dynamodb_operation(operation) {
+                ErrorResponse::NotImplemented(operation.to_string())
+            } else {
+                ErrorResponse::InvalidOperation(e)
+            }
+        })?;
         Ok(Self {
             version: version.to_string(),
-            name: operation
-                .parse()
-                .map_err(|e| format!("parsing operation: {e:?}"))?,
+            name,
         })
     }
 }
@@ -70,17 +132,317 @@ impl<S> FromRequestParts<S> for Operation
 where
     S: Send + Sync,
 {
-    type Rejection = String;
+    type Rejection = ErrorResponse;
 
     async fn from_request_parts(
         parts: &mut Parts,
         _state: &S,
     ) -> std::result::Result<Self, Self::Rejection> {
-        if let Some(raw_target_string) = parts.headers.get(HeaderName::from_static("x-amz-target"))
-        {
-            raw_target_string.try_into()
-        } else {
-            Err("missing_target_header".to_string())
+        let headers = &parts.headers;
+        let header = |name| {
+            headers
+                .get(HeaderName::from_static(name))
+                .map(|value| {
+                    value.to_str().map_err(|e| {
+                        ErrorResponse::InvalidOperation(format!("invalid {name} header: {e}"))
+                    })
+                })
+                .transpose()
+        };
+
+        let target = header("x-amz-target")?
+            .ok_or_else(|| ErrorResponse::InvalidOperation("missing target header".to_string()))?;
+        Self::parse(target.trim(), header("x-amz-api-version")?.map(str::trim))
+    }
+}
+
+/// Header a test fixture can send to identify its requests, echoed back in the response
+pub const TEST_ID_HEADER: &str = "x-rynamodb-test-id";
+
+/// Extractor for the `x-rynamodb-test-id` header, which fixture frameworks send to correlate
+/// their requests with the emulator's logs and journal. Missing or non-UTF-8 values are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct TestId(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for TestId
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(Self(
+            parts
+                .headers
+                .get(TEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string),
+        ))
+    }
+}
+
+/// Extractor for the headers the AWS SDKs send to identify each call and its retries
+///
+/// `amz-sdk-invocation-id` is the same for every attempt of a call, and `amz-sdk-request` numbers
+/// the attempts, as in `attempt=2; max=3`. Both are optional, so missing or malformed headers are
+/// ignored.
+#[derive(Debug, Default, Clone)]
+pub struct Invocation {
+    pub id: Option<String>,
+    pub attempt: Option<u32>,
+    pub max_attempts: Option<u32>,
+}
+
+impl Invocation {
+    fn parse(id: Option<&str>, request: Option<&str>) -> Self {
+        let mut invocation = Self {
+            id: id.map(|id| id.trim().to_string()),
+            ..Default::default()
+        };
+        for field in request.unwrap_or_default().split(';') {
+            match field.trim().split_once('=') {
+                Some(("attempt", value)) => invocation.attempt = value.trim().parse().ok(),
+                Some(("max", value)) => invocation.max_attempts = value.trim().parse().ok(),
+                _ => {}
+            }
         }
+        invocation
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Invocation
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let header = |name| {
+            parts
+                .headers
+                .get(HeaderName::from_static(name))
+                .and_then(|value| value.to_str().ok())
+        };
+        Ok(Self::parse(
+            header("amz-sdk-invocation-id"),
+            header("amz-sdk-request"),
+        ))
+    }
+}
+
+/// Extractor for the namespace a request is scoped to, see [`crate::namespaces`]
+///
+/// Requests without the header, or with an empty one, use the default namespace.
+#[derive(Debug, Default, Clone)]
+pub struct Namespace {
+    pub name: Option<String>,
+    /// time to live of the namespace, if the request changes it
+    pub ttl: Option<Duration>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Namespace
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let header = |name| {
+            parts
+                .headers
+                .get(HeaderName::from_static(name))
+                .map(|value| {
+                    value.to_str().map(str::trim).map_err(|_| {
+                        ErrorResponse::ValidationError(format!("invalid {name} header"))
+                    })
+                })
+                .transpose()
+        };
+        let name = header(NAMESPACE_HEADER)?.filter(|name| !name.is_empty());
+        let ttl = header(NAMESPACE_TTL_HEADER)?
+            .map(|ttl| {
+                ttl.parse().map(Duration::from_secs).map_err(|_| {
+                    ErrorResponse::ValidationError(format!(
+                        "{NAMESPACE_TTL_HEADER} must be a number of seconds"
+                    ))
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            name: name.map(str::to_string),
+            ttl,
+        })
+    }
+}
+
+/// Extractor for the region a request was signed for, from the credential scope of its SigV4
+/// `Authorization` header, e.g. `Credential=AKID/20240101/eu-west-1/dynamodb/aws4_request`
+///
+/// Unsigned requests name no region and use the configured one. Requests signed for a region
+/// which DynamoDB is not available in are rejected, rather than creating tables in another region.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RequestRegion(pub Option<Region>);
+
+impl RequestRegion {
+    fn parse(authorization: &str) -> Result<Self, ErrorResponse> {
+        let Some(region) = authorization
+            .split_once("Credential=")
+            .and_then(|(_, credential)| credential.split([',', ' ']).next())
+            .and_then(|scope| scope.split('/').nth(2))
+        else {
+            return Ok(Self(None));
+        };
+        region
+            .parse()
+            .map(|region| Self(Some(region)))
+            .map_err(|_| {
+                ErrorResponse::ValidationError(format!(
+                    "The request is signed for region {region}, which rynamodb does not support"
+                ))
+            })
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestRegion
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(Self::parse)
+            .unwrap_or(Ok(Self(None)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OperationType;
+
+    fn parse(target: &str, api_version: Option<&str>) -> Result<OperationType, ErrorResponse> {
+        Operation::parse(target, api_version).map(|operation| operation.name)
+    }
+
+    #[test]
+    fn prefixed_target() {
+        assert_eq!(
+            parse("DynamoDB_20120810.PutItem", None).unwrap(),
+            OperationType::PutItem
+        );
+        assert_eq!(
+            parse("DynamoDB_20120810.PutItem", Some("20120810")).unwrap(),
+            OperationType::PutItem
+        );
+    }
+
+    #[test]
+    fn bare_operation_name() {
+        assert_eq!(parse("Query", None).unwrap(), OperationType::Query);
+        assert_eq!(
+            parse("Query", Some("20120810")).unwrap(),
+            OperationType::Query
+        );
+    }
+
+    #[test]
+    fn unsupported_version() {
+        assert!(matches!(
+            parse("DynamoDB_20111205.PutItem", None),
+            Err(ErrorResponse::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            parse("PutItem", Some("20111205")),
+            Err(ErrorResponse::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            parse("DynamoDB_20120810.PutItem", Some("20111205")),
+            Err(ErrorResponse::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn other_service() {
+        assert!(matches!(
+            parse("Kinesis_20131202.PutRecord", None),
+            Err(ErrorResponse::MissingAuthenticationToken)
+        ));
+    }
+
+    #[test]
+    fn unimplemented_operation() {
+        assert!(matches!(
+            parse("DynamoDB_20120810.TransactWriteItems", None),
+            Err(ErrorResponse::NotImplemented(operation)) if operation == "TransactWriteItems"
+        ));
+    }
+
+    #[test]
+    fn unknown_operation() {
+        assert!(matches!(
+            parse("DynamoDB_20120810.NotAnOperation", None),
+            Err(ErrorResponse::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn invocation_headers() {
+        let invocation = Invocation::parse(
+            Some("a5bdd5a8-5c8e-4d5b-9d87-3f1b7c6f0a2e"),
+            Some("attempt=2; max=3"),
+        );
+        assert_eq!(
+            invocation.id.as_deref(),
+            Some("a5bdd5a8-5c8e-4d5b-9d87-3f1b7c6f0a2e")
+        );
+        assert_eq!(invocation.attempt, Some(2));
+        assert_eq!(invocation.max_attempts, Some(3));
+
+        let invocation = Invocation::parse(None, Some("attempt=x"));
+        assert_eq!(invocation.id, None);
+        assert_eq!(invocation.attempt, None);
+    }
+
+    #[test]
+    fn request_region() {
+        let region = |authorization| RequestRegion::parse(authorization).map(|region| region.0);
+        assert_eq!(
+            region(
+                "AWS4-HMAC-SHA256 Credential=AKID/20240101/eu-west-1/dynamodb/aws4_request, \
+                SignedHeaders=host;x-amz-date, Signature=abc"
+            )
+            .unwrap(),
+            Some(Region::EuWest1)
+        );
+        assert_eq!(
+            region("AWS4-HMAC-SHA256 Credential=AKID/20240101/ap-south-1/dynamodb/aws4_request")
+                .unwrap(),
+            Some(Region::ApSouth1)
+        );
+        assert!(matches!(
+            region("AWS4-HMAC-SHA256 Credential=AKID/20240101/mars-east-1/dynamodb/aws4_request"),
+            Err(ErrorResponse::ValidationError(_))
+        ));
+        assert_eq!(region("Bearer token").unwrap(), None);
     }
 }
@@ -0,0 +1,161 @@
+//! Simulated network failures below the HTTP level
+//!
+//! Errors returned as HTTP responses are retried by the SDKs in one code path, while dropped
+//! connections, truncated bodies and stalled responses are handled by their HTTP clients in
+//! another. These faults are injected by breaking the response body after the operation has been
+//! handled, so a faulted write may still have been applied, as on a real network.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::{self, Body, Bytes, HttpBody},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use tokio::time::Sleep;
+
+/// Break the connection for a fraction of operations. By default no faults are injected.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionFaults {
+    /// probability that each operation's response is broken
+    pub probability: f64,
+    /// time a stalled response waits, part way through its body, before the connection is
+    /// closed. This should be longer than the client's timeout.
+    pub stall_duration: Duration,
+}
+
+impl Default for ConnectionFaults {
+    fn default() -> Self {
+        Self {
+            probability: 0.0,
+            stall_duration: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fault {
+    /// close the connection without sending the body
+    Close,
+    /// send half of the body, then close the connection
+    Truncate,
+    /// send half of the body, then wait before closing the connection
+    Stall,
+}
+
+impl ConnectionFaults {
+    fn choose(&self) -> Option<Fault> {
+        if fastrand::f64() >= self.probability {
+            return None;
+        }
+        Some(match fastrand::u8(0..3) {
+            0 => Fault::Close,
+            1 => Fault::Truncate,
+            _ => Fault::Stall,
+        })
+    }
+}
+
+/// Middleware breaking the responses of operations, leaving the admin endpoints alone
+///
+/// The faults are shared with [`crate::Settings`], so they can be changed while running.
+pub async fn inject(
+    State(faults): State<Arc<RwLock<ConnectionFaults>>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let faults = *faults.read().unwrap_or_else(|e| e.into_inner());
+    let is_admin = request.uri().path().starts_with("/_");
+    let response = next.run(request).await;
+    let fault = match faults.choose() {
+        Some(fault) if !is_admin => fault,
+        _ => return response,
+    };
+    tracing::debug!(?fault, "injecting connection fault");
+
+    let (mut parts, mut original) = response.into_parts();
+    let mut contents = Vec::new();
+    while let Some(Ok(chunk)) = original.data().await {
+        contents.extend_from_slice(&chunk);
+    }
+    // announce the full length, so the client knows the body was cut short
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(contents.len()));
+    let sent = match fault {
+        Fault::Close => None,
+        Fault::Truncate | Fault::Stall => {
+            Some(Bytes::copy_from_slice(&contents[..contents.len() / 2]))
+        }
+    };
+    let stall =
+        (fault == Fault::Stall).then(|| Box::pin(tokio::time::sleep(faults.stall_duration)));
+    Response::from_parts(
+        parts,
+        body::boxed(FaultyBody {
+            sent: sent.filter(|sent| !sent.is_empty()),
+            stall,
+        }),
+    )
+}
+
+/// Body yielding part of the response, then failing, which makes hyper close the connection
+struct FaultyBody {
+    sent: Option<Bytes>,
+    stall: Option<Pin<Box<Sleep>>>,
+}
+
+impl HttpBody for FaultyBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if let Some(sent) = self.sent.take() {
+            return Poll::Ready(Some(Ok(sent)));
+        }
+        if let Some(stall) = self.stall.as_mut() {
+            if stall.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(Some(Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionAborted,
+            "simulated connection fault",
+        ))))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probability() {
+        let never = ConnectionFaults::default();
+        assert!((0..100).all(|_| never.choose().is_none()));
+
+        let always = ConnectionFaults {
+            probability: 1.0,
+            ..Default::default()
+        };
+        assert!((0..100).all(|_| always.choose().is_some()));
+    }
+}
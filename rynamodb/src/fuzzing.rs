@@ -0,0 +1,82 @@
+//! Entry points for the fuzz targets in `fuzz/`, only built by `cargo fuzz`
+//!
+//! The key condition parser and placeholder visitor are internal, so the targets reach them
+//! through queries of a small table, which also runs the parsed conditions.
+
+use std::collections::HashMap;
+
+use serde_dynamo::AttributeValue;
+
+use crate::table::{Table, TableOptions};
+
+/// Placeholders used by the expressions in the tests, so that their variations get past
+/// substitution
+pub fn default_placeholders() -> (HashMap<String, String>, HashMap<String, AttributeValue>) {
+    let names = [
+        ("#K", "pk"),
+        ("#S", "sk"),
+        ("#s", "sk"),
+        ("#pk", "pk"),
+        ("#sk", "sk"),
+    ]
+    .into_iter()
+    .map(|(placeholder, name)| (placeholder.to_string(), name.to_string()))
+    .collect();
+    let s = |s: &str| AttributeValue::S(s.to_string());
+    let n = |n: &str| AttributeValue::N(n.to_string());
+    let values = [
+        (":a", s("a")),
+        (":b", s("b")),
+        (":c", s("c")),
+        (":v", s("a")),
+        (":val", s("a")),
+        (":pk", s("a")),
+        (":sk", s("b")),
+        (":lower", s("a")),
+        (":upper", s("c")),
+        (":missing", s("missing")),
+        (":number", n("1")),
+        (":n", n("1.5")),
+        (":bin", AttributeValue::B(vec![0, 1])),
+        (":bool", AttributeValue::Bool(true)),
+    ]
+    .into_iter()
+    .map(|(placeholder, value)| (placeholder.to_string(), value))
+    .collect();
+    (names, values)
+}
+
+/// Run a Query with a key condition expression against a table with a partition and sort key.
+/// Errors are expected for most inputs, only panics are failures.
+pub fn query(
+    expression: &str,
+    names: Option<HashMap<String, String>>,
+    values: Option<HashMap<String, AttributeValue>>,
+) {
+    let _ = table().query(expression, &names, &values);
+}
+
+fn table() -> Table {
+    let mut table = Table::new(
+        Default::default(),
+        crate::DEFAULT_ACCOUNT_ID,
+        TableOptions {
+            name: "fuzz".to_string(),
+            partition_key: "pk".to_string(),
+            sort_key: Some("sk".to_string()),
+            ..Default::default()
+        },
+    );
+    for (pk, sk) in [("a", "a"), ("a", "b"), ("a", "c"), ("b", "a")] {
+        table
+            .insert(
+                [
+                    ("pk".to_string(), AttributeValue::S(pk.to_string())),
+                    ("sk".to_string(), AttributeValue::S(sk.to_string())),
+                ]
+                .into(),
+            )
+            .expect("inserting fuzz item");
+    }
+    table
+}
@@ -0,0 +1,260 @@
+//! Write rates of each partition key, for testing write sharding schemes
+//!
+//! DynamoDB limits each partition key to 1000 write capacity units per second, so tables whose
+//! writes concentrate on a few keys are throttled however much capacity they have. Keys can be
+//! sharded, e.g. by appending a random suffix, to spread the writes. With recording enabled, the
+//! write capacity units written to each key are counted in one second windows and reported with
+//! the busiest window, and a warning is logged when a key exceeds the alert threshold within a
+//! window. Writes are never throttled.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use serde_dynamo::AttributeValue;
+
+use crate::table::{key::Key, size, Table};
+
+/// Write capacity units DynamoDB allows each partition key per second
+pub const PARTITION_WRITE_UNITS_PER_SECOND: u64 = 1000;
+
+/// size of a write capacity unit
+const WRITE_UNIT_BYTES: usize = 1024;
+
+/// length of the windows write rates are measured over
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// only the busiest keys of each table are reported
+const MAX_REPORTED_KEYS: usize = 100;
+
+type Item = HashMap<String, AttributeValue>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HotKeyConfig {
+    /// count the writes to each partition key
+    pub enabled: bool,
+    /// log a warning when a partition key is written more than this many write capacity units
+    /// within a second, e.g. [`PARTITION_WRITE_UNITS_PER_SECOND`]
+    pub alert_write_units: Option<u64>,
+}
+
+/// A write of one item, measured before it is stored
+#[derive(Debug)]
+pub struct Write {
+    key: Key,
+    partition_key: AttributeValue,
+    write_units: u64,
+}
+
+#[derive(Debug)]
+struct KeyWrites {
+    partition_key: AttributeValue,
+    writes: u64,
+    write_units: u64,
+    window_start: Instant,
+    window_write_units: u64,
+    peak_write_units_per_second: u64,
+    alerts: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotKey {
+    pub partition_key: AttributeValue,
+    pub writes: u64,
+    pub write_units: u64,
+    /// most write capacity units written within one second
+    pub peak_write_units_per_second: u64,
+    /// number of seconds in which the alert threshold was exceeded
+    pub alerts: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotKeysReport {
+    pub alert_write_units: Option<u64>,
+    /// the busiest partition keys of each table, by peak write rate
+    pub tables: BTreeMap<String, Vec<HotKey>>,
+}
+
+#[derive(Debug, Default)]
+pub struct HotKeys {
+    config: HotKeyConfig,
+    tables: Mutex<HashMap<String, HashMap<Key, KeyWrites>>>,
+}
+
+impl HotKeys {
+    pub fn new(config: HotKeyConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Measure a write of an item before it is stored. Returns `None` when recording is
+    /// disabled, so that items are not measured needlessly, or the item has no valid partition
+    /// key.
+    pub fn measure(&self, table: &Table, item: &Item) -> Option<Write> {
+        if !self.config.enabled {
+            return None;
+        }
+        let partition_key = item.get(table.partition_key())?;
+        Some(Write {
+            key: Key::from_value(partition_key)?,
+            partition_key: partition_key.clone(),
+            write_units: size::item_size(item).div_ceil(WRITE_UNIT_BYTES).max(1) as u64,
+        })
+    }
+
+    /// Record a write which has been stored
+    pub fn record(&self, table_name: &str, write: Option<Write>) {
+        if let Some(write) = write {
+            self.record_at(table_name, write, Instant::now());
+        }
+    }
+
+    fn record_at(&self, table_name: &str, write: Write, now: Instant) {
+        let mut tables = self.tables.lock().unwrap();
+        let writes = tables
+            .entry(table_name.to_string())
+            .or_default()
+            .entry(write.key)
+            .or_insert_with(|| KeyWrites {
+                partition_key: write.partition_key,
+                writes: 0,
+                write_units: 0,
+                window_start: now,
+                window_write_units: 0,
+                peak_write_units_per_second: 0,
+                alerts: 0,
+            });
+        if now.saturating_duration_since(writes.window_start) >= WINDOW {
+            writes.window_start = now;
+            writes.window_write_units = 0;
+        }
+        let previous = writes.window_write_units;
+        writes.writes += 1;
+        writes.write_units += write.write_units;
+        writes.window_write_units += write.write_units;
+        writes.peak_write_units_per_second = writes
+            .peak_write_units_per_second
+            .max(writes.window_write_units);
+
+        // warn once per window, when the threshold is first crossed
+        if let Some(threshold) = self.config.alert_write_units {
+            if previous <= threshold && writes.window_write_units > threshold {
+                writes.alerts += 1;
+                tracing::warn!(
+                    %table_name,
+                    partition_key = ?writes.partition_key,
+                    write_units = writes.window_write_units,
+                    threshold,
+                    "partition key written faster than the alert threshold"
+                );
+            }
+        }
+    }
+
+    pub fn report(&self) -> HotKeysReport {
+        let tables = self.tables.lock().unwrap();
+        let tables = tables
+            .iter()
+            .map(|(table_name, keys)| {
+                let mut hot_keys: Vec<_> = keys
+                    .values()
+                    .map(|writes| HotKey {
+                        partition_key: writes.partition_key.clone(),
+                        writes: writes.writes,
+                        write_units: writes.write_units,
+                        peak_write_units_per_second: writes.peak_write_units_per_second,
+                        alerts: writes.alerts,
+                    })
+                    .collect();
+                hot_keys.sort_by(|a, b| {
+                    b.peak_write_units_per_second
+                        .cmp(&a.peak_write_units_per_second)
+                        .then(b.write_units.cmp(&a.write_units))
+                });
+                hot_keys.truncate(MAX_REPORTED_KEYS);
+                (table_name.clone(), hot_keys)
+            })
+            .collect();
+        HotKeysReport {
+            alert_write_units: self.config.alert_write_units,
+            tables,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{table::TableOptions, Region};
+
+    fn table() -> Table {
+        Table::new(
+            Region::default(),
+            crate::DEFAULT_ACCOUNT_ID,
+            TableOptions {
+                name: "a".to_string(),
+                partition_key: "pk".to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn item(pk: &str, payload_size: usize) -> Item {
+        [
+            ("pk".to_string(), AttributeValue::S(pk.to_string())),
+            (
+                "payload".to_string(),
+                AttributeValue::S("x".repeat(payload_size)),
+            ),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn disabled() {
+        let hot_keys = HotKeys::new(HotKeyConfig::default());
+        let write = hot_keys.measure(&table(), &item("a", 10));
+        assert!(write.is_none());
+        hot_keys.record("a", write);
+        assert!(hot_keys.report().tables.is_empty());
+    }
+
+    #[test]
+    fn write_rates() {
+        let hot_keys = HotKeys::new(HotKeyConfig {
+            enabled: true,
+            alert_write_units: Some(5),
+        });
+        let table = table();
+        let start = Instant::now();
+        // 2 units each, so the threshold is crossed by the third write in a window
+        for i in 0..4 {
+            let write = hot_keys.measure(&table, &item("hot", 1500)).unwrap();
+            hot_keys.record_at("a", write, start + Duration::from_millis(i * 100));
+        }
+        for i in 0..3 {
+            let write = hot_keys.measure(&table, &item("hot", 1500)).unwrap();
+            hot_keys.record_at("a", write, start + Duration::from_millis(1000 + i * 100));
+        }
+        let write = hot_keys.measure(&table, &item("cold", 10)).unwrap();
+        hot_keys.record_at("a", write, start);
+
+        let report = hot_keys.report();
+        let keys = &report.tables["a"];
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].partition_key, AttributeValue::S("hot".to_string()));
+        assert_eq!(keys[0].writes, 7);
+        assert_eq!(keys[0].write_units, 14);
+        assert_eq!(keys[0].peak_write_units_per_second, 8);
+        assert_eq!(keys[0].alerts, 2);
+        assert_eq!(keys[1].write_units, 1);
+        assert_eq!(keys[1].alerts, 0);
+    }
+}
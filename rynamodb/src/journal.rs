@@ -0,0 +1,210 @@
+//! Journal of the operations which changed the emulator's state, for replaying later
+//!
+//! Every successful mutating operation is appended to the journal as a line of JSON, holding the
+//! time it completed and its request body. Replaying a journal, up to some point in time, into a
+//! fresh emulator rebuilds the tables as they were then, e.g. to see what a test saw when it
+//! failed in CI.
+//!
+//! Changes made through the `/_rynamodb` endpoints or in namespaces, and expiry of items by time
+//! to live sweeps, are not journaled. Batch writes are replayed in full, including any entries which were
+//! returned unprocessed.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower::Service;
+
+use crate::{
+    error::{Error, Result},
+    table::Table,
+    OperationType, Rynamodb, RynamodbConfig,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    pub operation: String,
+    pub body: serde_json::Value,
+    /// `x-rynamodb-test-id` header sent with the operation, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub test_id: Option<String>,
+}
+
+/// Append only journal file, see [`crate::RynamodbBuilder::journal`]
+#[derive(Debug)]
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Open a journal, appending to it if it already exists
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Append an operation, which completed `at` by the emulator's clock, if it changes the
+    /// emulator's state
+    pub(crate) fn record(
+        &self,
+        operation: OperationType,
+        body: &[u8],
+        test_id: Option<&str>,
+        at: DateTime<Utc>,
+    ) {
+        if !operation.is_mutating() {
+            return;
+        }
+        let entry = JournalEntry {
+            at,
+            operation: operation.name().to_string(),
+            body: serde_json::from_slice(body).unwrap_or_default(),
+            test_id: test_id.map(ToString::to_string),
+        };
+        let mut line = serde_json::to_vec(&entry).expect("journal entries are serializable");
+        line.push(b'\n');
+        // a failed write loses the entry, but should not fail the operation which has already
+        // been applied
+        if let Err(error) = self.file.lock().unwrap().write_all(&line) {
+            tracing::error!(%error, "could not write to journal");
+        }
+    }
+}
+
+/// Rebuild the tables of a journal, applying the operations which completed no later than
+/// `until`, or all of them
+///
+/// The operations are replayed in the account and region of `config`, without any of its
+/// simulated delays or faults. The tables can then be served with
+/// [`crate::RynamodbBuilder::tables`].
+pub async fn replay(
+    path: impl AsRef<Path>,
+    until: Option<DateTime<Utc>>,
+    config: &RynamodbConfig,
+) -> Result<Vec<Table>> {
+    let (mut router, tables) = Rynamodb::builder()
+        .config(RynamodbConfig {
+            account_id: config.account_id.clone(),
+            region: config.region,
+            ..Default::default()
+        })
+        .build();
+
+    let reader = BufReader::new(File::open(path)?);
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: JournalEntry = serde_json::from_str(&line)
+            .map_err(|e| Error::InvalidJournal(format!("line {}: {e}", number + 1)))?;
+        if until.is_some_and(|until| entry.at > until) {
+            break;
+        }
+
+        let request = Request::post("/")
+            .header(
+                "x-amz-target",
+                format!(
+                    "DynamoDB_{}.{}",
+                    crate::extractors::API_VERSION,
+                    entry.operation
+                ),
+            )
+            .body(Body::from(entry.body.to_string()))
+            .expect("journal requests are valid");
+        // routers are always ready, and never fail
+        std::future::poll_fn(|cx| router.poll_ready(cx))
+            .await
+            .unwrap_or_else(|e| match e {});
+        let response = router.call(request).await.unwrap_or_else(|e| match e {});
+        if response.status() != StatusCode::OK {
+            tracing::warn!(
+                line = number + 1,
+                operation = %entry.operation,
+                status = %response.status(),
+                "replayed operation failed"
+            );
+        }
+    }
+    Ok(tables.all())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replay_until() {
+        let path = std::env::temp_dir().join(format!("rynamodb-{}.jsonl", uuid::Uuid::new_v4()));
+        let journal = Journal::open(&path).unwrap();
+        let start = Utc::now();
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+
+        journal.record(
+            OperationType::CreateTable,
+            br#"{
+                "TableName": "journaled",
+                "AttributeDefinitions": [{"AttributeName": "pk", "AttributeType": "S"}],
+                "KeySchema": [{"AttributeName": "pk", "KeyType": "HASH"}]
+            }"#,
+            None,
+            at(0),
+        );
+        let put = |pk: &str| {
+            format!(r#"{{"TableName": "journaled", "Item": {{"pk": {{"S": "{pk}"}}}}}}"#)
+        };
+        journal.record(
+            OperationType::PutItem,
+            put("first").as_bytes(),
+            Some("test-1"),
+            at(1),
+        );
+        // reads are not journaled
+        journal.record(
+            OperationType::Scan,
+            br#"{"TableName": "journaled"}"#,
+            None,
+            at(2),
+        );
+        let cutoff = at(3);
+        journal.record(
+            OperationType::PutItem,
+            put("second").as_bytes(),
+            None,
+            at(4),
+        );
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<JournalEntry> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].test_id.as_deref(), Some("test-1"));
+        assert_eq!(entries[1].at, at(1));
+        assert_eq!(entries[2].test_id, None);
+
+        let config = RynamodbConfig::default();
+        let tables = replay(&path, Some(cutoff), &config).await.unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].iter().count(), 1);
+
+        let tables = replay(&path, None, &config).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tables[0].iter().count(), 2);
+    }
+}
@@ -0,0 +1,92 @@
+//! Simulated operation latency, proportional to the size of the request and response and to the
+//! number of items returned
+//!
+//! Operations on the emulator complete in microseconds however large their items are, so local
+//! performance tests cannot show whether shrinking payloads or reading fewer items helps. With a
+//! cost per kilobyte and per item, larger operations take proportionally longer, which is only
+//! directionally like DynamoDB's latency but enough to compare two designs.
+
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Latency added to every successful operation. By default no latency is added.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencySimulation {
+    /// added for every kilobyte of the request and response bodies
+    pub per_kb: Duration,
+    /// added for every item returned
+    pub per_item: Duration,
+}
+
+impl LatencySimulation {
+    fn is_enabled(&self) -> bool {
+        !self.per_kb.is_zero() || !self.per_item.is_zero()
+    }
+
+    /// Latency of an operation with a request body of `request_bytes` returning `output`
+    pub fn delay(&self, request_bytes: usize, output: &impl Serialize) -> Duration {
+        if !self.is_enabled() {
+            return Duration::ZERO;
+        }
+        let Ok(output) = serde_json::to_value(output) else {
+            return Duration::ZERO;
+        };
+        let bytes = request_bytes + output.to_string().len();
+        self.per_kb.mul_f64(bytes as f64 / 1024.0) + self.per_item * returned_items(&output) as u32
+    }
+}
+
+/// Number of items in a serialised output, from the `Item` of GetItem, the `Items` of Query and
+/// Scan and the `Responses` of BatchGetItem
+fn returned_items(output: &Value) -> usize {
+    let item = match output.get("Item") {
+        Some(Value::Object(_)) => 1,
+        _ => 0,
+    };
+    let items = match output.get("Items") {
+        Some(Value::Array(items)) => items.len(),
+        _ => 0,
+    };
+    let responses = match output.get("Responses") {
+        Some(Value::Object(tables)) => tables
+            .values()
+            .filter_map(Value::as_array)
+            .map(Vec::len)
+            .sum(),
+        _ => 0,
+    };
+    item + items + responses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled() {
+        let latency = LatencySimulation::default();
+        let output = serde_json::json!({"Items": [{}, {}]});
+        assert_eq!(latency.delay(2048, &output), Duration::ZERO);
+    }
+
+    #[test]
+    fn proportional_to_size_and_items() {
+        let latency = LatencySimulation {
+            per_kb: Duration::from_micros(100),
+            per_item: Duration::from_micros(10),
+        };
+        let empty = serde_json::json!({});
+        assert_eq!(latency.delay(1022, &empty), Duration::from_micros(100));
+
+        let query = serde_json::json!({"Items": [{}, {}, {}], "Count": 3});
+        let bytes = 1024 - query.to_string().len();
+        assert_eq!(latency.delay(bytes, &query), Duration::from_micros(130));
+
+        let batch_get = serde_json::json!({"Responses": {"a": [{}], "b": [{}, {}]}});
+        assert_eq!(returned_items(&batch_get), 3);
+        assert_eq!(returned_items(&serde_json::json!({"Item": {}})), 1);
+        assert_eq!(returned_items(&serde_json::json!({})), 0);
+    }
+}
@@ -1,347 +1,1266 @@
-use eyre::Context;
 use std::{
+    collections::HashMap,
     future::Future,
-    str::FromStr,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 use tracing::Instrument;
 
 use axum::{
     extract::State,
-    http::{HeaderMap, Method, Uri},
-    routing::{any, get},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, Uri},
+    response::{IntoResponse, Response},
     Json, Router,
 };
 
-use crate::{errors::ErrorResponse, types::ListTablesOutput};
+use crate::{errors::ErrorResponse, extractors::AwsJson, types::ListTablesOutput};
 
+mod access_patterns;
+mod admin;
+mod builder;
+#[cfg(feature = "cluster")]
+mod cluster;
+mod config;
+mod connections;
+pub mod convert;
+mod csv;
+mod dax;
+mod deadline;
+#[cfg(feature = "dynamodb-local")]
+pub mod dynamodb_local;
+mod edge;
+mod error;
+mod error_log;
 mod errors;
+mod events;
 mod extractors;
+mod faults;
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing;
+mod hot_keys;
+pub mod journal;
+mod latency;
+mod limits;
+mod metrics;
+mod namespaces;
+mod operations;
+mod page_tokens;
+mod providers;
+mod scheduler;
+mod schemas;
+mod settings;
+mod slow_log;
 mod table;
 mod table_manager;
+mod tail;
 pub mod types;
+mod unimplemented;
+mod validation;
+
+pub use access_patterns::{AttributeAccess, TableAccessReport};
+pub use builder::{OperationHook, OperationRequest, Rynamodb, RynamodbBuilder, Tables};
+#[cfg(feature = "cluster")]
+pub use cluster::ClusterConfig;
+pub use config::RynamodbConfig;
+pub use dax::{CacheStatistics, DaxReport, DaxSimulation};
+pub use edge::EdgeRouting;
+pub use error::Error;
+pub use events::{DeletionReason, TableEvent};
+pub use faults::ConnectionFaults;
+pub use hot_keys::{HotKey, HotKeyConfig, HotKeysReport, PARTITION_WRITE_UNITS_PER_SECOND};
+pub use latency::LatencySimulation;
+pub use limits::Limits;
+pub use metrics::MetricsConfig;
+pub use operations::{OperationInput, OperationType};
+pub use providers::{Clock, FixedClock, IdProvider, RandomIds, SequentialIds, SystemClock};
+pub use settings::{CurrentSettings, Settings, SettingsUpdate};
+pub use slow_log::{SlowOperation, SlowOperationThresholds};
+pub use table::{
+    cost::CostEstimate,
+    pagination::PageJitter,
+    schema::{IndexSchema, KeyAttribute, TableSchema},
+    Table, TableError,
+};
+pub use table_manager::{
+    LifecycleSimulation, LimitExceeded, Region, TableDefaults, TimeToLiveSimulation,
+    UnprocessedSimulation,
+};
 
 pub static DEFAULT_ACCOUNT_ID: &str = "000000000000";
 
-pub async fn run_server(router: Router, port: u16) -> eyre::Result<()> {
+pub async fn run_server(router: Router, port: u16) -> error::Result<()> {
     let addr = format!("127.0.0.1:{port}").parse().unwrap();
 
-    let server = axum::Server::bind(&addr).serve(router.into_make_service());
-    server.await.wrap_err("server shutdown incorrectly")?;
+    let server = axum::Server::bind(&addr)
+        .serve(router.into_make_service_with_connect_info::<connections::ConnectionInfo>());
+    server.await.map_err(|e| Error::Server(Box::new(e)))?;
     Ok(())
 }
 
+/// Serve the router on a random port while running `f`, for integration tests
+#[doc(hidden)]
 pub async fn test_run_server<F>(router: Router, f: F) -> eyre::Result<()>
 where
     F: FnOnce(u16) -> Box<dyn Future<Output = eyre::Result<()>> + Unpin>,
 {
-    let server =
-        axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router.into_make_service());
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+        .serve(router.into_make_service_with_connect_info::<connections::ConnectionInfo>());
     let listening_port = server.local_addr().port();
     tracing::debug!(?listening_port, "server listening");
-    let handle = tokio::spawn(async { server.await });
+    let handle = tokio::spawn(server);
     let result = f(listening_port).await;
     tracing::debug!("stopping server");
     drop(handle);
     result
 }
 
-#[derive(Debug)]
-pub enum OperationType {
-    CreateTable,
-    PutItem,
-    DescribeTable,
-    DeleteTable,
-    Query,
-    GetItem,
-    ListTables,
-    Scan,
-    BatchWriteItem,
-}
-
-impl FromStr for OperationType {
-    type Err = String;
-
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s {
-            "CreateTable" => Ok(OperationType::CreateTable),
-            "PutItem" => Ok(OperationType::PutItem),
-            "DescribeTable" => Ok(OperationType::DescribeTable),
-            "DeleteTable" => Ok(OperationType::DeleteTable),
-            "Query" => Ok(OperationType::Query),
-            "GetItem" => Ok(OperationType::GetItem),
-            "ListTables" => Ok(OperationType::ListTables),
-            "Scan" => Ok(OperationType::Scan),
-            "BatchWriteItem" => Ok(OperationType::BatchWriteItem),
-            s => Err(format!("operation {s} not handled")),
+/// State shared between all requests
+#[derive(Clone)]
+pub struct AppState {
+    manager: Arc<RwLock<table_manager::TableManager>>,
+    settings: settings::Settings,
+    operation_hooks: Arc<[OperationHook]>,
+    slow_operations: Arc<slow_log::SlowOperationLog>,
+    access_patterns: Arc<access_patterns::AccessPatterns>,
+    hot_keys: Arc<hot_keys::HotKeys>,
+    errors: Arc<error_log::ErrorLog>,
+    metrics: Arc<metrics::Metrics>,
+    connections: Arc<connections::Connections>,
+    unimplemented: Arc<unimplemented::Unimplemented>,
+    tail: Arc<tail::Tail>,
+    journal: Option<Arc<journal::Journal>>,
+    namespaces: Arc<namespaces::Namespaces>,
+    /// maximum time spent handling each operation
+    request_timeout: Option<Duration>,
+    /// region of the tables the request reads and writes: the region it was signed for, or the
+    /// configured region
+    region: Region,
+    latency: latency::LatencySimulation,
+    page_jitter: Option<table::pagination::PageJitter>,
+    scan_shuffle_seed: Option<u64>,
+    /// signs the pagination tokens returned to clients
+    page_tokens: Arc<page_tokens::PageTokens>,
+    /// source of request ids
+    ids: Arc<dyn providers::IdProvider>,
+    /// source of the times of tailed operations, shared with the tables
+    clock: Arc<dyn providers::Clock>,
+    /// check requests and responses against the schemas of their operations
+    validate_schemas: bool,
+    dax: Option<Arc<dax::Dax>>,
+    /// runs periodic background work, until the router is dropped
+    scheduler: Arc<scheduler::Scheduler>,
+}
+
+impl AppState {
+    /// Scope the state to the tables of a request's namespace, if it has one
+    fn in_namespace(mut self, namespace: &extractors::Namespace) -> Self {
+        if let Some(name) = &namespace.name {
+            self.manager = self.namespaces.manager(name, namespace.ttl, &self.manager);
+            self.page_tokens = Arc::new(self.page_tokens.in_namespace(name));
+            self.journal = None;
+            self.dax = None;
         }
+        self
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handler(
     uri: Uri,
     method: Method,
     headers: HeaderMap,
-    operation_extractor: std::result::Result<extractors::Operation, String>,
-    State(manager): State<Arc<RwLock<table_manager::TableManager>>>,
-    // we cannot use the Json extractor since it requires the `Content-Type: application/json`
-    // header, which the SDK does not send.
+    operation_extractor: std::result::Result<extractors::Operation, ErrorResponse>,
+    invocation: extractors::Invocation,
+    test_id: extractors::TestId,
+    namespace: extractors::Namespace,
+    region: extractors::RequestRegion,
+    State(state): State<AppState>,
+    // the body is only parsed once the operation is known, see `call`
     body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
-    let request_id = uuid::Uuid::new_v4().to_string();
-    let span = tracing::debug_span!("request", request_id = request_id);
+) -> Result<Response, ErrorResponse> {
+    let request_id = state.ids.new_id();
+    // the SDK's invocation id is shared by retries of the same call, so they can be correlated
+    let span = tracing::debug_span!(
+        "request",
+        request_id = request_id,
+        invocation_id = invocation.id.as_deref(),
+        attempt = invocation.attempt,
+        namespace = namespace.name.as_deref(),
+        test_id = test_id.0.as_deref(),
+    );
 
     let extractors::Operation {
         name: operation, ..
-    } = operation_extractor.map_err(|e| {
-        tracing::error!(error = ?e, "operation unhandled");
-        ErrorResponse::InvalidOperation(e)
-    })?;
+    } = match operation_extractor {
+        Ok(operation) => operation,
+        Err(e) => {
+            tracing::error!(error = ?e, "operation unhandled");
+            let e = state.unimplemented.observe(None, e);
+            state.errors.record(None, None, &e);
+            let mut response = e.into_response();
+            echo_test_id(&mut response, &test_id);
+            return Ok(response);
+        }
+    };
+    let mut state = state.in_namespace(&namespace);
+    if let Some(region) = region.0 {
+        state.region = region;
+        state.page_tokens = Arc::new(state.page_tokens.in_region(region));
+    }
 
     async move {
         tracing::debug!(?uri, ?method, ?operation, "handler invoked");
         tracing::trace!(?headers, "with headers");
 
-        // parse the body
-        let res = match operation {
-            OperationType::CreateTable => handle_create_table(manager, body).await,
-            OperationType::PutItem => handle_put_item(manager, body).await,
-            OperationType::DescribeTable => handle_describe_table(manager, body).await,
-            OperationType::DeleteTable => handle_delete_table(manager, body).await,
-            OperationType::Query => handle_query(manager, body).await,
-            OperationType::GetItem => handle_get_item(manager, body).await,
-            OperationType::ListTables => handle_list_tables(manager, body).await,
-            OperationType::Scan => handle_scan(manager, body).await,
-            OperationType::BatchWriteItem => handle_batch_write_item(manager, body).await,
+        let request = OperationRequest {
+            operation,
+            headers: &headers,
+            body: &body,
+        };
+        if let Some(mut response) = state.operation_hooks.iter().find_map(|hook| hook(&request)) {
+            tracing::debug!("operation intercepted by hook");
+            echo_test_id(&mut response, &test_id);
+            return Ok(response);
+        }
+
+        let start = std::time::Instant::now();
+        let errors = Arc::clone(&state.errors);
+        let metrics = Arc::clone(&state.metrics);
+        let tail = Arc::clone(&state.tail);
+        let clock = Arc::clone(&state.clock);
+        let unimplemented = Arc::clone(&state.unimplemented);
+        let journal = state.journal.clone();
+        let request_timeout = state.request_timeout;
+        let body = body.as_bytes();
+        let res = async move {
+            match operation {
+                OperationType::CreateTable => call(handle_create_table, state, body).await,
+                OperationType::PutItem => call(handle_put_item, state, body).await,
+                OperationType::DescribeTable => call(handle_describe_table, state, body).await,
+                OperationType::DeleteTable => call(handle_delete_table, state, body).await,
+                OperationType::Query => call(handle_query, state, body).await,
+                OperationType::GetItem => call(handle_get_item, state, body).await,
+                OperationType::ListTables => call(handle_list_tables, state, body).await,
+                OperationType::Scan => call(handle_scan, state, body).await,
+                OperationType::BatchWriteItem => call(handle_batch_write_item, state, body).await,
+                OperationType::UpdateTable => call(handle_update_table, state, body).await,
+                OperationType::DescribeContinuousBackups => {
+                    call(handle_describe_continuous_backups, state, body).await
+                }
+                OperationType::UpdateContinuousBackups => {
+                    call(handle_update_continuous_backups, state, body).await
+                }
+                OperationType::RestoreTableToPointInTime => {
+                    call(handle_restore_table_to_point_in_time, state, body).await
+                }
+                OperationType::BatchGetItem => call(handle_batch_get_item, state, body).await,
+                OperationType::UpdateTimeToLive => {
+                    call(handle_update_time_to_live, state, body).await
+                }
+                OperationType::DescribeTimeToLive => {
+                    call(handle_describe_time_to_live, state, body).await
+                }
+                OperationType::DescribeTableReplicaAutoScaling => {
+                    call(handle_describe_table_replica_auto_scaling, state, body).await
+                }
+                OperationType::UpdateTableReplicaAutoScaling => {
+                    call(handle_update_table_replica_auto_scaling, state, body).await
+                }
+                OperationType::UpdateItem => call(handle_update_item, state, body).await,
+            }
+        };
+        // handlers reading many items check the deadline themselves, as they do not yield while
+        // holding the table lock
+        let res = match request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, res)
+                .await
+                .unwrap_or_else(|_| Err(table::TableError::DeadlineExceeded.into())),
+            None => res.await,
+        };
+        let res = res.map_err(|e| unimplemented.observe(Some(operation), e));
+        let duration = start.elapsed();
+        let table_name = table_name(body);
+        metrics.record(operation, table_name.as_deref(), duration, res.is_err());
+        let followed_error_type = match &res {
+            Err(e) if tail.is_followed() => Some(error_log::describe(e).0),
+            _ => None,
         };
-        tracing::info!(?res, "got result");
-        res
+        match (&res, &journal) {
+            (Err(e), _) => errors.record(Some(operation), table_name.clone(), e),
+            (Ok(_), Some(journal)) => {
+                journal.record(operation, body, test_id.0.as_deref(), clock.now())
+            }
+            (Ok(_), None) => {}
+        }
+
+        let mut response = res.into_response();
+        tracing::info!(
+            ?operation,
+            table_name = table_name.as_deref(),
+            status = response.status().as_u16(),
+            duration_ms = duration.as_secs_f64() * 1000.0,
+            invocation_id = invocation.id.as_deref(),
+            attempt = invocation.attempt,
+            max_attempts = invocation.max_attempts,
+            "handled operation"
+        );
+        if tail.is_followed() {
+            tail.publish(tail::OperationEvent {
+                operation: format!("{operation:?}"),
+                table_name,
+                duration_ms: duration.as_secs_f64() * 1000.0,
+                status: response.status().as_u16(),
+                error_type: followed_error_type,
+                invocation_id: invocation.id,
+                attempt: invocation.attempt,
+                test_id: test_id.0.clone(),
+                at: clock.now(),
+            });
+        }
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(errors::AWS_JSON_CONTENT_TYPE),
+        );
+        if let Ok(request_id) = HeaderValue::from_str(&request_id) {
+            headers.insert(HeaderName::from_static("x-amzn-requestid"), request_id);
+        }
+        echo_test_id(&mut response, &test_id);
+        Ok(response)
     }
     .instrument(span)
     .await
 }
 
 async fn handle_batch_write_item(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager,
+        hot_keys,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::BatchWriteInput>,
+) -> Result<Json<types::BatchWriteItemOutput>, ErrorResponse> {
     tracing::debug!("handling batch write item");
-    let input: types::BatchWriteInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
     tracing::debug!(?input, "parsed input");
 
     let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
-    let unprocessed_items = unlocked_manager.batch_write_item(input);
+    let limits = unlocked_manager.limits;
+    let batch_size: usize = input.request_items.values().map(Vec::len).sum();
+    if batch_size > limits.max_batch_write_items {
+        return Err(ErrorResponse::ValidationError(
+            "Too many items requested for the BatchWriteItem call".to_string(),
+        ));
+    }
+    // invalid sets and oversized items fail the whole batch rather than leaving the item
+    // unprocessed
+    for put_requests in input.request_items.values() {
+        for req in put_requests {
+            table::sets::check_item(&req.put_request.item)?;
+            limits.check_item_size(&req.put_request.item)?;
+        }
+    }
 
-    Ok(Json(types::Response::BatchWriteItem(
-        types::BatchWriteItemOutput {
-            unprocessed_items: Some(unprocessed_items),
-        },
-    )))
+    let unprocessed_items = unlocked_manager.batch_write_item(region, input, &hot_keys);
+
+    Ok(Json(types::BatchWriteItemOutput {
+        unprocessed_items: Some(unprocessed_items),
+    }))
+}
+
+async fn handle_batch_get_item(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::BatchGetItemInput>,
+) -> Result<Json<types::BatchGetItemOutput>, ErrorResponse> {
+    tracing::debug!("handling batch get item");
+    tracing::debug!(?input, "parsed input");
+
+    let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let limits = unlocked_manager.limits;
+    let batch_size: usize = input
+        .request_items
+        .values()
+        .map(|request| request.keys.len())
+        .sum();
+    if batch_size > limits.max_batch_get_keys {
+        return Err(ErrorResponse::ValidationError(
+            "Too many items requested for the BatchGetItem call".to_string(),
+        ));
+    }
+    for request in input.request_items.values() {
+        check_parameter_styles(
+            &request.legacy_parameters(),
+            &request.expression_parameters(),
+        )?;
+        reject_projection_expression("BatchGetItem", request.projection_expression.as_deref())?;
+        limits.check_expressions(&[(
+            "ProjectionExpression",
+            request.projection_expression.as_deref(),
+        )])?;
+    }
+
+    for table_name in input.request_items.keys() {
+        if unlocked_manager
+            .get_active_table_in(region, table_name)
+            .is_none()
+        {
+            return Err(ErrorResponse::ResourceNotFound { name: None });
+        }
+    }
+
+    let output = unlocked_manager.batch_get_item(region, input)?;
+    Ok(Json(output))
 }
 
 async fn handle_scan(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager,
+        slow_operations,
+        access_patterns,
+        request_timeout,
+        scan_shuffle_seed,
+        page_jitter,
+        page_tokens,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(mut input): AwsJson<types::ScanInput>,
+) -> Result<Json<types::QueryOutput>, ErrorResponse> {
+    let start = std::time::Instant::now();
+    let deadline = deadline::Deadline::new(start, request_timeout);
     tracing::debug!("handling scan");
-    let input: types::ScanInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
     tracing::debug!(?input, "parsed input");
+    check_parameter_styles(&input.legacy_parameters(), &input.expression_parameters())?;
+    if input.limit == Some(0) {
+        return Err(ErrorResponse::ValidationError(
+            "1 validation error detected: Value '0' at 'limit' failed to satisfy constraint: \
+            Member must have value greater than or equal to 1"
+                .to_string(),
+        ));
+    }
+    input.exclusive_start_key = input
+        .exclusive_start_key
+        .take()
+        .map(|key| page_tokens.exclusive_start_key(&input.table_name, None, key))
+        .transpose()?;
 
-    let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
-    let table = unlocked_manager
-        .get_table(&input.table_name)
-        .ok_or_else(|| ErrorResponse::ResourceNotFound { name: None })?;
-    tracing::debug!(table_name = ?input.table_name, "found table");
+    // only hold the lock while taking the snapshot, so that writers are not blocked while the
+    // items are read
+    let (snapshot, order, limits) = {
+        let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+        unlocked_manager.limits.check_expressions(&[
+            ("FilterExpression", input.filter_expression.as_deref()),
+            (
+                "ProjectionExpression",
+                input.projection_expression.as_deref(),
+            ),
+        ])?;
+        let table = unlocked_manager
+            .get_active_table_in(region, &input.table_name)
+            .ok_or_else(|| ErrorResponse::ResourceNotFound { name: None })?;
+        tracing::debug!(table_name = ?input.table_name, "found table");
+        let order = table.scan_order(scan_shuffle_seed);
+        if let Some(start_key) = &input.exclusive_start_key {
+            order.validate_start_key(start_key)?;
+        }
+        (table.snapshot(), order, unlocked_manager.limits)
+    };
 
-    let res = table
-        .scan()
-        .map_err(|e| ErrorResponse::RynamodbError(Box::new(e)))?;
+    let limit = limits.page_items(input.limit);
+    let limit = match page_jitter {
+        Some(jitter) => Some(jitter.page_size(limit)),
+        None => limit,
+    };
+    let page = order.paginate(
+        snapshot.items().collect(),
+        input.exclusive_start_key.as_ref(),
+        limit,
+        limits.max_page_bytes,
+        true,
+    );
+
+    // filter and project the stored items before copying them, so that attributes which are not
+    // returned are never copied
+    let scan_filter = input.scan_filter.as_ref();
+    if let Some(conditions) = scan_filter {
+        table::legacy::validate(conditions)?;
+    }
+    let conditional_operator = input.conditional_operator.unwrap_or_default();
+    let mut res = Vec::new();
+    for item in page.items.iter().copied() {
+        deadline.check()?;
+        if scan_filter
+            .is_none_or(|conditions| table::legacy::matches(item, conditions, conditional_operator))
+        {
+            res.push(match input.attributes_to_get.as_deref() {
+                Some(attributes) => table::legacy::project_ref(item, attributes),
+                None => table::names::to_attributes(item),
+            });
+        }
+    }
+
+    let scanned_count = page.items.len();
+    access_patterns.record(
+        &input.table_name,
+        access_patterns.measure(page.items.iter().copied()),
+        &res,
+    );
 
     let count = res.len();
-    Ok(Json(types::Response::Query(types::QueryOutput {
+    slow_operations.record(
+        "Scan",
+        &input.table_name,
+        None,
+        count,
+        scanned_count,
+        start.elapsed(),
+    );
+    Ok(Json(types::QueryOutput {
         items: res,
         count,
-        // TODO
-        scanned_count: count,
-    })))
+        scanned_count,
+        last_evaluated_key: page
+            .last_evaluated_key
+            .map(|key| page_tokens.last_evaluated_key(&input.table_name, None, key)),
+    }))
+}
+
+/// Invoke an operation handler with the request body
+///
+/// Operations are all sent to the same path, so they cannot be routed to their handlers by axum
+/// and the input is extracted here instead. Each handler returns its operation's own output type,
+/// which is serialized here, so that outputs of the same shape cannot be confused. The simulated
+/// latency is added here too, as it depends on the size of the output, and the input and output
+/// are checked against their schemas. Reads answered by the simulated DAX cache skip the handler
+/// and the latency.
+async fn call<I, F, Fut>(
+    handler: F,
+    state: AppState,
+    body: &[u8],
+) -> Result<Response, ErrorResponse>
+where
+    I: operations::OperationInput,
+    F: FnOnce(State<AppState>, AwsJson<I>) -> Fut,
+    Fut: Future<Output = Result<Json<I::Output>, ErrorResponse>>,
+{
+    let input = AwsJson::from_bytes(body)?;
+    let latency = state.latency;
+    let validate_schemas = state.validate_schemas;
+    let dax = state.dax.clone();
+    let region = state.region;
+    let cache_key = dax
+        .as_ref()
+        .and_then(|dax| dax.cache_key(I::OPERATION, region, body));
+    if let (Some(dax), Some(key)) = (&dax, &cache_key) {
+        if let Some(output) = dax.get(key) {
+            tracing::debug!("answered from the DAX cache");
+            return Ok(Json(output).into_response());
+        }
+    }
+    if validate_schemas {
+        let input = serde_json::from_slice(body).unwrap_or_default();
+        let violations = schemas::validate(I::OPERATION, schemas::Shape::Input, &input);
+        if !violations.is_empty() {
+            tracing::warn!(
+                operation = I::OPERATION.name(),
+                violations = %join_violations(&violations),
+                "request does not match the operation's schema"
+            );
+        }
+    }
+    let output = handler(State(state), input).await;
+    tracing::info!(?output, "got result");
+    let Json(output) = output?;
+    if validate_schemas {
+        let value = serde_json::to_value(&output).unwrap_or_default();
+        let violations = schemas::validate(I::OPERATION, schemas::Shape::Output, &value);
+        if !violations.is_empty() {
+            let violations = join_violations(&violations);
+            tracing::error!(
+                operation = I::OPERATION.name(),
+                %violations,
+                "response does not match the operation's schema"
+            );
+            return Err(ErrorResponse::InternalServerError(format!(
+                "{} response does not match the DynamoDB model: {violations}",
+                I::OPERATION.name()
+            )));
+        }
+    }
+    if let Some(dax) = &dax {
+        match cache_key {
+            Some(key) => dax.insert(key, serde_json::to_value(&output).unwrap_or_default()),
+            None => dax.invalidate(I::OPERATION, region, body),
+        }
+    }
+    let delay = latency.delay(body.len(), &output);
+    if !delay.is_zero() {
+        tracing::debug!(?delay, "simulating latency");
+        tokio::time::sleep(delay).await;
+    }
+    Ok(Json(output).into_response())
 }
 
+/// Return the `x-rynamodb-test-id` of a request with its response
+fn echo_test_id(response: &mut Response, test_id: &extractors::TestId) {
+    if let Some(value) = test_id
+        .0
+        .as_deref()
+        .and_then(|id| HeaderValue::from_str(id).ok())
+    {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(extractors::TEST_ID_HEADER), value);
+    }
+}
+
+fn join_violations(violations: &[schemas::Violation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Table named by an operation input, for diagnostics
+fn table_name(body: &[u8]) -> Option<String> {
+    // only the table name is deserialised, as this runs for every request
+    #[derive(serde::Deserialize)]
+    struct Input {
+        #[serde(rename = "TableName")]
+        table_name: Option<String>,
+    }
+    serde_json::from_slice::<Input>(body).ok()?.table_name
+}
+
+/// Reject changes to a table which is still being created or deleted, as DynamoDB does
+fn check_table_is_active(table: &table::Table) -> Result<(), ErrorResponse> {
+    match table.status() {
+        table::TableStatus::Creating => Err(ErrorResponse::ResourceInUse(format!(
+            "Table is being created: {}",
+            table.name
+        ))),
+        table::TableStatus::Deleting | table::TableStatus::Deleted => Err(
+            ErrorResponse::ResourceInUse(format!("Table is being deleted: {}", table.name)),
+        ),
+        table::TableStatus::Active => Ok(()),
+    }
+}
+
+/// Reject requests which mix legacy and expression parameters, as DynamoDB does
+fn check_parameter_styles(legacy: &[&str], expression: &[&str]) -> Result<(), ErrorResponse> {
+    if legacy.is_empty() || expression.is_empty() {
+        return Ok(());
+    }
+    Err(ErrorResponse::ValidationError(format!(
+        "Can not use both expression and non-expression parameters in the same request: \
+        Non-expression parameters: {{{}}} Expression parameters: {{{}}}",
+        legacy.join(", "),
+        expression.join(", ")
+    )))
+}
+
+/// Projection expressions are only accepted so that mixing them with `AttributesToGet` is
+/// rejected as DynamoDB does; applying them is not supported yet
+fn reject_projection_expression(
+    operation: &str,
+    projection_expression: Option<&str>,
+) -> Result<(), ErrorResponse> {
+    match projection_expression {
+        Some(_) => Err(ErrorResponse::NotImplementedByRynamodb(format!(
+            "ProjectionExpression in {operation}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn project_legacy(
+    items: Vec<HashMap<String, serde_dynamo::AttributeValue>>,
+    attributes_to_get: Option<&[String]>,
+) -> Vec<HashMap<String, serde_dynamo::AttributeValue>> {
+    match attributes_to_get {
+        Some(attributes) => items
+            .into_iter()
+            .map(|item| table::legacy::project(item, attributes))
+            .collect(),
+        None => items,
+    }
+}
+
+/// Most table names returned in one page of `ListTables` results, and the default `Limit`
+const MAX_LIST_TABLES_LIMIT: usize = 100;
+
 async fn handle_list_tables(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager,
+        page_tokens,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::ListTablesInput>,
+) -> Result<Json<types::ListTablesOutput>, ErrorResponse> {
     tracing::debug!("handling list_tables");
-    let _input: types::ListTablesInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
+    tracing::debug!(?input, "parsed input");
+
+    let limit = input.limit.unwrap_or(MAX_LIST_TABLES_LIMIT);
+    if !(1..=MAX_LIST_TABLES_LIMIT).contains(&limit) {
+        return Err(ErrorResponse::ValidationError(format!(
+            "1 validation error detected: Value '{limit}' at 'limit' failed to satisfy \
+            constraint: Member must have value between 1 and {MAX_LIST_TABLES_LIMIT}"
+        )));
+    }
+    let exclusive_start_table_name = input
+        .exclusive_start_table_name
+        .map(|start| page_tokens.exclusive_start_table_name(start))
+        .transpose()?;
 
-    // TODO: input handling
     let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
-    let table_names = unlocked_manager.table_names();
+    let mut table_names = unlocked_manager.table_names_in(region);
     tracing::debug!(?table_names, "found table names");
 
-    Ok(Json(types::Response::ListTables(ListTablesOutput {
+    // names are listed in order, and pages end with the last name listed
+    table_names.sort();
+    if let Some(start) = &exclusive_start_table_name {
+        table_names.retain(|name| name > start);
+    }
+    let last_evaluated_table_name = if table_names.len() > limit {
+        table_names.truncate(limit);
+        table_names
+            .last()
+            .map(|name| page_tokens.last_evaluated_table_name(name.clone()))
+    } else {
+        None
+    };
+
+    Ok(Json(ListTablesOutput {
         table_names,
-    })))
+        last_evaluated_table_name,
+    }))
 }
 
 async fn handle_get_item(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::GetItemInput>,
+) -> Result<Json<types::GetItemOutput>, ErrorResponse> {
     tracing::debug!("handling get_item");
-    let input: types::GetItemInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
     tracing::debug!(?input, "parsed input");
+    check_parameter_styles(&input.legacy_parameters(), &input.expression_parameters())?;
+    reject_projection_expression("GetItem", input.projection_expression.as_deref())?;
 
     let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+    unlocked_manager.limits.check_expressions(&[(
+        "ProjectionExpression",
+        input.projection_expression.as_deref(),
+    )])?;
     let table = unlocked_manager
-        .get_table(&input.table_name)
+        .get_active_table_in(region, &input.table_name)
         .ok_or_else(|| ErrorResponse::ResourceNotFound { name: None })?;
     tracing::debug!(table_name = ?input.table_name, "found table");
 
-    let res = table.get_item(input.key);
+    let res = table
+        .get_item(input.key)?
+        .map(|item| match input.attributes_to_get.as_deref() {
+            Some(attributes) => table::legacy::project(item, attributes),
+            None => item,
+        });
     tracing::debug!(result = ?res, "found result");
 
-    Ok(Json(types::Response::GetItem(types::GetItemOutput {
-        item: res,
-    })))
+    Ok(Json(types::GetItemOutput { item: res }))
 }
 
 async fn handle_query(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager,
+        slow_operations,
+        access_patterns,
+        request_timeout,
+        page_jitter,
+        page_tokens,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(mut input): AwsJson<types::QueryInput>,
+) -> Result<Json<types::QueryOutput>, ErrorResponse> {
+    let start = std::time::Instant::now();
+    let deadline = deadline::Deadline::new(start, request_timeout);
     tracing::debug!("handling query");
-
-    tracing::debug!(?body, "got body");
-    let input: types::QueryInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
     tracing::debug!(?input, "parsed input");
+    check_parameter_styles(&input.legacy_parameters(), &input.expression_parameters())?;
+    if input.limit == Some(0) {
+        return Err(ErrorResponse::ValidationError(
+            "1 validation error detected: Value '0' at 'limit' failed to satisfy constraint: \
+            Member must have value greater than or equal to 1"
+                .to_string(),
+        ));
+    }
+    input.exclusive_start_key = input
+        .exclusive_start_key
+        .take()
+        .map(|key| {
+            page_tokens.exclusive_start_key(&input.table_name, input.index_name.as_deref(), key)
+        })
+        .transpose()?;
 
     let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let limits = unlocked_manager.limits;
+    limits.check_expressions(&[
+        (
+            "KeyConditionExpression",
+            input.key_condition_expression.as_deref(),
+        ),
+        ("FilterExpression", input.filter_expression.as_deref()),
+        (
+            "ProjectionExpression",
+            input.projection_expression.as_deref(),
+        ),
+    ])?;
     let table = unlocked_manager
-        .get_table(&input.table_name)
+        .get_active_table_in(region, &input.table_name)
         .ok_or_else(|| ErrorResponse::ResourceNotFound { name: None })?;
     // .ok_or_else(|| eyre::eyre!("no table found"))?;
     tracing::debug!(table_name = ?input.table_name, "found table");
+    if input.index_name.is_some() && input.consistent_read == Some(true) {
+        return Err(ErrorResponse::ValidationError(
+            "Consistent reads are not supported on global secondary indexes".to_string(),
+        ));
+    }
 
-    let res = table
-        .query(
-            &input.key_condition_expression,
-            &input.expression_attribute_names,
-            &input.expression_attribute_values,
-        )
-        .map_err(|e| ErrorResponse::RynamodbError(Box::new(e)))?;
+    let res = match (&input.key_condition_expression, &input.key_conditions) {
+        (Some(key_condition_expression), _) => match &input.index_name {
+            Some(index_name) => table.query_index(
+                index_name,
+                key_condition_expression,
+                &input.expression_attribute_names,
+                &input.expression_attribute_values,
+                &deadline,
+            )?,
+            None => table.query(
+                key_condition_expression,
+                &input.expression_attribute_names,
+                &input.expression_attribute_values,
+            )?,
+        },
+        (None, Some(key_conditions)) => {
+            table.query_key_conditions(key_conditions, input.index_name.as_deref())?
+        }
+        (None, None) => {
+            return Err(ErrorResponse::ValidationError(
+                "Either the KeyConditions or KeyConditionExpression parameter must be specified in the request."
+                    .to_string(),
+            ))
+        }
+    };
     tracing::debug!(result = ?res, "found result");
 
+    let order = table.query_order(input.index_name.as_deref())?;
+    if let Some(start_key) = &input.exclusive_start_key {
+        order.validate_start_key(start_key)?;
+    }
+    let limit = limits.page_items(input.limit);
+    let limit = match page_jitter {
+        Some(jitter) => Some(jitter.page_size(limit)),
+        None => limit,
+    };
+    let page = order.paginate(
+        res,
+        input.exclusive_start_key.as_ref(),
+        limit,
+        limits.max_page_bytes,
+        input.scan_index_forward.unwrap_or(true),
+    );
+    let res = page.items;
+
+    let scanned_count = res.len();
+    let read = access_patterns.measure(&res);
+    let res = table::legacy::filter(res, input.query_filter.as_ref(), input.conditional_operator)?;
+    let res = project_legacy(res, input.attributes_to_get.as_deref());
+    access_patterns.record(&input.table_name, read, &res);
+
     let count = res.len();
-    Ok(Json(types::Response::Query(types::QueryOutput {
+    slow_operations.record(
+        "Query",
+        &input.table_name,
+        input.key_condition_expression.as_deref(),
+        count,
+        scanned_count,
+        start.elapsed(),
+    );
+    Ok(Json(types::QueryOutput {
         items: res,
         count,
-        // TODO
-        scanned_count: count,
-    })))
+        scanned_count,
+        last_evaluated_key: page.last_evaluated_key.map(|key| {
+            page_tokens.last_evaluated_key(&input.table_name, input.index_name.as_deref(), key)
+        }),
+    }))
 }
 
 async fn handle_delete_table(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
-    tracing::debug!(%body, "handling delete table");
-
-    let input: types::DeleteTableInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::DeleteTableInput>,
+) -> Result<Json<types::DeleteTableOutput>, ErrorResponse> {
+    tracing::debug!("handling delete table");
     tracing::debug!(?input, "parsed input");
 
     let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
-    unlocked_manager
-        .delete_table(&input.table_name)
-        .map_err(|e| ErrorResponse::RynamodbError(format!("{e}").into()))?;
+    let table = unlocked_manager
+        .get_table_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+    match table.status() {
+        table::TableStatus::Creating => {
+            return Err(ErrorResponse::ResourceInUse(format!(
+                "Table is being created: {}",
+                input.table_name
+            )))
+        }
+        // deleting a table which is already being deleted is not an error
+        table::TableStatus::Deleting | table::TableStatus::Deleted => {}
+        table::TableStatus::Active => {
+            unlocked_manager.check_limits(false)?;
+            unlocked_manager.delete_table_in(region, &input.table_name)?;
+        }
+    }
 
-    Ok(Json(types::Response::DeleteTable(
-        types::DeleteTableOutput {},
-    )))
+    Ok(Json(types::DeleteTableOutput {}))
 }
 
 async fn handle_put_item(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager,
+        hot_keys,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::PutItemInput>,
+) -> Result<Json<types::PutItemOutput>, ErrorResponse> {
     tracing::debug!("handling put item");
-
-    let input: types::PutItemInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
     tracing::debug!(?input, "parsed input");
 
     // convert the item to our representation
     let attributes = input.item;
 
     let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    unlocked_manager.limits.check_item_size(&attributes)?;
     let table = unlocked_manager
-        .get_table_mut(&input.table_name)
+        .get_active_table_mut_in(region, &input.table_name)
         .ok_or_else(|| ErrorResponse::ResourceNotFound { name: None })?;
 
-    table
-        .insert(attributes)
-        .map_err(|e| ErrorResponse::RynamodbError(Box::new(e)))?;
+    let write = hot_keys.measure(table, &attributes);
+    table.insert(attributes)?;
+    hot_keys.record(&input.table_name, write);
 
-    Ok(Json(types::Response::PutItem(types::PutItemOutput {})))
+    Ok(Json(types::PutItemOutput {}))
+}
+
+async fn handle_update_item(
+    State(AppState {
+        manager,
+        hot_keys,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::UpdateItemInput>,
+) -> Result<Json<types::UpdateItemOutput>, ErrorResponse> {
+    tracing::debug!("handling update item");
+    tracing::debug!(?input, "parsed input");
+
+    let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let limits = unlocked_manager.limits;
+    limits.check_expressions(&[("UpdateExpression", input.update_expression.as_deref())])?;
+    let table = unlocked_manager
+        .get_active_table_mut_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound { name: None })?;
+
+    // the update is checked before it is stored, so that a rejected update changes nothing
+    let update = table.updated_item(&input)?;
+    limits.check_item_size(&update.new)?;
+    let write = hot_keys.measure(table, &update.new);
+    table.insert(update.new.clone())?;
+    hot_keys.record(&input.table_name, write);
+
+    Ok(Json(types::UpdateItemOutput {
+        attributes: update.attributes(input.return_values),
+    }))
 }
 
 async fn handle_describe_table(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::DescribeTableInput>,
+) -> Result<Json<types::DescribeTableOutput>, ErrorResponse> {
     tracing::debug!("handling describe table");
-
-    let input: types::DescribeTableInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
     tracing::debug!(?input, "parsed input");
 
     let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
-    match unlocked_manager.get_table(&input.table_name) {
-        Some(table) => Ok(Json(types::Response::DescribeTable(
-            types::DescribeTableOutput {
-                table: table.description(),
-            },
-        ))),
+    match unlocked_manager.get_table_in(region, &input.table_name) {
+        Some(table) => Ok(Json(types::DescribeTableOutput {
+            table: table.description(),
+        })),
         None => Err(ErrorResponse::ResourceNotFound {
             name: Some(input.table_name),
         }),
     }
 }
 
-async fn handle_create_table(
-    manager: Arc<RwLock<table_manager::TableManager>>,
-    body: String,
-) -> Result<Json<types::Response>, ErrorResponse> {
-    tracing::debug!(?body, "handling create table");
-    // parse the input
+async fn handle_describe_continuous_backups(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::DescribeContinuousBackupsInput>,
+) -> Result<Json<types::DescribeContinuousBackupsOutput>, ErrorResponse> {
+    tracing::debug!("handling describe continuous backups");
+    tracing::debug!(?input, "parsed input");
+
+    let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = unlocked_manager
+        .get_table_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+
+    Ok(Json(types::DescribeContinuousBackupsOutput {
+        continuous_backups_description: table.continuous_backups_description(),
+    }))
+}
 
-    let input: types::CreateTableInput =
-        serde_json::from_str(&body).map_err(|_| ErrorResponse::SerializationError)?;
+async fn handle_update_continuous_backups(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::UpdateContinuousBackupsInput>,
+) -> Result<Json<types::UpdateContinuousBackupsOutput>, ErrorResponse> {
+    tracing::debug!("handling update continuous backups");
     tracing::debug!(?input, "parsed input");
 
-    // lock: not great, but probably ok for now
     let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
     let table = unlocked_manager
-        .new_table(DEFAULT_ACCOUNT_ID, table_manager::Region::UsEast1, input)
-        // .map_err(|e| ErrorResponse::RynamodbError(format!("{e}").into()))?;
-        .map_err(|e| ErrorResponse::RynamodbError(format!("{e}").into()))?;
+        .get_table_mut_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+    check_table_is_active(table)?;
+    table.set_point_in_time_recovery(
+        input
+            .point_in_time_recovery_specification
+            .point_in_time_recovery_enabled,
+    );
 
-    Ok(Json(types::Response::CreateTable(
-        types::CreateTableOutput {
-            table_description: table.description(),
-        },
-    )))
+    Ok(Json(types::UpdateContinuousBackupsOutput {
+        continuous_backups_description: table.continuous_backups_description(),
+    }))
+}
+
+async fn handle_update_time_to_live(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::UpdateTimeToLiveInput>,
+) -> Result<Json<types::UpdateTimeToLiveOutput>, ErrorResponse> {
+    tracing::debug!("handling update time to live");
+    tracing::debug!(?input, "parsed input");
+
+    let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = unlocked_manager
+        .get_table_mut_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+    check_table_is_active(table)?;
+    table.set_time_to_live(&input.time_to_live_specification)?;
+
+    Ok(Json(types::UpdateTimeToLiveOutput {
+        time_to_live_specification: input.time_to_live_specification,
+    }))
+}
+
+async fn handle_describe_time_to_live(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::DescribeTimeToLiveInput>,
+) -> Result<Json<types::DescribeTimeToLiveOutput>, ErrorResponse> {
+    tracing::debug!("handling describe time to live");
+    tracing::debug!(?input, "parsed input");
+
+    let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = unlocked_manager
+        .get_table_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+
+    Ok(Json(types::DescribeTimeToLiveOutput {
+        time_to_live_description: table.time_to_live_description(),
+    }))
+}
+
+async fn handle_describe_table_replica_auto_scaling(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::DescribeTableReplicaAutoScalingInput>,
+) -> Result<Json<types::DescribeTableReplicaAutoScalingOutput>, ErrorResponse> {
+    tracing::debug!("handling describe table replica auto scaling");
+    tracing::debug!(?input, "parsed input");
+
+    let unlocked_manager = manager.read().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = unlocked_manager
+        .get_table_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+
+    Ok(Json(types::DescribeTableReplicaAutoScalingOutput {
+        table_auto_scaling_description: table.auto_scaling_description(),
+    }))
+}
+
+async fn handle_update_table_replica_auto_scaling(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::UpdateTableReplicaAutoScalingInput>,
+) -> Result<Json<types::UpdateTableReplicaAutoScalingOutput>, ErrorResponse> {
+    tracing::debug!("handling update table replica auto scaling");
+    tracing::debug!(?input, "parsed input");
+
+    let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    let table = unlocked_manager
+        .get_active_table_mut_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+    table.update_auto_scaling(&input)?;
+
+    Ok(Json(types::UpdateTableReplicaAutoScalingOutput {
+        table_auto_scaling_description: table.auto_scaling_description(),
+    }))
+}
+
+async fn handle_restore_table_to_point_in_time(
+    State(AppState {
+        manager,
+        clock,
+        region,
+        ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::RestoreTableToPointInTimeInput>,
+) -> Result<Json<types::RestoreTableToPointInTimeOutput>, ErrorResponse> {
+    tracing::debug!("handling restore table to point in time");
+    tracing::debug!(?input, "parsed input");
+
+    let restore_time = match (input.use_latest_restorable_time, input.restore_date_time) {
+        (Some(true), _) => clock.now(),
+        (_, Some(restore_date_time)) => {
+            types::from_epoch_seconds(restore_date_time).ok_or(ErrorResponse::InvalidRestoreTime)?
+        }
+        _ => {
+            return Err(ErrorResponse::ValidationError(
+                "Either UseLatestRestorableTime or RestoreDateTime must be specified".to_string(),
+            ))
+        }
+    };
+
+    let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    if unlocked_manager
+        .get_table_in(region, &input.target_table_name)
+        .is_some()
+    {
+        return Err(ErrorResponse::TableAlreadyExists(input.target_table_name));
+    }
+    unlocked_manager.check_limits(true)?;
+
+    let source = match (&input.source_table_name, &input.source_table_arn) {
+        (Some(name), _) => unlocked_manager.get_table_in(region, name),
+        (None, Some(arn)) => unlocked_manager.get_table_by_arn(arn),
+        (None, None) => None,
+    }
+    .ok_or_else(|| {
+        ErrorResponse::SourceTableNotFound(
+            input
+                .source_table_name
+                .clone()
+                .or_else(|| input.source_table_arn.clone())
+                .unwrap_or_default(),
+        )
+    })?;
+
+    let restored = source.restore_to_point_in_time(&input.target_table_name, restore_time)?;
+    let table = unlocked_manager.insert_table(restored)?;
+
+    Ok(Json(types::RestoreTableToPointInTimeOutput {
+        table_description: table.description(),
+    }))
+}
+
+async fn handle_update_table(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::UpdateTableInput>,
+) -> Result<Json<types::UpdateTableOutput>, ErrorResponse> {
+    tracing::debug!("handling update table");
+    tracing::debug!(?input, "parsed input");
+
+    let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    unlocked_manager.check_limits(false)?;
+    let index_backfill_delay = unlocked_manager.index_backfill_delay();
+    let table = unlocked_manager
+        .get_table_mut_in(region, &input.table_name)
+        .ok_or_else(|| ErrorResponse::ResourceNotFound {
+            name: Some(input.table_name.clone()),
+        })?;
+    check_table_is_active(table)?;
+    table.update(&input, index_backfill_delay)?;
+
+    Ok(Json(types::UpdateTableOutput {
+        table_description: table.description(),
+    }))
+}
+
+async fn handle_create_table(
+    State(AppState {
+        manager, region, ..
+    }): State<AppState>,
+    AwsJson(input): AwsJson<types::CreateTableInput>,
+) -> Result<Json<types::CreateTableOutput>, ErrorResponse> {
+    tracing::debug!("handling create table");
+    tracing::debug!(?input, "parsed input");
+
+    let options = table::TableOptions::from(input);
+
+    // lock: not great, but probably ok for now
+    let mut unlocked_manager = manager.write().map_err(|_| ErrorResponse::MutexUnlock)?;
+    if let Some(existing) = unlocked_manager.get_table_in(region, &options.name) {
+        if unlocked_manager.idempotent_create_table && existing.has_schema(&options) {
+            tracing::debug!(table_name = %options.name, "table already exists with this schema");
+            return Ok(Json(types::CreateTableOutput {
+                table_description: existing.description(),
+            }));
+        }
+        return Err(ErrorResponse::ResourceInUse(format!(
+            "Table already exists: {}",
+            options.name
+        )));
+    }
+    unlocked_manager.check_limits(true)?;
+    let table = unlocked_manager.new_table_in(region, options)?;
+
+    Ok(Json(types::CreateTableOutput {
+        table_description: table.description(),
+    }))
 }
 
-pub fn router() -> Router {
-    let manager = table_manager::TableManager::default();
-    Router::new()
-        .route("/_health", get(|| async { "ok" }))
-        .fallback(any(handler))
-        .with_state(Arc::new(RwLock::new(manager)))
+pub fn router(config: RynamodbConfig) -> Router {
+    Rynamodb::builder().config(config).build_router()
 }
@@ -0,0 +1,157 @@
+//! Limits DynamoDB places on accounts and requests
+//!
+//! Every limit defaults to DynamoDB's own, and can be shrunk by embedders so that tests exercise
+//! the handling of oversized items, partial pages and rejected batches without generating
+//! megabytes of fixture data:
+//!
+//! ```no_run
+//! let router = rynamodb::router(rynamodb::RynamodbConfig {
+//!     limits: rynamodb::Limits {
+//!         max_item_bytes: 1024,
+//!         max_page_items: Some(3),
+//!         ..Default::default()
+//!     },
+//!     ..Default::default()
+//! });
+//! ```
+
+use std::collections::HashMap;
+
+use serde_dynamo::AttributeValue;
+
+use crate::{
+    errors::ErrorResponse,
+    table::{names::AttributeName, size},
+};
+
+/// Limits applied to each account, and to the requests made to its tables
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_tables: usize,
+    /// maximum number of tables which can be in the `CREATING` or `DELETING` state at once
+    pub max_concurrent_control_plane_operations: usize,
+    /// largest item which can be written, counting attribute names as well as values
+    pub max_item_bytes: usize,
+    /// query and scan pages end once the items read reach this size
+    pub max_page_bytes: usize,
+    /// query and scan pages end after this many items, as if every request had a lower `Limit`
+    pub max_page_items: Option<usize>,
+    /// most keys a single `BatchGetItem` request can read
+    pub max_batch_get_keys: usize,
+    /// largest `BatchGetItem` response, after which the remaining keys are returned unprocessed
+    pub max_batch_get_response_bytes: usize,
+    /// most items a single `BatchWriteItem` request can write
+    pub max_batch_write_items: usize,
+    /// longest expression, such as a `KeyConditionExpression` or `FilterExpression`
+    pub max_expression_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_tables: 2500,
+            max_concurrent_control_plane_operations: 500,
+            max_item_bytes: 400 * 1024,
+            max_page_bytes: 1024 * 1024,
+            max_page_items: None,
+            max_batch_get_keys: 100,
+            max_batch_get_response_bytes: 16 * 1024 * 1024,
+            max_batch_write_items: 25,
+            max_expression_bytes: 4096,
+        }
+    }
+}
+
+impl Limits {
+    /// Number of items to read for the next page of a query or scan with the given `Limit`
+    pub(crate) fn page_items(&self, limit: Option<usize>) -> Option<usize> {
+        match (limit, self.max_page_items) {
+            (Some(limit), Some(max)) => Some(limit.min(max)),
+            (limit, max) => limit.or(max),
+        }
+    }
+
+    pub(crate) fn check_item_size<K: AttributeName>(
+        &self,
+        item: &HashMap<K, AttributeValue>,
+    ) -> Result<(), ErrorResponse> {
+        if size::item_size(item) > self.max_item_bytes {
+            return Err(ErrorResponse::ValidationError(
+                "Item size has exceeded the maximum allowed size".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check the length of each expression of a request, given with the name of its parameter
+    pub(crate) fn check_expressions(
+        &self,
+        expressions: &[(&str, Option<&str>)],
+    ) -> Result<(), ErrorResponse> {
+        for (parameter, expression) in expressions {
+            let Some(expression) = expression else {
+                continue;
+            };
+            if expression.len() > self.max_expression_bytes {
+                return Err(ErrorResponse::ValidationError(format!(
+                    "Invalid {parameter}: Expression size has exceeded the maximum allowed size; \
+                    expression size: {}",
+                    expression.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_items() {
+        let limits = Limits {
+            max_page_items: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(limits.page_items(None), Some(3));
+        assert_eq!(limits.page_items(Some(2)), Some(2));
+        assert_eq!(limits.page_items(Some(10)), Some(3));
+        assert_eq!(Limits::default().page_items(None), None);
+        assert_eq!(Limits::default().page_items(Some(10)), Some(10));
+    }
+
+    #[test]
+    fn shrunk_item_size() {
+        let limits = Limits {
+            max_item_bytes: 10,
+            ..Default::default()
+        };
+        let mut item = HashMap::new();
+        item.insert("pk".to_string(), AttributeValue::S("abcdefgh".to_string()));
+        assert!(limits.check_item_size(&item).is_ok());
+        item.insert("x".to_string(), AttributeValue::Bool(true));
+        assert!(limits.check_item_size(&item).is_err());
+    }
+
+    #[test]
+    fn expression_length() {
+        let limits = Limits {
+            max_expression_bytes: 8,
+            ..Default::default()
+        };
+        assert!(limits
+            .check_expressions(&[
+                ("FilterExpression", None),
+                ("KeyConditionExpression", Some("pk = :a"))
+            ])
+            .is_ok());
+        let err = limits
+            .check_expressions(&[("FilterExpression", Some("a = :a AND b = :b"))])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ErrorResponse::ValidationError(message) if message.starts_with("Invalid FilterExpression")
+        ));
+    }
+}
@@ -1,18 +1,369 @@
+use std::time::Duration;
+
 use clap::Parser;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter};
 
 #[derive(Parser, Debug)]
 struct Args {
     #[clap(short, long, default_value = "3050")]
     port: u16,
+
+    /// Account which owns the tables
+    #[clap(long, default_value = rynamodb::DEFAULT_ACCOUNT_ID)]
+    account_id: String,
+
+    /// Region of the tables of unsigned requests and the /_rynamodb endpoints. Ignored for signed
+    /// requests, such as those from the AWS SDKs and CLI, which use the region they are signed for
+    #[clap(long, default_value = "us-east-1")]
+    region: rynamodb::Region,
+
+    /// Probability that each entry of a batch operation is returned unprocessed
+    #[clap(long, default_value = "0")]
+    unprocessed_probability: f64,
+
+    /// Batches with fewer entries than this are always fully processed
+    #[clap(long, default_value = "0")]
+    unprocessed_min_batch_size: usize,
+
+    /// Time in milliseconds that new tables spend in the CREATING state
+    #[clap(long, default_value = "0")]
+    creation_delay_ms: u64,
+
+    /// Time in milliseconds that deleted tables spend in the DELETING state
+    #[clap(long, default_value = "0")]
+    deletion_delay_ms: u64,
+
+    /// Time in milliseconds that global secondary indexes added to existing tables spend
+    /// backfilling
+    #[clap(long, default_value = "0")]
+    index_backfill_delay_ms: u64,
+
+    /// Maximum number of tables per account
+    #[clap(long, default_value = "2500")]
+    max_tables: usize,
+
+    /// Maximum number of tables which can be created or deleted at the same time
+    #[clap(long, default_value = "500")]
+    max_concurrent_control_plane_operations: usize,
+
+    /// Read capacity units reported for new tables
+    #[clap(long, default_value = "10")]
+    default_read_capacity_units: u64,
+
+    /// Write capacity units reported for new tables
+    #[clap(long, default_value = "10")]
+    default_write_capacity_units: u64,
+
+    /// Encrypt new tables with an AWS managed KMS key unless the request specifies otherwise
+    #[clap(long)]
+    default_kms_encryption: bool,
+
+    /// Maximum number of tables with their own metrics series, operations on any other tables
+    /// are aggregated
+    #[clap(long, default_value = "100")]
+    metrics_max_tables: usize,
+
+    /// Refuse connections opened beyond this many with ServiceUnavailable, so that clients
+    /// leaking connections fail quickly
+    #[clap(long)]
+    max_connections: Option<usize>,
+
+    /// Return the existing table from CreateTable if it has the requested schema, rather than
+    /// failing with ResourceInUseException
+    #[clap(long)]
+    idempotent_create_table: bool,
+
+    /// Time in milliseconds between background sweeps for items whose time to live has passed.
+    /// Without it, expired items are only deleted by requests to `/_rynamodb/ttl/sweep`
+    #[clap(long)]
+    ttl_sweep_interval_ms: Option<u64>,
+
+    /// Time in milliseconds between background compactions, which release the memory left
+    /// unused by removed items
+    #[clap(long)]
+    compaction_interval_ms: Option<u64>,
+
+    /// Log Query and Scan operations taking longer than this many milliseconds
+    #[clap(long)]
+    slow_operation_ms: Option<u64>,
+
+    /// Log Query and Scan operations which read at least this many items
+    #[clap(long)]
+    slow_operation_scanned_count: Option<usize>,
+
+    /// Record the attributes read and returned by Query and Scan operations, reported at
+    /// `/_rynamodb/access-patterns`
+    #[clap(long)]
+    access_patterns: bool,
+
+    /// Record the write capacity units written to each partition key, reported at
+    /// `/_rynamodb/hot-keys`
+    #[clap(long)]
+    hot_keys: bool,
+
+    /// Log a warning when a partition key is written more than this many write capacity units
+    /// in a second, 1000 if no value is given, as DynamoDB's limit. Implies `--hot-keys`
+    #[clap(long, num_args = 0..=1, default_missing_value = "1000")]
+    hot_key_alert_wcu: Option<u64>,
+
+    /// Fail operations which take longer than this many milliseconds with an InternalServerError
+    #[clap(long)]
+    request_timeout_ms: Option<u64>,
+
+    /// Microseconds of latency added to each operation per kilobyte of its request and response
+    #[clap(long, default_value = "0")]
+    latency_us_per_kb: u64,
+
+    /// Microseconds of latency added to each operation per item it returns
+    #[clap(long, default_value = "0")]
+    latency_us_per_item: u64,
+
+    /// End each page of query and scan results after a random number of items, up to this many
+    #[clap(long)]
+    page_jitter_max_items: Option<usize>,
+
+    /// Fewest items returned in a page of query and scan results with `--page-jitter-max-items`, unless
+    /// fewer remain
+    #[clap(long, default_value = "1", requires = "page_jitter_max_items")]
+    page_jitter_min_items: usize,
+
+    /// Return the partitions of scanned tables in a random order derived from this seed
+    #[clap(long)]
+    scan_shuffle_seed: Option<u64>,
+
+    /// Answer eventually consistent reads from a cache, as DynamoDB Accelerator would
+    #[clap(long)]
+    dax: bool,
+
+    /// Time in milliseconds items read with GetItem are cached for with `--dax`
+    #[clap(long, default_value = "300000", requires = "dax")]
+    dax_item_ttl_ms: u64,
+
+    /// Time in milliseconds Query and Scan results are cached for with `--dax`
+    #[clap(long, default_value = "300000", requires = "dax")]
+    dax_query_ttl_ms: u64,
+
+    /// Keep cached items when they are written, as if written by clients bypassing the cache
+    #[clap(long, requires = "dax")]
+    dax_no_write_invalidation: bool,
+
+    /// Probability that each operation's connection is closed, or its response truncated or
+    /// stalled, to exercise client retries and timeouts
+    #[clap(long, default_value = "0")]
+    connection_fault_probability: f64,
+
+    /// Time in milliseconds a stalled response waits before its connection is closed
+    #[clap(long, default_value = "60000")]
+    connection_fault_stall_ms: u64,
+
+    /// Remove namespaces, and all of their tables, once they have not been used for this many
+    /// seconds
+    #[clap(long)]
+    namespace_ttl_secs: Option<u64>,
+
+    /// Serve the emulator under this path, e.g. `/dynamodb`, when running behind an edge proxy
+    #[clap(long)]
+    path_prefix: Option<String>,
+
+    /// Reject requests whose Host header names another AWS service, e.g.
+    /// `s3.localhost.localstack.cloud`
+    #[clap(long)]
+    host_routing: bool,
+
+    /// Answer requests needing behaviour rynamodb does not implement with a
+    /// NotImplementedByRynamodb error naming the feature
+    #[clap(long)]
+    fail_fast: bool,
+
+    /// Check requests and responses against the schemas of their operations, as debug builds
+    /// always do
+    #[clap(long)]
+    validate_schemas: bool,
+
+    /// URL at which the other nodes of a simulated cluster reach this node
+    #[cfg(feature = "cluster")]
+    #[clap(long, requires = "peer")]
+    node_url: Option<String>,
+
+    /// URL of another node of the simulated cluster, may be repeated
+    #[cfg(feature = "cluster")]
+    #[clap(long, requires = "node_url")]
+    peer: Vec<String>,
+
+    /// Time in milliseconds between table metadata exchanges with the other nodes
+    #[cfg(feature = "cluster")]
+    #[clap(long, default_value = "1000")]
+    gossip_interval_ms: u64,
+
+    /// JSON file of settings to apply on startup, and again whenever the server receives SIGHUP,
+    /// in the format accepted by `PUT /_rynamodb/config`
+    #[clap(long)]
+    config_file: Option<std::path::PathBuf>,
+
+    /// Append every operation which changes the tables to this file
+    #[clap(long)]
+    journal: Option<std::path::PathBuf>,
+
+    /// Start with the tables rebuilt by replaying a journal
+    #[clap(long)]
+    replay_journal: Option<std::path::PathBuf>,
+
+    /// Only replay the operations of the journal which completed by this time, e.g.
+    /// `2023-06-01T12:00:00Z`
+    #[clap(long, requires = "replay_journal")]
+    replay_until: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Start with the tables and items of a DynamoDB Local database file
+    #[cfg(feature = "dynamodb-local")]
+    #[clap(long)]
+    import_dynamodb_local: Option<std::path::PathBuf>,
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    // the filter can be replaced at runtime through the settings
+    let (filter, filter_handle) = reload::Layer::new(EnvFilter::from_default_env());
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
 
     let args = Args::parse();
 
-    let app = rynamodb::router();
+    #[cfg(feature = "dynamodb-local")]
+    let mut tables = match &args.import_dynamodb_local {
+        Some(path) => rynamodb::dynamodb_local::import(path, args.region, &args.account_id)
+            .await
+            .expect("could not import DynamoDB Local database"),
+        None => Vec::new(),
+    };
+    #[cfg(not(feature = "dynamodb-local"))]
+    let mut tables = Vec::new();
+
+    let config = rynamodb::RynamodbConfig {
+        account_id: args.account_id,
+        region: args.region,
+        unprocessed_simulation: rynamodb::UnprocessedSimulation {
+            probability: args.unprocessed_probability,
+            min_batch_size: args.unprocessed_min_batch_size,
+        },
+        lifecycle: rynamodb::LifecycleSimulation {
+            creation_delay: Duration::from_millis(args.creation_delay_ms),
+            deletion_delay: Duration::from_millis(args.deletion_delay_ms),
+            index_backfill_delay: Duration::from_millis(args.index_backfill_delay_ms),
+        },
+        limits: rynamodb::Limits {
+            max_tables: args.max_tables,
+            max_concurrent_control_plane_operations: args.max_concurrent_control_plane_operations,
+            ..Default::default()
+        },
+        table_defaults: rynamodb::TableDefaults {
+            read_capacity_units: args.default_read_capacity_units,
+            write_capacity_units: args.default_write_capacity_units,
+            kms_encryption: args.default_kms_encryption,
+        },
+        idempotent_create_table: args.idempotent_create_table,
+        time_to_live: rynamodb::TimeToLiveSimulation {
+            sweep_interval: args.ttl_sweep_interval_ms.map(Duration::from_millis),
+        },
+        compaction_interval: args.compaction_interval_ms.map(Duration::from_millis),
+        slow_operations: rynamodb::SlowOperationThresholds {
+            duration: args.slow_operation_ms.map(Duration::from_millis),
+            scanned_count: args.slow_operation_scanned_count,
+        },
+        access_patterns: args.access_patterns,
+        hot_keys: rynamodb::HotKeyConfig {
+            enabled: args.hot_keys || args.hot_key_alert_wcu.is_some(),
+            alert_write_units: args.hot_key_alert_wcu,
+        },
+        metrics: rynamodb::MetricsConfig {
+            max_tables: args.metrics_max_tables,
+        },
+        max_connections: args.max_connections,
+        request_timeout: args.request_timeout_ms.map(Duration::from_millis),
+        latency: rynamodb::LatencySimulation {
+            per_kb: Duration::from_micros(args.latency_us_per_kb),
+            per_item: Duration::from_micros(args.latency_us_per_item),
+        },
+        page_jitter: args
+            .page_jitter_max_items
+            .map(|max_page_size| rynamodb::PageJitter {
+                min_page_size: args.page_jitter_min_items,
+                max_page_size,
+            }),
+        scan_shuffle_seed: args.scan_shuffle_seed,
+        dax: args.dax.then(|| rynamodb::DaxSimulation {
+            item_ttl: Duration::from_millis(args.dax_item_ttl_ms),
+            query_ttl: Duration::from_millis(args.dax_query_ttl_ms),
+            invalidate_on_write: !args.dax_no_write_invalidation,
+        }),
+        connection_faults: rynamodb::ConnectionFaults {
+            probability: args.connection_fault_probability,
+            stall_duration: Duration::from_millis(args.connection_fault_stall_ms),
+        },
+        namespace_ttl: args.namespace_ttl_secs.map(Duration::from_secs),
+        edge: rynamodb::EdgeRouting {
+            path_prefix: args.path_prefix,
+            host_routing: args.host_routing,
+        },
+        fail_fast: args.fail_fast,
+        validate_schemas: args.validate_schemas || cfg!(debug_assertions),
+        #[cfg(feature = "cluster")]
+        cluster: args.node_url.map(|node_url| rynamodb::ClusterConfig {
+            node_url,
+            peers: args.peer,
+            gossip_interval: Duration::from_millis(args.gossip_interval_ms),
+        }),
+    };
+    if let Some(path) = &args.replay_journal {
+        let replayed = rynamodb::journal::replay(path, args.replay_until, &config)
+            .await
+            .expect("could not replay journal");
+        tables.extend(replayed);
+    }
+    let mut builder = rynamodb::Rynamodb::builder();
+    if let Some(path) = &args.journal {
+        builder = builder
+            .journal(rynamodb::journal::Journal::open(path).expect("could not open journal"));
+    }
+    let (app, tables) = builder
+        .config(config)
+        .tables(tables)
+        .log_level_reloader(move |level| {
+            let filter = EnvFilter::try_new(level).map_err(|e| e.to_string())?;
+            filter_handle.reload(filter).map_err(|e| e.to_string())
+        })
+        .build();
+    if let Some(path) = args.config_file {
+        let settings = tables.settings();
+        load_settings(&settings, &path).expect("could not apply config file");
+        #[cfg(unix)]
+        tokio::spawn(reload_on_hangup(settings, path));
+    }
     tracing::info!(%args.port, "running server");
     rynamodb::run_server(app, args.port).await.unwrap();
 }
+
+fn load_settings(settings: &rynamodb::Settings, path: &std::path::Path) -> Result<(), String> {
+    let contents = std::fs::read(path).map_err(|e| e.to_string())?;
+    let update = serde_json::from_slice(&contents).map_err(|e| e.to_string())?;
+    settings.update(update).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Apply the config file again every time the process receives SIGHUP
+#[cfg(unix)]
+async fn reload_on_hangup(settings: rynamodb::Settings, path: std::path::PathBuf) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup()).expect("could not listen for SIGHUP");
+    while hangups.recv().await.is_some() {
+        match load_settings(&settings, &path) {
+            Ok(()) => tracing::info!(path = %path.display(), "reloaded config file"),
+            // keep running with the previous settings
+            Err(error) => {
+                tracing::error!(%error, path = %path.display(), "could not reload config file")
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! Prometheus metrics about the operations handled, the sizes of the items written and the
+//! compaction of tables, served at `/_rynamodb/metrics`
+//!
+//! Series are labelled by account, region and table. Test suites often create thousands of
+//! short lived tables, so only the first tables seen are tracked individually, and operations on
+//! any other table are aggregated under the table label `other`.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+    sync::Mutex,
+    time::Duration,
+};
+
+use crate::{
+    table::{
+        stats::{SizeHistogram, ITEM_SIZE_BUCKETS},
+        Table,
+    },
+    table_manager::Region,
+    OperationType,
+};
+
+/// table label of operations on tables which are not tracked individually
+const OTHER_TABLES: &str = "other";
+
+#[derive(Debug, Clone, Copy)]
+pub struct MetricsConfig {
+    /// maximum number of tables with their own series
+    pub max_tables: usize,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { max_tables: 100 }
+    }
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    account_id: String,
+    region: Region,
+    config: MetricsConfig,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    tracked_tables: HashSet<String>,
+    /// keyed by table label (empty for operations without a table) and operation
+    series: BTreeMap<(String, String), Series>,
+    compactions: u64,
+    reclaimed_bytes: u64,
+}
+
+/// Renders one metric of a series
+type SeriesValue = fn(&Series) -> String;
+
+#[derive(Debug, Default)]
+struct Series {
+    operations: u64,
+    errors: u64,
+    duration: Duration,
+}
+
+impl Metrics {
+    pub fn new(account_id: String, region: Region, config: MetricsConfig) -> Self {
+        Self {
+            account_id,
+            region,
+            config,
+            inner: Default::default(),
+        }
+    }
+
+    pub fn record(
+        &self,
+        operation: OperationType,
+        table_name: Option<&str>,
+        duration: Duration,
+        failed: bool,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        let table = match table_name {
+            Some(name) => self.table_label(&mut inner, name),
+            None => String::new(),
+        };
+        let series = inner
+            .series
+            .entry((table, format!("{operation:?}")))
+            .or_default();
+        series.operations += 1;
+        series.errors += u64::from(failed);
+        series.duration += duration;
+    }
+
+    /// Label of a table, which is tracked individually if there is room for it
+    fn table_label(&self, inner: &mut Inner, name: &str) -> String {
+        if inner.tracked_tables.contains(name) {
+            name.to_string()
+        } else if inner.tracked_tables.len() < self.config.max_tables {
+            inner.tracked_tables.insert(name.to_string());
+            name.to_string()
+        } else {
+            OTHER_TABLES.to_string()
+        }
+    }
+
+    /// Label of a table, without starting to track it
+    fn tracked_label<'a>(inner: &Inner, name: &'a str) -> &'a str {
+        if inner.tracked_tables.contains(name) {
+            name
+        } else {
+            OTHER_TABLES
+        }
+    }
+
+    /// Histograms of the sizes of the items written to `tables`, in the Prometheus text format
+    ///
+    /// Only tables which operations have been recorded for have their own histogram, so that
+    /// scraping the metrics does not use up the series of tables which are later operated on.
+    pub fn render_item_sizes<'a>(&self, tables: impl Iterator<Item = &'a Table>) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut histograms: BTreeMap<&str, SizeHistogram> = BTreeMap::new();
+        for table in tables {
+            histograms
+                .entry(Self::tracked_label(&inner, &table.name))
+                .or_default()
+                .merge(table.item_sizes());
+        }
+
+        let name = "rynamodb_item_size_bytes";
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP {name} Sizes of the items written");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (table, histogram) in histograms {
+            let labels = format!(
+                "account=\"{}\",region=\"{}\",table=\"{}\"",
+                escape(&self.account_id),
+                self.region,
+                escape(table),
+            );
+            let mut cumulative = 0;
+            for (bound, bucket) in ITEM_SIZE_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += bucket.count;
+                let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+            }
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels},le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(out, "{name}_sum{{{labels}}} {}", histogram.sum);
+            let _ = writeln!(out, "{name}_count{{{labels}}} {}", histogram.count);
+        }
+        out
+    }
+
+    /// Count a compaction of the tables, which released `reclaimed_bytes`
+    pub fn record_compaction(&self, reclaimed_bytes: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.compactions += 1;
+        inner.reclaimed_bytes += reclaimed_bytes as u64;
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+        let metrics: [(&str, &str, &str, SeriesValue); 3] = [
+            (
+                "rynamodb_operations_total",
+                "counter",
+                "Operations handled",
+                |series| series.operations.to_string(),
+            ),
+            (
+                "rynamodb_operation_errors_total",
+                "counter",
+                "Operations which returned an error",
+                |series| series.errors.to_string(),
+            ),
+            (
+                "rynamodb_operation_duration_seconds_total",
+                "counter",
+                "Time spent handling operations",
+                |series| series.duration.as_secs_f64().to_string(),
+            ),
+        ];
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for ((table, operation), series) in &inner.series {
+                let _ = writeln!(
+                    out,
+                    "{name}{{account=\"{}\",region=\"{}\",table=\"{}\",operation=\"{operation}\"}} {}",
+                    escape(&self.account_id),
+                    self.region,
+                    escape(table),
+                    value(series),
+                );
+            }
+        }
+
+        let compaction_metrics = [
+            (
+                "rynamodb_compactions_total",
+                "Compactions of the tables",
+                inner.compactions,
+            ),
+            (
+                "rynamodb_compaction_reclaimed_bytes_total",
+                "Memory released by compacting the tables",
+                inner.reclaimed_bytes,
+            ),
+        ];
+        for (name, help, value) in compaction_metrics {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(
+                out,
+                "{name}{{account=\"{}\",region=\"{}\"}} {value}",
+                escape(&self.account_id),
+                self.region,
+            );
+        }
+        out
+    }
+}
+
+pub(crate) fn escape(label: &str) -> String {
+    label
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untracked_tables_are_aggregated() {
+        let metrics = Metrics::new(
+            crate::DEFAULT_ACCOUNT_ID.to_string(),
+            Region::EuWest1,
+            MetricsConfig { max_tables: 2 },
+        );
+        for table in ["a", "b", "c", "d", "a"] {
+            metrics.record(
+                OperationType::PutItem,
+                Some(table),
+                Duration::from_millis(500),
+                table == "d",
+            );
+        }
+        metrics.record(OperationType::ListTables, None, Duration::ZERO, false);
+
+        let rendered = metrics.render();
+        let labels = |table: &str, operation: &str| {
+            format!(
+                "{{account=\"000000000000\",region=\"eu-west-1\",table=\"{table}\",operation=\"{operation}\"}}"
+            )
+        };
+        for line in [
+            format!("rynamodb_operations_total{} 2", labels("a", "PutItem")),
+            format!("rynamodb_operations_total{} 1", labels("b", "PutItem")),
+            format!("rynamodb_operations_total{} 2", labels("other", "PutItem")),
+            format!("rynamodb_operations_total{} 1", labels("", "ListTables")),
+            format!(
+                "rynamodb_operation_errors_total{} 1",
+                labels("other", "PutItem")
+            ),
+            format!(
+                "rynamodb_operation_duration_seconds_total{} 1",
+                labels("a", "PutItem")
+            ),
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {line}");
+        }
+        assert!(!rendered.contains("table=\"c\""));
+    }
+
+    #[test]
+    fn item_size_histograms() {
+        let metrics = Metrics::new(
+            crate::DEFAULT_ACCOUNT_ID.to_string(),
+            Region::EuWest1,
+            MetricsConfig { max_tables: 2 },
+        );
+        metrics.record(OperationType::PutItem, Some("a"), Duration::ZERO, false);
+        let tables: Vec<Table> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| {
+                let mut table = Table::new(
+                    Region::EuWest1,
+                    crate::DEFAULT_ACCOUNT_ID,
+                    crate::table::TableOptions {
+                        name: name.to_string(),
+                        partition_key: "pk".to_string(),
+                        ..Default::default()
+                    },
+                );
+                let mut item = std::collections::HashMap::new();
+                item.insert(
+                    "pk".to_string(),
+                    serde_dynamo::AttributeValue::S("x".repeat(2000)),
+                );
+                table.insert(item).unwrap();
+                table
+            })
+            .collect();
+
+        let rendered = metrics.render_item_sizes(tables.iter());
+        let labels = |table: &str| {
+            format!("account=\"000000000000\",region=\"eu-west-1\",table=\"{table}\"")
+        };
+        for line in [
+            format!(
+                "rynamodb_item_size_bytes_bucket{{{},le=\"1024\"}} 0",
+                labels("a")
+            ),
+            format!(
+                "rynamodb_item_size_bytes_bucket{{{},le=\"4096\"}} 1",
+                labels("a")
+            ),
+            format!(
+                "rynamodb_item_size_bytes_bucket{{{},le=\"+Inf\"}} 1",
+                labels("a")
+            ),
+            format!("rynamodb_item_size_bytes_sum{{{}}} 2002", labels("a")),
+            format!("rynamodb_item_size_bytes_count{{{}}} 2", labels("other")),
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {line}");
+        }
+
+        // rendering does not track the tables, so a table operated on later has its own series
+        metrics.record(OperationType::PutItem, Some("c"), Duration::ZERO, false);
+        assert!(metrics.render().contains("table=\"c\""));
+        assert!(metrics.render_item_sizes(tables.iter()).contains(&format!(
+            "rynamodb_item_size_bytes_count{{{}}} 1",
+            labels("c")
+        )));
+    }
+
+    #[test]
+    fn compactions_are_counted() {
+        let metrics = Metrics::new(
+            crate::DEFAULT_ACCOUNT_ID.to_string(),
+            Region::EuWest1,
+            Default::default(),
+        );
+        metrics.record_compaction(100);
+        metrics.record_compaction(0);
+
+        let rendered = metrics.render();
+        let labels = "{account=\"000000000000\",region=\"eu-west-1\"}";
+        for line in [
+            format!("rynamodb_compactions_total{labels} 2"),
+            format!("rynamodb_compaction_reclaimed_bytes_total{labels} 100"),
+        ] {
+            assert!(rendered.lines().any(|l| l == line), "missing {line}");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Isolated sets of tables, selected per request with the `x-rynamodb-namespace` header
+//!
+//! Tests running in parallel against one emulator can each send a unique namespace, e.g. a
+//! random UUID, and see only the tables they created, under the same table names. Each namespace
+//! is created empty the first time it is used, with the emulator's settings. Requests without
+//! the header use the default set of tables.
+//!
+//! Operations in a namespace are not journaled, and the `/_rynamodb` endpoints which act on
+//! tables use the namespace of their request in the same way.
+//!
+//! Namespaces which are not used for their time to live, given by the
+//! `x-rynamodb-namespace-ttl` header in seconds or [`crate::RynamodbConfig::namespace_ttl`], are
+//! removed with all of their tables, so that test runs which crash before cleaning up do not
+//! leak memory in a shared emulator.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{scheduler::Scheduler, table_manager::TableManager};
+
+/// Header selecting the namespace of a request
+pub const NAMESPACE_HEADER: &str = "x-rynamodb-namespace";
+
+/// Header setting the time to live of a namespace in seconds
+pub const NAMESPACE_TTL_HEADER: &str = "x-rynamodb-namespace-ttl";
+
+/// how often namespaces are checked for expiry in the background
+const REAP_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Entry {
+    manager: Arc<RwLock<TableManager>>,
+    last_used: Instant,
+    ttl: Option<Duration>,
+}
+
+impl Entry {
+    fn has_expired(&self, now: Instant) -> bool {
+        self.ttl
+            .is_some_and(|ttl| now.saturating_duration_since(self.last_used) >= ttl)
+    }
+}
+
+/// The tables of every namespace which has been used
+#[derive(Default)]
+pub struct Namespaces {
+    namespaces: Mutex<HashMap<String, Entry>>,
+    /// time to live of namespaces which do not set their own
+    default_ttl: Option<Duration>,
+}
+
+impl Namespaces {
+    pub fn new(default_ttl: Option<Duration>) -> Self {
+        Self {
+            default_ttl,
+            ..Default::default()
+        }
+    }
+
+    /// The tables of a namespace, creating it with the settings of `default` if it is new
+    ///
+    /// Using a namespace restarts its time to live, and `ttl` replaces its time to live if given.
+    /// Expired namespaces are removed first, so an expired namespace is recreated empty.
+    pub fn manager(
+        &self,
+        namespace: &str,
+        ttl: Option<Duration>,
+        default: &RwLock<TableManager>,
+    ) -> Arc<RwLock<TableManager>> {
+        let now = Instant::now();
+        let mut namespaces = self.namespaces.lock().unwrap();
+        reap(&mut namespaces, now);
+        let entry = namespaces.entry(namespace.to_string()).or_insert_with(|| {
+            tracing::debug!(%namespace, "creating namespace");
+            let default = default.read().unwrap_or_else(|e| e.into_inner());
+            Entry {
+                manager: Arc::new(RwLock::new(default.without_tables())),
+                last_used: now,
+                ttl: self.default_ttl,
+            }
+        });
+        entry.last_used = now;
+        if ttl.is_some() {
+            entry.ttl = ttl;
+        }
+        Arc::clone(&entry.manager)
+    }
+
+    /// The tables of every namespace, for changing their settings or sweeping them
+    pub fn managers(&self) -> Vec<Arc<RwLock<TableManager>>> {
+        self.namespaces
+            .lock()
+            .unwrap()
+            .values()
+            .map(|namespace| Arc::clone(&namespace.manager))
+            .collect()
+    }
+
+    /// Remove the namespaces whose time to live has passed, returning how many were removed
+    pub fn reap(&self) -> usize {
+        reap(&mut self.namespaces.lock().unwrap(), Instant::now())
+    }
+}
+
+fn reap(namespaces: &mut HashMap<String, Entry>, now: Instant) -> usize {
+    let before = namespaces.len();
+    namespaces.retain(|name, namespace| {
+        let expired = namespace.has_expired(now);
+        if expired {
+            tracing::info!(namespace = %name, "removing expired namespace");
+        }
+        !expired
+    });
+    before - namespaces.len()
+}
+
+/// Periodically remove expired namespaces in the background, so that their memory is released
+/// even if no other namespace is used
+pub fn schedule_reaper(scheduler: &Scheduler, namespaces: Arc<Namespaces>) {
+    scheduler.every("namespace-expiry", REAP_INTERVAL, move || {
+        namespaces.reap();
+        async {}
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::TableOptions;
+
+    #[test]
+    fn namespaces_are_isolated() {
+        let default = RwLock::new(TableManager::default());
+        let namespaces = Namespaces::default();
+
+        let first = namespaces.manager("first", None, &default);
+        first
+            .write()
+            .unwrap()
+            .new_table(TableOptions {
+                name: "shared-name".to_string(),
+                partition_key: "pk".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(first.read().unwrap().get_table("shared-name").is_some());
+        // the same namespace is returned again
+        let again = namespaces.manager("first", None, &default);
+        assert!(again.read().unwrap().get_table("shared-name").is_some());
+        let second = namespaces.manager("second", None, &default);
+        assert!(second.read().unwrap().get_table("shared-name").is_none());
+        assert!(default.read().unwrap().get_table("shared-name").is_none());
+        assert_eq!(namespaces.managers().len(), 2);
+    }
+
+    #[test]
+    fn expired_namespaces_are_removed() {
+        let default = RwLock::new(TableManager::default());
+        let namespaces = Namespaces::new(Some(Duration::from_secs(3600)));
+
+        namespaces.manager("kept", None, &default);
+        namespaces.manager("expiring", Some(Duration::ZERO), &default);
+        assert_eq!(namespaces.managers().len(), 2);
+        assert_eq!(namespaces.reap(), 1);
+        assert_eq!(namespaces.managers().len(), 1);
+        assert_eq!(namespaces.reap(), 0);
+    }
+}
@@ -0,0 +1,220 @@
+//! Registry of the DynamoDB API operations, and which of them are implemented
+//!
+//! Each implemented operation is declared once, with its input and output types, which generates
+//! [`OperationType`] and links the input types back to their operation through
+//! [`OperationInput`]. Requests for operations which DynamoDB has but the emulator does not are
+//! rejected with an error saying so, rather than as unknown operations.
+
+use std::str::FromStr;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::types;
+
+/// Every operation of the DynamoDB API, as of API version 2012-08-10
+pub const DYNAMODB_OPERATIONS: &[&str] = &[
+    "BatchExecuteStatement",
+    "BatchGetItem",
+    "BatchWriteItem",
+    "CreateBackup",
+    "CreateGlobalTable",
+    "CreateTable",
+    "DeleteBackup",
+    "DeleteItem",
+    "DeleteResourcePolicy",
+    "DeleteTable",
+    "DescribeBackup",
+    "DescribeContinuousBackups",
+    "DescribeContributorInsights",
+    "DescribeEndpoints",
+    "DescribeExport",
+    "DescribeGlobalTable",
+    "DescribeGlobalTableSettings",
+    "DescribeImport",
+    "DescribeKinesisStreamingDestination",
+    "DescribeLimits",
+    "DescribeTable",
+    "DescribeTableReplicaAutoScaling",
+    "DescribeTimeToLive",
+    "DisableKinesisStreamingDestination",
+    "EnableKinesisStreamingDestination",
+    "ExecuteStatement",
+    "ExecuteTransaction",
+    "ExportTableToPointInTime",
+    "GetItem",
+    "GetResourcePolicy",
+    "ImportTable",
+    "ListBackups",
+    "ListContributorInsights",
+    "ListExports",
+    "ListGlobalTables",
+    "ListImports",
+    "ListTables",
+    "ListTagsOfResource",
+    "PutItem",
+    "PutResourcePolicy",
+    "Query",
+    "RestoreTableFromBackup",
+    "RestoreTableToPointInTime",
+    "Scan",
+    "TagResource",
+    "TransactGetItems",
+    "TransactWriteItems",
+    "UntagResource",
+    "UpdateContinuousBackups",
+    "UpdateContributorInsights",
+    "UpdateGlobalTable",
+    "UpdateGlobalTableSettings",
+    "UpdateItem",
+    "UpdateKinesisStreamingDestination",
+    "UpdateTable",
+    "UpdateTableReplicaAutoScaling",
+    "UpdateTimeToLive",
+];
+
+/// Where to ask for operations which are not implemented
+pub const ISSUES_URL: &str = "https://github.com/simonrw/rynamodb/issues";
+
+/// Input of an implemented operation, linking it to the operation and the operation's output
+pub trait OperationInput: DeserializeOwned {
+    const OPERATION: OperationType;
+    type Output: Serialize + std::fmt::Debug;
+}
+
+macro_rules! operations {
+    ($($name:ident => $input:ty, $output:ty;)*) => {
+        /// Operations implemented by the emulator
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum OperationType {
+            $($name,)*
+        }
+
+        impl OperationType {
+            pub const ALL: &'static [OperationType] = &[$(OperationType::$name,)*];
+
+            /// Name of the operation, as in the `X-Amz-Target` header
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(OperationType::$name => stringify!($name),)*
+                }
+            }
+        }
+
+        $(
+            impl OperationInput for $input {
+                const OPERATION: OperationType = OperationType::$name;
+                type Output = $output;
+            }
+        )*
+    };
+}
+
+operations! {
+    CreateTable => types::CreateTableInput, types::CreateTableOutput;
+    PutItem => types::PutItemInput, types::PutItemOutput;
+    DescribeTable => types::DescribeTableInput, types::DescribeTableOutput;
+    DeleteTable => types::DeleteTableInput, types::DeleteTableOutput;
+    Query => types::QueryInput, types::QueryOutput;
+    GetItem => types::GetItemInput, types::GetItemOutput;
+    ListTables => types::ListTablesInput, types::ListTablesOutput;
+    // scans are returned in the same shape as queries
+    Scan => types::ScanInput, types::QueryOutput;
+    BatchWriteItem => types::BatchWriteInput, types::BatchWriteItemOutput;
+    UpdateTable => types::UpdateTableInput, types::UpdateTableOutput;
+    DescribeContinuousBackups => types::DescribeContinuousBackupsInput, types::DescribeContinuousBackupsOutput;
+    UpdateContinuousBackups => types::UpdateContinuousBackupsInput, types::UpdateContinuousBackupsOutput;
+    RestoreTableToPointInTime => types::RestoreTableToPointInTimeInput, types::RestoreTableToPointInTimeOutput;
+    BatchGetItem => types::BatchGetItemInput, types::BatchGetItemOutput;
+    UpdateTimeToLive => types::UpdateTimeToLiveInput, types::UpdateTimeToLiveOutput;
+    DescribeTimeToLive => types::DescribeTimeToLiveInput, types::DescribeTimeToLiveOutput;
+    DescribeTableReplicaAutoScaling => types::DescribeTableReplicaAutoScalingInput, types::DescribeTableReplicaAutoScalingOutput;
+    UpdateTableReplicaAutoScaling => types::UpdateTableReplicaAutoScalingInput, types::UpdateTableReplicaAutoScalingOutput;
+    UpdateItem => types::UpdateItemInput, types::UpdateItemOutput;
+}
+
+impl FromStr for OperationType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        OperationType::ALL
+            .iter()
+            .find(|operation| operation.name() == s)
+            .copied()
+            .ok_or_else(|| format!("operation {s} not handled"))
+    }
+}
+
+impl OperationType {
+    /// Whether the operation can change the emulator's state, and so is journaled
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            OperationType::CreateTable
+                | OperationType::PutItem
+                | OperationType::DeleteTable
+                | OperationType::BatchWriteItem
+                | OperationType::UpdateTable
+                | OperationType::UpdateContinuousBackups
+                | OperationType::RestoreTableToPointInTime
+                | OperationType::UpdateTimeToLive
+                | OperationType::UpdateTableReplicaAutoScaling
+                | OperationType::UpdateItem
+        )
+    }
+}
+
+/// Whether DynamoDB has an operation with this name, implemented here or not
+pub fn is_dynamodb_operation(name: &str) -> bool {
+    DYNAMODB_OPERATIONS.contains(&name)
+}
+
+/// Which of the DynamoDB operations are implemented, served at `/_rynamodb/operations`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationsReport {
+    pub implemented: Vec<&'static str>,
+    pub not_implemented: Vec<&'static str>,
+}
+
+pub fn report() -> OperationsReport {
+    let (implemented, not_implemented) = DYNAMODB_OPERATIONS
+        .iter()
+        .copied()
+        .partition(|name| name.parse::<OperationType>().is_ok());
+    OperationsReport {
+        implemented,
+        not_implemented,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn implemented_operations_exist() {
+        for operation in OperationType::ALL {
+            assert!(
+                is_dynamodb_operation(operation.name()),
+                "{operation:?} is not a DynamoDB operation"
+            );
+            assert_eq!(operation.name().parse::<OperationType>(), Ok(*operation));
+        }
+    }
+
+    #[test]
+    fn report_covers_every_operation() {
+        let report = report();
+        assert_eq!(report.implemented.len(), OperationType::ALL.len());
+        assert_eq!(
+            report.implemented.len() + report.not_implemented.len(),
+            DYNAMODB_OPERATIONS.len()
+        );
+        assert!(report.not_implemented.contains(&"TransactWriteItems"));
+
+        let mut sorted = DYNAMODB_OPERATIONS.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted, DYNAMODB_OPERATIONS);
+    }
+}
@@ -0,0 +1,310 @@
+//! Signed pagination tokens
+//!
+//! `LastEvaluatedKey` and `LastEvaluatedTableName` are returned as opaque tokens rather than the
+//! key of the last item or the name of the last table, so that clients cannot come to depend on
+//! what they contain, which differs from what DynamoDB returns. A `LastEvaluatedKey` is still an
+//! attribute map, as the SDKs expect, holding a single binary attribute.
+//!
+//! Tokens are signed with HMAC-SHA256 under a key generated when the emulator starts, and name
+//! the namespace, region, table and index they were issued for, so tokens which were altered,
+//! issued for another table, or issued by an earlier run of the emulator are rejected with a
+//! `ValidationException`.
+//! Start keys and table names built by the client, which DynamoDB also accepts, are passed
+//! through to be validated as before.
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_dynamo::AttributeValue;
+use sha2::Sha256;
+
+use crate::{errors::ErrorResponse, table_manager::Region};
+
+type Item = HashMap<String, AttributeValue>;
+
+/// attribute holding a `LastEvaluatedKey` token
+const KEY_TOKEN_ATTRIBUTE: &str = "rynamodb:token";
+
+/// start of a `LastEvaluatedTableName` token, which cannot start a table name
+const TABLE_NAME_TOKEN_PREFIX: &str = "rynamodb:";
+
+/// length of an HMAC-SHA256 signature, which comes before the payload of a token
+const SIGNATURE_BYTES: usize = 32;
+
+/// Contents of a `LastEvaluatedKey` token
+#[derive(Serialize, Deserialize)]
+struct KeyToken {
+    table_name: String,
+    index_name: Option<String>,
+    key: Item,
+}
+
+/// Contents of a `LastEvaluatedTableName` token
+#[derive(Serialize, Deserialize)]
+struct TableNameToken {
+    last_evaluated_table_name: String,
+}
+
+/// Signs and checks pagination tokens with a key of its own
+pub struct PageTokens {
+    key: Vec<u8>,
+    /// namespace of the tables whose pages the tokens continue, see [`crate::namespaces`]
+    namespace: Option<String>,
+    /// region of the tables, as tables in different regions can share a name
+    region: Region,
+}
+
+impl Default for PageTokens {
+    // version 4 UUIDs come from the operating system's random number generator
+    fn default() -> Self {
+        let key = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()]
+            .iter()
+            .flat_map(|id| id.into_bytes())
+            .collect();
+        Self {
+            key,
+            namespace: None,
+            region: Region::default(),
+        }
+    }
+}
+
+impl PageTokens {
+    /// Tokens for the tables of a namespace, which are only accepted within that namespace
+    pub fn in_namespace(&self, namespace: &str) -> Self {
+        Self {
+            key: self.key.clone(),
+            namespace: Some(namespace.to_string()),
+            region: self.region,
+        }
+    }
+
+    /// Tokens for the tables of a region, which are only accepted within that region
+    pub fn in_region(&self, region: Region) -> Self {
+        Self {
+            key: self.key.clone(),
+            namespace: self.namespace.clone(),
+            region,
+        }
+    }
+
+    /// Wrap the key of the last item of a page read from `table_name`, or its index `index_name`
+    pub fn last_evaluated_key(
+        &self,
+        table_name: &str,
+        index_name: Option<&str>,
+        key: Item,
+    ) -> Item {
+        let token = self.seal(&KeyToken {
+            table_name: table_name.to_string(),
+            index_name: index_name.map(str::to_string),
+            key,
+        });
+        [(KEY_TOKEN_ATTRIBUTE.to_string(), AttributeValue::B(token))].into()
+    }
+
+    /// Unwrap an `ExclusiveStartKey`, checking that it was issued for the same table and index
+    pub fn exclusive_start_key(
+        &self,
+        table_name: &str,
+        index_name: Option<&str>,
+        start_key: Item,
+    ) -> Result<Item, ErrorResponse> {
+        if !start_key.contains_key(KEY_TOKEN_ATTRIBUTE) {
+            return Ok(start_key);
+        }
+        let token = match start_key.get(KEY_TOKEN_ATTRIBUTE) {
+            Some(AttributeValue::B(token)) if start_key.len() == 1 => token,
+            _ => return Err(invalid_start_key()),
+        };
+        let token: KeyToken = self.open(token).ok_or_else(invalid_start_key)?;
+        if token.table_name != table_name || token.index_name.as_deref() != index_name {
+            return Err(invalid_start_key());
+        }
+        Ok(token.key)
+    }
+
+    /// Wrap the name of the last table of a page of `ListTables` results
+    pub fn last_evaluated_table_name(&self, table_name: String) -> String {
+        let token = self.seal(&TableNameToken {
+            last_evaluated_table_name: table_name,
+        });
+        format!("{TABLE_NAME_TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(token))
+    }
+
+    /// Unwrap an `ExclusiveStartTableName`
+    pub fn exclusive_start_table_name(&self, start: String) -> Result<String, ErrorResponse> {
+        let Some(token) = start.strip_prefix(TABLE_NAME_TOKEN_PREFIX) else {
+            return Ok(start);
+        };
+        let token: TableNameToken = URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|token| self.open(&token))
+            .ok_or_else(|| {
+                ErrorResponse::ValidationError(
+                    "The provided starting table name is invalid".to_string(),
+                )
+            })?;
+        Ok(token.last_evaluated_table_name)
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    fn seal(&self, payload: &impl Serialize) -> Vec<u8> {
+        let scope = (&self.namespace, self.region.to_string());
+        let payload = serde_json::to_vec(&(scope, payload)).expect("tokens can be serialized");
+        let mut mac = self.mac();
+        mac.update(&payload);
+        let mut token = mac.finalize().into_bytes().to_vec();
+        token.extend(payload);
+        token
+    }
+
+    /// The payload of a token, if it was signed with this emulator's key for the same namespace
+    /// and region
+    fn open<T: for<'de> Deserialize<'de>>(&self, token: &[u8]) -> Option<T> {
+        if token.len() < SIGNATURE_BYTES {
+            return None;
+        }
+        let (signature, payload) = token.split_at(SIGNATURE_BYTES);
+        let mut mac = self.mac();
+        mac.update(payload);
+        mac.verify_slice(signature).ok()?;
+        let ((namespace, region), payload): ((Option<String>, String), T) =
+            serde_json::from_slice(payload).ok()?;
+        (namespace == self.namespace && region == self.region.as_str()).then_some(payload)
+    }
+}
+
+fn invalid_start_key() -> ErrorResponse {
+    ErrorResponse::ValidationError(
+        "The provided starting key is invalid: The provided token is not valid for this request"
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(sk: &str) -> Item {
+        [
+            ("pk".to_string(), AttributeValue::S("abc".to_string())),
+            ("sk".to_string(), AttributeValue::S(sk.to_string())),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn start_keys_round_trip() {
+        let tokens = PageTokens::default();
+        let token = tokens.last_evaluated_key("table", Some("index"), key("def"));
+        assert_eq!(token.len(), 1);
+        assert_eq!(
+            tokens
+                .exclusive_start_key("table", Some("index"), token)
+                .unwrap(),
+            key("def")
+        );
+
+        // keys built by the client are left to be validated against the key schema
+        assert_eq!(
+            tokens
+                .exclusive_start_key("table", None, key("def"))
+                .unwrap(),
+            key("def")
+        );
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let tokens = PageTokens::default();
+        let mut token = tokens.last_evaluated_key("table", None, key("def"));
+        let Some(AttributeValue::B(bytes)) = token.get_mut(KEY_TOKEN_ATTRIBUTE) else {
+            unreachable!()
+        };
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(
+            tokens.exclusive_start_key("table", None, token),
+            Err(ErrorResponse::ValidationError(_))
+        ));
+
+        let mut token = tokens.last_evaluated_key("table", None, key("def"));
+        token.extend(key("ghi"));
+        assert!(tokens.exclusive_start_key("table", None, token).is_err());
+
+        // signed by another emulator
+        let token = PageTokens::default().last_evaluated_key("table", None, key("def"));
+        assert!(tokens.exclusive_start_key("table", None, token).is_err());
+    }
+
+    #[test]
+    fn tokens_only_apply_to_their_table_and_index() {
+        let tokens = PageTokens::default();
+        let token = tokens.last_evaluated_key("table", None, key("def"));
+        assert!(tokens
+            .exclusive_start_key("other", None, token.clone())
+            .is_err());
+        assert!(tokens
+            .exclusive_start_key("table", Some("index"), token.clone())
+            .is_err());
+
+        // nor to the table of the same name in a namespace, or in another namespace
+        let namespaced = tokens.in_namespace("a");
+        assert!(namespaced
+            .exclusive_start_key("table", None, token.clone())
+            .is_err());
+        let namespaced_token = namespaced.last_evaluated_key("table", None, key("def"));
+        assert!(tokens
+            .exclusive_start_key("table", None, namespaced_token.clone())
+            .is_err());
+        assert!(tokens
+            .in_namespace("b")
+            .exclusive_start_key("table", None, namespaced_token.clone())
+            .is_err());
+        assert_eq!(
+            tokens
+                .in_namespace("a")
+                .exclusive_start_key("table", None, namespaced_token)
+                .unwrap(),
+            key("def")
+        );
+
+        // nor to the table of the same name in another region
+        let token = tokens
+            .in_region(Region::EuWest1)
+            .last_evaluated_key("table", None, key("def"));
+        assert!(tokens
+            .exclusive_start_key("table", None, token.clone())
+            .is_err());
+        assert!(tokens
+            .in_region(Region::EuWest1)
+            .exclusive_start_key("table", None, token)
+            .is_ok());
+    }
+
+    #[test]
+    fn table_names() {
+        let tokens = PageTokens::default();
+        let token = tokens.last_evaluated_table_name("table".to_string());
+        assert_ne!(token, "table");
+        assert_eq!(tokens.exclusive_start_table_name(token).unwrap(), "table");
+        assert_eq!(
+            tokens
+                .exclusive_start_table_name("table".to_string())
+                .unwrap(),
+            "table"
+        );
+
+        let mut tampered = tokens.last_evaluated_table_name("table".to_string());
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == 'A' { 'B' } else { 'A' });
+        assert!(tokens.exclusive_start_table_name(tampered).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+//! Sources of the ids and timestamps which the emulator makes up, so that tests can fix them
+//!
+//! Request ids, table ids, generated KMS key ids and timestamps are random or taken from the wall
+//! clock by default, so responses differ on every run. Snapshot tests can swap in a
+//! [`FixedClock`] and [`SequentialIds`] through the builder:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! let clock = Arc::new(rynamodb::FixedClock::new(chrono::Utc::now()));
+//! let router = rynamodb::Rynamodb::builder()
+//!     .clock(Arc::clone(&clock) as _)
+//!     .id_provider(Arc::new(rynamodb::SequentialIds::default()))
+//!     .build_router();
+//! // tables created after this report a later creation time, and simulated delays elapse
+//! clock.advance(chrono::Duration::minutes(5));
+//! ```
+//!
+//! The clock also decides when simulated delays, such as table activation and index backfills,
+//! are over, when items expire, and the times recorded for point in time recovery, tailed and
+//! journaled operations and background jobs, so with a [`FixedClock`] they only move when the test
+//! advances it.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+use chrono::{DateTime, Utc};
+
+/// Source of the current time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Source of unique ids, formatted as UUIDs
+pub trait IdProvider: Send + Sync {
+    fn new_id(&self) -> String;
+}
+
+/// The wall clock, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Random version 4 UUIDs, used by default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+impl IdProvider for RandomIds {
+    fn new_id(&self) -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+}
+
+/// A clock which only moves when told to
+#[derive(Debug)]
+pub struct FixedClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl FixedClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Ids counting up from `00000000-0000-0000-0000-000000000001`
+#[derive(Debug, Default)]
+pub struct SequentialIds {
+    last: AtomicU64,
+}
+
+impl IdProvider for SequentialIds {
+    fn new_id(&self) -> String {
+        let id = self.last.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("00000000-0000-0000-0000-{id:012x}")
+    }
+}
+
+/// The clock and ids used by a table manager and the tables it creates
+#[derive(Clone)]
+pub(crate) struct Providers {
+    pub clock: Arc<dyn Clock>,
+    pub ids: Arc<dyn IdProvider>,
+}
+
+impl Default for Providers {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn sequential_ids() {
+        let ids = SequentialIds::default();
+        assert_eq!(ids.new_id(), "00000000-0000-0000-0000-000000000001");
+        assert_eq!(ids.new_id(), "00000000-0000-0000-0000-000000000002");
+        assert!(uuid::Uuid::parse_str(&ids.new_id()).is_ok());
+    }
+
+    #[test]
+    fn fixed_clock() {
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let clock = FixedClock::new(start);
+        assert_eq!(clock.now(), start);
+        clock.advance(chrono::Duration::seconds(90));
+        assert_eq!(clock.now().timestamp(), 1_700_000_090);
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
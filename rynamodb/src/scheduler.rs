@@ -0,0 +1,158 @@
+//! Periodic background work
+//!
+//! Subsystems which need work done periodically, such as time to live sweeps, compaction and the
+//! removal of expired namespaces, register a job with the [`Scheduler`] rather than spawning their
+//! own tasks. Jobs run on tokio's timer, so tests can pause time with `tokio::time::pause` and
+//! advance it to run them deterministically. The jobs stop when the scheduler is dropped along with
+//! the router, and their runs are reported at `/_rynamodb/jobs`, timed by the emulator's
+//! [`Clock`].
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+use crate::providers::{Clock, SystemClock};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobReport {
+    pub name: &'static str,
+    pub period_ms: u64,
+    /// times the job has completed
+    pub runs: u64,
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+struct Job {
+    report: Arc<Mutex<JobReport>>,
+    handle: JoinHandle<()>,
+}
+
+pub struct Scheduler {
+    jobs: Mutex<Vec<Job>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock))
+    }
+}
+
+impl Scheduler {
+    /// A scheduler which reports when jobs last ran by `clock`
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            jobs: Default::default(),
+            clock,
+        }
+    }
+
+    /// Run `job` now and then every `period`. A run which overruns delays the next rather than
+    /// being followed by a burst of runs.
+    ///
+    /// This spawns a task, so it must be called within a tokio runtime.
+    pub fn every<F, Fut>(&self, name: &'static str, period: Duration, mut job: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let report = Arc::new(Mutex::new(JobReport {
+            name,
+            period_ms: period.as_millis() as u64,
+            runs: 0,
+            last_run: None,
+        }));
+        let handle = tokio::spawn({
+            let report = Arc::clone(&report);
+            let clock = Arc::clone(&self.clock);
+            async move {
+                let mut interval = tokio::time::interval(period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    job().await;
+                    let mut report = report.lock().unwrap();
+                    report.runs += 1;
+                    report.last_run = Some(clock.now());
+                }
+            }
+        });
+        tracing::debug!(%name, ?period, "scheduled job");
+        self.jobs.lock().unwrap().push(Job { report, handle });
+    }
+
+    pub fn report(&self) -> Vec<JobReport> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|job| job.report.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        for job in self.jobs.get_mut().unwrap().iter() {
+            job.handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::providers::FixedClock;
+
+    #[tokio::test(start_paused = true)]
+    async fn jobs_run_periodically_until_dropped() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let scheduler = Scheduler::default();
+        scheduler.every("count", Duration::from_secs(10), {
+            let runs = Arc::clone(&runs);
+            move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                async {}
+            }
+        });
+
+        // the first run is immediate
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let report = scheduler.report();
+        assert_eq!(report[0].name, "count");
+        assert_eq!(report[0].runs, 3);
+
+        drop(scheduler);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_are_timed_by_the_clock() {
+        let now = "2020-01-01T00:00:00Z".parse().unwrap();
+        let clock = Arc::new(FixedClock::new(now));
+        let scheduler = Scheduler::new(Arc::clone(&clock) as _);
+        scheduler.every("noop", Duration::from_secs(10), || async {});
+
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert_eq!(scheduler.report()[0].last_run, Some(now));
+        clock.advance(chrono::Duration::minutes(5));
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(
+            scheduler.report()[0].last_run,
+            Some(now + chrono::Duration::minutes(5))
+        );
+    }
+}
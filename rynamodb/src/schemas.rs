@@ -0,0 +1,229 @@
+//! JSON schemas of the request and response of each implemented operation
+//!
+//! The schemas, in `schemas/dynamodb.json`, follow the DynamoDB Smithy model. When enabled, see
+//! [`crate::RynamodbConfig::validate_schemas`], requests which do not match their schema are
+//! logged, and responses which do not match theirs are replaced with an `InternalServerError`
+//! describing the mismatch, so that a misspelt or missing field fails the emulator's own tests
+//! rather than a user's SDK.
+//!
+//! Only the parts of JSON schema the document uses are supported: `$ref`s to its definitions,
+//! `type`, `enum`, `properties`, `required`, `additionalProperties`, `items`, `minProperties` and
+//! `maxProperties`. Members which are not required may be `null`, which the SDKs treat as absent.
+
+use std::{fmt, sync::OnceLock};
+
+use serde_json::Value;
+
+use crate::OperationType;
+
+const SCHEMAS: &str = include_str!("../schemas/dynamodb.json");
+
+/// Which side of an operation to validate
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Input,
+    Output,
+}
+
+/// Part of a request or response which does not match its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON pointer to the value, e.g. `/Table/KeySchema/0/KeyType`
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{path}: {}", self.message)
+    }
+}
+
+fn definitions() -> &'static serde_json::Map<String, Value> {
+    static DEFINITIONS: OnceLock<serde_json::Map<String, Value>> = OnceLock::new();
+    DEFINITIONS.get_or_init(|| {
+        let mut schemas: Value = serde_json::from_str(SCHEMAS).expect("invalid embedded schemas");
+        match schemas["definitions"].take() {
+            Value::Object(definitions) => definitions,
+            _ => panic!("embedded schemas have no definitions"),
+        }
+    })
+}
+
+/// Check a request or response of an operation against its schema
+pub fn validate(operation: OperationType, shape: Shape, value: &Value) -> Vec<Violation> {
+    let name = match shape {
+        Shape::Input => format!("{}Input", operation.name()),
+        Shape::Output => format!("{}Output", operation.name()),
+    };
+    let mut violations = Vec::new();
+    match definitions().get(&name) {
+        Some(schema) => check(schema, value, String::new(), &mut violations),
+        None => violations.push(Violation {
+            path: String::new(),
+            message: format!("no schema for {name}"),
+        }),
+    }
+    violations
+}
+
+fn check(schema: &Value, value: &Value, path: String, violations: &mut Vec<Violation>) {
+    let mut violation = |message: String| {
+        violations.push(Violation {
+            path: path.clone(),
+            message,
+        })
+    };
+
+    if let Some(reference) = schema["$ref"].as_str() {
+        let definition = reference
+            .strip_prefix("#/definitions/")
+            .and_then(|name| definitions().get(name));
+        match definition {
+            Some(definition) => check(definition, value, path, violations),
+            None => violation(format!("unknown schema reference {reference}")),
+        }
+        return;
+    }
+
+    if let Some(expected) = schema["type"].as_str() {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !matches {
+            violation(format!("expected {expected}, found {value}"));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            violation(format!(
+                "{value} is not one of {}",
+                Value::from(allowed.clone())
+            ));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                check(item_schema, item, format!("{path}/{i}"), violations);
+            }
+        }
+        return;
+    }
+
+    let Some(members) = value.as_object() else {
+        return;
+    };
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|required| required.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    for name in &required {
+        if members.get(*name).is_none_or(Value::is_null) {
+            violation(format!("missing required member {name}"));
+        }
+    }
+    let count = members.values().filter(|v| !v.is_null()).count();
+    if let Some(min) = schema["minProperties"].as_u64() {
+        if (count as u64) < min {
+            violation(format!("expected at least {min} members, found {count}"));
+        }
+    }
+    if let Some(max) = schema["maxProperties"].as_u64() {
+        if count as u64 > max {
+            violation(format!("expected at most {max} members, found {count}"));
+        }
+    }
+
+    for (name, member) in members {
+        let member_path = format!("{path}/{name}");
+        if member.is_null() && !required.contains(&name.as_str()) {
+            continue;
+        }
+        match (
+            schema["properties"].get(name),
+            schema.get("additionalProperties"),
+        ) {
+            (Some(member_schema), _) => check(member_schema, member, member_path, violations),
+            (None, Some(Value::Bool(false))) => violations.push(Violation {
+                path: member_path,
+                message: "unknown member".to_string(),
+            }),
+            (None, Some(member_schema @ Value::Object(_))) => {
+                check(member_schema, member, member_path, violations)
+            }
+            (None, _) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn every_operation_has_schemas() {
+        for operation in OperationType::ALL {
+            for shape in [Shape::Input, Shape::Output] {
+                let violations = validate(*operation, shape, &json!({}));
+                assert!(
+                    violations
+                        .iter()
+                        .all(|v| !v.message.starts_with("no schema")),
+                    "{operation:?} {shape:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reports_shape_drift() {
+        let output = json!({
+            "Table": {
+                "TableName": "t",
+                "tableStatus": "ACTIVE",
+                "KeySchema": [{"AttributeName": "pk", "KeyType": "PARTITION"}],
+                "ItemCount": "1",
+                "ProvisionedThroughput": {"LastIncreaseDateTime": null},
+            }
+        });
+        let violations: Vec<String> =
+            validate(OperationType::DescribeTable, Shape::Output, &output)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+        assert_eq!(
+            violations,
+            [
+                "/Table/ItemCount: expected integer, found \"1\"",
+                "/Table/KeySchema/0/KeyType: \"PARTITION\" is not one of [\"HASH\",\"RANGE\"]",
+                "/Table/tableStatus: unknown member",
+            ]
+        );
+
+        let output = json!({"Items": [{"pk": {"S": "a", "N": "1"}}], "Count": 1});
+        let violations = validate(OperationType::Query, Shape::Output, &output);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "/Items/0/pk");
+
+        let input = json!({"Key": {"pk": {"S": "a"}}});
+        let violations = validate(OperationType::GetItem, Shape::Input, &input);
+        assert_eq!(violations[0].message, "missing required member TableName");
+    }
+}
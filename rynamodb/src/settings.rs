@@ -0,0 +1,188 @@
+//! Settings which can be changed while the emulator is running
+//!
+//! Fault injection, table limits and the log level can be changed with `PUT /_rynamodb/config`,
+//! or by embedders through [`Settings`], so long running shared instances do not need restarting
+//! to change their behaviour. Other options are fixed when the router is built.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    faults::ConnectionFaults,
+    namespaces::Namespaces,
+    table_manager::TableManager,
+};
+
+/// Callback changing the log level, given a filter directive such as `rynamodb=debug`
+pub type LogLevelReloader = Arc<dyn Fn(&str) -> std::result::Result<(), String> + Send + Sync>;
+
+/// Changes to the settings. Settings which are not given keep their current value.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SettingsUpdate {
+    pub unprocessed_probability: Option<f64>,
+    pub unprocessed_min_batch_size: Option<usize>,
+    pub connection_fault_probability: Option<f64>,
+    pub connection_fault_stall_ms: Option<u64>,
+    pub max_tables: Option<usize>,
+    pub max_concurrent_control_plane_operations: Option<usize>,
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentSettings {
+    pub unprocessed_probability: f64,
+    pub unprocessed_min_batch_size: usize,
+    pub connection_fault_probability: f64,
+    pub connection_fault_stall_ms: u64,
+    pub max_tables: usize,
+    pub max_concurrent_control_plane_operations: usize,
+    /// the last log level set at runtime, if any
+    pub log_level: Option<String>,
+}
+
+/// Handle to the runtime settings of an emulator, see [`crate::Tables::settings`]
+#[derive(Clone)]
+pub struct Settings {
+    pub(crate) manager: Arc<RwLock<TableManager>>,
+    pub(crate) namespaces: Arc<Namespaces>,
+    pub(crate) connection_faults: Arc<RwLock<ConnectionFaults>>,
+    pub(crate) reload_log_level: Option<LogLevelReloader>,
+    pub(crate) log_level: Arc<Mutex<Option<String>>>,
+}
+
+impl Settings {
+    pub fn current(&self) -> CurrentSettings {
+        let manager = self.manager.read().unwrap_or_else(|e| e.into_inner());
+        let faults = *self
+            .connection_faults
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        CurrentSettings {
+            unprocessed_probability: manager.unprocessed_simulation.probability,
+            unprocessed_min_batch_size: manager.unprocessed_simulation.min_batch_size,
+            connection_fault_probability: faults.probability,
+            connection_fault_stall_ms: faults.stall_duration.as_millis() as u64,
+            max_tables: manager.limits.max_tables,
+            max_concurrent_control_plane_operations: manager
+                .limits
+                .max_concurrent_control_plane_operations,
+            log_level: self.log_level.lock().unwrap().clone(),
+        }
+    }
+
+    /// Apply an update, returning the new settings
+    ///
+    /// The update is validated before any of it is applied, so an invalid update changes
+    /// nothing.
+    pub fn update(&self, update: SettingsUpdate) -> Result<CurrentSettings> {
+        for (name, probability) in [
+            ("unprocessedProbability", update.unprocessed_probability),
+            (
+                "connectionFaultProbability",
+                update.connection_fault_probability,
+            ),
+        ] {
+            if probability.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+                return Err(Error::InvalidSettings(format!(
+                    "{name} must be between 0 and 1"
+                )));
+            }
+        }
+        if let Some(level) = &update.log_level {
+            let reload = self.reload_log_level.as_ref().ok_or_else(|| {
+                Error::InvalidSettings("the log level cannot be changed".to_string())
+            })?;
+            reload(level).map_err(Error::InvalidSettings)?;
+            *self.log_level.lock().unwrap() = Some(level.clone());
+        }
+
+        // namespaces have the same settings as the default tables
+        for manager in std::iter::once(Arc::clone(&self.manager)).chain(self.namespaces.managers())
+        {
+            let mut manager = manager.write().unwrap_or_else(|e| e.into_inner());
+            let unprocessed = &mut manager.unprocessed_simulation;
+            if let Some(probability) = update.unprocessed_probability {
+                unprocessed.probability = probability;
+            }
+            if let Some(min_batch_size) = update.unprocessed_min_batch_size {
+                unprocessed.min_batch_size = min_batch_size;
+            }
+            let limits = &mut manager.limits;
+            if let Some(max_tables) = update.max_tables {
+                limits.max_tables = max_tables;
+            }
+            if let Some(max_operations) = update.max_concurrent_control_plane_operations {
+                limits.max_concurrent_control_plane_operations = max_operations;
+            }
+        }
+        {
+            let mut faults = self
+                .connection_faults
+                .write()
+                .unwrap_or_else(|e| e.into_inner());
+            if let Some(probability) = update.connection_fault_probability {
+                faults.probability = probability;
+            }
+            if let Some(stall_ms) = update.connection_fault_stall_ms {
+                faults.stall_duration = std::time::Duration::from_millis(stall_ms);
+            }
+        }
+        tracing::info!(settings = ?self.current(), "updated settings");
+        Ok(self.current())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            manager: Default::default(),
+            namespaces: Default::default(),
+            connection_faults: Default::default(),
+            reload_log_level: None,
+            log_level: Default::default(),
+        }
+    }
+
+    #[test]
+    fn partial_update() {
+        let settings = settings();
+        let current = settings
+            .update(SettingsUpdate {
+                unprocessed_probability: Some(0.5),
+                max_tables: Some(3),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(current.unprocessed_probability, 0.5);
+        assert_eq!(current.max_tables, 3);
+        assert_eq!(current.max_concurrent_control_plane_operations, 500);
+        assert_eq!(current.connection_fault_probability, 0.0);
+    }
+
+    #[test]
+    fn invalid_update_changes_nothing() {
+        let settings = settings();
+        let err = settings
+            .update(SettingsUpdate {
+                max_tables: Some(3),
+                connection_fault_probability: Some(2.0),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidSettings(_)));
+        assert!(settings
+            .update(SettingsUpdate {
+                log_level: Some("debug".to_string()),
+                ..Default::default()
+            })
+            .is_err());
+        assert_eq!(settings.current().max_tables, 2500);
+    }
+}
@@ -0,0 +1,146 @@
+//! Log of Query and Scan operations which would be expensive on real DynamoDB
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// only keep the most recent entries
+const MAX_ENTRIES: usize = 1000;
+
+/// Operations exceeding either threshold are logged. Unset thresholds are ignored, so by default
+/// nothing is logged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlowOperationThresholds {
+    pub duration: Option<Duration>,
+    pub scanned_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowOperation {
+    pub operation: &'static str,
+    pub table_name: String,
+    pub expression: Option<String>,
+    pub count: usize,
+    pub scanned_count: usize,
+    pub duration_ms: f64,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct SlowOperationLog {
+    thresholds: SlowOperationThresholds,
+    entries: Mutex<VecDeque<SlowOperation>>,
+}
+
+impl SlowOperationLog {
+    pub fn new(thresholds: SlowOperationThresholds) -> Self {
+        Self {
+            thresholds,
+            ..Default::default()
+        }
+    }
+
+    fn is_slow(&self, duration: Duration, scanned_count: usize) -> bool {
+        let too_long = self
+            .thresholds
+            .duration
+            .map(|threshold| duration >= threshold)
+            .unwrap_or(false);
+        let too_many = self
+            .thresholds
+            .scanned_count
+            .map(|threshold| scanned_count >= threshold)
+            .unwrap_or(false);
+        too_long || too_many
+    }
+
+    /// Record an operation if it exceeds the configured thresholds
+    pub fn record(
+        &self,
+        operation: &'static str,
+        table_name: &str,
+        expression: Option<&str>,
+        count: usize,
+        scanned_count: usize,
+        duration: Duration,
+    ) {
+        if !self.is_slow(duration, scanned_count) {
+            return;
+        }
+
+        let entry = SlowOperation {
+            operation,
+            table_name: table_name.to_string(),
+            expression: expression.map(ToString::to_string),
+            count,
+            scanned_count,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            at: Utc::now(),
+        };
+        tracing::warn!(
+            operation = entry.operation,
+            table_name = %entry.table_name,
+            expression = ?entry.expression,
+            count = entry.count,
+            scanned_count = entry.scanned_count,
+            duration_ms = entry.duration_ms,
+            "slow operation"
+        );
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> Vec<SlowOperation> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds() {
+        let log = SlowOperationLog::new(SlowOperationThresholds {
+            duration: Some(Duration::from_millis(100)),
+            scanned_count: Some(10),
+        });
+
+        log.record(
+            "Query",
+            "a",
+            Some("pk = :a"),
+            1,
+            1,
+            Duration::from_millis(1),
+        );
+        assert!(log.entries().is_empty());
+
+        log.record(
+            "Query",
+            "a",
+            Some("pk = :a"),
+            1,
+            1,
+            Duration::from_millis(200),
+        );
+        log.record("Scan", "b", None, 1, 20, Duration::from_millis(1));
+        let entries = log.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].expression.as_deref(), Some("pk = :a"));
+        assert_eq!(entries[1].operation, "Scan");
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let log = SlowOperationLog::default();
+        log.record("Scan", "a", None, 0, 1_000_000, Duration::from_secs(60));
+        assert!(log.entries().is_empty());
+    }
+}
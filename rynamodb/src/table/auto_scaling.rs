@@ -0,0 +1,152 @@
+//! Auto scaling settings of tables and their global secondary indexes
+//!
+//! Application Auto Scaling is not emulated, but the settings given to
+//! `UpdateTableReplicaAutoScaling` are kept and returned by `DescribeTableReplicaAutoScaling`, so
+//! that tooling which manages auto scaling can be pointed at the emulator. Tables are not
+//! replicated, so each table is the only replica of itself, in its own region. Like AWS,
+//! registering a range of capacity moves the provisioned capacity into it, but the capacity does
+//! not otherwise follow the load.
+
+use std::collections::BTreeMap;
+
+use super::{Result, TableError};
+use crate::types::{
+    AutoScalingPolicyDescription, AutoScalingSettingsDescription, AutoScalingSettingsUpdate,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct AutoScaling {
+    pub read: Option<AutoScalingSettingsDescription>,
+    pub write: Option<AutoScalingSettingsDescription>,
+    /// settings of each global secondary index, by index name
+    pub indexes: BTreeMap<String, IndexAutoScaling>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexAutoScaling {
+    pub read: Option<AutoScalingSettingsDescription>,
+    pub write: Option<AutoScalingSettingsDescription>,
+}
+
+/// Apply an update to the settings of one dimension, e.g. the table's write capacity, naming its
+/// policy `default_policy_name` unless the update names it
+pub fn apply(
+    settings: &mut Option<AutoScalingSettingsDescription>,
+    update: &AutoScalingSettingsUpdate,
+    default_policy_name: impl FnOnce() -> String,
+) -> Result<()> {
+    let settings = settings.get_or_insert_with(Default::default);
+    if let Some(minimum_units) = update.minimum_units {
+        settings.minimum_units = Some(minimum_units);
+    }
+    if let Some(maximum_units) = update.maximum_units {
+        settings.maximum_units = Some(maximum_units);
+    }
+    if let Some(auto_scaling_disabled) = update.auto_scaling_disabled {
+        settings.auto_scaling_disabled = auto_scaling_disabled;
+    }
+    if let Some(role_arn) = &update.auto_scaling_role_arn {
+        settings.auto_scaling_role_arn = Some(role_arn.clone());
+    }
+    if let Some(policy_update) = &update.scaling_policy_update {
+        let target_value = policy_update
+            .target_tracking_scaling_policy_configuration
+            .target_value;
+        if !(20.0..=90.0).contains(&target_value) {
+            return Err(TableError::InvalidParameter(format!(
+                "TargetValue must be between 20 and 90, got {target_value}"
+            )));
+        }
+        let policy = AutoScalingPolicyDescription {
+            policy_name: policy_update
+                .policy_name
+                .clone()
+                .unwrap_or_else(default_policy_name),
+            target_tracking_scaling_policy_configuration: policy_update
+                .target_tracking_scaling_policy_configuration
+                .clone(),
+        };
+        // a policy with the same name is replaced
+        settings
+            .scaling_policies
+            .retain(|existing| existing.policy_name != policy.policy_name);
+        settings.scaling_policies.push(policy);
+    }
+
+    match (settings.minimum_units, settings.maximum_units) {
+        (Some(0), _) | (_, Some(0)) => Err(TableError::InvalidParameter(
+            "MinimumUnits and MaximumUnits must be at least 1".to_string(),
+        )),
+        (Some(minimum), Some(maximum)) if minimum > maximum => Err(TableError::InvalidParameter(
+            format!("MinimumUnits ({minimum}) must not be greater than MaximumUnits ({maximum})"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Capacity units moved into the range of enabled settings, if any
+pub fn clamp(units: u64, settings: Option<&AutoScalingSettingsDescription>) -> u64 {
+    match settings {
+        Some(settings) if !settings.auto_scaling_disabled => {
+            let units = settings.minimum_units.map_or(units, |min| units.max(min));
+            settings.maximum_units.map_or(units, |max| units.min(max))
+        }
+        _ => units,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AutoScalingPolicyUpdate, TargetTrackingScalingPolicyConfiguration};
+
+    fn update(minimum_units: Option<u64>, maximum_units: Option<u64>) -> AutoScalingSettingsUpdate {
+        AutoScalingSettingsUpdate {
+            minimum_units,
+            maximum_units,
+            auto_scaling_disabled: None,
+            auto_scaling_role_arn: None,
+            scaling_policy_update: None,
+        }
+    }
+
+    #[test]
+    fn updates_merge_into_settings() {
+        let mut settings = None;
+        let policy_name = || "default-policy".to_string();
+        apply(&mut settings, &update(Some(5), Some(50)), policy_name).unwrap();
+
+        let with_policy = AutoScalingSettingsUpdate {
+            scaling_policy_update: Some(AutoScalingPolicyUpdate {
+                policy_name: None,
+                target_tracking_scaling_policy_configuration:
+                    TargetTrackingScalingPolicyConfiguration {
+                        disable_scale_in: None,
+                        scale_in_cooldown: None,
+                        scale_out_cooldown: None,
+                        target_value: 70.0,
+                    },
+            }),
+            ..update(None, Some(100))
+        };
+        apply(&mut settings, &with_policy, policy_name).unwrap();
+        apply(&mut settings, &with_policy, policy_name).unwrap();
+
+        let settings = settings.unwrap();
+        assert_eq!(settings.minimum_units, Some(5));
+        assert_eq!(settings.maximum_units, Some(100));
+        assert_eq!(settings.scaling_policies.len(), 1);
+        assert_eq!(settings.scaling_policies[0].policy_name, "default-policy");
+
+        assert_eq!(clamp(1, Some(&settings)), 5);
+        assert_eq!(clamp(500, Some(&settings)), 100);
+        assert_eq!(clamp(500, None), 500);
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        let mut settings = None;
+        assert!(apply(&mut settings, &update(Some(10), Some(5)), String::new).is_err());
+        assert!(apply(&mut settings, &update(Some(0), None), String::new).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Estimates of what Query and Scan operations would cost on DynamoDB
+//!
+//! DynamoDB reads at most 1MB of items per page, before filters are applied, and charges each
+//! page for the total size of the items it read, rounded up to 4KB read capacity units.
+//! Eventually consistent reads cost half as much. The estimate is made by reading the items the
+//! operation would examine, in order, and dividing them into pages as the emulator would under
+//! its [`Limits`], which are DynamoDB's unless they were shrunk.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_dynamo::AttributeValue;
+
+use super::{names::AttributeName, size};
+use crate::limits::Limits;
+
+/// size of a read capacity unit for a strongly consistent read
+const READ_UNIT_BYTES: usize = 4096;
+
+type Item<K = String> = HashMap<K, AttributeValue>;
+
+/// Estimated cost of reading every page of a Query or Scan
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CostEstimate {
+    /// items read, before filters are applied
+    pub items_scanned: usize,
+    pub bytes_scanned: usize,
+    /// requests needed to read every page
+    pub pages: usize,
+    pub read_capacity_units: f64,
+    pub consistent_read: bool,
+}
+
+/// Estimate the cost of reading `items`, in the order they would be read, by requests with the
+/// given `Limit`
+pub fn estimate<'a, K: AttributeName + 'a>(
+    items: impl IntoIterator<Item = &'a Item<K>>,
+    limit: Option<usize>,
+    consistent_read: bool,
+    limits: &Limits,
+) -> CostEstimate {
+    let limit = limits.page_items(limit);
+    let mut pages = Vec::new();
+    let (mut page_items, mut page_bytes) = (0, 0);
+    for item in items {
+        page_items += 1;
+        page_bytes += size::item_size(item);
+        // a page ends with the item which reaches either limit
+        if page_bytes >= limits.max_page_bytes || limit.is_some_and(|limit| page_items >= limit) {
+            pages.push((page_items, page_bytes));
+            (page_items, page_bytes) = (0, 0);
+        }
+    }
+    // an empty result still takes a request
+    if page_items > 0 || pages.is_empty() {
+        pages.push((page_items, page_bytes));
+    }
+
+    let units_per_page = |bytes: usize| bytes.div_ceil(READ_UNIT_BYTES).max(1) as f64;
+    let read_capacity_units: f64 = pages.iter().map(|(_, bytes)| units_per_page(*bytes)).sum();
+    CostEstimate {
+        items_scanned: pages.iter().map(|(items, _)| items).sum(),
+        bytes_scanned: pages.iter().map(|(_, bytes)| bytes).sum(),
+        pages: pages.len(),
+        read_capacity_units: if consistent_read {
+            read_capacity_units
+        } else {
+            read_capacity_units / 2.0
+        },
+        consistent_read,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(payload_size: usize) -> Item {
+        [(
+            "payload".to_string(),
+            AttributeValue::S("x".repeat(payload_size - "payload".len())),
+        )]
+        .into()
+    }
+
+    #[test]
+    fn empty() {
+        let items: Vec<Item> = Vec::new();
+        let estimate = estimate(&items, None, false, &Limits::default());
+        assert_eq!(estimate.pages, 1);
+        assert_eq!(estimate.items_scanned, 0);
+        assert_eq!(estimate.read_capacity_units, 0.5);
+    }
+
+    #[test]
+    fn pages_by_size() {
+        // three pages: 1MB, 1MB and 0.5MB
+        let items: Vec<_> = (0..10).map(|_| item(256 * 1024)).collect();
+        let estimate = estimate(&items, None, true, &Limits::default());
+        assert_eq!(estimate.items_scanned, 10);
+        assert_eq!(estimate.bytes_scanned, 10 * 256 * 1024);
+        assert_eq!(estimate.pages, 3);
+        assert_eq!(estimate.read_capacity_units, 256.0 + 256.0 + 128.0);
+    }
+
+    #[test]
+    fn pages_by_limit() {
+        let items: Vec<_> = (0..5).map(|_| item(100)).collect();
+        let estimate = estimate(&items, Some(2), false, &Limits::default());
+        assert_eq!(estimate.pages, 3);
+        // each page is rounded up to a whole unit, then halved
+        assert_eq!(estimate.read_capacity_units, 1.5);
+    }
+
+    #[test]
+    fn pages_by_shrunk_limits() {
+        let items: Vec<_> = (0..5).map(|_| item(100)).collect();
+        let limits = Limits {
+            max_page_items: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(estimate(&items, None, false, &limits).pages, 3);
+        assert_eq!(estimate(&items, Some(1), false, &limits).pages, 5);
+
+        let limits = Limits {
+            max_page_bytes: 250,
+            ..Default::default()
+        };
+        assert_eq!(estimate(&items, None, false, &limits).pages, 2);
+    }
+}
@@ -0,0 +1,216 @@
+//! Global secondary indexes
+//!
+//! Indexes do not hold their own copy of the data. Instead the visible items are computed from
+//! the base table when the index is queried, which keeps writes cheap and the index trivially
+//! consistent.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde_dynamo::AttributeValue;
+
+use super::{
+    has_type,
+    key::ItemKey,
+    names::Item,
+    schema::{IndexSchema, KeyAttribute},
+    size, type_descriptor, Result, TableError,
+};
+use crate::types::{self, AttributeDefinition, KeySchema, KeyType};
+
+#[derive(Clone)]
+pub struct GlobalSecondaryIndex {
+    pub name: String,
+    pub partition_key: String,
+    pub sort_key: Option<String>,
+    pub projection: types::Projection,
+    /// when the simulated backfill of items written before the index was created completes
+    backfilled_at: DateTime<Utc>,
+    /// keys of the items written while the index was backfilling
+    written_during_backfill: HashSet<ItemKey>,
+}
+
+impl GlobalSecondaryIndex {
+    pub fn new(definition: &types::GlobalSecondaryIndex) -> Self {
+        let mut partition_key = String::new();
+        let mut sort_key = None;
+        for key in &definition.key_schema {
+            match key.key_type {
+                KeyType::HASH => partition_key = key.attribute_name.clone(),
+                KeyType::RANGE => sort_key = Some(key.attribute_name.clone()),
+            }
+        }
+
+        Self {
+            name: definition.index_name.clone(),
+            partition_key,
+            sort_key,
+            projection: definition.projection.clone(),
+            backfilled_at: DateTime::<Utc>::default(),
+            written_during_backfill: HashSet::new(),
+        }
+    }
+
+    /// Only expose items written from `now` on, until `delay` has passed
+    pub fn start_backfill(&mut self, now: DateTime<Utc>, delay: chrono::Duration) {
+        self.backfilled_at = now + delay;
+        self.written_during_backfill.clear();
+    }
+
+    pub fn is_backfilling(&self, now: DateTime<Utc>) -> bool {
+        now < self.backfilled_at
+    }
+
+    /// When the simulated backfill completes, if it is still running at `now`
+    pub fn backfill_completes_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.is_backfilling(now).then_some(self.backfilled_at)
+    }
+
+    /// Note that an item has been written to the base table at `now`
+    pub fn record_write(&mut self, key: ItemKey, now: DateTime<Utc>) {
+        if self.is_backfilling(now) {
+            self.written_during_backfill.insert(key);
+        }
+    }
+
+    /// Check that the index key attributes of an item being written have the types they are
+    /// defined with
+    ///
+    /// Items which are missing either key are valid, they are just not indexed, which makes the
+    /// index sparse.
+    pub fn check_keys(
+        &self,
+        item: &HashMap<String, AttributeValue>,
+        definitions: &[AttributeDefinition],
+    ) -> Result<()> {
+        for key in std::iter::once(&self.partition_key).chain(&self.sort_key) {
+            let Some(value) = item.get(key) else {
+                continue;
+            };
+            if matches!(value, AttributeValue::S(s) if s.is_empty())
+                || matches!(value, AttributeValue::B(b) if b.is_empty())
+            {
+                return Err(TableError::EmptyIndexKey {
+                    index_name: self.name.clone(),
+                    key: key.clone(),
+                });
+            }
+            let Some(definition) = definitions
+                .iter()
+                .find(|definition| &definition.attribute_name == key)
+            else {
+                continue;
+            };
+            if !has_type(value, &definition.attribute_type) {
+                return Err(TableError::InvalidParameter(format!(
+                    "Type mismatch for Index Key {key} Expected: {:?} Actual: {} IndexName: {}",
+                    definition.attribute_type,
+                    type_descriptor(value),
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an item of the base table, with primary key `key`, appears in the index at `now`
+    pub fn contains(&self, key: &ItemKey, item: &Item, now: DateTime<Utc>) -> bool {
+        let has_keys = item.contains_key(self.partition_key.as_str())
+            && self
+                .sort_key
+                .as_deref()
+                .map(|sort_key| item.contains_key(sort_key))
+                .unwrap_or(true);
+        has_keys && (!self.is_backfilling(now) || self.written_during_backfill.contains(key))
+    }
+
+    /// Restrict an item to the attributes projected into the index
+    pub fn project(
+        &self,
+        item: HashMap<String, AttributeValue>,
+        table_keys: &[&str],
+    ) -> HashMap<String, AttributeValue> {
+        if self.projects_all() {
+            return item;
+        }
+        item.into_iter()
+            .filter(|(name, _)| self.is_projected(name, table_keys))
+            .collect()
+    }
+
+    /// Size of the projection of an item into the index, without copying it
+    pub fn projected_size(&self, item: &Item, table_keys: &[&str]) -> usize {
+        item.iter()
+            .filter(|(name, _)| self.projects_all() || self.is_projected(name, table_keys))
+            .map(|(name, value)| size::attribute_size(name, value))
+            .sum()
+    }
+
+    fn projects_all(&self) -> bool {
+        !matches!(
+            self.projection.projection_type.as_deref(),
+            Some("KEYS_ONLY" | "INCLUDE")
+        )
+    }
+
+    fn is_projected(&self, name: &str, table_keys: &[&str]) -> bool {
+        let non_key_attributes = match self.projection.projection_type.as_deref() {
+            Some("INCLUDE") => self.projection.non_key_attributes.as_deref().unwrap_or(&[]),
+            _ => &[][..],
+        };
+        name == self.partition_key
+            || self.sort_key.as_deref() == Some(name)
+            || table_keys.contains(&name)
+            || non_key_attributes.iter().any(|attribute| attribute == name)
+    }
+
+    pub fn schema(&self, definitions: &[AttributeDefinition]) -> IndexSchema {
+        IndexSchema {
+            index_name: self.name.clone(),
+            partition_key: KeyAttribute::new(&self.partition_key, definitions),
+            sort_key: self
+                .sort_key
+                .as_deref()
+                .map(|sort_key| KeyAttribute::new(sort_key, definitions)),
+            projection: self.projection.clone(),
+        }
+    }
+
+    pub fn description(
+        &self,
+        table_arn: &str,
+        statistics: IndexStatistics,
+        now: DateTime<Utc>,
+    ) -> types::GlobalSecondaryIndexDescription {
+        let mut key_schema = vec![KeySchema {
+            attribute_name: self.partition_key.clone(),
+            key_type: KeyType::HASH,
+        }];
+        if let Some(sort_key) = &self.sort_key {
+            key_schema.push(KeySchema {
+                attribute_name: sort_key.clone(),
+                key_type: KeyType::RANGE,
+            });
+        }
+
+        let backfilling = self.is_backfilling(now);
+        types::GlobalSecondaryIndexDescription {
+            index_name: self.name.clone(),
+            key_schema,
+            projection: self.projection.clone(),
+            index_status: if backfilling { "CREATING" } else { "ACTIVE" }.to_string(),
+            backfilling: backfilling.then_some(true),
+            index_arn: format!("{table_arn}/index/{}", self.name),
+            item_count: statistics.item_count,
+            index_size_bytes: statistics.size_bytes,
+        }
+    }
+}
+
+/// Number and total size of the items in an index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexStatistics {
+    pub item_count: usize,
+    /// size of the projected attributes of the items
+    pub size_bytes: usize,
+}
@@ -0,0 +1,101 @@
+//! Typed key attribute values
+//!
+//! Only strings, numbers and binary values can be used as keys. Converting them to [`Key`] gives
+//! values which can be hashed and ordered consistently with how DynamoDB compares them, so they
+//! can be used to look up partitions and rows.
+
+use std::cmp::Ordering;
+
+use serde_dynamo::AttributeValue;
+
+use super::numbers;
+
+/// Value of a partition or sort key attribute
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Key {
+    S(String),
+    N(Number),
+    B(Vec<u8>),
+}
+
+impl Key {
+    /// Convert an attribute value, returning `None` for types which cannot be keys or
+    /// unparseable numbers
+    pub fn from_value(value: &AttributeValue) -> Option<Self> {
+        match value {
+            AttributeValue::S(s) => Some(Key::S(s.clone())),
+            AttributeValue::N(n) => numbers::canonical(n).map(|n| Key::N(Number(n))),
+            AttributeValue::B(b) => Some(Key::B(b.clone())),
+            _ => None,
+        }
+    }
+
+    /// Bytes of the value, which differ between any two values of the same type
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Key::S(s) => s.as_bytes(),
+            Key::N(Number(n)) => n.as_bytes(),
+            Key::B(b) => b,
+        }
+    }
+}
+
+/// Primary key of an item: the partition key value and, for tables with a sort key, the sort key
+/// value
+pub type ItemKey = (Key, Option<Key>);
+
+/// Numeric key value, in canonical form so that e.g. `1` and `1.0` are the same key, and
+/// compared exactly, as keys can have up to 38 significant digits
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Number(String);
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        numbers::compare(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(value: &str) -> Key {
+        Key::from_value(&AttributeValue::N(value.to_string())).unwrap()
+    }
+
+    #[test]
+    fn numbers_are_compared_by_value() {
+        assert_eq!(n("1"), n("1.0"));
+        assert_eq!(n("0"), n("-0"));
+        assert!(n("2") < n("10"));
+        assert!(n("-5") < n("1"));
+
+        assert!(n("0.05") < n("0.5"));
+        assert!(n("-10") < n("-2"));
+        assert!(n("-0.1") < n("0"));
+        assert!(n("99") < n("1E2"));
+
+        let keys: std::collections::HashSet<_> = [n("1"), n("1.00"), n("1e0")].into();
+        assert_eq!(keys.len(), 1);
+
+        // more digits than a float can hold
+        assert_ne!(n("12345678901234567890"), n("12345678901234567891"));
+        assert!(n("12345678901234567890") < n("12345678901234567891"));
+    }
+
+    #[test]
+    fn only_scalar_types_are_keys() {
+        assert_eq!(Key::from_value(&AttributeValue::Bool(true)), None);
+        assert_eq!(Key::from_value(&AttributeValue::N("abc".to_string())), None);
+        assert_eq!(
+            Key::from_value(&AttributeValue::B(vec![1, 2])),
+            Some(Key::B(vec![1, 2]))
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Evaluation of the legacy conditional parameters (`KeyConditions`, `QueryFilter` and
+//! `ScanFilter`), which predate condition expressions
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use serde_dynamo::AttributeValue;
+
+use super::{begins_with, compare_values, names::AttributeName, Result, TableError};
+use crate::types::{ComparisonOperator, Condition, ConditionalOperator};
+
+/// Check that every condition has the number of arguments its operator requires
+pub fn validate(conditions: &HashMap<String, Condition>) -> Result<()> {
+    for condition in conditions.values() {
+        let count = condition.attribute_value_list.len();
+        let valid = match condition.comparison_operator {
+            ComparisonOperator::Null | ComparisonOperator::NotNull => count == 0,
+            ComparisonOperator::Between => count == 2,
+            ComparisonOperator::In => count > 0,
+            _ => count == 1,
+        };
+        if !valid {
+            return Err(TableError::InvalidCondition(format!(
+                "Invalid number of argument(s) for the {} ComparisonOperator",
+                condition.comparison_operator
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether an item satisfies the conditions, combined with the conditional operator
+///
+/// The conditions must have been checked with [`validate`] first.
+pub fn matches<K: AttributeName>(
+    item: &HashMap<K, AttributeValue>,
+    conditions: &HashMap<String, Condition>,
+    operator: ConditionalOperator,
+) -> bool {
+    let mut results = conditions
+        .iter()
+        .map(|(name, condition)| evaluate(item.get(name.as_str()), condition));
+    match operator {
+        ConditionalOperator::And => results.all(|matched| matched),
+        ConditionalOperator::Or => results.any(|matched| matched),
+    }
+}
+
+/// Apply an optional filter to a set of items
+pub fn filter(
+    items: Vec<HashMap<String, AttributeValue>>,
+    conditions: Option<&HashMap<String, Condition>>,
+    operator: Option<ConditionalOperator>,
+) -> Result<Vec<HashMap<String, AttributeValue>>> {
+    let Some(conditions) = conditions else {
+        return Ok(items);
+    };
+    validate(conditions)?;
+    let operator = operator.unwrap_or_default();
+    Ok(items
+        .into_iter()
+        .filter(|item| matches(item, conditions, operator))
+        .collect())
+}
+
+/// Only keep the requested attributes of an item (`AttributesToGet`)
+pub fn project(
+    item: HashMap<String, AttributeValue>,
+    attributes: &[String],
+) -> HashMap<String, AttributeValue> {
+    item.into_iter()
+        .filter(|(name, _)| attributes.contains(name))
+        .collect()
+}
+
+/// Copy the requested attributes of a stored item, without copying the attributes which are not
+/// requested
+pub fn project_ref<K: AttributeName>(
+    item: &HashMap<K, AttributeValue>,
+    attributes: &[String],
+) -> HashMap<String, AttributeValue> {
+    attributes
+        .iter()
+        .filter_map(|name| Some((name.clone(), item.get(name.as_str())?.clone())))
+        .collect()
+}
+
+fn evaluate(value: Option<&AttributeValue>, condition: &Condition) -> bool {
+    let args = &condition.attribute_value_list;
+    let Some(value) = value else {
+        // a missing attribute only matches the negative comparisons
+        return matches!(
+            condition.comparison_operator,
+            ComparisonOperator::Null | ComparisonOperator::Ne | ComparisonOperator::NotContains
+        );
+    };
+
+    match condition.comparison_operator {
+        ComparisonOperator::Null => false,
+        ComparisonOperator::NotNull => true,
+        ComparisonOperator::Eq => equal(value, &args[0]),
+        ComparisonOperator::Ne => !equal(value, &args[0]),
+        ComparisonOperator::Lt => compare_values(value, &args[0]) == Some(Ordering::Less),
+        ComparisonOperator::Le => matches!(
+            compare_values(value, &args[0]),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        ComparisonOperator::Gt => compare_values(value, &args[0]) == Some(Ordering::Greater),
+        ComparisonOperator::Ge => matches!(
+            compare_values(value, &args[0]),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        ComparisonOperator::Between => {
+            matches!(
+                compare_values(value, &args[0]),
+                Some(Ordering::Greater | Ordering::Equal)
+            ) && matches!(
+                compare_values(value, &args[1]),
+                Some(Ordering::Less | Ordering::Equal)
+            )
+        }
+        ComparisonOperator::In => args.iter().any(|arg| equal(value, arg)),
+        ComparisonOperator::BeginsWith => begins_with(value, &args[0]),
+        ComparisonOperator::Contains => contains(value, &args[0]),
+        ComparisonOperator::NotContains => !contains(value, &args[0]),
+    }
+}
+
+// numbers compare by value rather than representation
+fn equal(a: &AttributeValue, b: &AttributeValue) -> bool {
+    match compare_values(a, b) {
+        Some(ordering) => ordering == Ordering::Equal,
+        None => a == b,
+    }
+}
+
+fn contains(haystack: &AttributeValue, needle: &AttributeValue) -> bool {
+    match (haystack, needle) {
+        (AttributeValue::S(s), AttributeValue::S(sub)) => s.contains(sub.as_str()),
+        (AttributeValue::B(b), AttributeValue::B(sub)) => {
+            sub.is_empty() || b.windows(sub.len()).any(|window| window == sub.as_slice())
+        }
+        (AttributeValue::Ss(set), AttributeValue::S(s)) => set.contains(s),
+        (AttributeValue::Ns(set), AttributeValue::N(_)) => set
+            .iter()
+            .any(|n| equal(&AttributeValue::N(n.clone()), needle)),
+        (AttributeValue::Bs(set), AttributeValue::B(b)) => set.contains(b),
+        (AttributeValue::L(values), needle) => values.iter().any(|value| equal(value, needle)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(comparison_operator: ComparisonOperator, args: &[AttributeValue]) -> Condition {
+        Condition {
+            comparison_operator,
+            attribute_value_list: args.to_vec(),
+        }
+    }
+
+    fn s(value: &str) -> AttributeValue {
+        AttributeValue::S(value.to_string())
+    }
+
+    fn n(value: &str) -> AttributeValue {
+        AttributeValue::N(value.to_string())
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let value = Some(&AttributeValue::N("5".to_string()));
+
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::Eq, &[n("5.0")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::Ne, &[n("4")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::Lt, &[n("10")])
+        ));
+        assert!(!evaluate(
+            value,
+            &condition(ComparisonOperator::Gt, &[n("10")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::Between, &[n("1"), n("5")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::In, &[n("1"), n("5")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::NotNull, &[])
+        ));
+        assert!(evaluate(None, &condition(ComparisonOperator::Null, &[])));
+        assert!(!evaluate(
+            None,
+            &condition(ComparisonOperator::Eq, &[n("5")])
+        ));
+    }
+
+    #[test]
+    fn string_operators() {
+        let value = Some(&AttributeValue::S("hello world".to_string()));
+
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::BeginsWith, &[s("hello")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::Contains, &[s("o w")])
+        ));
+        assert!(evaluate(
+            value,
+            &condition(ComparisonOperator::NotContains, &[s("goodbye")])
+        ));
+
+        let set = AttributeValue::Ss(vec!["a".to_string(), "b".to_string()]);
+        assert!(evaluate(
+            Some(&set),
+            &condition(ComparisonOperator::Contains, &[s("b")])
+        ));
+    }
+
+    #[test]
+    fn conditional_operator() {
+        let item: HashMap<_, _> = [("a".to_string(), s("1")), ("b".to_string(), s("2"))]
+            .into_iter()
+            .collect();
+        let conditions: HashMap<_, _> = [
+            (
+                "a".to_string(),
+                condition(ComparisonOperator::Eq, &[s("1")]),
+            ),
+            (
+                "b".to_string(),
+                condition(ComparisonOperator::Eq, &[s("3")]),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(!matches(&item, &conditions, ConditionalOperator::And));
+        assert!(matches(&item, &conditions, ConditionalOperator::Or));
+    }
+
+    #[test]
+    fn invalid_argument_count() {
+        let conditions: HashMap<_, _> = [(
+            "a".to_string(),
+            condition(ComparisonOperator::Between, &[s("1")]),
+        )]
+        .into_iter()
+        .collect();
+
+        let err = validate(&conditions).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "One or more parameter values were invalid: Invalid number of argument(s) for the BETWEEN ComparisonOperator"
+        );
+    }
+
+    #[test]
+    fn projection_of_stored_item() {
+        let item: HashMap<_, _> = [
+            ("a".to_string(), s("1")),
+            ("b".to_string(), AttributeValue::L(vec![n("2")])),
+        ]
+        .into_iter()
+        .collect();
+        let attributes = ["a".to_string(), "missing".to_string()];
+
+        let projected = project_ref(&item, &attributes);
+        assert_eq!(projected, project(item, &attributes));
+        assert_eq!(projected.len(), 1);
+    }
+}
@@ -1,32 +1,103 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use chrono::{DateTime, Utc};
 use serde_dynamo::AttributeValue;
-use std::collections::HashMap;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
 use thiserror::Error;
 
 use crate::{
+    deadline::Deadline,
+    events::{DeletionReason, Events, TableEvent},
+    limits::Limits,
+    providers::Providers,
     table_manager::Region,
-    types::{self, AttributeDefinition, KeySchema, KeyType},
+    types::{self, AttributeDefinition, AttributeType},
 };
 
-use self::queries::{Node, Operator};
+use self::{
+    key::{ItemKey, Key},
+    names::Item,
+    queries::{Node, Operator},
+};
 
+mod auto_scaling;
+pub mod cost;
+mod index;
+pub mod key;
+pub mod legacy;
+pub mod names;
+mod numbers;
+pub mod pagination;
+mod patch;
 mod queries;
+pub mod schema;
+pub mod sets;
+pub mod size;
+pub mod stats;
+mod ttl;
+pub mod update;
 mod visitor;
 
 #[derive(Debug, Error)]
 pub enum TableError {
     #[error("missing partition key")]
     MissingPartitionKey,
+    #[error("missing sort key")]
+    MissingSortKey,
+    #[error("The provided key element does not match the schema")]
+    InvalidKey,
     #[error("parsing condition expression")]
     ParseError(#[from] queries::ParserError),
     #[error("partition key specified is not valid")]
     InvalidPartitionKey,
-    #[error("attribute name {0} not supplied")]
+    #[error("An expression attribute name used in the document path is not defined; attribute name: {0}")]
     NoAttributeName(String),
-    #[error("attribute value {0} not supplied")]
+    #[error(
+        "An expression attribute value used in expression is not defined; attribute value: {0}"
+    )]
     NoAttributeValue(String),
     #[error("invalid attribute map, no types found")]
     InvalidAttributeMap,
+    #[error("point in time recovery is not enabled for table {0}")]
+    PointInTimeRecoveryUnavailable(String),
+    #[error("restore time is outside of the restorable window")]
+    InvalidRestoreTime,
+    #[error("One or more parameter values were invalid: {0}")]
+    InvalidCondition(String),
+    #[error("Query condition missed key schema element: {0}")]
+    MissingKeyCondition(String),
+    #[error("The table does not have the specified index: {0}")]
+    IndexNotFound(String),
+    #[error("Invalid KeyConditionExpression: {0}")]
+    InvalidKeyConditionExpression(String),
+    #[error("One or more parameter values were invalid: {0}")]
+    InvalidParameter(String),
+    #[error("{0}")]
+    InvalidTimeToLive(String),
+    #[error("A value provided cannot be converted into a number")]
+    InvalidNumber,
+    #[error(
+        "One or more parameter values are not valid. A value specified for a secondary index key \
+        is not supported. The AttributeValue for a key attribute cannot contain an empty string \
+        value. IndexName: {index_name}, IndexKey: {key}"
+    )]
+    EmptyIndexKey { index_name: String, key: String },
+    #[error("Query key condition not supported")]
+    UnsupportedKeyCondition,
+    #[error("The provided starting key is invalid: {0}")]
+    InvalidStartKey(String),
+    #[error("The request did not complete before its deadline")]
+    DeadlineExceeded,
+    #[error("Table already exists: {0}")]
+    TableAlreadyExists(String),
+    #[error("Invalid UpdateExpression: {0}")]
+    InvalidUpdateExpression(String),
+    /// behaviour of DynamoDB which the emulator does not implement, named by the feature
+    #[error("{0} is not implemented by rynamodb")]
+    NotImplemented(String),
 }
 
 pub type Result<T> = std::result::Result<T, TableError>;
@@ -39,87 +110,904 @@ pub struct Table {
     pub table_id: String,
     pub created_at: DateTime<Utc>,
     pub provisioned_throughput: types::ProvisionedThroughputDescription,
+    pub sse_description: Option<types::SseDescription>,
+    pub on_demand_throughput: Option<types::OnDemandThroughput>,
+    pub warm_throughput: Option<types::WarmThroughput>,
+    /// name of the attribute holding the expiry time of items, if time to live is enabled
+    time_to_live: Option<String>,
+    auto_scaling: auto_scaling::AutoScaling,
     // internal information
+    region: Region,
+    account_id: String,
     partition_key: String,
     sort_key: Option<String>,
-    /// map partition key to partitions
-    partitions: HashMap<String, Partition>,
+    /// map partition key to partitions. Partitions are shared with snapshots and copied on
+    /// write, see [`Table::snapshot`]
+    partitions: HashMap<Key, Arc<Partition>>,
+    /// names of the attributes of the items in the table, shared by the items
+    names: names::Interner,
+    /// sizes of the items written to the table
+    item_sizes: stats::SizeHistogram,
+    point_in_time_recovery: Option<PointInTimeRecovery>,
+    global_secondary_indexes: Vec<index::GlobalSecondaryIndex>,
+    /// where changes are published, once the table is added to a table manager
+    events: Option<Events>,
+    /// source of the ids and creation times of this table and its copies
+    providers: Providers,
+    // lifecycle simulation
+    active_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Lifecycle state of a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableStatus {
+    Creating,
+    Active,
+    Deleting,
+    /// deletion has completed, so the table should be treated as if it does not exist
+    Deleted,
+}
+
+impl TableStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TableStatus::Creating => "CREATING",
+            TableStatus::Active => "ACTIVE",
+            TableStatus::Deleting | TableStatus::Deleted => "DELETING",
+        }
+    }
+}
+
+/// A write recorded while point in time recovery is enabled
+#[derive(Clone)]
+enum Mutation {
+    Put(Item),
+    Delete(ItemKey),
+}
+
+#[derive(Clone)]
+struct ChangeRecord {
+    at: DateTime<Utc>,
+    mutation: Mutation,
+}
+
+/// State required to restore a table to an earlier point in time
+#[derive(Clone)]
+struct PointInTimeRecovery {
+    enabled_at: DateTime<Utc>,
+    /// table contents at the time recovery was enabled
+    base: HashMap<Key, Arc<Partition>>,
+    /// mutations since recovery was enabled, oldest first
+    changes: Vec<ChangeRecord>,
 }
 
 impl Table {
     pub fn new(region: Region, account: impl Into<String>, options: TableOptions) -> Self {
+        Self::with_providers(region, account, options, Providers::default())
+    }
+
+    pub(crate) fn with_providers(
+        region: Region,
+        account: impl Into<String>,
+        options: TableOptions,
+        providers: Providers,
+    ) -> Self {
         let table_name = options.name;
-        Self {
+        let account_id = account.into();
+        let mut table = Self {
             name: table_name.clone(),
             partition_key: options.partition_key,
             sort_key: options.sort_key,
             attribute_definitions: options.attribute_definitions,
+            arn: format!("arn:aws:dynamodb:{region}:{account_id}:table/{table_name}"),
+            table_id: providers.ids.new_id(),
+            created_at: providers.clock.now(),
+            on_demand_throughput: options.on_demand_throughput,
+            warm_throughput: options.warm_throughput,
+            // indexes created with the table have nothing to backfill
+            global_secondary_indexes: options
+                .global_secondary_indexes
+                .iter()
+                .map(index::GlobalSecondaryIndex::new)
+                .collect(),
+            region,
+            account_id,
+            providers,
+            ..Default::default()
+        };
+
+        if let Some(sse_specification) = &options.sse_specification {
+            table.set_sse_specification(sse_specification);
+        }
+
+        table
+    }
+
+    /// Duplicate the table, including its items and indexes, under a new name
+    ///
+    /// The change history used for point in time recovery is not copied.
+    pub fn copy_as(&self, name: impl Into<String>) -> Table {
+        let name = name.into();
+        Table {
             arn: format!(
-                "arn:aws:dynamodb:{region}:{account}:table/{name}",
-                account = account.into(),
-                name = &table_name,
+                "arn:aws:dynamodb:{}:{}:table/{name}",
+                self.region, self.account_id
             ),
-            table_id: uuid::Uuid::new_v4().to_string(),
-            ..Default::default()
+            table_id: self.providers.ids.new_id(),
+            created_at: self.providers.clock.now(),
+            name,
+            point_in_time_recovery: None,
+            active_at: Default::default(),
+            deleted_at: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn status(&self) -> TableStatus {
+        let now = self.providers.clock.now();
+        match self.deleted_at {
+            Some(deleted_at) if now >= deleted_at => TableStatus::Deleted,
+            Some(_) => TableStatus::Deleting,
+            None if now < self.active_at => TableStatus::Creating,
+            None => TableStatus::Active,
+        }
+    }
+
+    /// When the last simulated transition still in progress completes, so that the table and its
+    /// indexes reflect every write made so far. `None` if nothing is pending.
+    pub fn settles_at(&self) -> Option<DateTime<Utc>> {
+        let now = self.providers.clock.now();
+        [Some(self.active_at), self.deleted_at]
+            .into_iter()
+            .flatten()
+            .filter(|at| *at > now)
+            .chain(
+                self.global_secondary_indexes
+                    .iter()
+                    .filter_map(|index| index.backfill_completes_at(now)),
+            )
+            .max()
+    }
+
+    /// Keep the table in the `CREATING` state for `delay`
+    pub fn delay_activation(&mut self, delay: chrono::Duration) {
+        self.active_at = self.providers.clock.now() + delay;
+    }
+
+    /// Move the table into the `DELETING` state, until it is removed after `delay`
+    pub fn start_deletion(&mut self, delay: chrono::Duration) {
+        self.deleted_at = Some(self.providers.clock.now() + delay);
+    }
+
+    /// Apply the changes requested by an `UpdateTable` call
+    ///
+    /// New global secondary indexes only contain items written after they were created until
+    /// `index_backfill_delay` has passed.
+    pub fn update(
+        &mut self,
+        input: &types::UpdateTableInput,
+        index_backfill_delay: chrono::Duration,
+    ) -> Result<()> {
+        // validate the whole request before changing anything
+        let attribute_definitions = self.merge_attribute_definitions(input)?;
+
+        if let Some(sse_specification) = &input.sse_specification {
+            self.set_sse_specification(sse_specification);
+        }
+
+        self.attribute_definitions = attribute_definitions;
+
+        for index_update in input.global_secondary_index_updates.iter().flatten() {
+            if let Some(definition) = &index_update.create {
+                let mut index = index::GlobalSecondaryIndex::new(definition);
+                index.start_backfill(self.providers.clock.now(), index_backfill_delay);
+                self.global_secondary_indexes.push(index);
+            }
+            if let Some(delete) = &index_update.delete {
+                self.global_secondary_indexes
+                    .retain(|index| index.name != delete.index_name);
+            }
+        }
+
+        // only the supplied throughput values are changed
+        if let Some(requested) = &input.on_demand_throughput {
+            let current = self
+                .on_demand_throughput
+                .get_or_insert_with(Default::default);
+            if requested.max_read_request_units.is_some() {
+                current.max_read_request_units = requested.max_read_request_units;
+            }
+            if requested.max_write_request_units.is_some() {
+                current.max_write_request_units = requested.max_write_request_units;
+            }
+        }
+
+        if let Some(requested) = &input.warm_throughput {
+            let current = self.warm_throughput.get_or_insert_with(Default::default);
+            if requested.read_units_per_second.is_some() {
+                current.read_units_per_second = requested.read_units_per_second;
+            }
+            if requested.write_units_per_second.is_some() {
+                current.write_units_per_second = requested.write_units_per_second;
+            }
         }
+
+        Ok(())
     }
 
-    pub fn insert(&mut self, attributes: HashMap<String, AttributeValue>) -> Result<()> {
+    /// Add the attribute definitions of an `UpdateTable` request to the existing definitions
+    ///
+    /// Like DynamoDB, the request must define the key attributes of every index it creates, even
+    /// if the table already defines them, and must not change the type of existing attributes.
+    fn merge_attribute_definitions(
+        &self,
+        input: &types::UpdateTableInput,
+    ) -> Result<Vec<AttributeDefinition>> {
+        let requested = input.attribute_definitions.as_deref().unwrap_or_default();
+
+        let mut merged = self.attribute_definitions.clone();
+        for definition in requested {
+            match merged
+                .iter()
+                .find(|existing| existing.attribute_name == definition.attribute_name)
+            {
+                Some(existing) if existing.attribute_type != definition.attribute_type => {
+                    return Err(TableError::InvalidParameter(format!(
+                        "Cannot change the type of attribute {} from {:?} to {:?}",
+                        definition.attribute_name,
+                        existing.attribute_type,
+                        definition.attribute_type
+                    )));
+                }
+                Some(_) => {}
+                None => merged.push(definition.clone()),
+            }
+        }
+
+        let undefined: Vec<&str> = input
+            .global_secondary_index_updates
+            .iter()
+            .flatten()
+            .filter_map(|update| update.create.as_ref())
+            .flat_map(|index| &index.key_schema)
+            .map(|key| key.attribute_name.as_str())
+            .filter(|name| {
+                !requested
+                    .iter()
+                    .any(|definition| definition.attribute_name == *name)
+            })
+            .collect();
+        if !undefined.is_empty() {
+            let defined: Vec<&str> = requested
+                .iter()
+                .map(|definition| definition.attribute_name.as_str())
+                .collect();
+            return Err(TableError::InvalidParameter(format!(
+                "Some index key attributes are not defined in AttributeDefinitions. \
+                Keys: [{}], AttributeDefinitions: [{}]",
+                undefined.join(", "),
+                defined.join(", ")
+            )));
+        }
+
+        Ok(merged)
+    }
+
+    /// Encryption at rest is not simulated, but the settings are recorded so that the table
+    /// description matches what infrastructure tooling expects
+    fn set_sse_specification(&mut self, sse_specification: &types::SseSpecification) {
+        if !sse_specification.enabled.unwrap_or(false) {
+            // encryption with an AWS owned key, which is not included in the description
+            self.sse_description = None;
+            return;
+        }
+
+        let kms_master_key_arn = match &sse_specification.kms_master_key_id {
+            Some(key_id) if key_id.starts_with("arn:") => key_id.clone(),
+            Some(key_id) => format!(
+                "arn:aws:kms:{region}:{account}:key/{key_id}",
+                region = self.region,
+                account = self.account_id
+            ),
+            None => format!(
+                "arn:aws:kms:{region}:{account}:key/{key_id}",
+                region = self.region,
+                account = self.account_id,
+                key_id = self.providers.ids.new_id()
+            ),
+        };
+
+        self.sse_description = Some(types::SseDescription {
+            status: "ENABLED".to_string(),
+            sse_type: sse_specification
+                .sse_type
+                .clone()
+                .unwrap_or_else(|| "KMS".to_string()),
+            kms_master_key_arn,
+        });
+    }
+
+    /// Region the table was created in
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Account which owns the table
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    /// Name of the partition key attribute
+    pub fn partition_key(&self) -> &str {
+        &self.partition_key
+    }
+
+    /// Name of the sort key attribute, if the table has one
+    pub fn sort_key(&self) -> Option<&str> {
+        self.sort_key.as_deref()
+    }
+
+    /// Check that an item has the keys required to be inserted and valid sets, so that callers
+    /// can keep ownership of items which would be rejected by [`Table::insert`]
+    pub fn validate_item(&self, attributes: &HashMap<String, AttributeValue>) -> Result<()> {
+        sets::check_item(attributes)?;
         let partition_key_value = attributes
             .get(&self.partition_key)
             .ok_or(TableError::MissingPartitionKey)?;
 
-        match partition_key_value {
-            serde_dynamo::AttributeValue::S(partition_key_value) => {
-                let partition = self
-                    .partitions
-                    .entry(partition_key_value.clone())
-                    .or_insert_with(|| {
-                        tracing::debug!(?partition_key_value, "creating new partition");
-                        Default::default()
-                    });
-                partition.insert(attributes);
+        if let Some(sort_key) = &self.sort_key {
+            if !attributes.contains_key(sort_key) {
+                return Err(TableError::MissingSortKey);
             }
-            _ => todo!(),
         }
 
+        Key::from_value(partition_key_value).ok_or(TableError::InvalidPartitionKey)?;
+
+        // items without the index keys are left out of the index, but items with them must be
+        // able to be indexed
+        self.global_secondary_indexes
+            .iter()
+            .try_for_each(|index| index.check_keys(attributes, &self.attribute_definitions))
+    }
+
+    pub fn insert(&mut self, mut attributes: HashMap<String, AttributeValue>) -> Result<()> {
+        self.validate_item(&attributes)?;
+        numbers::canonicalise_item(&mut attributes);
+        self.item_sizes.record(size::item_size(&attributes));
+        let item = self.names.intern_item(attributes);
+        let event = self.subscribed_events().map(|_| TableEvent::ItemPut {
+            table_name: self.name.clone(),
+            item: names::to_attributes(&item),
+            old_item: self
+                .item_key(&item)
+                .and_then(|key| self.stored_item(&key))
+                .map(names::to_attributes),
+        });
+        self.put(item)?;
+        if let (Some(events), Some(event)) = (&self.events, event) {
+            events.send(event);
+        }
         Ok(())
     }
 
+    pub(crate) fn set_events(&mut self, events: Events) {
+        self.events = Some(events);
+    }
+
+    fn subscribed_events(&self) -> Option<&Events> {
+        self.events.as_ref().filter(|events| events.is_subscribed())
+    }
+
+    fn stored_item(&self, (partition_key, sort_key): &ItemKey) -> Option<&Item> {
+        let partition = self.partitions.get(partition_key)?;
+        let position = partition.positions.get(sort_key)?;
+        partition.rows.get(*position)
+    }
+
+    /// Store an item which has already been validated
+    fn put(&mut self, attributes: Item) -> Result<()> {
+        let partition_key_value = Key::from_value(&attributes[self.partition_key.as_str()])
+            .ok_or(TableError::InvalidPartitionKey)?;
+        let now = self.providers.clock.now();
+        if !self.global_secondary_indexes.is_empty() {
+            if let Some(key) = self.item_key(&attributes) {
+                for index in &mut self.global_secondary_indexes {
+                    index.record_write(key.clone(), now);
+                }
+            }
+        }
+        if let Some(point_in_time_recovery) = &mut self.point_in_time_recovery {
+            point_in_time_recovery.changes.push(ChangeRecord {
+                at: now,
+                mutation: Mutation::Put(attributes.clone()),
+            });
+        }
+
+        let partition = self
+            .partitions
+            .entry(partition_key_value.clone())
+            .or_insert_with(|| {
+                tracing::debug!(?partition_key_value, "creating new partition");
+                Default::default()
+            });
+        // copies the partition if a snapshot still refers to it
+        Arc::make_mut(partition).insert(attributes, self.sort_key.as_deref());
+
+        Ok(())
+    }
+
+    /// Remove the item with the given key, if it exists
+    fn remove(&mut self, key: &ItemKey) {
+        let (partition_key, sort_key) = key;
+        let Some(partition) = self.partitions.get_mut(partition_key) else {
+            return;
+        };
+        if !partition.positions.contains_key(sort_key) {
+            return;
+        }
+
+        if let Some(point_in_time_recovery) = &mut self.point_in_time_recovery {
+            point_in_time_recovery.changes.push(ChangeRecord {
+                at: self.providers.clock.now(),
+                mutation: Mutation::Delete(key.clone()),
+            });
+        }
+        // copies the partition if a snapshot still refers to it
+        let partition = Arc::make_mut(partition);
+        partition.remove(sort_key, self.sort_key.as_deref());
+        if partition.rows.is_empty() {
+            self.partitions.remove(partition_key);
+        }
+    }
+
+    /// Release the memory left unused by removed items, returning roughly how many bytes were
+    /// reclaimed
+    ///
+    /// Partitions shared with a snapshot are skipped, as compacting them would copy them.
+    pub fn compact(&mut self) -> usize {
+        let mut reclaimed = 0;
+        for partition in self.partitions.values_mut() {
+            if let Some(partition) = Arc::get_mut(partition) {
+                reclaimed += partition.compact();
+            }
+        }
+        let capacity = self.partitions.capacity();
+        self.partitions.shrink_to_fit();
+        reclaimed += capacity.saturating_sub(self.partitions.capacity())
+            * std::mem::size_of::<(Key, Arc<Partition>)>();
+        reclaimed
+    }
+
+    /// Delete the items whose time to live has passed, returning how many were deleted
+    pub fn expire_items(&mut self, now: DateTime<Utc>) -> usize {
+        let Some(attribute_name) = &self.time_to_live else {
+            return 0;
+        };
+        let expired: Vec<ItemKey> = self
+            .iter()
+            .filter(|item| ttl::is_expired(item, attribute_name, now))
+            .filter_map(|item| self.item_key(item))
+            .collect();
+        for key in &expired {
+            let old_item = self
+                .subscribed_events()
+                .and_then(|_| self.stored_item(key))
+                .map(names::to_attributes);
+            self.remove(key);
+            if let (Some(events), Some(old_item)) = (&self.events, old_item) {
+                events.send(TableEvent::ItemDeleted {
+                    table_name: self.name.clone(),
+                    old_item,
+                    reason: DeletionReason::Expired,
+                });
+            }
+        }
+        expired.len()
+    }
+
+    /// Apply an `UpdateTimeToLive` request
+    pub fn set_time_to_live(
+        &mut self,
+        specification: &types::TimeToLiveSpecification,
+    ) -> Result<()> {
+        match (&self.time_to_live, specification.enabled) {
+            (Some(_), true) => Err(TableError::InvalidTimeToLive(
+                "TimeToLive is already enabled".to_string(),
+            )),
+            (None, false) => Err(TableError::InvalidTimeToLive(
+                "TimeToLive is already disabled".to_string(),
+            )),
+            (Some(attribute_name), false) if *attribute_name != specification.attribute_name => {
+                Err(TableError::InvalidTimeToLive(format!(
+                    "TimeToLive is enabled on a different attribute: {attribute_name}"
+                )))
+            }
+            (_, enabled) => {
+                self.time_to_live = enabled.then(|| specification.attribute_name.clone());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn time_to_live_description(&self) -> types::TimeToLiveDescription {
+        match &self.time_to_live {
+            Some(attribute_name) => types::TimeToLiveDescription {
+                time_to_live_status: "ENABLED".to_string(),
+                attribute_name: Some(attribute_name.clone()),
+            },
+            None => types::TimeToLiveDescription {
+                time_to_live_status: "DISABLED".to_string(),
+                attribute_name: None,
+            },
+        }
+    }
+
+    /// Change the auto scaling settings of the table and its indexes, moving the provisioned
+    /// capacity into the range of the new settings. Nothing is changed if any update is invalid.
+    pub fn update_auto_scaling(
+        &mut self,
+        input: &types::UpdateTableReplicaAutoScalingInput,
+    ) -> Result<()> {
+        let mut settings = self.auto_scaling.clone();
+        let policy_name = |dimension: &str, index: Option<&str>| {
+            let resource = match index {
+                Some(index) => format!("table/{}/index/{index}", self.name),
+                None => format!("table/{}", self.name),
+            };
+            format!("DynamoDB{dimension}CapacityUtilization:{resource}")
+        };
+        fn index_settings<'a>(
+            settings: &'a mut auto_scaling::AutoScaling,
+            indexes: &[index::GlobalSecondaryIndex],
+            name: &Option<String>,
+        ) -> Result<&'a mut auto_scaling::IndexAutoScaling> {
+            let name = name.as_deref().unwrap_or_default();
+            if !indexes.iter().any(|index| index.name == name) {
+                return Err(TableError::IndexNotFound(name.to_string()));
+            }
+            Ok(settings.indexes.entry(name.to_string()).or_default())
+        }
+
+        if let Some(update) = &input.provisioned_write_capacity_auto_scaling_update {
+            auto_scaling::apply(&mut settings.write, update, || policy_name("Write", None))?;
+        }
+        for index_update in &input.global_secondary_index_updates {
+            if let Some(update) = &index_update.provisioned_write_capacity_auto_scaling_update {
+                let index = index_settings(
+                    &mut settings,
+                    &self.global_secondary_indexes,
+                    &index_update.index_name,
+                )?;
+                auto_scaling::apply(&mut index.write, update, || {
+                    policy_name("Write", index_update.index_name.as_deref())
+                })?;
+            }
+        }
+        for replica_update in &input.replica_updates {
+            if replica_update.region_name != self.region.to_string() {
+                return Err(TableError::InvalidParameter(format!(
+                    "Table {} has no replica in region {}",
+                    self.name, replica_update.region_name
+                )));
+            }
+            if let Some(update) =
+                &replica_update.replica_provisioned_read_capacity_auto_scaling_update
+            {
+                auto_scaling::apply(&mut settings.read, update, || policy_name("Read", None))?;
+            }
+            for index_update in &replica_update.replica_global_secondary_index_updates {
+                if let Some(update) = &index_update.provisioned_read_capacity_auto_scaling_update {
+                    let index = index_settings(
+                        &mut settings,
+                        &self.global_secondary_indexes,
+                        &index_update.index_name,
+                    )?;
+                    auto_scaling::apply(&mut index.read, update, || {
+                        policy_name("Read", index_update.index_name.as_deref())
+                    })?;
+                }
+            }
+        }
+
+        let read = self.provisioned_throughput.read_capacity_units();
+        let write = self.provisioned_throughput.write_capacity_units();
+        if let (Some(read), Some(write)) = (read, write) {
+            self.provisioned_throughput.set_capacity(
+                auto_scaling::clamp(read, settings.read.as_ref()),
+                auto_scaling::clamp(write, settings.write.as_ref()),
+                self.providers.clock.now(),
+            );
+        }
+        self.auto_scaling = settings;
+        Ok(())
+    }
+
+    pub fn auto_scaling_description(&self) -> types::TableAutoScalingDescription {
+        let status = self.status().as_str().to_string();
+        let global_secondary_indexes = self
+            .global_secondary_indexes
+            .iter()
+            .map(|index| {
+                let settings = self.auto_scaling.indexes.get(&index.name);
+                types::ReplicaGlobalSecondaryIndexAutoScalingDescription {
+                    index_name: index.name.clone(),
+                    index_status: if index.is_backfilling(self.providers.clock.now()) {
+                        "CREATING"
+                    } else {
+                        "ACTIVE"
+                    }
+                    .to_string(),
+                    provisioned_read_capacity_auto_scaling_settings: settings
+                        .and_then(|settings| settings.read.clone()),
+                    provisioned_write_capacity_auto_scaling_settings: settings
+                        .and_then(|settings| settings.write.clone()),
+                }
+            })
+            .collect();
+        types::TableAutoScalingDescription {
+            table_name: self.name.clone(),
+            table_status: status.clone(),
+            replicas: vec![types::ReplicaAutoScalingDescription {
+                region_name: self.region.to_string(),
+                replica_status: status,
+                global_secondary_indexes,
+                replica_provisioned_read_capacity_auto_scaling_settings: self
+                    .auto_scaling
+                    .read
+                    .clone(),
+                replica_provisioned_write_capacity_auto_scaling_settings: self
+                    .auto_scaling
+                    .write
+                    .clone(),
+            }],
+        }
+    }
+
+    pub fn set_point_in_time_recovery(&mut self, enabled: bool) {
+        if !enabled {
+            // the change history is discarded when recovery is disabled
+            self.point_in_time_recovery = None;
+        } else if self.point_in_time_recovery.is_none() {
+            self.point_in_time_recovery = Some(PointInTimeRecovery {
+                enabled_at: self.providers.clock.now(),
+                base: self.partitions.clone(),
+                changes: Vec::new(),
+            });
+        }
+    }
+
+    pub fn continuous_backups_description(&self) -> types::ContinuousBackupsDescription {
+        let point_in_time_recovery_description = match &self.point_in_time_recovery {
+            Some(point_in_time_recovery) => types::PointInTimeRecoveryDescription {
+                point_in_time_recovery_status: "ENABLED".to_string(),
+                earliest_restorable_date_time: Some(types::to_epoch_seconds(
+                    &point_in_time_recovery.enabled_at,
+                )),
+                latest_restorable_date_time: Some(types::to_epoch_seconds(
+                    &self.providers.clock.now(),
+                )),
+            },
+            None => types::PointInTimeRecoveryDescription {
+                point_in_time_recovery_status: "DISABLED".to_string(),
+                earliest_restorable_date_time: None,
+                latest_restorable_date_time: None,
+            },
+        };
+
+        types::ContinuousBackupsDescription {
+            continuous_backups_status: "ENABLED".to_string(),
+            point_in_time_recovery_description,
+        }
+    }
+
+    /// Create a new table with the contents of this table at the given time, by replaying the
+    /// change log on top of the contents at the time recovery was enabled
+    ///
+    /// Times are compared at millisecond precision, matching the wire format.
+    pub fn restore_to_point_in_time(
+        &self,
+        target_table_name: impl Into<String>,
+        at: DateTime<Utc>,
+    ) -> Result<Table> {
+        let point_in_time_recovery = self
+            .point_in_time_recovery
+            .as_ref()
+            .ok_or_else(|| TableError::PointInTimeRecoveryUnavailable(self.name.clone()))?;
+
+        let at = at.timestamp_millis();
+        if at < point_in_time_recovery.enabled_at.timestamp_millis()
+            || at > self.providers.clock.now().timestamp_millis()
+        {
+            return Err(TableError::InvalidRestoreTime);
+        }
+
+        let mut restored = Table::with_providers(
+            self.region,
+            self.account_id.clone(),
+            TableOptions {
+                name: target_table_name.into(),
+                partition_key: self.partition_key.clone(),
+                sort_key: self.sort_key.clone(),
+                attribute_definitions: self.attribute_definitions.clone(),
+                ..Default::default()
+            },
+            self.providers.clone(),
+        );
+        restored.provisioned_throughput = self.provisioned_throughput.clone();
+        // restored indexes are built along with the table
+        restored.global_secondary_indexes = self
+            .global_secondary_indexes
+            .iter()
+            .cloned()
+            .map(|mut index| {
+                index.start_backfill(self.providers.clock.now(), chrono::Duration::zero());
+                index
+            })
+            .collect();
+        restored.partitions = point_in_time_recovery.base.clone();
+        restored.names = self.names.clone();
+
+        for change in point_in_time_recovery
+            .changes
+            .iter()
+            .take_while(|change| change.at.timestamp_millis() <= at)
+        {
+            match &change.mutation {
+                Mutation::Put(item) => restored.put(item.clone())?,
+                Mutation::Delete(key) => restored.remove(key),
+            }
+        }
+
+        Ok(restored)
+    }
+
     pub fn statistics(&self) -> Statistics {
         Statistics {
             num_partitions: self.partitions.len(),
         }
     }
 
-    pub fn description(&self) -> types::TableDescription {
-        let mut key_schema = vec![KeySchema {
-            attribute_name: self.partition_key.clone(),
-            key_type: KeyType::HASH,
-        }];
-
-        if let Some(sk) = &self.sort_key {
-            key_schema.push(KeySchema {
-                attribute_name: sk.clone(),
-                key_type: KeyType::RANGE,
-            });
+    /// Keys, indexes and time to live attribute of the table
+    pub fn schema(&self) -> schema::TableSchema {
+        let definitions = &self.attribute_definitions;
+        schema::TableSchema {
+            table_name: self.name.clone(),
+            partition_key: schema::KeyAttribute::new(&self.partition_key, definitions),
+            sort_key: self
+                .sort_key
+                .as_deref()
+                .map(|sort_key| schema::KeyAttribute::new(sort_key, definitions)),
+            attribute_definitions: definitions.clone(),
+            global_secondary_indexes: self
+                .global_secondary_indexes
+                .iter()
+                .map(|index| index.schema(definitions))
+                .collect(),
+            time_to_live_attribute: self.time_to_live.clone(),
         }
+    }
 
+    pub fn description(&self) -> types::TableDescription {
+        let schema = self.schema();
         types::TableDescription {
             table_name: Some(self.name.clone()),
-            table_status: Some("ACTIVE".to_string()),
-            attribute_definitions: Some(self.attribute_definitions.clone()),
-            table_size_bytes: Some(0),
+            table_status: Some(self.status().as_str().to_string()),
+            table_size_bytes: Some(self.iter().map(size::item_size).sum()),
             item_count: Some(self.len()),
-            key_schema: Some(key_schema),
+            key_schema: Some(schema.key_schema()),
+            attribute_definitions: Some(schema.attribute_definitions),
             table_arn: Some(self.arn.clone()),
             table_id: Some(self.table_id.clone()),
-            // TODO
             creation_date_time: Some(self.created_at.timestamp_millis()),
             provisioned_throughput: Some(self.provisioned_throughput.clone()),
+            sse_description: self.sse_description.clone(),
+            on_demand_throughput: self.on_demand_throughput.clone(),
+            warm_throughput: self.warm_throughput.as_ref().map(|warm_throughput| {
+                types::TableWarmThroughputDescription {
+                    read_units_per_second: warm_throughput.read_units_per_second,
+                    write_units_per_second: warm_throughput.write_units_per_second,
+                    status: "ACTIVE".to_string(),
+                }
+            }),
+            global_secondary_indexes: (!self.global_secondary_indexes.is_empty()).then(|| {
+                self.global_secondary_indexes
+                    .iter()
+                    .map(|index| {
+                        index.description(
+                            &self.arn,
+                            self.index_statistics(index),
+                            self.providers.clock.now(),
+                        )
+                    })
+                    .collect()
+            }),
         }
     }
 
+    /// Whether the table has the keys, attribute definitions and indexes of `options`, as when
+    /// it was created by the same `CreateTable` request
+    pub fn has_schema(&self, options: &TableOptions) -> bool {
+        fn attribute_definitions(
+            definitions: &[AttributeDefinition],
+        ) -> HashMap<&str, &AttributeType> {
+            definitions
+                .iter()
+                .map(|definition| {
+                    (
+                        definition.attribute_name.as_str(),
+                        &definition.attribute_type,
+                    )
+                })
+                .collect()
+        }
+        fn indexes<'a>(
+            indexes: impl Iterator<Item = &'a index::GlobalSecondaryIndex>,
+        ) -> HashMap<String, (String, Option<String>, Option<String>)> {
+            indexes
+                .map(|index| {
+                    (
+                        index.name.clone(),
+                        (
+                            index.partition_key.clone(),
+                            index.sort_key.clone(),
+                            index.projection.projection_type.clone(),
+                        ),
+                    )
+                })
+                .collect()
+        }
+
+        let requested_indexes: Vec<_> = options
+            .global_secondary_indexes
+            .iter()
+            .map(index::GlobalSecondaryIndex::new)
+            .collect();
+        self.partition_key == options.partition_key
+            && self.sort_key == options.sort_key
+            && attribute_definitions(&self.attribute_definitions)
+                == attribute_definitions(&options.attribute_definitions)
+            && indexes(self.global_secondary_indexes.iter()) == indexes(requested_indexes.iter())
+    }
+
+    /// Primary key of an item
+    fn item_key(&self, item: &Item) -> Option<ItemKey> {
+        let partition_key = Key::from_value(item.get(self.partition_key.as_str())?)?;
+        let sort_key = match &self.sort_key {
+            Some(sort_key) => Some(Key::from_value(item.get(sort_key.as_str())?)?),
+            None => None,
+        };
+        Some((partition_key, sort_key))
+    }
+
+    fn index_items<'a>(
+        &'a self,
+        index: &'a index::GlobalSecondaryIndex,
+    ) -> impl Iterator<Item = &'a Item> + 'a {
+        let now = self.providers.clock.now();
+        self.partitions
+            .values()
+            .flat_map(|partition| partition.rows.iter())
+            .filter(move |item| {
+                self.item_key(item)
+                    .map(|key| index.contains(&key, item, now))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Count the items in an index and the size of their projections
+    fn index_statistics(&self, index: &index::GlobalSecondaryIndex) -> index::IndexStatistics {
+        let mut table_keys = vec![self.partition_key.as_str()];
+        table_keys.extend(self.sort_key.as_deref());
+        self.index_items(index)
+            .fold(index::IndexStatistics::default(), |statistics, item| {
+                index::IndexStatistics {
+                    item_count: statistics.item_count + 1,
+                    size_bytes: statistics.size_bytes + index.projected_size(item, &table_keys),
+                }
+            })
+    }
+
     fn len(&self) -> usize {
         self.partitions.values().map(|p| p.item_count()).sum()
     }
@@ -134,85 +1022,604 @@ impl Table {
         // remove placeholders
         let placeholder_remover =
             visitor::NodeVisitor::new(expression_attribute_names, expression_attribute_values);
-        let ast = placeholder_remover.visit(ast);
+        let ast = placeholder_remover.visit(ast)?;
+        self.validate_key_condition(&ast)?;
 
-        match ast {
-            // simple equality check with the partition key
-            Node::Binop { op, lhs, rhs } if op == queries::Operator::Eq => {
-                match (lhs.as_ref(), rhs.as_ref()) {
-                    (Node::Attribute(key), Node::Attribute(value)) => {
-                        if key != &self.partition_key {
-                            return Err(TableError::InvalidPartitionKey);
-                        }
-
-                        match self.partitions.get(value) {
-                            Some(p) => Ok(p.rows.clone()),
-                            None => Ok(Vec::new()),
-                        }
-                    }
-                    (l, r) => unreachable!("lhs: {l:?} rhs: {r:?}"),
-                }
+        query_partitions(
+            |value| self.partition_for(value),
+            &self.partition_key,
+            self.sort_key.as_deref(),
+            ast,
+        )
+    }
+
+    /// Query a global secondary index
+    ///
+    /// This reads every item in the table, so gives up once `deadline` has passed.
+    pub fn query_index(
+        &self,
+        index_name: &str,
+        key_condition_expression: &str,
+        expression_attribute_names: &Option<HashMap<String, String>>,
+        expression_attribute_values: &Option<HashMap<String, AttributeValue>>,
+        deadline: &Deadline,
+    ) -> Result<Vec<HashMap<String, AttributeValue>>> {
+        let index = self.index(index_name)?;
+
+        let ast = queries::parse(key_condition_expression)?;
+        let placeholder_remover =
+            visitor::NodeVisitor::new(expression_attribute_names, expression_attribute_values);
+        let ast = placeholder_remover.visit(ast)?;
+        self.validate_key_condition(&ast)?;
+
+        // index keys are not unique, so the items are added without replacement
+        let mut partitions: HashMap<Key, Partition> = HashMap::new();
+        for item in self.index_items(index) {
+            deadline.check()?;
+            if let Some(key) = Key::from_value(&item[index.partition_key.as_str()]) {
+                partitions.entry(key).or_default().rows.push(item.clone());
             }
-            Node::Binop { op, lhs, rhs } if op == queries::Operator::And => {
-                // TODO: assume the lhs is the primary key for now
-                let pk_query = lhs.as_ref();
-                match pk_query {
-                    Node::Binop {
-                        lhs: pk_lhs,
-                        rhs: pk_rhs,
-                        // operator _must_ be =
-                        ..
-                    } => match (pk_lhs.as_ref(), pk_rhs.as_ref()) {
-                        (Node::Attribute(_), Node::Attribute(value)) => {
-                            let partition = self
-                                .partitions
-                                .get(value)
-                                .ok_or(TableError::InvalidPartitionKey)?;
-
-                            // delegate to the partition
-                            // the rhs _must_ be the sk
-                            partition.query(*rhs)
-                        }
-                        (l, r) => unreachable!("lhs: {l:?} rhs: {r:?}"),
-                    },
-                    n => unreachable!("node: {n:?}"),
-                }
+        }
+
+        let mut table_keys = vec![self.partition_key.as_str()];
+        table_keys.extend(self.sort_key.as_deref());
+        let partition_for =
+            |value: &AttributeValue| Key::from_value(value).and_then(|key| partitions.get(&key));
+        Ok(query_partitions(
+            partition_for,
+            &index.partition_key,
+            index.sort_key.as_deref(),
+            ast,
+        )?
+        .into_iter()
+        .map(|item| index.project(item, &table_keys))
+        .collect())
+    }
+
+    fn index(&self, index_name: &str) -> Result<&index::GlobalSecondaryIndex> {
+        self.global_secondary_indexes
+            .iter()
+            .find(|index| index.name == index_name)
+            .ok_or_else(|| TableError::IndexNotFound(index_name.to_string()))
+    }
+
+    /// Order in which the results of a query on the table, or one of its indexes, are returned
+    pub fn query_order(&self, index_name: Option<&str>) -> Result<pagination::KeyOrder> {
+        let mut table_keys = vec![self.partition_key.clone()];
+        table_keys.extend(self.sort_key.clone());
+
+        let Some(index_name) = index_name else {
+            return Ok(pagination::KeyOrder::new(
+                table_keys,
+                self.sort_key.iter().cloned().collect(),
+            ));
+        };
+
+        let index = self.index(index_name)?;
+        let mut key_attributes = vec![index.partition_key.clone()];
+        key_attributes.extend(index.sort_key.clone());
+        // index keys are not unique, so items with the same index key are ordered by their table
+        // key
+        let mut sort_attributes: Vec<String> = index.sort_key.iter().cloned().collect();
+        sort_attributes.extend(table_keys.iter().cloned());
+        for key in table_keys {
+            if !key_attributes.contains(&key) {
+                key_attributes.push(key);
             }
-            _ => todo!(),
         }
+        Ok(pagination::KeyOrder::new(key_attributes, sort_attributes))
     }
 
-    // TODO: horrible memory behaviour - iterators?
-    pub fn scan(&self) -> Result<Vec<HashMap<String, serde_dynamo::AttributeValue>>> {
-        let mut out = Vec::new();
-        for partition in self.partitions.values() {
-            for item in &partition.rows {
-                out.push(item.clone());
+    /// How a scan orders the items, see [`Table::query_order`]
+    ///
+    /// Items are ordered by their table key. DynamoDB scans return partitions in the order of
+    /// their hashed keys, and the items of each partition in sort key order, so with a
+    /// `shuffle_seed` the partitions are ordered by a hash of their key, the seed and the table
+    /// name instead. This surfaces clients which depend on scan order while keeping failures
+    /// reproducible, and keeps the order stable from one page to the next.
+    pub fn scan_order(&self, shuffle_seed: Option<u64>) -> pagination::KeyOrder {
+        let mut table_keys = vec![self.partition_key.clone()];
+        table_keys.extend(self.sort_key.clone());
+        let order = pagination::KeyOrder::new(table_keys.clone(), table_keys);
+        match shuffle_seed {
+            Some(seed) => order.shuffled(
+                self.partition_key.clone(),
+                seed ^ pagination::fnv1a(self.name.as_bytes()),
+            ),
+            None => order,
+        }
+    }
+
+    /// Estimate what a Query would cost on DynamoDB, reading every page from its
+    /// `ExclusiveStartKey` under `limits`, see [`cost`]
+    pub fn estimate_query(
+        &self,
+        input: &types::QueryInput,
+        limits: &Limits,
+    ) -> Result<cost::CostEstimate> {
+        let items = match (&input.key_condition_expression, &input.key_conditions) {
+            (Some(key_condition_expression), _) => match &input.index_name {
+                Some(index_name) => self.query_index(
+                    index_name,
+                    key_condition_expression,
+                    &input.expression_attribute_names,
+                    &input.expression_attribute_values,
+                    &Deadline::default(),
+                )?,
+                None => self.query(
+                    key_condition_expression,
+                    &input.expression_attribute_names,
+                    &input.expression_attribute_values,
+                )?,
+            },
+            (None, Some(key_conditions)) => {
+                self.query_key_conditions(key_conditions, input.index_name.as_deref())?
             }
+            (None, None) => {
+                return Err(TableError::InvalidParameter(
+                    "Either the KeyConditions or KeyConditionExpression parameter must be \
+                    specified in the request"
+                        .to_string(),
+                ))
+            }
+        };
+        let order = self.query_order(input.index_name.as_deref())?;
+        if let Some(start_key) = &input.exclusive_start_key {
+            order.validate_start_key(start_key)?;
+        }
+        let items = order
+            .paginate(
+                items,
+                input.exclusive_start_key.as_ref(),
+                None,
+                usize::MAX,
+                input.scan_index_forward.unwrap_or(true),
+            )
+            .items;
+        Ok(cost::estimate(
+            &items,
+            input.limit,
+            input.consistent_read.unwrap_or(false),
+            limits,
+        ))
+    }
+
+    /// Estimate what a Scan would cost on DynamoDB, reading every page under `limits`, see
+    /// [`cost`]
+    pub fn estimate_scan(&self, input: &types::ScanInput, limits: &Limits) -> cost::CostEstimate {
+        cost::estimate(
+            self.iter(),
+            input.limit,
+            input.consistent_read.unwrap_or(false),
+            limits,
+        )
+    }
+
+    /// Query the table, or its index `index_name`, using the legacy `KeyConditions` parameter
+    pub fn query_key_conditions(
+        &self,
+        key_conditions: &HashMap<String, types::Condition>,
+        index_name: Option<&str>,
+    ) -> Result<Vec<HashMap<String, AttributeValue>>> {
+        legacy::validate(key_conditions)?;
+
+        let index = index_name
+            .map(|index_name| self.index(index_name))
+            .transpose()?;
+        let (partition_key, sort_key) = match index {
+            Some(index) => (index.partition_key.as_str(), index.sort_key.as_deref()),
+            None => (self.partition_key.as_str(), self.sort_key.as_deref()),
+        };
+        if let Some(name) = key_conditions
+            .keys()
+            .find(|name| name.as_str() != partition_key && Some(name.as_str()) != sort_key)
+        {
+            return Err(TableError::InvalidCondition(format!(
+                "Query key condition not supported: {name} is not a key attribute"
+            )));
+        }
+        let partition_key_value = match key_conditions.get(partition_key) {
+            Some(types::Condition {
+                comparison_operator: types::ComparisonOperator::Eq,
+                attribute_value_list,
+            }) => &attribute_value_list[0],
+            _ => return Err(TableError::MissingKeyCondition(partition_key.to_string())),
+        };
+        let matches =
+            |item: &&Item| legacy::matches(item, key_conditions, types::ConditionalOperator::And);
+
+        let Some(index) = index else {
+            let Some(partition) = self.partition_for(partition_key_value) else {
+                return Ok(Vec::new());
+            };
+            return Ok(partition
+                .rows
+                .iter()
+                .filter(matches)
+                .map(names::to_attributes)
+                .collect());
+        };
+        // as for a KeyConditionExpression, only items the index holds are read, and they are
+        // projected
+        let mut table_keys = vec![self.partition_key.as_str()];
+        table_keys.extend(self.sort_key.as_deref());
+        Ok(self
+            .index_items(index)
+            .filter(matches)
+            .map(|item| index.project(names::to_attributes(item), &table_keys))
+            .collect())
+    }
+
+    fn partition_for(&self, partition_key_value: &AttributeValue) -> Option<&Partition> {
+        self.partitions
+            .get(&Key::from_value(partition_key_value)?)
+            .map(Arc::as_ref)
+    }
+
+    /// Iterate over the items in the table, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &Item> {
+        self.partitions
+            .values()
+            .flat_map(|partition| partition.rows.iter())
+    }
+
+    /// Iterate over the items with the partition key value `partition_key`, in no particular
+    /// order
+    pub fn iter_partition(&self, partition_key: &AttributeValue) -> impl Iterator<Item = &Item> {
+        self.partition_for(partition_key)
+            .into_iter()
+            .flat_map(|partition| partition.rows.iter())
+    }
+
+    /// Distribution of the sizes of the items written to the table since it was created
+    pub fn item_sizes(&self) -> &stats::SizeHistogram {
+        &self.item_sizes
+    }
+
+    /// Take a consistent point in time view of the items in the table
+    ///
+    /// This only clones references to the partitions, so it is cheap enough to take while
+    /// holding the table lock, and the snapshot can then be read after the lock is released.
+    /// Later writes copy the partitions they modify rather than changing the snapshot.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            written_item_sizes: self.item_sizes.clone(),
+            partitions: self.partitions.values().cloned().collect(),
         }
-        Ok(out)
     }
+
     // key is something like {"pk": {"S": "def"}, "sk": {"S": "ghj"}}
     pub fn get_item(
         &self,
         key: HashMap<String, AttributeValue>,
-    ) -> Option<HashMap<String, serde_dynamo::AttributeValue>> {
-        assert!(!key.is_empty());
+    ) -> Result<Option<HashMap<String, serde_dynamo::AttributeValue>>> {
+        self.validate_key(&key)?;
 
-        let partition_name = key.get(&self.partition_key).map(|k| match k {
-            AttributeValue::S(s) => s,
-            _ => unreachable!(),
-        })?;
-        let partition = self.partitions.get(partition_name)?;
+        let Some(partition) = self.partition_for(&key[&self.partition_key]) else {
+            return Ok(None);
+        };
+        let sort_key = self.sort_key.as_deref().map(|sort_key| &key[sort_key]);
 
-        if let Some(sort_key) = &self.sort_key {
-            let sort_key_value = key.get(sort_key).map(|k| match k {
-                AttributeValue::S(s) => s,
-                _ => unreachable!(),
-            })?;
-            partition.get_item(sort_key, sort_key_value.as_str())
-        } else {
-            partition.get_by_pk_only()
+        Ok(partition
+            .position(sort_key)
+            .map(|idx| names::to_attributes(&partition.rows[idx])))
+    }
+
+    /// Apply an `UpdateItem` request to the item with its key, without storing the result, so
+    /// that the updated item can be checked before it is passed to [`Table::insert`]
+    pub fn updated_item(&self, input: &types::UpdateItemInput) -> Result<update::Update> {
+        let old = self.get_item(input.key.clone())?;
+        let key_attributes: Vec<&str> = std::iter::once(self.partition_key.as_str())
+            .chain(self.sort_key.as_deref())
+            .collect();
+        update::Update::apply(input.key.clone(), old, input, &key_attributes)
+    }
+
+    /// Apply a JSON merge patch to the item with `key`, creating it if it does not exist, and
+    /// return the patched item
+    ///
+    /// `null` removes an attribute and map values patch existing maps. Key attributes cannot be
+    /// patched, as that would move the item.
+    pub fn patch_item(
+        &mut self,
+        key: HashMap<String, AttributeValue>,
+        patch: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<HashMap<String, AttributeValue>> {
+        if let Some(name) = patch
+            .keys()
+            .find(|name| *name == &self.partition_key || self.sort_key.as_ref() == Some(*name))
+        {
+            return Err(TableError::InvalidParameter(format!(
+                "Cannot patch key attribute {name}"
+            )));
+        }
+        let mut item = self.get_item(key.clone())?.unwrap_or(key);
+        patch::merge(&mut item, patch)?;
+        self.insert(item.clone())?;
+        Ok(item)
+    }
+
+    /// Check the operands of `BETWEEN` and `begins_with` conditions in a key condition expression,
+    /// whose placeholders have already been substituted
+    fn validate_key_condition(&self, ast: &Node) -> Result<()> {
+        match ast {
+            Node::Binop { lhs, rhs, .. } => {
+                self.validate_key_condition(lhs)?;
+                self.validate_key_condition(rhs)
+            }
+            Node::Between { key, lower, upper } => {
+                let (Some(lower), Some(upper)) = (literal_value(lower), literal_value(upper))
+                else {
+                    return Ok(());
+                };
+                if type_descriptor(&lower) != type_descriptor(&upper) {
+                    return Err(TableError::InvalidKeyConditionExpression(format!(
+                        "The BETWEEN operator requires same data type for lower and upper bounds; \
+                        lower bound operand: {}, upper bound operand: {}",
+                        describe_value(&lower),
+                        describe_value(&upper)
+                    )));
+                }
+
+                let definition = key.as_str().ok().and_then(|name| {
+                    self.attribute_definitions
+                        .iter()
+                        .find(|definition| definition.attribute_name == name)
+                });
+                if let Some(definition) = definition {
+                    if !has_type(&lower, &definition.attribute_type) {
+                        return Err(TableError::InvalidCondition(
+                            "Condition parameter type does not match schema type".to_string(),
+                        ));
+                    }
+                }
+
+                if compare_values(&lower, &upper) == Some(Ordering::Greater) {
+                    return Err(TableError::InvalidKeyConditionExpression(format!(
+                        "The BETWEEN operator requires upper bound to be greater than or equal to \
+                        lower bound; lower bound operand: {}, upper bound operand: {}",
+                        describe_value(&lower),
+                        describe_value(&upper)
+                    )));
+                }
+                Ok(())
+            }
+            Node::FunctionCall { name, args } if name == "begins_with" => {
+                let Some(prefix) = args.get(1).and_then(literal_value) else {
+                    return Ok(());
+                };
+                if !matches!(prefix, AttributeValue::S(_) | AttributeValue::B(_)) {
+                    return Err(TableError::InvalidKeyConditionExpression(format!(
+                        "Incorrect operand type for operator or function; operator or function: \
+                        begins_with, operand type: {}",
+                        type_descriptor(&prefix)
+                    )));
+                }
+                let definition = args
+                    .first()
+                    .and_then(|key| key.as_str().ok())
+                    .and_then(|name| {
+                        self.attribute_definitions
+                            .iter()
+                            .find(|definition| definition.attribute_name == name)
+                    });
+                if let Some(definition) = definition {
+                    if !has_type(&prefix, &definition.attribute_type) {
+                        return Err(TableError::InvalidCondition(
+                            "Condition parameter type does not match schema type".to_string(),
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that a key contains exactly the key attributes of the table, with the types
+    /// given in the attribute definitions
+    fn validate_key(&self, key: &HashMap<String, AttributeValue>) -> Result<()> {
+        let key_names: Vec<&String> = std::iter::once(&self.partition_key)
+            .chain(self.sort_key.as_ref())
+            .collect();
+
+        if key.len() != key_names.len() {
+            return Err(TableError::InvalidKey);
+        }
+
+        for name in key_names {
+            let value = key.get(name).ok_or(TableError::InvalidKey)?;
+            let definition = self
+                .attribute_definitions
+                .iter()
+                .find(|definition| &definition.attribute_name == name);
+            if let Some(definition) = definition {
+                if !has_type(value, &definition.attribute_type) {
+                    return Err(TableError::InvalidKey);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn type_descriptor(value: &AttributeValue) -> &'static str {
+    match value {
+        AttributeValue::S(_) => "S",
+        AttributeValue::N(_) => "N",
+        AttributeValue::B(_) => "B",
+        AttributeValue::Bool(_) => "BOOL",
+        AttributeValue::Null(_) => "NULL",
+        AttributeValue::M(_) => "M",
+        AttributeValue::L(_) => "L",
+        AttributeValue::Ss(_) => "SS",
+        AttributeValue::Ns(_) => "NS",
+        AttributeValue::Bs(_) => "BS",
+    }
+}
+
+/// Format a value the way DynamoDB does in error messages, e.g. `AttributeValue: {N:5}`
+fn describe_value(value: &AttributeValue) -> String {
+    let contents = match value {
+        AttributeValue::S(s) | AttributeValue::N(s) => s.clone(),
+        AttributeValue::B(b) => STANDARD.encode(b),
+        AttributeValue::Bool(b) | AttributeValue::Null(b) => b.to_string(),
+        other => format!("{other:?}"),
+    };
+    format!("AttributeValue: {{{}:{contents}}}", type_descriptor(value))
+}
+
+fn has_type(value: &AttributeValue, attribute_type: &AttributeType) -> bool {
+    matches!(
+        (value, attribute_type),
+        (AttributeValue::S(_), AttributeType::S)
+            | (AttributeValue::N(_), AttributeType::N)
+            | (AttributeValue::B(_), AttributeType::B)
+    )
+}
+
+/// Compare two attribute values, returning `None` if the values are not comparable
+///
+/// Numbers are compared numerically rather than lexicographically.
+fn compare_values(a: &AttributeValue, b: &AttributeValue) -> Option<Ordering> {
+    let (a, b) = (Key::from_value(a)?, Key::from_value(b)?);
+    (std::mem::discriminant(&a) == std::mem::discriminant(&b)).then(|| a.cmp(&b))
+}
+
+/// Whether a string or binary value starts with a prefix of the same type
+fn begins_with(value: &AttributeValue, prefix: &AttributeValue) -> bool {
+    match (value, prefix) {
+        (AttributeValue::S(s), AttributeValue::S(prefix)) => s.starts_with(prefix.as_str()),
+        (AttributeValue::B(b), AttributeValue::B(prefix)) => b.starts_with(prefix),
+        _ => false,
+    }
+}
+
+/// Resolve the value side of a condition
+///
+/// Bare words (e.g. `pk = abc`) are treated as string literals.
+fn literal_value(node: &Node) -> Option<AttributeValue> {
+    match node {
+        Node::Value(value) => Some(value.clone()),
+        Node::Attribute(s) => Some(AttributeValue::S(s.clone())),
+        _ => None,
+    }
+}
+
+/// Evaluate a key condition against partitions keyed by the `partition_key` attribute
+fn query_partitions<'a>(
+    partition_for: impl Fn(&AttributeValue) -> Option<&'a Partition>,
+    partition_key: &str,
+    sort_key: Option<&str>,
+    ast: Node,
+) -> Result<Vec<HashMap<String, AttributeValue>>> {
+    let (value, sort_condition) = split_key_condition(ast, partition_key, sort_key)?;
+
+    // like a missing item, a missing partition is an empty result
+    let Some(partition) = partition_for(&value) else {
+        return Ok(Vec::new());
+    };
+    match sort_condition {
+        Some(condition) => partition.query(condition),
+        None => Ok(partition.rows.iter().map(names::to_attributes).collect()),
+    }
+}
+
+/// Split a key condition into the partition key value, and the condition on the sort key if
+/// there is one
+///
+/// Every query must constrain the partition key by equality, optionally combined with a single
+/// condition on the sort key. The conditions may be given in either order.
+fn split_key_condition(
+    ast: Node,
+    partition_key: &str,
+    sort_key: Option<&str>,
+) -> Result<(AttributeValue, Option<Node>)> {
+    let (lhs, rhs) = match ast {
+        Node::Binop {
+            op: Operator::And,
+            lhs,
+            rhs,
+        } => (lhs, rhs),
+        condition => return Ok((partition_key_value(condition, partition_key)?, None)),
+    };
+
+    let (partition_condition, sort_condition) = if condition_attribute(&rhs) == Some(partition_key)
+    {
+        (*rhs, *lhs)
+    } else {
+        (*lhs, *rhs)
+    };
+    let value = partition_key_value(partition_condition, partition_key)?;
+
+    let attribute = condition_attribute(&sort_condition);
+    if attribute == Some(partition_key) {
+        return Err(TableError::InvalidKeyConditionExpression(
+            "KeyConditionExpressions must only contain one condition per key".to_string(),
+        ));
+    }
+    if attribute.is_none() || attribute != sort_key {
+        return Err(TableError::UnsupportedKeyCondition);
+    }
+    Ok((value, Some(sort_condition)))
+}
+
+/// Value the partition key must be equal to
+fn partition_key_value(condition: Node, partition_key: &str) -> Result<AttributeValue> {
+    if condition_attribute(&condition) != Some(partition_key) {
+        return Err(TableError::MissingKeyCondition(partition_key.to_string()));
+    }
+    match condition {
+        Node::Binop {
+            op: Operator::Eq,
+            rhs,
+            ..
+        } => literal_value(&rhs).ok_or(TableError::UnsupportedKeyCondition),
+        _ => Err(TableError::UnsupportedKeyCondition),
+    }
+}
+
+/// Attribute constrained by a single key condition, e.g. `sk` in `begins_with(sk, :v)`
+fn condition_attribute(condition: &Node) -> Option<&str> {
+    let key = match condition {
+        Node::Binop { op, lhs, .. } if *op != Operator::And => lhs.as_ref(),
+        Node::Between { key, .. } => key.as_ref(),
+        Node::FunctionCall { args, .. } => args.first()?,
+        _ => return None,
+    };
+    match key {
+        Node::Attribute(name) => Some(name),
+        _ => None,
+    }
+}
+
+/// Point in time view of the items in a table, see [`Table::snapshot`]
+pub struct Snapshot {
+    partitions: Vec<Arc<Partition>>,
+    written_item_sizes: stats::SizeHistogram,
+}
+
+impl Snapshot {
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.partitions
+            .iter()
+            .flat_map(|partition| partition.rows.iter())
+    }
+
+    pub fn item_count(&self) -> usize {
+        self.partitions
+            .iter()
+            .map(|partition| partition.rows.len())
+            .sum()
+    }
+
+    /// Statistics about the attributes of up to `sample_size` items, and the sizes of all the
+    /// items written
+    pub fn attribute_statistics(&self, sample_size: usize) -> stats::AttributeStatistics {
+        stats::AttributeStatistics {
+            written_item_sizes: self.written_item_sizes.clone(),
+            ..stats::attribute_statistics(self.items(), self.item_count(), sample_size)
         }
     }
 }
@@ -221,12 +1628,16 @@ pub struct Statistics {
     pub num_partitions: usize,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct TableOptions {
     pub name: String,
     pub partition_key: String,
     pub sort_key: Option<String>,
     pub attribute_definitions: Vec<AttributeDefinition>,
+    pub sse_specification: Option<types::SseSpecification>,
+    pub on_demand_throughput: Option<types::OnDemandThroughput>,
+    pub warm_throughput: Option<types::WarmThroughput>,
+    pub global_secondary_indexes: Vec<types::GlobalSecondaryIndex>,
 }
 
 impl From<types::CreateTableInput> for TableOptions {
@@ -253,64 +1664,151 @@ impl From<types::CreateTableInput> for TableOptions {
             partition_key,
             sort_key,
             attribute_definitions: value.attribute_definitions,
+            sse_specification: value.sse_specification,
+            on_demand_throughput: value.on_demand_throughput,
+            warm_throughput: value.warm_throughput,
+            global_secondary_indexes: value.global_secondary_indexes.unwrap_or_default(),
         }
     }
 }
 
 #[derive(Default, Clone)]
 pub struct Partition {
-    rows: Vec<HashMap<String, AttributeValue>>,
+    rows: Vec<Item>,
+    /// index into `rows` by sort key value, or `None` for tables without a sort key
+    positions: HashMap<Option<Key>, usize>,
 }
 
 impl Partition {
-    pub fn insert(&mut self, attributes: HashMap<String, AttributeValue>) {
-        self.rows.push(attributes);
+    /// Insert an item, replacing any existing item with the same key
+    pub fn insert(&mut self, attributes: Item, sort_key: Option<&str>) {
+        let row_key = match sort_key {
+            Some(name) => attributes.get(name).and_then(Key::from_value).map(Some),
+            None => Some(None),
+        };
+        let Some(row_key) = row_key else {
+            // the key has already been validated, but never silently drop the item
+            self.rows.push(attributes);
+            return;
+        };
+
+        match self.positions.entry(row_key) {
+            Entry::Occupied(entry) => self.rows[*entry.get()] = attributes,
+            Entry::Vacant(entry) => {
+                entry.insert(self.rows.len());
+                self.rows.push(attributes);
+            }
+        }
+    }
+
+    /// Remove the row with the given sort key value
+    fn remove(&mut self, row_key: &Option<Key>, sort_key: Option<&str>) {
+        let Some(position) = self.positions.remove(row_key) else {
+            return;
+        };
+        self.rows.swap_remove(position);
+
+        // the last row has been moved into the removed row's position
+        let Some(moved) = self.rows.get(position) else {
+            return;
+        };
+        let moved_key = match sort_key {
+            Some(name) => moved.get(name).and_then(Key::from_value).map(Some),
+            None => Some(None),
+        };
+        if let Some(moved_key) = moved_key {
+            self.positions.insert(moved_key, position);
+        }
+    }
+
+    /// Shrink the storage of the rows to fit, returning the number of bytes released
+    fn compact(&mut self) -> usize {
+        let (rows, positions) = (self.rows.capacity(), self.positions.capacity());
+        self.rows.shrink_to_fit();
+        self.positions.shrink_to_fit();
+        rows.saturating_sub(self.rows.capacity()) * std::mem::size_of::<Item>()
+            + positions.saturating_sub(self.positions.capacity())
+                * std::mem::size_of::<(Option<Key>, usize)>()
+    }
+
+    /// Find the row with the given sort key value
+    ///
+    /// Partitions of tables without a sort key hold at most one item.
+    fn position(&self, sort_key: Option<&AttributeValue>) -> Option<usize> {
+        let row_key = match sort_key {
+            Some(value) => Some(Key::from_value(value)?),
+            None => None,
+        };
+        self.positions.get(&row_key).copied()
     }
 
     fn query(&self, ast: Node) -> Result<Vec<HashMap<String, AttributeValue>>> {
         match ast {
-            Node::Binop { lhs, rhs, op } => match (lhs.as_ref(), rhs.as_ref(), op) {
-                (Node::Attribute(key), Node::Attribute(value), Operator::Eq) => Ok(self
+            Node::Binop { lhs, rhs, op } => match (lhs.as_ref(), literal_value(&rhs)) {
+                (Node::Attribute(key), Some(value)) => Ok(self
                     .rows
                     .iter()
                     .filter(|row| {
                         row.get(key.as_str())
-                            .map(|v| match v {
-                                serde_dynamo::AttributeValue::S(s) => value == s,
-                                _ => todo!(),
-                            })
+                            .and_then(|v| compare_values(v, &value))
+                            .map(|ordering| op.matches(ordering))
                             .unwrap_or(false)
                     })
-                    .cloned()
+                    .map(names::to_attributes)
                     .collect()),
-                (l, r, o) => todo!("lhs: {l:?}, rhs: {r:?}, op: {o:?}"),
+                _ => Err(TableError::NotImplemented(
+                    "sort key conditions not comparing the key with a value".to_string(),
+                )),
             },
-            _ => todo!("unhandled query for secondary: {ast:?}"),
-        }
-    }
-
-    fn get_by_pk_only(&self) -> Option<HashMap<String, AttributeValue>> {
-        self.rows.first().cloned()
-    }
-
-    fn get_item(
-        &self,
-        sort_key_name: &str,
-        sort_key_value: &str,
-    ) -> Option<HashMap<String, AttributeValue>> {
-        for row in &self.rows {
-            let sk_value = row.get(sort_key_name)?;
-            match sk_value {
-                serde_dynamo::AttributeValue::S(sort_key) => {
-                    if sort_key == sort_key_value {
-                        return Some(row.clone());
-                    }
+            Node::Between { key, lower, upper } => {
+                match (key.as_ref(), literal_value(&lower), literal_value(&upper)) {
+                    (Node::Attribute(key), Some(lower), Some(upper)) => Ok(self
+                        .rows
+                        .iter()
+                        .filter(|row| {
+                            row.get(key.as_str())
+                                .map(|v| {
+                                    matches!(
+                                        compare_values(v, &lower),
+                                        Some(Ordering::Greater | Ordering::Equal)
+                                    ) && matches!(
+                                        compare_values(v, &upper),
+                                        Some(Ordering::Less | Ordering::Equal)
+                                    )
+                                })
+                                .unwrap_or(false)
+                        })
+                        .map(names::to_attributes)
+                        .collect()),
+                    _ => Err(TableError::NotImplemented(
+                        "BETWEEN sort key conditions without value bounds".to_string(),
+                    )),
+                }
+            }
+            Node::FunctionCall { name, args } if name == "begins_with" => {
+                match (args.first(), args.get(1).and_then(literal_value)) {
+                    (Some(Node::Attribute(key)), Some(prefix)) => Ok(self
+                        .rows
+                        .iter()
+                        .filter(|row| {
+                            row.get(key.as_str())
+                                .map(|v| begins_with(v, &prefix))
+                                .unwrap_or(false)
+                        })
+                        .map(names::to_attributes)
+                        .collect()),
+                    _ => Err(TableError::NotImplemented(
+                        "begins_with sort key conditions without a value prefix".to_string(),
+                    )),
                 }
-                _ => todo!("{sk_value:?}"),
             }
+            Node::FunctionCall { name, .. } => Err(TableError::NotImplemented(format!(
+                "{name} sort key conditions"
+            ))),
+            _ => Err(TableError::NotImplemented(
+                "sort key conditions which are not comparisons".to_string(),
+            )),
         }
-
-        None
     }
 
     pub fn item_count(&self) -> usize {
@@ -320,9 +1818,8 @@ impl Partition {
 
 #[cfg(test)]
 mod tests {
-    use crate::types::AttributeType;
-
     use super::*;
+    use crate::types::{KeySchema, KeyType};
 
     fn init_logging() {
         let _ = tracing_subscriber::fmt::try_init();
@@ -346,6 +1843,7 @@ mod tests {
                         attribute_type: AttributeType::S,
                     },
                 ],
+                ..Default::default()
             },
         );
 
@@ -447,4 +1945,1034 @@ mod tests {
             assert_eq!(rows.into_iter().next().unwrap(), attributes);
         }
     }
+
+    #[test]
+    fn missing_partition_is_empty() {
+        init_logging();
+
+        let mut table = default_table();
+        insert_into_table!(table, "pk" => "abc", "sk" => "def");
+
+        let queries = &[
+            "pk = :missing",
+            "pk = :missing AND sk = :sk",
+            "pk = :missing AND sk > :sk",
+            "pk = :missing AND sk BETWEEN :sk AND :upper",
+            // values of the wrong type can never match a partition
+            "pk = :number AND sk = :sk",
+        ];
+        let expression_attribute_values: HashMap<_, _> = [
+            (":missing", AttributeValue::S("missing".to_string())),
+            (":number", AttributeValue::N("1".to_string())),
+            (":sk", AttributeValue::S("def".to_string())),
+            (":upper", AttributeValue::S("xyz".to_string())),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        for query in queries {
+            eprintln!("testing query {query}");
+            let rows = table
+                .query(query, &None, &Some(expression_attribute_values.clone()))
+                .unwrap();
+            assert!(rows.is_empty());
+        }
+
+        let key_conditions = [(
+            "pk".to_string(),
+            types::Condition {
+                comparison_operator: types::ComparisonOperator::Eq,
+                attribute_value_list: vec![AttributeValue::S("missing".to_string())],
+            },
+        )]
+        .into_iter()
+        .collect();
+        assert!(table
+            .query_key_conditions(&key_conditions, None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn key_condition_requires_partition_key_equality() {
+        init_logging();
+
+        let mut table = default_table();
+        let attributes = insert_into_table!(table, "pk" => "abc", "sk" => "def");
+        let values = Some(
+            [(":v".to_string(), AttributeValue::S("abc".to_string()))]
+                .into_iter()
+                .collect(),
+        );
+        let query = |expression: &str| table.query(expression, &None, &values);
+
+        // the conditions may be in either order
+        assert_eq!(query("sk > :v AND pk = :v").unwrap(), vec![attributes]);
+
+        for expression in ["sk = :v", "other = :v", "sk = :v AND sk = :v"] {
+            assert_eq!(
+                query(expression).unwrap_err().to_string(),
+                "Query condition missed key schema element: pk"
+            );
+        }
+        for expression in ["pk > :v", "pk BETWEEN :v AND :v", "pk = :v AND other = :v"] {
+            assert_eq!(
+                query(expression).unwrap_err().to_string(),
+                "Query key condition not supported",
+                "{expression}"
+            );
+        }
+        assert!(matches!(
+            query("pk = :v AND pk = :v"),
+            Err(TableError::InvalidKeyConditionExpression(_))
+        ));
+    }
+
+    #[test]
+    fn get_item_requires_full_key() {
+        init_logging();
+
+        let mut table = default_table();
+        let attributes =
+            insert_into_table!(table, "pk" => "abc", "sk" => "def", "value" => "great");
+        insert_into_table!(table, "pk" => "abc", "sk" => "ghi", "value" => "other");
+
+        let key = |values: &[(&str, &str)]| -> HashMap<String, AttributeValue> {
+            values
+                .iter()
+                .map(|(k, v)| (k.to_string(), AttributeValue::S(v.to_string())))
+                .collect()
+        };
+
+        assert_eq!(
+            table
+                .get_item(key(&[("pk", "abc"), ("sk", "def")]))
+                .unwrap(),
+            Some(attributes)
+        );
+        assert_eq!(
+            table
+                .get_item(key(&[("pk", "abc"), ("sk", "missing")]))
+                .unwrap(),
+            None
+        );
+
+        for invalid in [
+            key(&[("pk", "abc")]),
+            key(&[("pk", "abc"), ("sk", "def"), ("value", "great")]),
+            key(&[("pk", "abc"), ("other", "def")]),
+        ] {
+            assert!(matches!(
+                table.get_item(invalid),
+                Err(TableError::InvalidKey)
+            ));
+        }
+
+        let mut wrong_type = key(&[("pk", "abc")]);
+        wrong_type.insert("sk".to_string(), AttributeValue::N("1".to_string()));
+        assert!(matches!(
+            table.get_item(wrong_type),
+            Err(TableError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn insert_replaces_existing_item() {
+        init_logging();
+
+        let mut table = default_table();
+        insert_into_table!(table, "pk" => "abc", "sk" => "def", "value" => "first");
+        let attributes =
+            insert_into_table!(table, "pk" => "abc", "sk" => "def", "value" => "second");
+        insert_into_table!(table, "pk" => "abc", "sk" => "ghi", "value" => "third");
+
+        assert_eq!(table.len(), 2);
+
+        let key = [
+            ("pk".to_string(), AttributeValue::S("abc".to_string())),
+            ("sk".to_string(), AttributeValue::S("def".to_string())),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(table.get_item(key).unwrap(), Some(attributes));
+    }
+
+    #[test]
+    fn update_merges_throughput_settings() {
+        let mut table = default_table();
+
+        let update = |on_demand_throughput, warm_throughput| types::UpdateTableInput {
+            table_name: table.name.clone(),
+            attribute_definitions: None,
+            sse_specification: None,
+            on_demand_throughput,
+            warm_throughput,
+            global_secondary_index_updates: None,
+        };
+
+        let first = update(
+            Some(types::OnDemandThroughput {
+                max_read_request_units: Some(100),
+                max_write_request_units: Some(50),
+            }),
+            Some(types::WarmThroughput {
+                read_units_per_second: Some(12000),
+                write_units_per_second: None,
+            }),
+        );
+        let second = update(
+            Some(types::OnDemandThroughput {
+                max_read_request_units: None,
+                max_write_request_units: Some(75),
+            }),
+            None,
+        );
+        table.update(&first, chrono::Duration::zero()).unwrap();
+        table.update(&second, chrono::Duration::zero()).unwrap();
+
+        let description = table.description();
+        assert_eq!(
+            description.on_demand_throughput,
+            Some(types::OnDemandThroughput {
+                max_read_request_units: Some(100),
+                max_write_request_units: Some(75),
+            })
+        );
+        assert_eq!(
+            description.warm_throughput,
+            Some(types::TableWarmThroughputDescription {
+                read_units_per_second: Some(12000),
+                write_units_per_second: None,
+                status: "ACTIVE".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn added_index_keys_must_be_defined() {
+        init_logging();
+
+        let mut table = default_table();
+        let table_name = table.name.clone();
+        let add_index = |definitions: Vec<(&str, AttributeType)>| types::UpdateTableInput {
+            table_name: table_name.clone(),
+            attribute_definitions: Some(
+                definitions
+                    .into_iter()
+                    .map(|(name, attribute_type)| AttributeDefinition {
+                        attribute_name: name.to_string(),
+                        attribute_type,
+                    })
+                    .collect(),
+            ),
+            sse_specification: None,
+            on_demand_throughput: None,
+            warm_throughput: None,
+            global_secondary_index_updates: Some(vec![types::GlobalSecondaryIndexUpdate {
+                create: Some(types::GlobalSecondaryIndex {
+                    index_name: "by-gsi-pk".to_string(),
+                    key_schema: vec![
+                        KeySchema {
+                            attribute_name: "gsi_pk".to_string(),
+                            key_type: KeyType::HASH,
+                        },
+                        KeySchema {
+                            attribute_name: "sk".to_string(),
+                            key_type: KeyType::RANGE,
+                        },
+                    ],
+                    projection: Default::default(),
+                }),
+                delete: None,
+            }]),
+        };
+
+        // the table defines sk, but the request must define it again
+        let err = table
+            .update(
+                &add_index(vec![("gsi_pk", AttributeType::S)]),
+                chrono::Duration::zero(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "One or more parameter values were invalid: Some index key attributes are not \
+            defined in AttributeDefinitions. Keys: [sk], AttributeDefinitions: [gsi_pk]"
+        );
+
+        let err = table
+            .update(
+                &add_index(vec![("gsi_pk", AttributeType::S), ("sk", AttributeType::N)]),
+                chrono::Duration::zero(),
+            )
+            .unwrap_err();
+        assert!(matches!(err, TableError::InvalidParameter(_)));
+        assert!(table.global_secondary_indexes.is_empty());
+
+        table
+            .update(
+                &add_index(vec![("gsi_pk", AttributeType::S), ("sk", AttributeType::S)]),
+                chrono::Duration::zero(),
+            )
+            .unwrap();
+        let names: Vec<_> = table
+            .attribute_definitions
+            .iter()
+            .map(|definition| definition.attribute_name.as_str())
+            .collect();
+        assert_eq!(names, ["pk", "sk", "gsi_pk"]);
+        assert_eq!(table.global_secondary_indexes.len(), 1);
+    }
+
+    #[test]
+    fn added_index_is_backfilled() {
+        init_logging();
+
+        let mut table = default_table();
+        let item = |sk: &str| {
+            let mut item = HashMap::new();
+            item.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+            item.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+            item.insert("gsi_pk".to_string(), AttributeValue::S("x".to_string()));
+            item.insert(
+                "value".to_string(),
+                AttributeValue::S("ignored".to_string()),
+            );
+            item
+        };
+        table.insert(item("before")).unwrap();
+
+        let add_index = types::UpdateTableInput {
+            table_name: table.name.clone(),
+            attribute_definitions: Some(vec![AttributeDefinition {
+                attribute_name: "gsi_pk".to_string(),
+                attribute_type: AttributeType::S,
+            }]),
+            sse_specification: None,
+            on_demand_throughput: None,
+            warm_throughput: None,
+            global_secondary_index_updates: Some(vec![types::GlobalSecondaryIndexUpdate {
+                create: Some(types::GlobalSecondaryIndex {
+                    index_name: "by-gsi-pk".to_string(),
+                    key_schema: vec![KeySchema {
+                        attribute_name: "gsi_pk".to_string(),
+                        key_type: KeyType::HASH,
+                    }],
+                    projection: types::Projection {
+                        projection_type: Some("KEYS_ONLY".to_string()),
+                        non_key_attributes: None,
+                    },
+                }),
+                delete: None,
+            }]),
+        };
+        table
+            .update(&add_index, chrono::Duration::hours(1))
+            .unwrap();
+        table.insert(item("after")).unwrap();
+
+        let description = table.description().global_secondary_indexes.unwrap();
+        assert_eq!(description[0].index_status, "CREATING");
+        assert_eq!(description[0].backfilling, Some(true));
+
+        // only the item written after the index was created is visible during the backfill
+        let values = Some(
+            [(":v".to_string(), AttributeValue::S("x".to_string()))]
+                .into_iter()
+                .collect(),
+        );
+        let res = table
+            .query_index(
+                "by-gsi-pk",
+                "gsi_pk = :v",
+                &None,
+                &values,
+                &Deadline::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0]["sk"], AttributeValue::S("after".to_string()));
+
+        // once the backfill has completed all items are visible
+        let now = table.providers.clock.now();
+        table.global_secondary_indexes[0].start_backfill(now, chrono::Duration::zero());
+        let res = table
+            .query_index(
+                "by-gsi-pk",
+                "gsi_pk = :v",
+                &None,
+                &values,
+                &Deadline::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 2);
+        let description = table.description();
+        let index_description = &description.global_secondary_indexes.unwrap()[0];
+        assert_eq!(index_description.index_status, "ACTIVE");
+        assert_eq!(index_description.item_count, 2);
+        // only the keys are projected, so `value` is not counted
+        assert_eq!(
+            index_description.index_size_bytes,
+            (5 + 8 + 7) + (5 + 7 + 7)
+        );
+        assert_eq!(
+            description.table_size_bytes,
+            Some((5 + 8 + 7 + 12) + (5 + 7 + 7 + 12))
+        );
+
+        assert!(matches!(
+            table.query_index(
+                "missing",
+                "gsi_pk = :v",
+                &None,
+                &values,
+                &Deadline::default()
+            ),
+            Err(TableError::IndexNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn sparse_index() {
+        init_logging();
+
+        let mut table = Table::new(
+            Region::UsEast1,
+            crate::DEFAULT_ACCOUNT_ID,
+            TableOptions {
+                name: format!("table-{}", uuid::Uuid::new_v4()),
+                partition_key: "pk".to_string(),
+                sort_key: Some("sk".to_string()),
+                attribute_definitions: ["pk", "sk", "status", "updated"]
+                    .into_iter()
+                    .map(|name| AttributeDefinition {
+                        attribute_name: name.to_string(),
+                        attribute_type: AttributeType::S,
+                    })
+                    .collect(),
+                global_secondary_indexes: vec![types::GlobalSecondaryIndex {
+                    index_name: "active".to_string(),
+                    key_schema: vec![
+                        KeySchema {
+                            attribute_name: "status".to_string(),
+                            key_type: KeyType::HASH,
+                        },
+                        KeySchema {
+                            attribute_name: "updated".to_string(),
+                            key_type: KeyType::RANGE,
+                        },
+                    ],
+                    projection: types::Projection {
+                        projection_type: Some("KEYS_ONLY".to_string()),
+                        non_key_attributes: None,
+                    },
+                }],
+                ..Default::default()
+            },
+        );
+        insert_into_table!(table, "pk" => "a", "sk" => "1", "status" => "active", "updated" => "2023-01");
+        insert_into_table!(table, "pk" => "a", "sk" => "2", "status" => "active", "updated" => "2023-06");
+        // missing one or both of the index keys, so not indexed
+        insert_into_table!(table, "pk" => "a", "sk" => "3", "status" => "active");
+        insert_into_table!(table, "pk" => "a", "sk" => "4", "updated" => "2023-06");
+        insert_into_table!(table, "pk" => "a", "sk" => "5", "value" => "x");
+
+        let values = Some(
+            [
+                (":s".to_string(), AttributeValue::S("active".to_string())),
+                (":u".to_string(), AttributeValue::S("2023-03".to_string())),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let res = table
+            .query_index(
+                "active",
+                "#s = :s AND updated > :u",
+                &Some([("#s".to_string(), "status".to_string())].into()),
+                &values,
+                &Deadline::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0]["sk"], AttributeValue::S("2".to_string()));
+        assert_eq!(res[0].len(), 4);
+
+        // the legacy KeyConditions read the same items through the index's keys
+        let condition = |comparison_operator, value: &str| types::Condition {
+            comparison_operator,
+            attribute_value_list: vec![AttributeValue::S(value.to_string())],
+        };
+        let mut key_conditions: HashMap<_, _> = [
+            (
+                "status".to_string(),
+                condition(types::ComparisonOperator::Eq, "active"),
+            ),
+            (
+                "updated".to_string(),
+                condition(types::ComparisonOperator::Gt, "2023-03"),
+            ),
+        ]
+        .into();
+        assert_eq!(
+            table
+                .query_key_conditions(&key_conditions, Some("active"))
+                .unwrap(),
+            res
+        );
+        assert!(matches!(
+            table.query_key_conditions(&key_conditions, None),
+            Err(TableError::InvalidCondition(_))
+        ));
+        key_conditions.insert(
+            "value".to_string(),
+            condition(types::ComparisonOperator::Eq, "x"),
+        );
+        assert!(matches!(
+            table.query_key_conditions(&key_conditions, Some("active")),
+            Err(TableError::InvalidCondition(_))
+        ));
+        key_conditions.remove("status");
+        key_conditions.remove("value");
+        assert!(matches!(
+            table.query_key_conditions(&key_conditions, Some("active")),
+            Err(TableError::MissingKeyCondition(_))
+        ));
+
+        let description = table.description().global_secondary_indexes.unwrap();
+        assert_eq!(description[0].item_count, 2);
+
+        // index keys which are present must be valid
+        let mut item = insert_into_table!(table, "pk" => "b", "sk" => "1");
+        item.insert("status".to_string(), AttributeValue::N("1".to_string()));
+        assert_eq!(
+            table.insert(item.clone()).unwrap_err().to_string(),
+            "One or more parameter values were invalid: Type mismatch for Index Key status \
+            Expected: S Actual: N IndexName: active"
+        );
+        item.insert("status".to_string(), AttributeValue::S(String::new()));
+        assert!(matches!(
+            table.insert(item),
+            Err(TableError::EmptyIndexKey { .. })
+        ));
+    }
+
+    #[test]
+    fn schema() {
+        let mut table = Table::new(
+            Region::UsEast1,
+            crate::DEFAULT_ACCOUNT_ID,
+            TableOptions {
+                name: "orders".to_string(),
+                partition_key: "pk".to_string(),
+                sort_key: None,
+                attribute_definitions: vec![
+                    AttributeDefinition {
+                        attribute_name: "pk".to_string(),
+                        attribute_type: AttributeType::S,
+                    },
+                    AttributeDefinition {
+                        attribute_name: "total".to_string(),
+                        attribute_type: AttributeType::N,
+                    },
+                ],
+                global_secondary_indexes: vec![types::GlobalSecondaryIndex {
+                    index_name: "by-total".to_string(),
+                    key_schema: vec![KeySchema {
+                        attribute_name: "total".to_string(),
+                        key_type: KeyType::HASH,
+                    }],
+                    projection: Default::default(),
+                }],
+                ..Default::default()
+            },
+        );
+        table
+            .set_time_to_live(&types::TimeToLiveSpecification {
+                attribute_name: "expires".to_string(),
+                enabled: true,
+            })
+            .unwrap();
+
+        let schema = table.schema();
+        assert_eq!(schema.table_name, "orders");
+        assert_eq!(
+            schema.partition_key,
+            schema::KeyAttribute {
+                name: "pk".to_string(),
+                attribute_type: Some(AttributeType::S),
+            }
+        );
+        assert_eq!(schema.sort_key, None);
+        assert_eq!(schema.time_to_live_attribute.as_deref(), Some("expires"));
+        let index = schema.index("by-total").unwrap();
+        assert_eq!(index.partition_key.attribute_type, Some(AttributeType::N));
+        assert_eq!(index.key_schema().len(), 1);
+        assert!(schema.index("missing").is_none());
+
+        // descriptions are built from the schema
+        let description = table.description();
+        assert_eq!(
+            description.key_schema.unwrap()[0].attribute_name,
+            schema.key_schema()[0].attribute_name
+        );
+        assert_eq!(description.attribute_definitions.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn restore_to_point_in_time() {
+        init_logging();
+
+        let mut table = default_table();
+        insert_into_table!(table, "pk" => "abc", "sk" => "before", "value" => "a");
+
+        assert!(matches!(
+            table.restore_to_point_in_time("restored", Utc::now()),
+            Err(TableError::PointInTimeRecoveryUnavailable(_))
+        ));
+
+        table.set_point_in_time_recovery(true);
+        let first = insert_into_table!(table, "pk" => "abc", "sk" => "def", "value" => "first");
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let restore_time = Utc::now();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        insert_into_table!(table, "pk" => "abc", "sk" => "def", "value" => "second");
+        insert_into_table!(table, "pk" => "abc", "sk" => "ghi", "value" => "third");
+
+        let restored = table
+            .restore_to_point_in_time("restored", restore_time)
+            .unwrap();
+        assert_eq!(restored.name, "restored");
+        assert_eq!(restored.len(), 2);
+
+        let key = [
+            ("pk".to_string(), AttributeValue::S("abc".to_string())),
+            ("sk".to_string(), AttributeValue::S("def".to_string())),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(restored.get_item(key).unwrap(), Some(first));
+
+        assert!(matches!(
+            table.restore_to_point_in_time("restored", restore_time - chrono::Duration::days(1)),
+            Err(TableError::InvalidRestoreTime)
+        ));
+    }
+
+    #[test]
+    fn numeric_keys_compare_as_numbers() {
+        init_logging();
+
+        let mut table = Table::new(
+            Region::UsEast1,
+            crate::DEFAULT_ACCOUNT_ID,
+            TableOptions {
+                name: format!("table-{}", uuid::Uuid::new_v4()),
+                partition_key: "pk".to_string(),
+                sort_key: Some("sk".to_string()),
+                attribute_definitions: vec![
+                    AttributeDefinition {
+                        attribute_name: "pk".to_string(),
+                        attribute_type: AttributeType::N,
+                    },
+                    AttributeDefinition {
+                        attribute_name: "sk".to_string(),
+                        attribute_type: AttributeType::N,
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+
+        for sk in ["2", "10"] {
+            let mut attributes = HashMap::new();
+            attributes.insert("pk".to_string(), AttributeValue::N("1".to_string()));
+            attributes.insert("sk".to_string(), AttributeValue::N(sk.to_string()));
+            table.insert(attributes).unwrap();
+        }
+
+        let expression_attribute_values = {
+            let mut res = HashMap::new();
+            res.insert(":pk".to_string(), AttributeValue::N("1.0".to_string()));
+            res.insert(":sk".to_string(), AttributeValue::N("9".to_string()));
+            res
+        };
+
+        // lexicographically "10" < "9", so this would return both rows if compared as strings
+        let rows = table
+            .query(
+                "pk = :pk AND sk < :sk",
+                &None,
+                &Some(expression_attribute_values),
+            )
+            .unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["sk"], AttributeValue::N("2".to_string()));
+    }
+
+    #[test]
+    fn shuffled_scan_order() {
+        let mut table = default_table();
+        for pk in 0..20 {
+            for sk in ["a", "b"] {
+                insert_into_table!(table, "pk" => pk, "sk" => sk);
+            }
+        }
+        let name = |item: &Item| match (&item["pk"], &item["sk"]) {
+            (AttributeValue::S(pk), AttributeValue::S(sk)) => format!("{pk}{sk}"),
+            _ => unreachable!(),
+        };
+        let order = |table: &Table, seed| -> Vec<String> {
+            let snapshot = table.snapshot();
+            table
+                .scan_order(Some(seed))
+                .paginate(snapshot.items().collect(), None, None, usize::MAX, true)
+                .items
+                .into_iter()
+                .map(name)
+                .collect()
+        };
+
+        let shuffled = order(&table, 1);
+        assert_eq!(shuffled.len(), 40);
+        assert_eq!(order(&table, 1), shuffled);
+        assert_ne!(order(&table, 2), shuffled);
+        // partitions are shuffled but the items within them stay in sort key order
+        for pair in shuffled.chunks(2) {
+            assert!(pair[0].ends_with('a') && pair[1].ends_with('b'));
+        }
+
+        // the order depends on the partition keys, not on the order they were written in
+        let mut copy = default_table();
+        copy.name = table.name.clone();
+        for pk in (0..20).rev() {
+            for sk in ["b", "a"] {
+                insert_into_table!(copy, "pk" => pk, "sk" => sk);
+            }
+        }
+        assert_eq!(order(&copy, 1), shuffled);
+
+        // paging through the scan gives the same order
+        let snapshot = table.snapshot();
+        let scan_order = table.scan_order(Some(1));
+        let mut paged = Vec::new();
+        let mut start_key = None;
+        loop {
+            let page = scan_order.paginate(
+                snapshot.items().collect(),
+                start_key.as_ref(),
+                Some(3),
+                usize::MAX,
+                true,
+            );
+            paged.extend(page.items.into_iter().map(name));
+            match page.last_evaluated_key {
+                Some(key) => start_key = Some(key),
+                None => break,
+            }
+        }
+        assert_eq!(paged, shuffled);
+    }
+
+    #[test]
+    fn snapshot_is_unaffected_by_later_writes() {
+        init_logging();
+
+        let mut table = default_table();
+        let item = |sk: &str, value: &str| {
+            let mut item = HashMap::new();
+            item.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+            item.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+            item.insert("value".to_string(), AttributeValue::S(value.to_string()));
+            item
+        };
+        table.insert(item("a", "old")).unwrap();
+
+        let snapshot = table.snapshot();
+        table.insert(item("a", "new")).unwrap();
+        table.insert(item("b", "new")).unwrap();
+
+        let items: Vec<_> = snapshot.items().collect();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["value"], AttributeValue::S("old".to_string()));
+        assert_eq!(table.snapshot().items().count(), 2);
+    }
+
+    #[test]
+    fn iterate_items() {
+        let mut table = default_table();
+        let item = |pk: &str, sk: &str| {
+            let mut item = HashMap::new();
+            item.insert("pk".to_string(), AttributeValue::S(pk.to_string()));
+            item.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+            item
+        };
+        table.insert(item("abc", "a")).unwrap();
+        table.insert(item("abc", "b")).unwrap();
+        table.insert(item("def", "a")).unwrap();
+
+        assert_eq!(table.iter().count(), 3);
+        let partition: Vec<_> = table
+            .iter_partition(&AttributeValue::S("abc".to_string()))
+            .collect();
+        assert_eq!(partition.len(), 2);
+        assert!(partition
+            .iter()
+            .all(|item| item["pk"] == AttributeValue::S("abc".to_string())));
+        assert_eq!(
+            table
+                .iter_partition(&AttributeValue::S("missing".to_string()))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn partition_rows_are_keyed_by_sort_key() {
+        let mut names = names::Interner::default();
+        let mut row = |sk: &str, value: &str| {
+            let mut item = HashMap::new();
+            item.insert("sk".to_string(), AttributeValue::N(sk.to_string()));
+            item.insert("value".to_string(), AttributeValue::S(value.to_string()));
+            names.intern_item(item)
+        };
+
+        let mut partition = Partition::default();
+        for i in 0..1000 {
+            partition.insert(row(&i.to_string(), "old"), Some("sk"));
+        }
+        // numerically equal sort keys refer to the same row
+        partition.insert(row("10.0", "new"), Some("sk"));
+        assert_eq!(partition.item_count(), 1000);
+
+        let idx = partition
+            .position(Some(&AttributeValue::N("10".to_string())))
+            .unwrap();
+        assert_eq!(
+            partition.rows[idx]["value"],
+            AttributeValue::S("new".to_string())
+        );
+        assert!(partition
+            .position(Some(&AttributeValue::N("1000".to_string())))
+            .is_none());
+    }
+
+    #[test]
+    fn between_sort_keys() {
+        init_logging();
+
+        let mut table = default_table();
+        for sk in ["a", "b", "c", "d"] {
+            insert_into_table!(table, "pk" => "abc", "sk" => sk);
+        }
+
+        let values = |lower: AttributeValue, upper: AttributeValue| {
+            let mut values = HashMap::new();
+            values.insert(":pk".to_string(), AttributeValue::S("abc".to_string()));
+            values.insert(":lower".to_string(), lower);
+            values.insert(":upper".to_string(), upper);
+            Some(values)
+        };
+        let s = |value: &str| AttributeValue::S(value.to_string());
+        let n = |value: &str| AttributeValue::N(value.to_string());
+        let expression = "pk = :pk AND sk BETWEEN :lower AND :upper";
+
+        let mut rows = table
+            .query(expression, &None, &values(s("b"), s("c")))
+            .unwrap();
+        rows.sort_by(|a, b| compare_values(&a["sk"], &b["sk"]).unwrap());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["sk"], s("b"));
+        assert_eq!(rows[1]["sk"], s("c"));
+
+        let err = table
+            .query(expression, &None, &values(s("a"), n("1")))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid KeyConditionExpression: The BETWEEN operator requires same data type for lower and upper bounds; lower bound operand: AttributeValue: {S:a}, upper bound operand: AttributeValue: {N:1}"
+        );
+
+        let err = table
+            .query(expression, &None, &values(n("1"), n("2")))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "One or more parameter values were invalid: Condition parameter type does not match schema type"
+        );
+
+        let err = table
+            .query(expression, &None, &values(s("c"), s("b")))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid KeyConditionExpression: The BETWEEN operator requires upper bound to be greater than or equal to lower bound; lower bound operand: AttributeValue: {S:c}, upper bound operand: AttributeValue: {S:b}"
+        );
+    }
+
+    #[test]
+    fn begins_with_sort_keys() {
+        init_logging();
+
+        let mut table = default_table();
+        for sk in ["order#1", "order#2", "orders", "invoice#1"] {
+            insert_into_table!(table, "pk" => "abc", "sk" => sk);
+        }
+        insert_into_table!(table, "pk" => "def", "sk" => "order#3");
+
+        let values = |prefix: AttributeValue| {
+            let mut values = HashMap::new();
+            values.insert(":pk".to_string(), AttributeValue::S("abc".to_string()));
+            values.insert(":prefix".to_string(), prefix);
+            Some(values)
+        };
+        let expression = "pk = :pk AND begins_with(sk, :prefix)";
+
+        let mut rows = table
+            .query(
+                expression,
+                &None,
+                &values(AttributeValue::S("order#".to_string())),
+            )
+            .unwrap();
+        rows.sort_by(|a, b| compare_values(&a["sk"], &b["sk"]).unwrap());
+        let sort_keys: Vec<_> = rows.iter().map(|row| row["sk"].clone()).collect();
+        assert_eq!(
+            sort_keys,
+            [
+                AttributeValue::S("order#1".to_string()),
+                AttributeValue::S("order#2".to_string())
+            ]
+        );
+
+        let err = table
+            .query(
+                expression,
+                &None,
+                &values(AttributeValue::N("1".to_string())),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid KeyConditionExpression: Incorrect operand type for operator or function; operator or function: begins_with, operand type: N"
+        );
+
+        let err = table
+            .query(expression, &None, &values(AttributeValue::B(vec![1])))
+            .unwrap_err();
+        assert!(matches!(err, TableError::InvalidCondition(_)));
+    }
+
+    #[test]
+    fn expire_items() {
+        let mut table = default_table();
+        let now = Utc::now();
+        let item = |sk: &str, expires: AttributeValue| {
+            let mut item = HashMap::new();
+            item.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+            item.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+            item.insert("expires".to_string(), expires);
+            item
+        };
+        table
+            .insert(item(
+                "a",
+                AttributeValue::N((now.timestamp() - 10).to_string()),
+            ))
+            .unwrap();
+        table
+            .insert(item(
+                "b",
+                AttributeValue::N((now.timestamp() + 60).to_string()),
+            ))
+            .unwrap();
+        table
+            .insert(item(
+                "c",
+                AttributeValue::S((now.timestamp() - 10).to_string()),
+            ))
+            .unwrap();
+
+        // nothing expires until time to live is enabled
+        assert_eq!(table.expire_items(now), 0);
+
+        let specification = types::TimeToLiveSpecification {
+            attribute_name: "expires".to_string(),
+            enabled: true,
+        };
+        table.set_time_to_live(&specification).unwrap();
+        assert!(matches!(
+            table.set_time_to_live(&specification),
+            Err(TableError::InvalidTimeToLive(_))
+        ));
+
+        let events = Events::default();
+        table.set_events(events.clone());
+        let mut receiver = events.subscribe();
+        assert_eq!(table.expire_items(now), 1);
+        match receiver.try_recv().unwrap() {
+            TableEvent::ItemDeleted {
+                old_item, reason, ..
+            } => {
+                assert_eq!(old_item["sk"], AttributeValue::S("a".to_string()));
+                assert_eq!(reason, DeletionReason::Expired);
+            }
+            event => panic!("unexpected event {event:?}"),
+        }
+        let mut remaining: Vec<_> = table.iter().map(|item| item["sk"].clone()).collect();
+        remaining.sort_by_key(|sk| format!("{sk:?}"));
+        assert_eq!(
+            remaining,
+            vec![
+                AttributeValue::S("b".to_string()),
+                AttributeValue::S("c".to_string())
+            ]
+        );
+        // the moved row can still be replaced by key
+        table
+            .insert(item("c", AttributeValue::N(now.timestamp().to_string())))
+            .unwrap();
+        assert_eq!(table.iter().count(), 2);
+        assert_eq!(table.expire_items(now + chrono::Duration::seconds(120)), 2);
+        assert_eq!(table.iter().count(), 0);
+    }
+
+    #[test]
+    fn compact_after_removing_items() {
+        let mut table = default_table();
+        let now = Utc::now();
+        for i in 0..100 {
+            let mut item = HashMap::new();
+            item.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+            item.insert("sk".to_string(), AttributeValue::S(i.to_string()));
+            if i > 0 {
+                item.insert(
+                    "expires".to_string(),
+                    AttributeValue::N((now.timestamp() - 10).to_string()),
+                );
+            }
+            table.insert(item).unwrap();
+        }
+        table
+            .set_time_to_live(&types::TimeToLiveSpecification {
+                attribute_name: "expires".to_string(),
+                enabled: true,
+            })
+            .unwrap();
+        assert_eq!(table.expire_items(now), 99);
+
+        // partitions shared with a snapshot are left alone
+        let snapshot = table.snapshot();
+        assert_eq!(table.compact(), 0);
+        drop(snapshot);
+
+        assert!(table.compact() > 0);
+        assert_eq!(table.compact(), 0);
+        assert_eq!(table.iter().count(), 1);
+        let partition = table
+            .partition_for(&AttributeValue::S("abc".to_string()))
+            .unwrap();
+        assert_eq!(partition.item_count(), 1);
+    }
 }
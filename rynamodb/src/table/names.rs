@@ -0,0 +1,124 @@
+//! Interned attribute names
+//!
+//! Most items in a table share the same few attribute names, so rather than each item owning a
+//! copy of every name, a table interns the names of the items written to it and its items share
+//! one allocation per name. Only the names of top level attributes are interned, as the names in
+//! nested maps belong to the [`AttributeValue`]s.
+
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    ops::Deref,
+    sync::Arc,
+};
+
+use serde::{Serialize, Serializer};
+use serde_dynamo::AttributeValue;
+
+/// Item stored in a table, keyed by interned attribute names
+pub type Item = HashMap<Name, AttributeValue>;
+
+/// Attribute names of items, whether owned by a request or interned by a table, so that
+/// functions reading items can accept either
+pub trait AttributeName: Borrow<str> + Eq + Hash {}
+
+impl<T: Borrow<str> + Eq + Hash> AttributeName for T {}
+
+/// Attribute name shared by the items of a table
+///
+/// Names are compared and hashed as strings, so items can be looked up by `&str`.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name(Arc<str>);
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Name {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Attribute names seen by a table
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    names: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, name: &str) -> Name {
+        if let Some(name) = self.names.get(name) {
+            return Name(Arc::clone(name));
+        }
+        let name: Arc<str> = name.into();
+        self.names.insert(Arc::clone(&name));
+        Name(name)
+    }
+
+    /// Convert an item from a request into one stored by the table
+    pub fn intern_item(&mut self, item: HashMap<String, AttributeValue>) -> Item {
+        item.into_iter()
+            .map(|(name, value)| (self.intern(&name), value))
+            .collect()
+    }
+}
+
+/// Copy a stored item into the form it is returned to clients in
+pub fn to_attributes(item: &Item) -> HashMap<String, AttributeValue> {
+    item.iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_shared() {
+        let mut interner = Interner::default();
+        let item = |value: &str| -> HashMap<String, AttributeValue> {
+            [
+                ("pk".to_string(), AttributeValue::S(value.to_string())),
+                ("value".to_string(), AttributeValue::N("1".to_string())),
+            ]
+            .into_iter()
+            .collect()
+        };
+        let a = interner.intern_item(item("a"));
+        let b = interner.intern_item(item("b"));
+        assert_eq!(interner.names.len(), 2);
+
+        let (a_name, _) = a.get_key_value("pk").unwrap();
+        let (b_name, _) = b.get_key_value("pk").unwrap();
+        assert!(Arc::ptr_eq(&a_name.0, &b_name.0));
+
+        assert_eq!(b["pk"], AttributeValue::S("b".to_string()));
+        assert_eq!(to_attributes(&b), item("b"));
+    }
+}
@@ -0,0 +1,249 @@
+//! Canonical form of stored numbers
+//!
+//! DynamoDB stores numbers by value rather than as the strings they were written as, so they are
+//! read back without leading or trailing zeros, exponents or a `+` sign: `{"N": "01.50"}` is
+//! returned as `{"N": "1.5"}` and `{"N": "1E+2"}` as `{"N": "100"}`. Clients comparing the
+//! returned strings depend on this.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use serde_dynamo::AttributeValue;
+
+/// Largest number of zeros added when writing out an exponent. DynamoDB's exponents range from
+/// -130 to 125, so this only guards against numbers which were never valid.
+const MAX_EXPONENT: i64 = 200;
+
+/// Rewrite every number in an item in canonical form, including those in sets, lists and maps
+pub fn canonicalise_item(item: &mut HashMap<String, AttributeValue>) {
+    item.values_mut().for_each(canonicalise_value);
+}
+
+fn canonicalise_value(value: &mut AttributeValue) {
+    match value {
+        AttributeValue::N(n) => canonicalise_string(n),
+        AttributeValue::Ns(values) => values.iter_mut().for_each(canonicalise_string),
+        AttributeValue::L(values) => values.iter_mut().for_each(canonicalise_value),
+        AttributeValue::M(map) => map.values_mut().for_each(canonicalise_value),
+        _ => {}
+    }
+}
+
+fn canonicalise_string(n: &mut String) {
+    if let Some(canonical) = canonical(n) {
+        *n = canonical;
+    }
+}
+
+/// The canonical form of a number, or `None` if it is not a decimal number
+pub fn canonical(n: &str) -> Option<String> {
+    let (negative, unsigned) = match n.as_bytes().first()? {
+        b'-' => (true, &n[1..]),
+        b'+' => (false, &n[1..]),
+        _ => (false, n),
+    };
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(position) => (
+            &unsigned[..position],
+            unsigned[position + 1..].parse::<i64>().ok()?,
+        ),
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if integer.is_empty() && fraction.is_empty()
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // position of the decimal point within the significant digits
+    let digits = format!("{integer}{fraction}");
+    let leading_zeros = digits.len() - digits.trim_start_matches('0').len();
+    let digits = digits.trim_matches('0');
+    if digits.is_empty() {
+        return Some("0".to_string());
+    }
+    let point = integer.len() as i64 - leading_zeros as i64 + exponent;
+    if !(-MAX_EXPONENT..=MAX_EXPONENT).contains(&point) {
+        return None;
+    }
+
+    let mut canonical = String::with_capacity(digits.len() + 2);
+    if negative {
+        canonical.push('-');
+    }
+    let length = digits.len() as i64;
+    if point <= 0 {
+        canonical.push_str("0.");
+        canonical.extend(std::iter::repeat_n('0', -point as usize));
+        canonical.push_str(digits);
+    } else if point >= length {
+        canonical.push_str(digits);
+        canonical.extend(std::iter::repeat_n('0', (point - length) as usize));
+    } else {
+        let (integer, fraction) = digits.split_at(point as usize);
+        canonical.push_str(integer);
+        canonical.push('.');
+        canonical.push_str(fraction);
+    }
+    Some(canonical)
+}
+
+/// Compare two numbers in canonical form by value
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (a_negative, a_magnitude) = magnitude(a);
+    let (b_negative, b_magnitude) = magnitude(b);
+    match (a_negative, b_negative) {
+        (false, false) => a_magnitude.cmp(&b_magnitude),
+        (true, true) => b_magnitude.cmp(&a_magnitude),
+        (negative, _) => {
+            if negative {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }
+    }
+}
+
+/// Sign of a canonical number, and its magnitude as the position of the decimal point and the
+/// significant digits, which order numbers of the same sign when compared in turn
+///
+/// Zero is given the lowest position, below any number DynamoDB can store.
+fn magnitude(canonical: &str) -> (bool, (i64, String)) {
+    let (negative, unsigned) = match canonical.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, canonical),
+    };
+    if unsigned == "0" {
+        return (false, (i64::MIN, String::new()));
+    }
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let magnitude = if integer == "0" {
+        let digits = fraction.trim_start_matches('0');
+        (
+            digits.len() as i64 - fraction.len() as i64,
+            digits.to_string(),
+        )
+    } else {
+        let digits = format!("{integer}{fraction}");
+        (
+            integer.len() as i64,
+            digits.trim_end_matches('0').to_string(),
+        )
+    };
+    (negative, magnitude)
+}
+
+/// Exact sum of two numbers, in canonical form, or `None` if either is not a number or they
+/// need more digits than fit in 128 bits
+pub fn add(a: &str, b: &str) -> Option<String> {
+    let (a, a_places) = decimal(a)?;
+    let (b, b_places) = decimal(b)?;
+    let places = a_places.max(b_places);
+    let a = a.checked_mul(10i128.checked_pow(places - a_places)?)?;
+    let b = b.checked_mul(10i128.checked_pow(places - b_places)?)?;
+    canonical(&format!("{}E-{places}", a.checked_add(b)?))
+}
+
+/// Exact difference of two numbers, see [`add`]
+pub fn subtract(a: &str, b: &str) -> Option<String> {
+    let (b, b_places) = decimal(b)?;
+    add(a, &format!("{}E-{b_places}", b.checked_neg()?))
+}
+
+/// A number as an integer and the number of decimal places it is shifted by
+fn decimal(n: &str) -> Option<(i128, u32)> {
+    let canonical = canonical(n)?;
+    let (integer, fraction) = canonical
+        .split_once('.')
+        .unwrap_or((canonical.as_str(), ""));
+    let digits = format!("{integer}{fraction}").parse().ok()?;
+    Some((digits, fraction.len() as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_numbers() {
+        for (n, expected) in [
+            ("1", "1"),
+            ("+1", "1"),
+            ("-1", "-1"),
+            ("01.50", "1.5"),
+            ("1.0", "1"),
+            ("-0", "0"),
+            ("0.000", "0"),
+            (".5", "0.5"),
+            ("5.", "5"),
+            ("0.0012", "0.0012"),
+            ("1E+2", "100"),
+            ("1.5e1", "15"),
+            ("123e-5", "0.00123"),
+            ("-12.5E-1", "-1.25"),
+            ("1000", "1000"),
+        ] {
+            assert_eq!(canonical(n).as_deref(), Some(expected), "{n}");
+        }
+        for n in ["", "-", ".", "abc", "1e", "1.2.3", "0x10", "1e1000"] {
+            assert_eq!(canonical(n), None, "{n}");
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        for (a, b, sum, difference) in [
+            ("1", "2", "3", "-1"),
+            ("1.5", "0.25", "1.75", "1.25"),
+            ("-0.5", "0.5", "0", "-1"),
+            ("1E+2", "1e-2", "100.01", "99.99"),
+            ("10", "10", "20", "0"),
+        ] {
+            assert_eq!(add(a, b).as_deref(), Some(sum), "{a} + {b}");
+            assert_eq!(subtract(a, b).as_deref(), Some(difference), "{a} - {b}");
+        }
+        assert_eq!(add("1", "x"), None);
+        assert_eq!(add("1E+100", "1"), None);
+    }
+
+    #[test]
+    fn nested_numbers() {
+        let mut item: HashMap<String, AttributeValue> = [
+            ("n".to_string(), AttributeValue::N("2.50".to_string())),
+            (
+                "ns".to_string(),
+                AttributeValue::Ns(vec!["1e1".to_string(), "007".to_string()]),
+            ),
+            (
+                "l".to_string(),
+                AttributeValue::L(vec![AttributeValue::M(
+                    [("n".to_string(), AttributeValue::N("+3".to_string()))].into(),
+                )]),
+            ),
+            ("s".to_string(), AttributeValue::S("1.0".to_string())),
+        ]
+        .into();
+        canonicalise_item(&mut item);
+
+        let expected: HashMap<String, AttributeValue> = [
+            ("n".to_string(), AttributeValue::N("2.5".to_string())),
+            (
+                "ns".to_string(),
+                AttributeValue::Ns(vec!["10".to_string(), "7".to_string()]),
+            ),
+            (
+                "l".to_string(),
+                AttributeValue::L(vec![AttributeValue::M(
+                    [("n".to_string(), AttributeValue::N("3".to_string()))].into(),
+                )]),
+            ),
+            ("s".to_string(), AttributeValue::S("1.0".to_string())),
+        ]
+        .into();
+        assert_eq!(item, expected);
+    }
+}
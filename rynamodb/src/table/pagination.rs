@@ -0,0 +1,293 @@
+//! Key ordered pagination of query and scan results
+//!
+//! Pages are delimited by the key of the last item returned rather than an offset, so items
+//! written or removed between page fetches never cause an item to be returned twice, and items
+//! which existed for the whole page sequence are never skipped.
+
+use std::{borrow::Borrow, cmp::Ordering, collections::HashMap};
+
+use serde_dynamo::AttributeValue;
+
+use super::{compare_values, key::Key, names::AttributeName, size, Result, TableError};
+
+type Item<K = String> = HashMap<K, AttributeValue>;
+
+/// How the items of a query are ordered, and which attributes identify an item's position
+pub struct KeyOrder {
+    /// attributes included in `LastEvaluatedKey`
+    key_attributes: Vec<String>,
+    /// attributes the items are sorted by, most significant first
+    sort_attributes: Vec<String>,
+    /// partition key attribute and seed, if partitions are shuffled before the items are sorted
+    shuffle: Option<(String, u64)>,
+}
+
+/// Random page sizes for queries and scans, to flush out clients which assume every page but the last is
+/// full. DynamoDB also ends pages early, once they reach a megabyte.
+#[derive(Debug, Clone, Copy)]
+pub struct PageJitter {
+    pub min_page_size: usize,
+    pub max_page_size: usize,
+}
+
+impl PageJitter {
+    /// Number of items to read for the next page, never more than the request's `Limit`
+    pub fn page_size(&self, limit: Option<usize>) -> usize {
+        let max = limit.map_or(self.max_page_size, |limit| limit.min(self.max_page_size));
+        let max = max.max(1);
+        fastrand::usize(self.min_page_size.clamp(1, max)..=max)
+    }
+}
+
+pub struct Page<T = Item> {
+    pub items: Vec<T>,
+    pub last_evaluated_key: Option<Item>,
+}
+
+impl KeyOrder {
+    pub fn new(key_attributes: Vec<String>, sort_attributes: Vec<String>) -> Self {
+        Self {
+            key_attributes,
+            sort_attributes,
+            shuffle: None,
+        }
+    }
+
+    /// Order the partitions by a hash of their key and `seed`, rather than by key, before
+    /// sorting the items of each partition
+    pub fn shuffled(self, partition_key: String, seed: u64) -> Self {
+        Self {
+            shuffle: Some((partition_key, seed)),
+            ..self
+        }
+    }
+
+    /// Check that an `ExclusiveStartKey` is a key of this order
+    ///
+    /// Keys returned as `LastEvaluatedKey` are wrapped in signed tokens, see
+    /// [`crate::page_tokens`], but clients can also build start keys themselves, as DynamoDB
+    /// allows. Those are validated like DynamoDB does, so that clients cannot rely on keys which
+    /// AWS would reject.
+    pub fn validate_start_key(&self, start_key: &Item) -> Result<()> {
+        let matches_schema = start_key.len() == self.key_attributes.len()
+            && self
+                .key_attributes
+                .iter()
+                .all(|name| start_key.get(name).and_then(Key::from_value).is_some());
+        if matches_schema {
+            Ok(())
+        } else {
+            Err(TableError::InvalidStartKey(
+                "The provided key element does not match the schema".to_string(),
+            ))
+        }
+    }
+
+    fn compare<A: AttributeName, B: AttributeName>(&self, a: &Item<A>, b: &Item<B>) -> Ordering {
+        let sorted = || {
+            self.sort_attributes
+                .iter()
+                .map(|name| match (a.get(name.as_str()), b.get(name.as_str())) {
+                    (Some(a), Some(b)) => compare_values(a, b).unwrap_or(Ordering::Equal),
+                    (a, b) => a.is_some().cmp(&b.is_some()),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        };
+        self.shuffle_rank(a)
+            .cmp(&self.shuffle_rank(b))
+            .then_with(sorted)
+    }
+
+    fn shuffle_rank<K: AttributeName>(&self, item: &Item<K>) -> Option<u64> {
+        let (partition_key, seed) = self.shuffle.as_ref()?;
+        let key = item.get(partition_key.as_str()).and_then(Key::from_value)?;
+        Some(mix(fnv1a(key.as_bytes()) ^ seed))
+    }
+
+    fn key_of<K: AttributeName>(&self, item: &Item<K>) -> Item {
+        self.key_attributes
+            .iter()
+            .filter_map(|name| Some((name.clone(), item.get(name.as_str())?.clone())))
+            .collect()
+    }
+
+    /// Select the page of `items` following `exclusive_start_key`
+    ///
+    /// `limit` is the number of items read, so the page may be shorter once filters are
+    /// applied. Like DynamoDB, a `LastEvaluatedKey` is returned whenever the limit is reached,
+    /// even if there are no more items. The page also ends with the item which takes the items
+    /// read to `max_bytes`.
+    pub fn paginate<K: AttributeName, T: Borrow<Item<K>>>(
+        &self,
+        mut items: Vec<T>,
+        exclusive_start_key: Option<&Item>,
+        limit: Option<usize>,
+        max_bytes: usize,
+        forward: bool,
+    ) -> Page<T> {
+        items.sort_by(|a, b| self.compare(a.borrow(), b.borrow()));
+        if !forward {
+            items.reverse();
+        }
+
+        let after_start = |item: &T| match exclusive_start_key {
+            Some(start) => {
+                let ordering = self.compare(item.borrow(), start);
+                if forward {
+                    ordering.is_gt()
+                } else {
+                    ordering.is_lt()
+                }
+            }
+            None => true,
+        };
+        let mut items: Vec<_> = items.into_iter().filter(after_start).collect();
+
+        let mut page_bytes = 0;
+        let full = items.iter().position(|item| {
+            page_bytes += size::item_size(item.borrow());
+            page_bytes >= max_bytes
+        });
+        let page_items = limit.into_iter().chain(full.map(|full| full + 1)).min();
+        let last_evaluated_key = match page_items {
+            Some(page_items) if items.len() >= page_items => {
+                items.truncate(page_items);
+                items.last().map(|item| self.key_of(item.borrow()))
+            }
+            _ => None,
+        };
+
+        Page {
+            items,
+            last_evaluated_key,
+        }
+    }
+}
+
+/// FNV-1a, as the standard library's hashes may change between releases
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// similar keys have similar FNV hashes, so they are mixed with the finaliser of MurmurHash3 to
+// spread them over the whole order
+fn mix(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(sk: &str) -> Item {
+        let mut item = HashMap::new();
+        item.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+        item.insert("sk".to_string(), AttributeValue::N(sk.to_string()));
+        item
+    }
+
+    fn order() -> KeyOrder {
+        KeyOrder::new(
+            vec!["pk".to_string(), "sk".to_string()],
+            vec!["sk".to_string()],
+        )
+    }
+
+    #[test]
+    fn jittered_page_sizes() {
+        let jitter = PageJitter {
+            min_page_size: 2,
+            max_page_size: 5,
+        };
+        let sizes: Vec<_> = (0..100).map(|_| jitter.page_size(None)).collect();
+        assert!(sizes.iter().all(|size| (2..=5).contains(size)));
+        assert!(sizes.iter().any(|size| *size != sizes[0]));
+        assert!((0..100).all(|_| jitter.page_size(Some(3)) <= 3));
+        assert_eq!(jitter.page_size(Some(1)), 1);
+    }
+
+    fn sort_keys(page: &Page) -> Vec<&AttributeValue> {
+        page.items.iter().map(|item| &item["sk"]).collect()
+    }
+
+    #[test]
+    fn pages_follow_key_order() {
+        let items = vec![item("10"), item("2"), item("1")];
+
+        let page = order().paginate(items.clone(), None, Some(2), usize::MAX, true);
+        assert_eq!(
+            sort_keys(&page),
+            vec![
+                &AttributeValue::N("1".to_string()),
+                &AttributeValue::N("2".to_string())
+            ]
+        );
+        assert_eq!(page.last_evaluated_key, Some(item("2")));
+
+        let page = order().paginate(
+            items,
+            page.last_evaluated_key.as_ref(),
+            Some(2),
+            usize::MAX,
+            true,
+        );
+        assert_eq!(sort_keys(&page), vec![&AttributeValue::N("10".to_string())]);
+        assert_eq!(page.last_evaluated_key, None);
+    }
+
+    #[test]
+    fn backwards() {
+        let items = vec![item("1"), item("2"), item("3")];
+
+        let page = order().paginate(items.clone(), Some(&item("3")), Some(1), usize::MAX, false);
+        assert_eq!(sort_keys(&page), vec![&AttributeValue::N("2".to_string())]);
+        assert_eq!(page.last_evaluated_key, Some(item("2")));
+    }
+
+    #[test]
+    fn start_key_must_match_the_key_schema() {
+        assert!(order().validate_start_key(&item("1")).is_ok());
+
+        let mut extra = item("1");
+        extra.insert("other".to_string(), AttributeValue::S("x".to_string()));
+        let mut missing = item("1");
+        missing.remove("sk");
+        let mut wrong_type = item("1");
+        wrong_type.insert("sk".to_string(), AttributeValue::Bool(true));
+
+        for start_key in [extra, missing, wrong_type] {
+            assert_eq!(
+                order().validate_start_key(&start_key).unwrap_err().to_string(),
+                "The provided starting key is invalid: The provided key element does not match the schema"
+            );
+        }
+    }
+
+    #[test]
+    fn limit_reached_exactly() {
+        let page = order().paginate(vec![item("1"), item("2")], None, Some(2), usize::MAX, true);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.last_evaluated_key, Some(item("2")));
+    }
+
+    #[test]
+    fn pages_end_at_max_bytes() {
+        let items = vec![item("1"), item("2"), item("3")];
+        let item_bytes = size::item_size(&item("1"));
+
+        let page = order().paginate(items.clone(), None, None, item_bytes + 1, true);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.last_evaluated_key, Some(item("2")));
+
+        let page = order().paginate(items, None, Some(1), item_bytes * 2, true);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.last_evaluated_key, Some(item("1")));
+    }
+}
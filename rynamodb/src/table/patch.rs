@@ -0,0 +1,99 @@
+//! JSON merge patches (RFC 7396) applied to items, to tweak stored state without expressions
+//!
+//! A patch is an object of attribute names to DynamoDB JSON values, which replace the attributes.
+//! `null` removes an attribute, and a map value patches an existing map attribute rather than
+//! replacing it, so that nested attributes can be changed on their own:
+//!
+//! ```json
+//! {"status": {"S": "shipped"}, "notes": null, "address": {"M": {"zip": {"S": "12345"}}}}
+//! ```
+
+use std::collections::HashMap;
+
+use serde_dynamo::AttributeValue;
+use serde_json::{Map, Value};
+
+use super::{Result, TableError};
+
+/// Apply a merge patch to the attributes of an item
+pub fn merge(item: &mut HashMap<String, AttributeValue>, patch: &Map<String, Value>) -> Result<()> {
+    for (name, value) in patch {
+        if value.is_null() {
+            item.remove(name);
+            continue;
+        }
+        // maps are patched by their attributes, so look inside them before parsing the value
+        let map_patch = value
+            .as_object()
+            .filter(|value| value.len() == 1)
+            .and_then(|value| value.get("M"))
+            .and_then(Value::as_object);
+        if let (Some(AttributeValue::M(existing)), Some(map_patch)) =
+            (item.get_mut(name), map_patch)
+        {
+            merge(existing, map_patch)?;
+            continue;
+        }
+        let value = serde_json::from_value(value.clone()).map_err(|e| {
+            TableError::InvalidParameter(format!("Invalid value for attribute {name}: {e}"))
+        })?;
+        item.insert(name.clone(), value);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(value: Value) -> HashMap<String, AttributeValue> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn patch(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn merge_patch() {
+        let mut patched = item(serde_json::json!({
+            "pk": {"S": "a"},
+            "status": {"S": "pending"},
+            "notes": {"S": "fragile"},
+            "address": {"M": {"city": {"S": "Paris"}, "zip": {"S": "75001"}}},
+        }));
+        merge(
+            &mut patched,
+            &patch(serde_json::json!({
+                "status": {"N": "3"},
+                "notes": null,
+                "missing": null,
+                "address": {"M": {"zip": {"S": "75002"}, "city": null}},
+                "tags": {"SS": ["x"]},
+            })),
+        )
+        .unwrap();
+        assert_eq!(
+            patched,
+            item(serde_json::json!({
+                "pk": {"S": "a"},
+                "status": {"N": "3"},
+                "address": {"M": {"zip": {"S": "75002"}}},
+                "tags": {"SS": ["x"]},
+            }))
+        );
+
+        // values which are not maps replace maps
+        merge(
+            &mut patched,
+            &patch(serde_json::json!({"address": {"S": "unknown"}})),
+        )
+        .unwrap();
+        assert_eq!(patched["address"], AttributeValue::S("unknown".to_string()));
+
+        assert!(matches!(
+            merge(&mut patched, &patch(serde_json::json!({"x": {"Q": "1"}}))),
+            Err(TableError::InvalidParameter(_))
+        ));
+    }
+}
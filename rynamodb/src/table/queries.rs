@@ -1,4 +1,7 @@
+use std::cmp::Ordering;
+
 use pest::{iterators::Pair, Parser};
+use serde_dynamo::AttributeValue;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -9,6 +12,10 @@ pub enum ParserError {
     Eoi,
     #[error("can not convert node to string")]
     NotStringlike,
+    #[error("invalid comparator: {0}")]
+    InvalidComparator(String),
+    #[error("unsupported function in key condition: {0}")]
+    UnsupportedFunction(String),
 }
 
 #[derive(pest_derive::Parser)]
@@ -22,12 +29,20 @@ pub enum Node {
         rhs: Box<Node>,
         op: Operator,
     },
+    /// `key BETWEEN lower AND upper`, inclusive of both bounds
+    Between {
+        key: Box<Node>,
+        lower: Box<Node>,
+        upper: Box<Node>,
+    },
     FunctionCall {
         name: String,
         args: Vec<Node>,
     },
     Attribute(String),
     Placeholder(String),
+    /// Typed value, substituted in from the expression attribute values
+    Value(AttributeValue),
 }
 
 impl Node {
@@ -43,9 +58,45 @@ impl Node {
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum Operator {
     Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
     And,
 }
 
+impl Operator {
+    /// Whether the result of comparing the lhs to the rhs satisfies this operator
+    pub fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering == Ordering::Equal,
+            Operator::Ne => ordering != Ordering::Equal,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::Le => ordering != Ordering::Greater,
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::Ge => ordering != Ordering::Less,
+            Operator::And => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Operator {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "=" => Ok(Operator::Eq),
+            "<>" => Ok(Operator::Ne),
+            "<" => Ok(Operator::Lt),
+            "<=" => Ok(Operator::Le),
+            ">" => Ok(Operator::Gt),
+            ">=" => Ok(Operator::Ge),
+            s => Err(ParserError::InvalidComparator(s.to_string())),
+        }
+    }
+}
+
 fn parse_and_condition(root: Pair<Rule>) -> Result<Node, ParserError> {
     assert_eq!(root.as_rule(), Rule::and_condition);
 
@@ -66,10 +117,8 @@ fn parse_key(root: Pair<Rule>) -> Result<Node, ParserError> {
     let inner = root.into_inner().next().ok_or(ParserError::Eoi)?;
     let node = match inner.as_rule() {
         Rule::column_name => Node::Attribute(inner.as_str().to_string()),
-        Rule::key_placeholder => {
-            let s = inner.as_str().strip_prefix('#').unwrap();
-            Node::Placeholder(s.to_string())
-        }
+        // keep the leading `#` so names and values with the same suffix are distinguishable
+        Rule::key_placeholder => Node::Placeholder(inner.as_str().to_string()),
         r => unreachable!("{r:?}"),
     };
     Ok(node)
@@ -80,10 +129,7 @@ fn parse_value(root: Pair<Rule>) -> Result<Node, ParserError> {
     let inner = root.into_inner().next().ok_or(ParserError::Eoi)?;
     let node = match inner.as_rule() {
         Rule::column_name => Node::Attribute(inner.as_str().to_string()),
-        Rule::value_placeholder => {
-            let s = inner.as_str().strip_prefix(':').unwrap();
-            Node::Placeholder(s.to_string())
-        }
+        Rule::value_placeholder => Node::Placeholder(inner.as_str().to_string()),
         r => unreachable!("{r:?}"),
     };
     Ok(node)
@@ -109,7 +155,9 @@ fn parse_function(root: Pair<Rule>) -> Result<Node, ParserError> {
     let inner = root.into_inner().next().ok_or(ParserError::Eoi)?;
     let node = match inner.as_rule() {
         Rule::begins_with => parse_begins_with(inner)?,
-        r => unreachable!("{r:?}"),
+        // the grammar also accepts the functions of condition expressions, which key
+        // conditions cannot use
+        r => return Err(ParserError::UnsupportedFunction(format!("{r:?}"))),
     };
 
     Ok(node)
@@ -138,8 +186,20 @@ fn parse_condition(root: Pair<Rule>) -> Result<Node, ParserError> {
         }
     };
 
-    // TODO: op
-    let _ = pairs.next().ok_or(ParserError::Eoi)?;
+    let op = {
+        let node = pairs.next().ok_or(ParserError::Eoi)?;
+        if node.as_rule() == Rule::value {
+            // the BETWEEN keyword is not part of the parse tree
+            let upper = parse_value(pairs.next().ok_or(ParserError::Eoi)?)?;
+            return Ok(Node::Between {
+                key: Box::new(lhs),
+                lower: Box::new(parse_value(node)?),
+                upper: Box::new(upper),
+            });
+        }
+        assert_eq!(node.as_rule(), Rule::comparator);
+        node.as_str().parse()?
+    };
 
     let rhs = {
         let node = pairs.next().ok_or(ParserError::Eoi)?;
@@ -153,12 +213,13 @@ fn parse_condition(root: Pair<Rule>) -> Result<Node, ParserError> {
     Ok(Node::Binop {
         lhs: Box::new(lhs),
         rhs: Box::new(rhs),
-        op: Operator::Eq,
+        op,
     })
 }
 
 pub fn parse(input: &str) -> Result<Node, ParserError> {
-    let mut pairs = DynamoDBParser::parse(Rule::condition_expression, input).unwrap();
+    let mut pairs = DynamoDBParser::parse(Rule::condition_expression, input)
+        .map_err(|e| ParserError::ParseError(e.to_string()))?;
     let root = pairs
         .next()
         .ok_or(ParserError::Eoi)?
@@ -185,14 +246,14 @@ mod tests {
             Node::Binop {
                 lhs: Box::new(Node::Binop {
                     lhs: Box::new(Node::Attribute("Id".to_string())),
-                    rhs: Box::new(Node::Placeholder("id".to_string())),
+                    rhs: Box::new(Node::Placeholder(":id".to_string())),
                     op: Operator::Eq,
                 }),
                 rhs: Box::new(Node::FunctionCall {
                     name: "begins_with".to_string(),
                     args: vec![
                         Node::Attribute("ReplyDateTime".to_string()),
-                        Node::Placeholder("dt".to_string()),
+                        Node::Placeholder(":dt".to_string()),
                     ],
                 }),
                 op: Operator::And,
@@ -208,9 +269,90 @@ mod tests {
             ast,
             Node::Binop {
                 lhs: Box::new(Node::Attribute("ForumName".to_string())),
-                rhs: Box::new(Node::Placeholder("name".to_string())),
+                rhs: Box::new(Node::Placeholder(":name".to_string())),
                 op: Operator::Eq,
             }
         );
     }
+
+    #[test]
+    fn between() {
+        let s = "pk = :pk AND sk BETWEEN :lower AND :upper";
+        let ast = parse(s).unwrap();
+        assert_eq!(
+            ast,
+            Node::Binop {
+                lhs: Box::new(Node::Binop {
+                    lhs: Box::new(Node::Attribute("pk".to_string())),
+                    rhs: Box::new(Node::Placeholder(":pk".to_string())),
+                    op: Operator::Eq,
+                }),
+                rhs: Box::new(Node::Between {
+                    key: Box::new(Node::Attribute("sk".to_string())),
+                    lower: Box::new(Node::Placeholder(":lower".to_string())),
+                    upper: Box::new(Node::Placeholder(":upper".to_string())),
+                }),
+                op: Operator::And,
+            }
+        );
+    }
+
+    #[test]
+    fn comparators() {
+        for (s, op) in [
+            ("sk = :v", Operator::Eq),
+            ("sk <> :v", Operator::Ne),
+            ("sk < :v", Operator::Lt),
+            ("sk <= :v", Operator::Le),
+            ("sk > :v", Operator::Gt),
+            ("sk >= :v", Operator::Ge),
+        ] {
+            let ast = parse(s).unwrap();
+            assert_eq!(
+                ast,
+                Node::Binop {
+                    lhs: Box::new(Node::Attribute("sk".to_string())),
+                    rhs: Box::new(Node::Placeholder(":v".to_string())),
+                    op,
+                },
+                "parsing {s}"
+            );
+        }
+    }
+
+    #[test]
+    fn underscores_in_names() {
+        let s = "gsi_pk = :gsi_pk AND #sort_key > :min_value";
+        let ast = parse(s).unwrap();
+        assert_eq!(
+            ast,
+            Node::Binop {
+                lhs: Box::new(Node::Binop {
+                    lhs: Box::new(Node::Attribute("gsi_pk".to_string())),
+                    rhs: Box::new(Node::Placeholder(":gsi_pk".to_string())),
+                    op: Operator::Eq,
+                }),
+                rhs: Box::new(Node::Binop {
+                    lhs: Box::new(Node::Placeholder("#sort_key".to_string())),
+                    rhs: Box::new(Node::Placeholder(":min_value".to_string())),
+                    op: Operator::Gt,
+                }),
+                op: Operator::And,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for s in [
+            "",
+            "pk",
+            "pk = ",
+            "= :v",
+            "attribute_exists(a.b)",
+            "size(a.b) = :v",
+        ] {
+            assert!(parse(s).is_err(), "parsing {s}");
+        }
+    }
 }
@@ -0,0 +1,86 @@
+//! Structure of a table, independent of its items and runtime state
+//!
+//! The schema is what a client needs to know to read and write the table: its keys and their
+//! types, its indexes and which attribute holds the expiry time of items. Embedders can use it to
+//! generate code or validation from a running emulator:
+//!
+//! ```no_run
+//! let (router, tables) = rynamodb::Rynamodb::builder().build();
+//! // ... serve the router and create a table
+//! let schema = tables.with_table("my-table", rynamodb::Table::schema);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{self, AttributeDefinition, AttributeType, KeySchema, KeyType};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSchema {
+    pub table_name: String,
+    pub partition_key: KeyAttribute,
+    pub sort_key: Option<KeyAttribute>,
+    /// types of the key attributes of the table and its indexes
+    pub attribute_definitions: Vec<AttributeDefinition>,
+    pub global_secondary_indexes: Vec<IndexSchema>,
+    /// name of the attribute holding the expiry time of items, if time to live is enabled
+    pub time_to_live_attribute: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyAttribute {
+    pub name: String,
+    /// type from the attribute definitions, which tables restored from older exports may lack
+    pub attribute_type: Option<AttributeType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexSchema {
+    pub index_name: String,
+    pub partition_key: KeyAttribute,
+    pub sort_key: Option<KeyAttribute>,
+    pub projection: types::Projection,
+}
+
+impl KeyAttribute {
+    pub(crate) fn new(name: &str, definitions: &[AttributeDefinition]) -> Self {
+        Self {
+            name: name.to_string(),
+            attribute_type: definitions
+                .iter()
+                .find(|definition| definition.attribute_name == name)
+                .map(|definition| definition.attribute_type.clone()),
+        }
+    }
+}
+
+/// Key schema in the form used by the DynamoDB API, partition key first
+fn key_schema(partition_key: &KeyAttribute, sort_key: Option<&KeyAttribute>) -> Vec<KeySchema> {
+    std::iter::once((partition_key, KeyType::HASH))
+        .chain(sort_key.map(|sort_key| (sort_key, KeyType::RANGE)))
+        .map(|(key, key_type)| KeySchema {
+            attribute_name: key.name.clone(),
+            key_type,
+        })
+        .collect()
+}
+
+impl TableSchema {
+    pub fn key_schema(&self) -> Vec<KeySchema> {
+        key_schema(&self.partition_key, self.sort_key.as_ref())
+    }
+
+    pub fn index(&self, index_name: &str) -> Option<&IndexSchema> {
+        self.global_secondary_indexes
+            .iter()
+            .find(|index| index.index_name == index_name)
+    }
+}
+
+impl IndexSchema {
+    pub fn key_schema(&self) -> Vec<KeySchema> {
+        key_schema(&self.partition_key, self.sort_key.as_ref())
+    }
+}
@@ -0,0 +1,113 @@
+//! Validation of the set attributes of items being written
+//!
+//! DynamoDB rejects sets containing duplicate elements rather than removing the duplicates, and
+//! number sets containing anything other than numbers. Numbers are compared by value, so `1` and
+//! `1.0` are duplicates. Sets nested in lists and maps are checked too.
+
+use std::collections::{HashMap, HashSet};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_dynamo::AttributeValue;
+
+use super::{key::Key, Result, TableError};
+
+/// Check every set in an item
+pub fn check_item(item: &HashMap<String, AttributeValue>) -> Result<()> {
+    item.values().try_for_each(check_value)
+}
+
+fn check_value(value: &AttributeValue) -> Result<()> {
+    match value {
+        AttributeValue::Ss(values) => check_unique(values.iter().cloned().map(Key::S), values),
+        AttributeValue::Ns(values) => {
+            let keys = values
+                .iter()
+                .map(|n| {
+                    Key::from_value(&AttributeValue::N(n.clone())).ok_or(TableError::InvalidNumber)
+                })
+                .collect::<Result<Vec<_>>>()?;
+            check_unique(keys, values)
+        }
+        AttributeValue::Bs(values) => check_unique(
+            values.iter().cloned().map(Key::B),
+            &values
+                .iter()
+                .map(|b| STANDARD.encode(b))
+                .collect::<Vec<_>>(),
+        ),
+        AttributeValue::N(n) => Key::from_value(&AttributeValue::N(n.clone()))
+            .map(|_| ())
+            .ok_or(TableError::InvalidNumber),
+        AttributeValue::L(values) => values.iter().try_for_each(check_value),
+        AttributeValue::M(map) => map.values().try_for_each(check_value),
+        _ => Ok(()),
+    }
+}
+
+/// Fail if any of `keys` are equal, listing the set's elements as `displayed`
+fn check_unique(keys: impl IntoIterator<Item = Key>, displayed: &[String]) -> Result<()> {
+    let mut seen = HashSet::new();
+    if keys.into_iter().all(|key| seen.insert(key)) {
+        return Ok(());
+    }
+    Err(TableError::InvalidParameter(format!(
+        "Input collection [{}] contains duplicates.",
+        displayed.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(value: AttributeValue) -> HashMap<String, AttributeValue> {
+        [("set".to_string(), value)].into()
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let strings = |values: &[&str]| values.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(check_item(&item(AttributeValue::Ss(strings(&["a", "b"])))).is_ok());
+        assert!(check_item(&item(AttributeValue::Ns(strings(&["1", "1.5"])))).is_ok());
+
+        let err = check_item(&item(AttributeValue::Ss(strings(&["a", "b", "a"])))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "One or more parameter values were invalid: Input collection [a, b, a] contains duplicates."
+        );
+        let err = check_item(&item(AttributeValue::Ns(strings(&["1", "1.0"])))).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "One or more parameter values were invalid: Input collection [1, 1.0] contains duplicates."
+        );
+        assert!(check_item(&item(AttributeValue::Bs(vec![
+            b"ab".to_vec(),
+            b"ab".to_vec()
+        ])))
+        .is_err());
+
+        // sets nested in documents
+        let nested = AttributeValue::M(
+            [(
+                "list".to_string(),
+                AttributeValue::L(vec![AttributeValue::Ss(strings(&["x", "x"]))]),
+            )]
+            .into(),
+        );
+        assert!(check_item(&item(nested)).is_err());
+    }
+
+    #[test]
+    fn number_sets_only_hold_numbers() {
+        let err = check_item(&item(AttributeValue::Ns(vec![
+            "1".to_string(),
+            "abc".to_string(),
+        ])))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "A value provided cannot be converted into a number"
+        );
+    }
+}
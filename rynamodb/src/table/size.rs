@@ -0,0 +1,92 @@
+//! Size of items as DynamoDB accounts for it
+//!
+//! See <https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/CapacityUnitCalculations.html>.
+//! Attribute names count towards the size of an item as well as the values.
+
+use std::collections::HashMap;
+
+use serde_dynamo::AttributeValue;
+
+use super::names::AttributeName;
+
+/// Size in bytes of an item
+pub fn item_size<K: AttributeName>(item: &HashMap<K, AttributeValue>) -> usize {
+    item.iter()
+        .map(|(name, value)| attribute_size(name.borrow(), value))
+        .sum()
+}
+
+/// Size in bytes of a single attribute, including its name
+pub fn attribute_size(name: &str, value: &AttributeValue) -> usize {
+    name.len() + value_size(value)
+}
+
+fn value_size(value: &AttributeValue) -> usize {
+    match value {
+        AttributeValue::S(s) => s.len(),
+        AttributeValue::N(n) => number_size(n),
+        AttributeValue::B(b) => b.len(),
+        AttributeValue::Bool(_) | AttributeValue::Null(_) => 1,
+        AttributeValue::Ss(values) => values.iter().map(String::len).sum(),
+        AttributeValue::Ns(values) => values.iter().map(|n| number_size(n)).sum(),
+        AttributeValue::Bs(values) => values.iter().map(Vec::len).sum(),
+        // documents have 3 bytes of overhead, and 1 byte per element
+        AttributeValue::L(values) => 3 + values.iter().map(|v| 1 + value_size(v)).sum::<usize>(),
+        AttributeValue::M(map) => {
+            3 + map
+                .iter()
+                .map(|(name, v)| 1 + attribute_size(name, v))
+                .sum::<usize>()
+        }
+    }
+}
+
+/// Numbers are stored with up to 38 significant digits, two digits per byte, plus one byte
+fn number_size(n: &str) -> usize {
+    let mantissa = n
+        .trim_start_matches(['-', '+'])
+        .split(['e', 'E'])
+        .next()
+        .unwrap_or_default();
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let significant = digits.trim_start_matches('0').trim_end_matches('0').len();
+    significant.div_ceil(2) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(
+            attribute_size("name", &AttributeValue::S("héllo".to_string())),
+            4 + 6
+        );
+        assert_eq!(value_size(&AttributeValue::N("0".to_string())), 1);
+        assert_eq!(value_size(&AttributeValue::N("12345".to_string())), 4);
+        assert_eq!(value_size(&AttributeValue::N("-1200.0".to_string())), 2);
+        assert_eq!(value_size(&AttributeValue::N("1.5e10".to_string())), 2);
+        assert_eq!(value_size(&AttributeValue::B(vec![0; 10])), 10);
+        assert_eq!(value_size(&AttributeValue::Bool(true)), 1);
+        assert_eq!(
+            value_size(&AttributeValue::L(vec![AttributeValue::S(
+                "ab".to_string()
+            )])),
+            3 + 1 + 2
+        );
+        assert_eq!(
+            value_size(&AttributeValue::M(
+                [("k".to_string(), AttributeValue::S("ab".to_string()))].into()
+            )),
+            3 + 1 + 1 + 2
+        );
+
+        let item: HashMap<_, _> = [
+            ("pk".to_string(), AttributeValue::S("abc".to_string())),
+            ("count".to_string(), AttributeValue::N("42".to_string())),
+        ]
+        .into();
+        assert_eq!(item_size(&item), 2 + 3 + 5 + 2);
+    }
+}
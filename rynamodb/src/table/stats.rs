@@ -0,0 +1,205 @@
+//! Statistics about the attributes of the items in a table, to help discover the schema of
+//! existing data
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use serde_dynamo::AttributeValue;
+
+use super::{names::AttributeName, type_descriptor};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeStatistics {
+    /// number of items the statistics were computed from
+    pub sampled_items: usize,
+    pub total_items: usize,
+    /// attributes seen in the sampled items, ordered by name
+    pub attributes: Vec<AttributeStatistic>,
+    /// sizes of every item written to the table, rather than of the sample
+    pub written_item_sizes: SizeHistogram,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeStatistic {
+    pub name: String,
+    /// number of sampled items with the attribute
+    pub count: usize,
+    /// fraction of the sampled items with the attribute
+    pub fill_rate: f64,
+    /// number of sampled items with each type of value, e.g. `{"S": 10, "N": 2}`
+    pub types: BTreeMap<&'static str, usize>,
+}
+
+/// Compute statistics from at most `sample_size` of `items`
+pub fn attribute_statistics<'a, K: AttributeName + 'a>(
+    items: impl Iterator<Item = &'a HashMap<K, AttributeValue>>,
+    total_items: usize,
+    sample_size: usize,
+) -> AttributeStatistics {
+    let mut sampled_items = 0;
+    let mut types_by_name: BTreeMap<&str, BTreeMap<&'static str, usize>> = BTreeMap::new();
+    for item in items.take(sample_size) {
+        sampled_items += 1;
+        for (name, value) in item {
+            *types_by_name
+                .entry(name.borrow())
+                .or_default()
+                .entry(type_descriptor(value))
+                .or_default() += 1;
+        }
+    }
+
+    let attributes = types_by_name
+        .into_iter()
+        .map(|(name, types)| {
+            let count = types.values().sum();
+            AttributeStatistic {
+                name: name.to_string(),
+                count,
+                fill_rate: count as f64 / sampled_items as f64,
+                types,
+            }
+        })
+        .collect();
+    AttributeStatistics {
+        sampled_items,
+        total_items,
+        attributes,
+        written_item_sizes: Default::default(),
+    }
+}
+
+/// Upper bounds of the buckets of item size histograms, in bytes, up to DynamoDB's limit of
+/// 400 KB
+pub const ITEM_SIZE_BUCKETS: [usize; 7] = [
+    1024,
+    4 * 1024,
+    16 * 1024,
+    64 * 1024,
+    128 * 1024,
+    256 * 1024,
+    400 * 1024,
+];
+
+/// Distribution of the sizes of the items written to a table, to catch items growing
+/// unexpectedly, e.g. when whole documents are stored by mistake
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeHistogram {
+    /// number of items in each bucket, which are not cumulative
+    pub buckets: Vec<SizeBucket>,
+    pub count: u64,
+    /// total size of the items, in bytes
+    pub sum: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeBucket {
+    /// largest size counted in the bucket, or `None` for the bucket above the largest bound,
+    /// which only holds items written while a larger limit was configured
+    pub max_bytes: Option<usize>,
+    pub count: u64,
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: ITEM_SIZE_BUCKETS
+                .into_iter()
+                .map(Some)
+                .chain([None])
+                .map(|max_bytes| SizeBucket {
+                    max_bytes,
+                    count: 0,
+                })
+                .collect(),
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+}
+
+impl SizeHistogram {
+    pub fn record(&mut self, size: usize) {
+        let bucket = ITEM_SIZE_BUCKETS
+            .iter()
+            .position(|bound| size <= *bound)
+            .unwrap_or(ITEM_SIZE_BUCKETS.len());
+        self.buckets[bucket].count += 1;
+        self.count += 1;
+        self.sum += size as u64;
+        self.max = self.max.max(size as u64);
+    }
+
+    /// Add the items counted by another histogram
+    pub fn merge(&mut self, other: &SizeHistogram) {
+        for (bucket, other) in self.buckets.iter_mut().zip(&other.buckets) {
+            bucket.count += other.count;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_attribute_types() {
+        let items: Vec<HashMap<String, AttributeValue>> = vec![
+            [
+                ("pk".to_string(), AttributeValue::S("a".to_string())),
+                ("value".to_string(), AttributeValue::N("1".to_string())),
+            ]
+            .into(),
+            [
+                ("pk".to_string(), AttributeValue::S("b".to_string())),
+                ("value".to_string(), AttributeValue::S("one".to_string())),
+            ]
+            .into(),
+            [("pk".to_string(), AttributeValue::S("c".to_string()))].into(),
+            [("pk".to_string(), AttributeValue::S("d".to_string()))].into(),
+        ];
+
+        let stats = attribute_statistics(items.iter(), items.len(), 3);
+        assert_eq!(stats.sampled_items, 3);
+        assert_eq!(stats.total_items, 4);
+
+        let names: Vec<_> = stats.attributes.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["pk", "value"]);
+        assert_eq!(stats.attributes[0].count, 3);
+        assert_eq!(stats.attributes[0].fill_rate, 1.0);
+
+        let value = &stats.attributes[1];
+        assert_eq!(value.count, 2);
+        assert_eq!(value.types, [("N", 1), ("S", 1)].into());
+    }
+
+    #[test]
+    fn size_histogram() {
+        let mut histogram = SizeHistogram::default();
+        for size in [10, 1024, 1025, 500 * 1024] {
+            histogram.record(size);
+        }
+        let counts: Vec<_> = histogram.buckets.iter().map(|b| b.count).collect();
+        assert_eq!(counts, [2, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(histogram.buckets[7].max_bytes, None);
+        assert_eq!(
+            (histogram.count, histogram.sum, histogram.max),
+            (4, 10 + 1024 + 1025 + 500 * 1024, 500 * 1024)
+        );
+
+        let mut merged = SizeHistogram::default();
+        merged.record(2000);
+        merged.merge(&histogram);
+        assert_eq!(merged.buckets[1].count, 2);
+        assert_eq!(merged.count, 5);
+    }
+}
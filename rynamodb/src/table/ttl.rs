@@ -0,0 +1,77 @@
+//! Expiry of items by their time to live attribute
+//!
+//! Like DynamoDB, only items whose TTL attribute is a number of seconds since the epoch expire.
+//! Items with any other type of value are never deleted, and neither are items whose timestamp is
+//! more than five years in the past.
+
+use chrono::{DateTime, Utc};
+use serde_dynamo::AttributeValue;
+
+use super::names::Item;
+
+/// timestamps further in the past than this are ignored
+const MAX_AGE_DAYS: i64 = 5 * 365;
+
+/// Whether an item has expired at `now`, given the name of the table's TTL attribute
+pub fn is_expired(item: &Item, attribute_name: &str, now: DateTime<Utc>) -> bool {
+    let Some(AttributeValue::N(value)) = item.get(attribute_name) else {
+        return false;
+    };
+    let Ok(expires_at) = value.parse::<f64>() else {
+        return false;
+    };
+    let now_seconds = now.timestamp() as f64;
+    let oldest = (now - chrono::Duration::days(MAX_AGE_DAYS)).timestamp() as f64;
+    (oldest..now_seconds).contains(&expires_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::names::Interner;
+
+    fn item(value: AttributeValue) -> Item {
+        Interner::default().intern_item([("expires".to_string(), value)].into())
+    }
+
+    #[test]
+    fn expiry_rules() {
+        let now = Utc::now();
+        let n = |seconds: i64| item(AttributeValue::N(seconds.to_string()));
+
+        assert!(is_expired(&n(now.timestamp() - 1), "expires", now));
+        assert!(is_expired(
+            &item(AttributeValue::N(format!("{}.5", now.timestamp() - 10))),
+            "expires",
+            now
+        ));
+        assert!(is_expired(
+            &n((now - chrono::Duration::days(4 * 365)).timestamp()),
+            "expires",
+            now
+        ));
+
+        // not expired yet
+        assert!(!is_expired(&n(now.timestamp() + 60), "expires", now));
+        // more than five years in the past
+        assert!(!is_expired(
+            &n((now - chrono::Duration::days(6 * 365)).timestamp()),
+            "expires",
+            now
+        ));
+        // milliseconds are far in the future
+        assert!(!is_expired(&n(now.timestamp_millis()), "expires", now));
+        // only numbers are timestamps
+        assert!(!is_expired(
+            &item(AttributeValue::S((now.timestamp() - 1).to_string())),
+            "expires",
+            now
+        ));
+        assert!(!is_expired(
+            &item(AttributeValue::Ns(vec![(now.timestamp() - 1).to_string()])),
+            "expires",
+            now
+        ));
+        assert!(!is_expired(&n(now.timestamp() - 1), "other", now));
+    }
+}
@@ -0,0 +1,743 @@
+//! Update expressions of `UpdateItem`
+//!
+//! `SET` and `REMOVE` actions are supported, on top level attributes and on paths into maps and
+//! lists. The values set can be expression attribute values, other attributes, `if_not_exists`,
+//! `list_append`, or the sum or difference of two numbers. As in DynamoDB, every value is read
+//! from the item as it was before the update, and list elements are removed by their index
+//! before the update, ignoring indexes past the end of the list. `ADD` and `DELETE` actions are
+//! not implemented.
+
+use std::{collections::HashMap, fmt};
+
+use pest::{iterators::Pair, Parser};
+use serde_dynamo::AttributeValue;
+
+use super::{numbers, type_descriptor, Result, TableError};
+use crate::types;
+
+type Item = HashMap<String, AttributeValue>;
+
+#[derive(pest_derive::Parser)]
+#[grammar = "update_item.pest"]
+struct UpdateExpressionParser;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathElement {
+    /// attribute name, or `#` placeholder before names are substituted
+    Name(String),
+    Index(usize),
+}
+
+/// Document path to an attribute, or to an element of a map or list attribute
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Path(pub Vec<PathElement>);
+
+impl Path {
+    /// Name of the top level attribute the path is in
+    fn attribute(&self) -> &str {
+        match self.0.first() {
+            Some(PathElement::Name(name)) => name,
+            // the grammar only allows paths starting with a name
+            _ => unreachable!("path does not start with a name"),
+        }
+    }
+
+    fn overlaps(&self, other: &Path) -> bool {
+        self.0.iter().zip(&other.0).all(|(a, b)| a == b)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let elements: Vec<_> = self
+            .0
+            .iter()
+            .map(|element| match element {
+                PathElement::Name(name) => name.clone(),
+                PathElement::Index(index) => format!("[{index}]"),
+            })
+            .collect();
+        write!(f, "[{}]", elements.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Path(Path),
+    /// `:` placeholder of an expression attribute value
+    Value(String),
+    IfNotExists(Path, Box<Operand>),
+    ListAppend(Box<Operand>, Box<Operand>),
+    Plus(Box<Operand>, Box<Operand>),
+    Minus(Box<Operand>, Box<Operand>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Set(Path, Operand),
+    Remove(Path),
+}
+
+impl Action {
+    fn path(&self) -> &Path {
+        match self {
+            Action::Set(path, _) | Action::Remove(path) => path,
+        }
+    }
+}
+
+fn syntax_error(e: impl fmt::Display) -> TableError {
+    TableError::InvalidUpdateExpression(format!("Syntax error; {e}"))
+}
+
+pub fn parse(input: &str) -> Result<Vec<Action>> {
+    let root = UpdateExpressionParser::parse(Rule::update_expression, input)
+        .map_err(syntax_error)?
+        .next()
+        .ok_or_else(|| syntax_error("empty expression"))?;
+
+    let mut actions = Vec::new();
+    let mut sections = Vec::new();
+    for clause in root.into_inner() {
+        let section = match clause.as_rule() {
+            Rule::set_clause => Some("SET"),
+            Rule::remove_clause => Some("REMOVE"),
+            Rule::add_clause => Some("ADD"),
+            Rule::delete_clause => Some("DELETE"),
+            _ => None,
+        };
+        if let Some(section) = section {
+            if sections.contains(&section) {
+                return Err(TableError::InvalidUpdateExpression(format!(
+                    "The \"{section}\" section can only be used once in an update expression;"
+                )));
+            }
+            sections.push(section);
+        }
+        match clause.as_rule() {
+            Rule::set_clause => {
+                for action in clause.into_inner() {
+                    let mut pairs = action.into_inner();
+                    let path = parse_path(next(&mut pairs)?);
+                    let lhs = parse_operand(next(&mut pairs)?)?;
+                    let operand = match pairs.next() {
+                        Some(arithmetic) => {
+                            let rhs = Box::new(parse_operand(next(&mut pairs)?)?);
+                            match arithmetic.as_str() {
+                                "+" => Operand::Plus(Box::new(lhs), rhs),
+                                _ => Operand::Minus(Box::new(lhs), rhs),
+                            }
+                        }
+                        None => lhs,
+                    };
+                    actions.push(Action::Set(path, operand));
+                }
+            }
+            Rule::remove_clause => {
+                actions.extend(
+                    clause
+                        .into_inner()
+                        .map(|path| Action::Remove(parse_path(path))),
+                );
+            }
+            Rule::add_clause => {
+                return Err(TableError::NotImplemented(
+                    "ADD actions in update expressions".to_string(),
+                ))
+            }
+            Rule::delete_clause => {
+                return Err(TableError::NotImplemented(
+                    "DELETE actions in update expressions".to_string(),
+                ))
+            }
+            Rule::EOI => {}
+            r => unreachable!("{r:?}"),
+        }
+    }
+    Ok(actions)
+}
+
+fn next<'a>(pairs: &mut pest::iterators::Pairs<'a, Rule>) -> Result<Pair<'a, Rule>> {
+    pairs
+        .next()
+        .ok_or_else(|| syntax_error("unexpected end of expression"))
+}
+
+fn parse_path(root: Pair<Rule>) -> Path {
+    assert_eq!(root.as_rule(), Rule::path);
+    Path(
+        root.into_inner()
+            .map(|element| {
+                let inner = element.into_inner().next().expect("empty path element");
+                match inner.as_rule() {
+                    Rule::attribute_name | Rule::name_placeholder => {
+                        PathElement::Name(inner.as_str().to_string())
+                    }
+                    // the grammar only allows digits, so this can only fail on overflow
+                    Rule::list_index => {
+                        PathElement::Index(inner.as_str().parse().unwrap_or(usize::MAX))
+                    }
+                    r => unreachable!("{r:?}"),
+                }
+            })
+            .collect(),
+    )
+}
+
+fn parse_operand(root: Pair<Rule>) -> Result<Operand> {
+    assert_eq!(root.as_rule(), Rule::operand);
+    let inner = root.into_inner().next().expect("empty operand");
+    let operand = match inner.as_rule() {
+        Rule::value => Operand::Value(inner.as_str().to_string()),
+        Rule::path => Operand::Path(parse_path(inner)),
+        Rule::if_not_exists => {
+            let mut pairs = inner.into_inner();
+            let path = parse_path(next(&mut pairs)?);
+            Operand::IfNotExists(path, Box::new(parse_operand(next(&mut pairs)?)?))
+        }
+        Rule::list_append => {
+            let mut pairs = inner.into_inner();
+            let first = parse_operand(next(&mut pairs)?)?;
+            Operand::ListAppend(Box::new(first), Box::new(parse_operand(next(&mut pairs)?)?))
+        }
+        r => unreachable!("{r:?}"),
+    };
+    Ok(operand)
+}
+
+/// An `UpdateItem` request applied to an item, before it is stored
+pub struct Update {
+    /// the item before the update, if there was one
+    pub old: Option<Item>,
+    pub new: Item,
+    /// top level attributes written or removed by the update
+    updated_attributes: Vec<String>,
+}
+
+impl Update {
+    /// Apply an update to `old`, or to a new item made of `key` if there is no item yet
+    pub fn apply(
+        key: Item,
+        old: Option<Item>,
+        input: &types::UpdateItemInput,
+        key_attributes: &[&str],
+    ) -> Result<Self> {
+        if input.condition_expression.is_some() {
+            return Err(TableError::NotImplemented(
+                "ConditionExpression in UpdateItem".to_string(),
+            ));
+        }
+        if input.attribute_updates.is_some() {
+            return Err(TableError::NotImplemented(
+                "AttributeUpdates in UpdateItem".to_string(),
+            ));
+        }
+        let actions = match &input.update_expression {
+            Some(expression) => parse(expression)?,
+            None => Vec::new(),
+        };
+        let actions = actions
+            .into_iter()
+            .map(|action| substitute_names(action, &input.expression_attribute_names))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (i, action) in actions.iter().enumerate() {
+            let attribute = action.path().attribute();
+            if key_attributes.contains(&attribute) {
+                return Err(TableError::InvalidUpdateExpression(format!(
+                    "Cannot update attribute {attribute}. This attribute is part of the key"
+                )));
+            }
+            if let Some(other) = actions[..i]
+                .iter()
+                .find(|other| other.path().overlaps(action.path()))
+            {
+                return Err(TableError::InvalidUpdateExpression(format!(
+                    "Two document paths overlap with each other; must remove or rewrite one of \
+                    these paths; path one: {}, path two: {}",
+                    other.path(),
+                    action.path()
+                )));
+            }
+        }
+
+        // every value is read from the item as it was before the update
+        let before = old.clone().unwrap_or(key);
+        let values = &input.expression_attribute_values;
+        let changes = actions
+            .iter()
+            .map(|action| match action {
+                Action::Set(_, operand) => evaluate(operand, &before, values).map(Some),
+                Action::Remove(_) => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut document = AttributeValue::M(before);
+        let mut removals = Vec::new();
+        for (action, value) in actions.iter().zip(changes) {
+            match value {
+                Some(value) => set(&mut document, &action.path().0, value)?,
+                None => removals.push(action.path()),
+            }
+        }
+        // removing a list element shifts the elements after it, so the highest indexes of each
+        // list are removed first for every index to refer to the list before the update
+        removals.sort_unstable_by(|a, b| b.cmp(a));
+        for path in removals {
+            remove(&mut document, &path.0);
+        }
+        let AttributeValue::M(mut new) = document else {
+            unreachable!("items are maps");
+        };
+        numbers::canonicalise_item(&mut new);
+
+        let updated_attributes = actions
+            .iter()
+            .map(|action| action.path().attribute().to_string())
+            .collect();
+        Ok(Self {
+            old,
+            new,
+            updated_attributes,
+        })
+    }
+
+    /// Attributes to return, as chosen by the request's `ReturnValues`
+    ///
+    /// `UPDATED_OLD` and `UPDATED_NEW` return whole top level attributes, where DynamoDB only
+    /// returns the parts of maps and lists which were updated.
+    pub fn attributes(&self, return_values: types::ReturnValues) -> Option<Item> {
+        let updated = |item: &Item| -> Item {
+            item.iter()
+                .filter(|(name, _)| self.updated_attributes.contains(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect()
+        };
+        let attributes = match return_values {
+            types::ReturnValues::None => return None,
+            types::ReturnValues::AllOld => self.old.clone()?,
+            types::ReturnValues::UpdatedOld => updated(self.old.as_ref()?),
+            types::ReturnValues::AllNew => self.new.clone(),
+            types::ReturnValues::UpdatedNew => updated(&self.new),
+        };
+        Some(attributes).filter(|attributes| !attributes.is_empty())
+    }
+}
+
+fn substitute_names(action: Action, names: &Option<HashMap<String, String>>) -> Result<Action> {
+    let substitute = |path: Path| -> Result<Path> {
+        path.0
+            .into_iter()
+            .map(|element| match element {
+                PathElement::Name(name) if name.starts_with('#') => names
+                    .as_ref()
+                    .and_then(|names| names.get(&name))
+                    .map(|name| PathElement::Name(name.clone()))
+                    .ok_or_else(|| {
+                        TableError::InvalidUpdateExpression(format!(
+                            "An expression attribute name used in the document path is not \
+                            defined; attribute name: {name}"
+                        ))
+                    }),
+                element => Ok(element),
+            })
+            .collect::<Result<_>>()
+            .map(Path)
+    };
+    fn substitute_operand(
+        operand: Operand,
+        substitute: &impl Fn(Path) -> Result<Path>,
+    ) -> Result<Operand> {
+        let boxed = |operand: Box<Operand>| substitute_operand(*operand, substitute).map(Box::new);
+        Ok(match operand {
+            Operand::Path(path) => Operand::Path(substitute(path)?),
+            Operand::Value(value) => Operand::Value(value),
+            Operand::IfNotExists(path, default) => {
+                Operand::IfNotExists(substitute(path)?, boxed(default)?)
+            }
+            Operand::ListAppend(a, b) => Operand::ListAppend(boxed(a)?, boxed(b)?),
+            Operand::Plus(a, b) => Operand::Plus(boxed(a)?, boxed(b)?),
+            Operand::Minus(a, b) => Operand::Minus(boxed(a)?, boxed(b)?),
+        })
+    }
+    Ok(match action {
+        Action::Set(path, operand) => {
+            Action::Set(substitute(path)?, substitute_operand(operand, &substitute)?)
+        }
+        Action::Remove(path) => Action::Remove(substitute(path)?),
+    })
+}
+
+fn get<'a>(item: &'a Item, path: &Path) -> Option<&'a AttributeValue> {
+    let (first, rest) = path.0.split_first()?;
+    let PathElement::Name(name) = first else {
+        return None;
+    };
+    rest.iter()
+        .try_fold(item.get(name)?, |value, element| match (value, element) {
+            (AttributeValue::M(map), PathElement::Name(name)) => map.get(name),
+            (AttributeValue::L(list), PathElement::Index(index)) => list.get(*index),
+            _ => None,
+        })
+}
+
+fn evaluate(
+    operand: &Operand,
+    item: &Item,
+    values: &Option<HashMap<String, AttributeValue>>,
+) -> Result<AttributeValue> {
+    let incorrect_type = |function: &str, value: &AttributeValue| {
+        TableError::InvalidUpdateExpression(format!(
+            "Incorrect operand type for operator or function; operator or function: {function}, \
+            operand type: {}",
+            type_descriptor(value)
+        ))
+    };
+    match operand {
+        Operand::Path(path) => get(item, path).cloned().ok_or_else(|| {
+            TableError::InvalidUpdateExpression(
+                "The provided expression refers to an attribute that does not exist in the item"
+                    .to_string(),
+            )
+        }),
+        Operand::Value(placeholder) => values
+            .as_ref()
+            .and_then(|values| values.get(placeholder))
+            .cloned()
+            .ok_or_else(|| {
+                TableError::InvalidUpdateExpression(format!(
+                    "An expression attribute value used in expression is not defined; attribute \
+                    value: {placeholder}"
+                ))
+            }),
+        Operand::IfNotExists(path, default) => match get(item, path) {
+            Some(value) => Ok(value.clone()),
+            None => evaluate(default, item, values),
+        },
+        Operand::ListAppend(a, b) => match (evaluate(a, item, values)?, evaluate(b, item, values)?)
+        {
+            (AttributeValue::L(mut a), AttributeValue::L(b)) => {
+                a.extend(b);
+                Ok(AttributeValue::L(a))
+            }
+            (AttributeValue::L(_), other) | (other, _) => {
+                Err(incorrect_type("list_append", &other))
+            }
+        },
+        Operand::Plus(a, b) | Operand::Minus(a, b) => {
+            let (symbol, arithmetic): (_, fn(&str, &str) -> Option<String>) = match operand {
+                Operand::Plus(..) => ("+", numbers::add),
+                _ => ("-", numbers::subtract),
+            };
+            match (evaluate(a, item, values)?, evaluate(b, item, values)?) {
+                (AttributeValue::N(a), AttributeValue::N(b)) => {
+                    if numbers::canonical(&a).is_none() || numbers::canonical(&b).is_none() {
+                        return Err(TableError::InvalidNumber);
+                    }
+                    // valid numbers only fail to add up if they have too many digits
+                    arithmetic(&a, &b).map(AttributeValue::N).ok_or_else(|| {
+                        TableError::NotImplemented(
+                            "arithmetic on numbers of more than 38 digits".to_string(),
+                        )
+                    })
+                }
+                (AttributeValue::N(_), other) | (other, _) => Err(incorrect_type(symbol, &other)),
+            }
+        }
+    }
+}
+
+fn invalid_path() -> TableError {
+    TableError::InvalidUpdateExpression(
+        "The document path provided in the update expression is invalid for update".to_string(),
+    )
+}
+
+/// Set the value at a path, whose parent must already exist. Setting an index past the end of a
+/// list appends to it.
+fn set(document: &mut AttributeValue, path: &[PathElement], value: AttributeValue) -> Result<()> {
+    let (element, rest) = path.split_first().ok_or_else(invalid_path)?;
+    match (document, element) {
+        (AttributeValue::M(map), PathElement::Name(name)) if rest.is_empty() => {
+            map.insert(name.clone(), value);
+            Ok(())
+        }
+        (AttributeValue::M(map), PathElement::Name(name)) => {
+            set(map.get_mut(name).ok_or_else(invalid_path)?, rest, value)
+        }
+        (AttributeValue::L(list), PathElement::Index(index)) if rest.is_empty() => {
+            match list.get_mut(*index) {
+                Some(element) => *element = value,
+                None => list.push(value),
+            }
+            Ok(())
+        }
+        (AttributeValue::L(list), PathElement::Index(index)) => {
+            set(list.get_mut(*index).ok_or_else(invalid_path)?, rest, value)
+        }
+        _ => Err(invalid_path()),
+    }
+}
+
+/// Remove the value at a path, if there is one
+fn remove(document: &mut AttributeValue, path: &[PathElement]) {
+    let Some((element, rest)) = path.split_first() else {
+        return;
+    };
+    match (document, element) {
+        (AttributeValue::M(map), PathElement::Name(name)) if rest.is_empty() => {
+            map.remove(name);
+        }
+        (AttributeValue::L(list), PathElement::Index(index))
+            if rest.is_empty() && *index < list.len() =>
+        {
+            list.remove(*index);
+        }
+        (AttributeValue::M(map), PathElement::Name(name)) => {
+            if let Some(value) = map.get_mut(name) {
+                remove(value, rest);
+            }
+        }
+        (AttributeValue::L(list), PathElement::Index(index)) => {
+            if let Some(value) = list.get_mut(*index) {
+                remove(value, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> PathElement {
+        PathElement::Name(name.to_string())
+    }
+
+    fn s(value: &str) -> AttributeValue {
+        AttributeValue::S(value.to_string())
+    }
+
+    fn n(value: &str) -> AttributeValue {
+        AttributeValue::N(value.to_string())
+    }
+
+    fn input(expression: &str, values: &[(&str, AttributeValue)]) -> types::UpdateItemInput {
+        types::UpdateItemInput {
+            table_name: "table".to_string(),
+            key: key(),
+            update_expression: Some(expression.to_string()),
+            expression_attribute_names: Some(
+                [("#n".to_string(), "nested".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            expression_attribute_values: Some(
+                values
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            ),
+            return_values: Default::default(),
+            condition_expression: None,
+            attribute_updates: None,
+        }
+    }
+
+    fn key() -> Item {
+        [("pk".to_string(), s("abc"))].into_iter().collect()
+    }
+
+    fn apply(
+        old: Option<Item>,
+        expression: &str,
+        values: &[(&str, AttributeValue)],
+    ) -> Result<Update> {
+        Update::apply(key(), old, &input(expression, values), &["pk"])
+    }
+
+    #[test]
+    fn parse_actions() {
+        let actions = parse("SET a = :v, #n.b[2] = a + :one REMOVE c, d[0], e").unwrap();
+        assert_eq!(
+            actions,
+            vec![
+                Action::Set(Path(vec![name("a")]), Operand::Value(":v".to_string())),
+                Action::Set(
+                    Path(vec![name("#n"), name("b"), PathElement::Index(2)]),
+                    Operand::Plus(
+                        Box::new(Operand::Path(Path(vec![name("a")]))),
+                        Box::new(Operand::Value(":one".to_string())),
+                    ),
+                ),
+                Action::Remove(Path(vec![name("c")])),
+                Action::Remove(Path(vec![name("d"), PathElement::Index(0)])),
+                Action::Remove(Path(vec![name("e")])),
+            ]
+        );
+        assert_eq!(
+            parse("REMOVE a SET b = if_not_exists(b, :v)").unwrap(),
+            vec![
+                Action::Remove(Path(vec![name("a")])),
+                Action::Set(
+                    Path(vec![name("b")]),
+                    Operand::IfNotExists(
+                        Path(vec![name("b")]),
+                        Box::new(Operand::Value(":v".to_string())),
+                    ),
+                ),
+            ]
+        );
+
+        // each section can only be used once, whatever its case
+        let err = parse("SET a = :v REMOVE c set e = :v").unwrap_err();
+        assert!(matches!(err, TableError::InvalidUpdateExpression(_)));
+        assert_eq!(
+            err.to_string(),
+            "Invalid UpdateExpression: The \"SET\" section can only be used once in an update \
+            expression;"
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in [
+            "",
+            "SET",
+            "SET a",
+            "SET a = ",
+            "REMOVE",
+            "a = :v",
+            "SET a = :v +",
+        ] {
+            assert!(
+                matches!(
+                    parse(expression),
+                    Err(TableError::InvalidUpdateExpression(_))
+                ),
+                "parsing {expression}"
+            );
+        }
+        assert!(matches!(
+            parse("ADD a :v"),
+            Err(TableError::NotImplemented(_))
+        ));
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let mut old = key();
+        old.insert("count".to_string(), n("1"));
+        old.insert("gone".to_string(), s("x"));
+        old.insert(
+            "nested".to_string(),
+            AttributeValue::M(
+                [("list".to_string(), AttributeValue::L(vec![s("a"), s("b")]))].into(),
+            ),
+        );
+
+        let update = apply(
+            Some(old),
+            "SET count = count + :one, #n.list[5] = :v, created = if_not_exists(created, :v) REMOVE gone",
+            &[(":one", n("1")), (":v", s("c"))],
+        )
+        .unwrap();
+        assert_eq!(update.new["count"], n("2"));
+        assert_eq!(update.new["created"], s("c"));
+        assert!(!update.new.contains_key("gone"));
+        assert_eq!(
+            update.new["nested"],
+            AttributeValue::M(
+                [(
+                    "list".to_string(),
+                    AttributeValue::L(vec![s("a"), s("b"), s("c")])
+                )]
+                .into()
+            )
+        );
+
+        assert_eq!(
+            update.attributes(types::ReturnValues::UpdatedOld),
+            Some(
+                [
+                    ("count".to_string(), n("1")),
+                    ("gone".to_string(), s("x")),
+                    (
+                        "nested".to_string(),
+                        update.old.as_ref().unwrap()["nested"].clone()
+                    ),
+                ]
+                .into()
+            )
+        );
+        assert_eq!(update.attributes(types::ReturnValues::None), None);
+    }
+
+    #[test]
+    fn remove_list_elements() {
+        let list = |elements: &[&str]| AttributeValue::L(elements.iter().map(|e| s(e)).collect());
+        let mut old = key();
+        old.insert("l".to_string(), list(&["a", "b", "c", "d"]));
+
+        for (expression, expected) in [
+            // indexes refer to the list before the update, whatever order they are removed in
+            ("REMOVE l[0], l[1]", list(&["c", "d"])),
+            ("REMOVE l[2], l[0]", list(&["b", "d"])),
+            ("SET l[3] = :v REMOVE l[0]", list(&["b", "c", "x"])),
+            // indexes past the end of the list are ignored
+            ("REMOVE l[7]", list(&["a", "b", "c", "d"])),
+            ("REMOVE l[1], l[4]", list(&["a", "c", "d"])),
+        ] {
+            let update = apply(Some(old.clone()), expression, &[(":v", s("x"))]).unwrap();
+            assert_eq!(update.new["l"], expected, "applying {expression}");
+        }
+    }
+
+    #[test]
+    fn new_item_from_key() {
+        let update = apply(None, "SET a = :v", &[(":v", s("x"))]).unwrap();
+        assert_eq!(
+            update.new,
+            [("pk".to_string(), s("abc")), ("a".to_string(), s("x"))].into()
+        );
+        assert_eq!(update.attributes(types::ReturnValues::AllOld), None);
+    }
+
+    #[test]
+    fn invalid_updates() {
+        for (expression, values) in [
+            ("SET pk = :v", vec![(":v", s("x"))]),
+            ("SET a = :missing", vec![]),
+            ("SET a = missing", vec![]),
+            ("SET a.b = :v", vec![(":v", s("x"))]),
+            ("SET a = :v, a = :v", vec![(":v", s("x"))]),
+            ("SET a = :v + :v", vec![(":v", s("x"))]),
+            ("SET a = list_append(:v, :v)", vec![(":v", s("x"))]),
+            ("SET #undefined = :v", vec![(":v", s("x"))]),
+        ] {
+            assert!(
+                matches!(
+                    apply(None, expression, &values),
+                    Err(TableError::InvalidUpdateExpression(_))
+                ),
+                "applying {expression}"
+            );
+        }
+
+        // invalid numbers are rejected, and only valid numbers with too many digits are reported
+        // as unsupported
+        let invalid = apply(
+            None,
+            "SET a = :v + :one",
+            &[(":v", n("abc")), (":one", n("1"))],
+        );
+        assert!(matches!(invalid, Err(TableError::InvalidNumber)));
+        let long = apply(
+            None,
+            "SET a = :v + :one",
+            &[(":v", n(&"9".repeat(60))), (":one", n("0.5"))],
+        );
+        assert!(matches!(long, Err(TableError::NotImplemented(_))));
+    }
+}
@@ -2,46 +2,77 @@ use std::collections::HashMap;
 
 use serde_dynamo::AttributeValue;
 
-use super::queries::Node;
+use super::{queries::Node, Result, TableError};
 
-pub fn walk_binop<V: Visitor + ?Sized>(v: &V, n: &mut Node) {
+pub fn walk_node<V: Visitor + ?Sized>(v: &V, n: &mut Node) -> Result<()> {
+    match n {
+        n @ Node::Binop { .. } => v.visit_binop(n),
+        n @ Node::Between { .. } => v.visit_between(n),
+        n @ Node::FunctionCall { .. } => v.visit_function_call(n),
+        n @ Node::Attribute(_) => v.visit_attribute(n),
+        n @ Node::Placeholder(_) => v.visit_placeholder(n),
+        n @ Node::Value(_) => v.visit_value(n),
+    }
+}
+
+pub fn walk_binop<V: Visitor + ?Sized>(v: &V, n: &mut Node) -> Result<()> {
     match n {
         Node::Binop { lhs, rhs, .. } => {
-            match lhs.as_mut() {
-                n @ Node::Binop { .. } => v.visit_binop(n),
-                n @ Node::FunctionCall { .. } => v.visit_function_call(n),
-                n @ Node::Attribute(_) => v.visit_attribute(n),
-                n @ Node::Placeholder(_) => v.visit_placeholder(n),
-            }
-            match rhs.as_mut() {
-                n @ Node::Binop { .. } => v.visit_binop(n),
-                n @ Node::FunctionCall { .. } => v.visit_function_call(n),
-                n @ Node::Attribute(_) => v.visit_attribute(n),
-                n @ Node::Placeholder(_) => v.visit_placeholder(n),
-            }
+            walk_node(v, lhs)?;
+            walk_node(v, rhs)
+        }
+        _ => unreachable!(),
+    }
+}
+pub fn walk_between<V: Visitor + ?Sized>(v: &V, n: &mut Node) -> Result<()> {
+    match n {
+        Node::Between { key, lower, upper } => {
+            walk_node(v, key)?;
+            walk_node(v, lower)?;
+            walk_node(v, upper)
         }
         _ => unreachable!(),
     }
 }
-pub fn walk_function_call<V: Visitor + ?Sized>(_: &V, _: &mut Node) {}
-pub fn walk_attribute<V: Visitor + ?Sized>(_: &V, _: &mut Node) {}
-pub fn walk_placeholder<V: Visitor + ?Sized>(_: &V, _: &mut Node) {}
+pub fn walk_function_call<V: Visitor + ?Sized>(v: &V, n: &mut Node) -> Result<()> {
+    match n {
+        Node::FunctionCall { args, .. } => args.iter_mut().try_for_each(|arg| walk_node(v, arg)),
+        _ => unreachable!(),
+    }
+}
+pub fn walk_attribute<V: Visitor + ?Sized>(_: &V, _: &mut Node) -> Result<()> {
+    Ok(())
+}
+pub fn walk_placeholder<V: Visitor + ?Sized>(_: &V, _: &mut Node) -> Result<()> {
+    Ok(())
+}
+pub fn walk_value<V: Visitor + ?Sized>(_: &V, _: &mut Node) -> Result<()> {
+    Ok(())
+}
 
 pub trait Visitor {
-    fn visit_binop(&self, n: &mut Node) {
-        walk_binop(self, n);
+    fn visit_binop(&self, n: &mut Node) -> Result<()> {
+        walk_binop(self, n)
     }
 
-    fn visit_function_call(&self, n: &mut Node) {
-        walk_function_call(self, n);
+    fn visit_between(&self, n: &mut Node) -> Result<()> {
+        walk_between(self, n)
     }
 
-    fn visit_attribute(&self, n: &mut Node) {
-        walk_attribute(self, n);
+    fn visit_function_call(&self, n: &mut Node) -> Result<()> {
+        walk_function_call(self, n)
     }
 
-    fn visit_placeholder(&self, n: &mut Node) {
-        walk_placeholder(self, n);
+    fn visit_attribute(&self, n: &mut Node) -> Result<()> {
+        walk_attribute(self, n)
+    }
+
+    fn visit_placeholder(&self, n: &mut Node) -> Result<()> {
+        walk_placeholder(self, n)
+    }
+
+    fn visit_value(&self, n: &mut Node) -> Result<()> {
+        walk_value(self, n)
     }
 }
 
@@ -62,48 +93,35 @@ impl<'a> NodeVisitor<'a> {
         }
     }
 
-    #[allow(clippy::needless_borrow)]
-    pub fn visit(&self, mut ast: Node) -> Node {
-        match &mut ast {
-            mut n @ Node::Binop { .. } => self.visit_binop(&mut n),
-            mut n @ Node::FunctionCall { .. } => self.visit_function_call(&mut n),
-            mut n @ Node::Attribute(_) => self.visit_attribute(&mut n),
-            mut n @ Node::Placeholder(_) => self.visit_placeholder(&mut n),
-        }
-        ast
+    pub fn visit(&self, mut ast: Node) -> Result<Node> {
+        walk_node(self, &mut ast)?;
+        Ok(ast)
     }
 }
 
 impl<'a> Visitor for NodeVisitor<'a> {
-    fn visit_placeholder(&self, n: &mut Node) {
-        // convert the placeholder to attribute
-        let key = n.as_str().unwrap();
-
-        let name_key = format!("#{key}");
-        let value_key = format!(":{key}");
-
-        if let Some(value) = self
-            .expression_attribute_names
-            .as_ref()
-            .and_then(|names| names.get(name_key.as_str()))
-        {
-            *n = Node::Attribute(value.to_string());
-            return;
-        }
-
-        if let Some(possible_values) = self
-            .expression_attribute_values
-            .as_ref()
-            .and_then(|values| values.get(&value_key))
-        {
-            match possible_values {
-                AttributeValue::S(s) => *n = Node::Attribute(s.clone()),
-                _ => todo!(),
-            }
-            return;
+    fn visit_placeholder(&self, n: &mut Node) -> Result<()> {
+        let key = n.as_str()?.to_string();
+
+        if key.starts_with('#') {
+            // convert the placeholder to attribute
+            let name = self
+                .expression_attribute_names
+                .as_ref()
+                .and_then(|names| names.get(&key))
+                .ok_or(TableError::NoAttributeName(key))?;
+            *n = Node::Attribute(name.to_string());
+        } else {
+            // keep the type information so that e.g. numbers compare numerically
+            let value = self
+                .expression_attribute_values
+                .as_ref()
+                .and_then(|values| values.get(&key))
+                .ok_or(TableError::NoAttributeValue(key))?;
+            *n = Node::Value(value.clone());
         }
 
-        unreachable!()
+        Ok(())
     }
 }
 
@@ -117,13 +135,13 @@ mod tests {
     fn visit_all_nodes() {
         let ast = Node::Binop {
             lhs: Box::new(Node::Binop {
-                lhs: Box::new(Node::Placeholder("a".to_string())),
-                rhs: Box::new(Node::Placeholder("b".to_string())),
+                lhs: Box::new(Node::Placeholder("#a".to_string())),
+                rhs: Box::new(Node::Placeholder(":b".to_string())),
                 op: Operator::Eq,
             }),
             rhs: Box::new(Node::Binop {
-                lhs: Box::new(Node::Placeholder("c".to_string())),
-                rhs: Box::new(Node::Placeholder("d".to_string())),
+                lhs: Box::new(Node::Placeholder("#c".to_string())),
+                rhs: Box::new(Node::Placeholder(":d".to_string())),
                 op: Operator::Eq,
             }),
             op: Operator::And,
@@ -139,27 +157,76 @@ mod tests {
         let expression_attribute_values = {
             let mut h = HashMap::new();
             h.insert(":b".to_string(), AttributeValue::S("f".to_string()));
-            h.insert(":d".to_string(), AttributeValue::S("h".to_string()));
+            h.insert(":d".to_string(), AttributeValue::N("8".to_string()));
             Some(h)
         };
 
         let visitor = NodeVisitor::new(&expression_attribute_names, &expression_attribute_values);
-        let new_ast = visitor.visit(ast);
+        let new_ast = visitor.visit(ast).unwrap();
         assert_eq!(
             new_ast,
             Node::Binop {
                 lhs: Box::new(Node::Binop {
                     lhs: Box::new(Node::Attribute("e".to_string())),
-                    rhs: Box::new(Node::Attribute("f".to_string())),
+                    rhs: Box::new(Node::Value(AttributeValue::S("f".to_string()))),
                     op: Operator::Eq,
                 }),
                 rhs: Box::new(Node::Binop {
                     lhs: Box::new(Node::Attribute("g".to_string())),
-                    rhs: Box::new(Node::Attribute("h".to_string())),
+                    rhs: Box::new(Node::Value(AttributeValue::N("8".to_string()))),
                     op: Operator::Eq,
                 }),
                 op: Operator::And,
             }
         );
     }
+
+    #[test]
+    fn visit_function_arguments() {
+        let ast = Node::FunctionCall {
+            name: "begins_with".to_string(),
+            args: vec![
+                Node::Placeholder("#a".to_string()),
+                Node::Placeholder(":b".to_string()),
+            ],
+        };
+        let expression_attribute_names = Some([("#a".to_string(), "sk".to_string())].into());
+        let expression_attribute_values =
+            Some([(":b".to_string(), AttributeValue::S("x".to_string()))].into());
+
+        let visitor = NodeVisitor::new(&expression_attribute_names, &expression_attribute_values);
+        assert_eq!(
+            visitor.visit(ast).unwrap(),
+            Node::FunctionCall {
+                name: "begins_with".to_string(),
+                args: vec![
+                    Node::Attribute("sk".to_string()),
+                    Node::Value(AttributeValue::S("x".to_string())),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn missing_placeholders() {
+        let ast = Node::Binop {
+            lhs: Box::new(Node::Placeholder("#a".to_string())),
+            rhs: Box::new(Node::Placeholder(":b".to_string())),
+            op: Operator::Eq,
+        };
+
+        let visitor = NodeVisitor::new(&None, &None);
+        assert!(matches!(
+            visitor.visit(ast.clone()),
+            Err(TableError::NoAttributeName(name)) if name == "#a"
+        ));
+
+        let expression_attribute_names =
+            Some([("#a".to_string(), "pk".to_string())].into_iter().collect());
+        let visitor = NodeVisitor::new(&expression_attribute_names, &None);
+        assert!(matches!(
+            visitor.visit(ast),
+            Err(TableError::NoAttributeValue(name)) if name == ":b"
+        ));
+    }
 }
@@ -1,92 +1,497 @@
-use eyre::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
 
-use crate::{table, types};
+use crate::{
+    error::Result,
+    events::{Events, TableEvent},
+    hot_keys::HotKeys,
+    limits::Limits,
+    metrics::Metrics,
+    namespaces::Namespaces,
+    providers::Providers,
+    scheduler::Scheduler,
+    table::{self, TableError, TableStatus},
+    types,
+};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// Regions of the `aws` partition which DynamoDB is available in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Region {
+    #[default]
     UsEast1,
+    UsEast2,
+    UsWest1,
+    UsWest2,
+    AfSouth1,
+    ApEast1,
+    ApSouth1,
+    ApSouth2,
+    ApSoutheast1,
+    ApSoutheast2,
+    ApSoutheast3,
+    ApSoutheast4,
+    ApNortheast1,
+    ApNortheast2,
+    ApNortheast3,
+    CaCentral1,
+    CaWest1,
+    EuCentral1,
+    EuCentral2,
+    EuWest1,
+    EuWest2,
+    EuWest3,
+    EuSouth1,
+    EuSouth2,
+    EuNorth1,
+    IlCentral1,
+    MeCentral1,
+    MeSouth1,
+    SaEast1,
 }
 
-impl Default for Region {
-    fn default() -> Self {
-        Self::UsEast1
+impl Region {
+    const ALL: [Region; 29] = [
+        Region::UsEast1,
+        Region::UsEast2,
+        Region::UsWest1,
+        Region::UsWest2,
+        Region::AfSouth1,
+        Region::ApEast1,
+        Region::ApSouth1,
+        Region::ApSouth2,
+        Region::ApSoutheast1,
+        Region::ApSoutheast2,
+        Region::ApSoutheast3,
+        Region::ApSoutheast4,
+        Region::ApNortheast1,
+        Region::ApNortheast2,
+        Region::ApNortheast3,
+        Region::CaCentral1,
+        Region::CaWest1,
+        Region::EuCentral1,
+        Region::EuCentral2,
+        Region::EuWest1,
+        Region::EuWest2,
+        Region::EuWest3,
+        Region::EuSouth1,
+        Region::EuSouth2,
+        Region::EuNorth1,
+        Region::IlCentral1,
+        Region::MeCentral1,
+        Region::MeSouth1,
+        Region::SaEast1,
+    ];
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Region::UsEast1 => "us-east-1",
+            Region::UsEast2 => "us-east-2",
+            Region::UsWest1 => "us-west-1",
+            Region::UsWest2 => "us-west-2",
+            Region::AfSouth1 => "af-south-1",
+            Region::ApEast1 => "ap-east-1",
+            Region::ApSouth1 => "ap-south-1",
+            Region::ApSouth2 => "ap-south-2",
+            Region::ApSoutheast1 => "ap-southeast-1",
+            Region::ApSoutheast2 => "ap-southeast-2",
+            Region::ApSoutheast3 => "ap-southeast-3",
+            Region::ApSoutheast4 => "ap-southeast-4",
+            Region::ApNortheast1 => "ap-northeast-1",
+            Region::ApNortheast2 => "ap-northeast-2",
+            Region::ApNortheast3 => "ap-northeast-3",
+            Region::CaCentral1 => "ca-central-1",
+            Region::CaWest1 => "ca-west-1",
+            Region::EuCentral1 => "eu-central-1",
+            Region::EuCentral2 => "eu-central-2",
+            Region::EuWest1 => "eu-west-1",
+            Region::EuWest2 => "eu-west-2",
+            Region::EuWest3 => "eu-west-3",
+            Region::EuSouth1 => "eu-south-1",
+            Region::EuSouth2 => "eu-south-2",
+            Region::EuNorth1 => "eu-north-1",
+            Region::IlCentral1 => "il-central-1",
+            Region::MeCentral1 => "me-central-1",
+            Region::MeSouth1 => "me-south-1",
+            Region::SaEast1 => "sa-east-1",
+        }
     }
 }
 
 impl fmt::Display for Region {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Region::UsEast1 => write!(f, "us-east-1"),
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Region::ALL
+            .into_iter()
+            .find(|region| region.as_str() == s)
+            .ok_or_else(|| format!("unsupported region {s}"))
+    }
+}
+
+/// Simulate batch operations only partially succeeding, as AWS does under load, so that client
+/// retry loops are exercised
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnprocessedSimulation {
+    /// probability that each entry in a batch is returned unprocessed
+    pub probability: f64,
+    /// batches with fewer entries than this are always fully processed
+    pub min_batch_size: usize,
+}
+
+impl UnprocessedSimulation {
+    fn defer(&self, batch_size: usize) -> bool {
+        batch_size >= self.min_batch_size && fastrand::f64() < self.probability
+    }
+}
+
+/// Simulate tables taking time to be created and deleted, as AWS does. By default tables are
+/// created and deleted instantly.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LifecycleSimulation {
+    /// time a new table spends in the `CREATING` state
+    pub creation_delay: Duration,
+    /// time a deleted table spends in the `DELETING` state before it is removed
+    pub deletion_delay: Duration,
+    /// time a global secondary index added to an existing table spends backfilling
+    pub index_backfill_delay: Duration,
+}
+
+/// Deletion of items whose time to live has passed
+///
+/// DynamoDB deletes expired items in the background, some time after they expire. By default
+/// items are only deleted when a sweep is requested at `/_rynamodb/ttl/sweep`, so that tests are
+/// deterministic.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeToLiveSimulation {
+    /// time between background sweeps for expired items
+    pub sweep_interval: Option<Duration>,
+}
+
+/// Settings of new tables which are not given in the `CreateTable` request
+#[derive(Debug, Clone, Copy)]
+pub struct TableDefaults {
+    pub read_capacity_units: u64,
+    pub write_capacity_units: u64,
+    /// encrypt tables with an AWS managed KMS key rather than an AWS owned key
+    pub kms_encryption: bool,
+}
+
+impl Default for TableDefaults {
+    fn default() -> Self {
+        Self {
+            read_capacity_units: 10,
+            write_capacity_units: 10,
+            kms_encryption: false,
         }
     }
 }
 
+#[derive(Debug, Error)]
+pub enum LimitExceeded {
+    #[error("Subscriber limit exceeded: Only {0} tables can be created for this account")]
+    Tables(usize),
+    #[error(
+        "Subscriber limit exceeded: Only {0} tables can be created, updated, or deleted simultaneously"
+    )]
+    ConcurrentOperations(usize),
+}
+
+fn to_chrono(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::max_value())
+}
+
 /// Handle the creation and destruction of tables
-#[derive(Default)]
 pub struct TableManager {
     // map from account to the tables in that account broken down by region
     pub per_account: HashMap<String, TablesPerRegion>,
+    /// account and region which new tables are created in
+    pub account_id: String,
+    pub region: Region,
+    pub unprocessed_simulation: UnprocessedSimulation,
+    pub lifecycle: LifecycleSimulation,
+    pub limits: Limits,
+    pub defaults: TableDefaults,
+    /// see [`crate::RynamodbConfig::idempotent_create_table`]
+    pub idempotent_create_table: bool,
+    /// changes to the tables, see [`TableManager::subscribe`]
+    pub events: Events,
+    /// source of the ids and creation times of new tables
+    pub(crate) providers: Providers,
+}
+
+impl Default for TableManager {
+    fn default() -> Self {
+        Self {
+            per_account: HashMap::new(),
+            account_id: crate::DEFAULT_ACCOUNT_ID.to_string(),
+            region: Region::default(),
+            unprocessed_simulation: Default::default(),
+            lifecycle: Default::default(),
+            limits: Default::default(),
+            defaults: Default::default(),
+            idempotent_create_table: false,
+            events: Events::default(),
+            providers: Providers::default(),
+        }
+    }
 }
 
 impl TableManager {
-    pub fn new_table(
+    /// A manager with the same settings and no tables
+    pub fn without_tables(&self) -> Self {
+        Self {
+            per_account: HashMap::new(),
+            account_id: self.account_id.clone(),
+            region: self.region,
+            unprocessed_simulation: self.unprocessed_simulation,
+            lifecycle: self.lifecycle,
+            limits: self.limits,
+            defaults: self.defaults,
+            idempotent_create_table: self.idempotent_create_table,
+            // the tables of each manager have their own subscribers
+            events: Events::default(),
+            providers: self.providers.clone(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn new_table(&mut self, options: table::TableOptions) -> Result<table::Table> {
+        self.new_table_in(self.region, options)
+    }
+
+    /// Create a table in a region other than the one new tables are created in by default
+    pub fn new_table_in(
         &mut self,
-        account: impl Into<String>,
         region: Region,
-        input: types::CreateTableInput,
+        mut options: table::TableOptions,
     ) -> Result<table::Table> {
-        let account_id = account.into();
-        let table = table::Table::new(region, &account_id, input.into());
+        if options.sse_specification.is_none() && self.defaults.kms_encryption {
+            options.sse_specification = Some(types::SseSpecification {
+                enabled: Some(true),
+                sse_type: Some("KMS".to_string()),
+                kms_master_key_id: None,
+            });
+        }
+        let mut table =
+            table::Table::with_providers(region, &self.account_id, options, self.providers.clone());
+        table.provisioned_throughput = types::ProvisionedThroughputDescription::with_capacity(
+            self.defaults.read_capacity_units,
+            self.defaults.write_capacity_units,
+        );
+        self.insert_table(table)
+    }
 
-        let entry = self.per_account.entry(account_id).or_default();
-        entry.tables.entry(region).or_default().push(table.clone());
+    /// Add an already constructed table, e.g. one restored from a backup
+    ///
+    /// Tables are looked up by name within a region, so a table is not added if one with the same
+    /// name exists in its region, in any account.
+    pub fn insert_table(&mut self, mut table: table::Table) -> Result<table::Table> {
+        self.remove_deleted_tables();
+        if self.get_table_in(table.region(), &table.name).is_some() {
+            return Err(TableError::TableAlreadyExists(table.name).into());
+        }
+        let count = self.stored_table_count();
+        table.delay_activation(to_chrono(self.lifecycle.creation_delay));
+        table.set_events(self.events.clone());
+        self.events.send(TableEvent::TableCreated {
+            table_name: table.name.clone(),
+            region: table.region(),
+        });
+        // filed under the table's own account, which an imported table may not share
+        let entry = self
+            .per_account
+            .entry(table.account_id().to_string())
+            .or_default();
+        entry
+            .tables
+            .entry(table.region())
+            .or_default()
+            .push(table.clone());
         tracing::debug!(table_name = %table.name, "created table");
+        self.debug_check_invariants(Some(count + 1));
         Ok(table)
     }
 
+    /// Copy an active table of the region new tables are created in to a new name in the same
+    /// account and region, optionally removing the original so that the table is renamed
+    pub fn copy_table(
+        &mut self,
+        source_name: &str,
+        target_name: &str,
+        remove_source: bool,
+    ) -> Option<table::Table> {
+        let count = self.stored_table_count();
+        let expected_count = if remove_source { count } else { count + 1 };
+        let region = self.region;
+        for account in self.per_account.values_mut() {
+            if let Some(tables) = account.tables.get_mut(&region) {
+                let Some(position) = tables.iter().position(|table| {
+                    table.name == source_name && table.status() == TableStatus::Active
+                }) else {
+                    continue;
+                };
+
+                let copy = tables[position].copy_as(target_name);
+                let region = copy.region();
+                if remove_source {
+                    tables.remove(position);
+                    self.events.send(TableEvent::TableDeleted {
+                        table_name: source_name.to_string(),
+                        region,
+                    });
+                }
+                self.events.send(TableEvent::TableCreated {
+                    table_name: target_name.to_string(),
+                    region,
+                });
+                tables.push(copy.clone());
+                tracing::debug!(%source_name, %target_name, %remove_source, "copied table");
+                self.debug_check_invariants(Some(expected_count));
+                return Some(copy);
+            }
+        }
+        None
+    }
+
+    pub fn get_table_by_arn(&self, table_arn: &str) -> Option<&table::Table> {
+        self.per_account
+            .values()
+            .flat_map(|account| account.tables.values())
+            .flatten()
+            .find(|table| table.arn == table_arn && table.status() != TableStatus::Deleted)
+    }
+
+    /// Look up a table in the region new tables are created in by default
     pub fn get_table(&self, table_name: &str) -> Option<&table::Table> {
+        self.get_table_in(self.region, table_name)
+    }
+
+    /// Look up a table in a region, in any account. Tables in different regions can share a name.
+    pub fn get_table_in(&self, region: Region, table_name: &str) -> Option<&table::Table> {
         for account in self.per_account.values() {
-            for tables in account.tables.values() {
-                for table in tables {
-                    tracing::trace!(created_table_name = %table.name, requested_table_name = %table_name, "checking table name");
-                    if table.name == table_name {
-                        return Some(table);
-                    }
+            for table in account.tables.get(&region).into_iter().flatten() {
+                tracing::trace!(created_table_name = %table.name, requested_table_name = %table_name, "checking table name");
+                if table.name == table_name && table.status() != TableStatus::Deleted {
+                    return Some(table);
                 }
             }
         }
 
-        tracing::debug!(%table_name, "could not find table");
+        tracing::debug!(%table_name, %region, "could not find table");
 
         None
     }
 
-    pub fn get_table_mut(&mut self, table_name: &str) -> Option<&mut table::Table> {
+    pub fn get_table_mut_in(
+        &mut self,
+        region: Region,
+        table_name: &str,
+    ) -> Option<&mut table::Table> {
         let mut count = 0;
         for account in self.per_account.values_mut() {
-            for tables in account.tables.values_mut() {
-                for table in tables {
-                    tracing::trace!(created_table_name = %table.name, requested_table_name = %table_name, "checking table name");
-                    if table.name == table_name {
-                        return Some(table);
-                    }
-                    count += 1;
+            for table in account.tables.get_mut(&region).into_iter().flatten() {
+                tracing::trace!(created_table_name = %table.name, requested_table_name = %table_name, "checking table name");
+                if table.name == table_name && table.status() != TableStatus::Deleted {
+                    return Some(table);
                 }
+                count += 1;
             }
         }
 
-        tracing::debug!(%table_name, checked = %count, "could not find table");
+        tracing::debug!(%table_name, %region, checked = %count, "could not find table");
 
         None
     }
 
+    /// Check whether a control plane operation is allowed, given the tables which already
+    /// exist in the account. Pass `creates_table` for operations which add a table.
+    pub fn check_limits(&self, creates_table: bool) -> Result<()> {
+        let statuses: Vec<_> = self
+            .per_account
+            .get(&self.account_id)
+            .into_iter()
+            .flat_map(|account| account.tables.values())
+            .flatten()
+            .map(table::Table::status)
+            .filter(|status| *status != TableStatus::Deleted)
+            .collect();
+
+        if creates_table && statuses.len() >= self.limits.max_tables {
+            return Err(LimitExceeded::Tables(self.limits.max_tables).into());
+        }
+
+        let in_progress = statuses
+            .iter()
+            .filter(|status| matches!(status, TableStatus::Creating | TableStatus::Deleting))
+            .count();
+        if in_progress >= self.limits.max_concurrent_control_plane_operations {
+            return Err(LimitExceeded::ConcurrentOperations(
+                self.limits.max_concurrent_control_plane_operations,
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    pub fn index_backfill_delay(&self) -> chrono::Duration {
+        to_chrono(self.lifecycle.index_backfill_delay)
+    }
+
+    /// Look up a table which can serve reads and writes. Tables which are being created or
+    /// deleted are not visible to data plane operations.
+    pub fn get_active_table(&self, table_name: &str) -> Option<&table::Table> {
+        self.get_active_table_in(self.region, table_name)
+    }
+
+    pub fn get_active_table_in(&self, region: Region, table_name: &str) -> Option<&table::Table> {
+        self.get_table_in(region, table_name)
+            .filter(|table| table.status() == TableStatus::Active)
+    }
+
+    pub fn get_active_table_mut(&mut self, table_name: &str) -> Option<&mut table::Table> {
+        self.get_active_table_mut_in(self.region, table_name)
+    }
+
+    pub fn get_active_table_mut_in(
+        &mut self,
+        region: Region,
+        table_name: &str,
+    ) -> Option<&mut table::Table> {
+        self.get_table_mut_in(region, table_name)
+            .filter(|table| table.status() == TableStatus::Active)
+    }
+
+    /// Iterate over the tables which can serve reads and writes
+    pub fn active_tables(&self) -> impl Iterator<Item = &table::Table> {
+        self.per_account
+            .values()
+            .flat_map(|account| account.tables.values())
+            .flatten()
+            .filter(|table| table.status() == TableStatus::Active)
+    }
+
     pub fn table_names(&self) -> Vec<String> {
+        self.table_names_in(self.region)
+    }
+
+    /// Names of the tables in a region, in every account
+    pub fn table_names_in(&self, region: Region) -> Vec<String> {
         let mut table_names = Vec::new();
         for account in self.per_account.values() {
-            for tables in account.tables.values() {
-                for table in tables {
+            for table in account.tables.get(&region).into_iter().flatten() {
+                if table.status() != TableStatus::Deleted {
                     table_names.push(table.name.clone());
                 }
             }
@@ -94,77 +499,514 @@ impl TableManager {
         table_names
     }
 
-    pub fn delete_table(&mut self, table_name: &str) -> Result<()> {
+    /// Start deleting a table in a region, which is removed once the simulated deletion delay has
+    /// passed
+    pub fn delete_table_in(&mut self, region: Region, table_name: &str) -> Result<()> {
+        let deletion_delay = to_chrono(self.lifecycle.deletion_delay);
+        if let Some(table) = self.get_table_mut_in(region, table_name) {
+            table.start_deletion(deletion_delay);
+            let region = table.region();
+            self.events.send(TableEvent::TableDeleted {
+                table_name: table_name.to_string(),
+                region,
+            });
+        }
+        self.remove_deleted_tables();
+        // other tables may finish deleting meanwhile, so the count cannot be predicted
+        self.debug_check_invariants(None);
+        Ok(())
+    }
+
+    /// When every simulated transition in progress, e.g. table creation or index backfills, has
+    /// completed. `None` if nothing is pending.
+    pub fn settles_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.per_account
+            .values()
+            .flat_map(|account| account.tables.values())
+            .flatten()
+            .filter_map(table::Table::settles_at)
+            .max()
+    }
+
+    fn remove_deleted_tables(&mut self) {
         for account in self.per_account.values_mut() {
-            account.remove(table_name);
+            for tables in account.tables.values_mut() {
+                tables.retain(|table| table.status() != TableStatus::Deleted);
+            }
+        }
+    }
+
+    /// Number of tables held, including those being deleted, in every account and region
+    fn stored_table_count(&self) -> usize {
+        self.per_account
+            .values()
+            .flat_map(|account| account.tables.values())
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Check that every table is stored under the account and region in its ARN, and that no two
+    /// tables of an account and region share a name, as lookups by name would find only one of
+    /// them
+    pub fn check_invariants(&self) -> std::result::Result<(), String> {
+        for (account_id, account) in &self.per_account {
+            for (region, tables) in &account.tables {
+                let mut names = HashSet::new();
+                for table in tables {
+                    let arn = format!(
+                        "arn:aws:dynamodb:{region}:{account_id}:table/{}",
+                        table.name
+                    );
+                    if table.arn != arn || table.region() != *region {
+                        return Err(format!(
+                            "table {} with ARN {} is stored as {arn}",
+                            table.name, table.arn
+                        ));
+                    }
+                    if table.status() != TableStatus::Deleted && !names.insert(&table.name) {
+                        return Err(format!(
+                            "more than one table in {account_id} {region} is named {}",
+                            table.name
+                        ));
+                    }
+                }
+            }
         }
         Ok(())
     }
 
+    /// Check the invariants after a mutation, and that it leaves `expected_count` tables, in
+    /// debug builds, so that tables lost or aliased by a change fail the tests that make it
+    fn debug_check_invariants(&self, expected_count: Option<usize>) {
+        if cfg!(debug_assertions) {
+            if let Err(violation) = self.check_invariants() {
+                panic!("table manager invariant violated: {violation}");
+            }
+            let count = self.stored_table_count();
+            if let Some(expected_count) = expected_count {
+                assert_eq!(
+                    count, expected_count,
+                    "table manager holds {count} tables, expected {expected_count}"
+                );
+            }
+        }
+    }
+
+    /// Receive the changes made to the tables from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<TableEvent> {
+        self.events.subscribe()
+    }
+
+    /// Write a batch of items to the tables of a region, returning the requests which were not
+    /// processed
     pub fn batch_write_item(
         &mut self,
+        region: Region,
         input: types::BatchWriteInput,
+        hot_keys: &HotKeys,
     ) -> HashMap<String, Vec<types::BatchPutRequest>> {
+        let unprocessed_simulation = self.unprocessed_simulation;
+        let batch_size: usize = input.request_items.values().map(Vec::len).sum();
+
+        // items are moved into the tables, and only the requests which are not processed are
+        // kept to be returned
         let mut unprocessed_items: HashMap<String, Vec<_>> = HashMap::new();
-        for (table_name, put_request) in input.request_items.into_iter() {
-            match self.get_table_mut(&table_name) {
-                Some(table) => {
-                    tracing::debug!(%table_name, "got table");
-                    for req in put_request {
-                        let item = req.put_request.item.clone();
-                        match table.insert(item.clone()) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                tracing::warn!(error = %e, "could not insert item");
-                                unprocessed_items
-                                    .entry(table_name.clone())
-                                    .or_default()
-                                    .push(req.clone());
-                            }
-                        }
+        for (table_name, put_requests) in input.request_items.into_iter() {
+            let Some(table) = self.get_active_table_mut_in(region, &table_name) else {
+                tracing::warn!(%table_name, "could not find table");
+                unprocessed_items.insert(table_name, put_requests);
+                continue;
+            };
+            tracing::debug!(%table_name, "got table");
+
+            let mut unprocessed = Vec::new();
+            for req in put_requests {
+                if unprocessed_simulation.defer(batch_size) {
+                    tracing::debug!(%table_name, "simulating unprocessed item");
+                    unprocessed.push(req);
+                    continue;
+                }
+
+                if let Err(e) = table.validate_item(&req.put_request.item) {
+                    tracing::warn!(error = %e, "could not insert item");
+                    unprocessed.push(req);
+                    continue;
+                }
+                let write = hot_keys.measure(table, &req.put_request.item);
+                match table.insert(req.put_request.item) {
+                    Ok(()) => hot_keys.record(&table_name, write),
+                    // unreachable, as the item has been validated
+                    Err(e) => tracing::error!(error = %e, "could not insert validated item"),
+                }
+            }
+            if !unprocessed.is_empty() {
+                unprocessed_items.insert(table_name, unprocessed);
+            }
+        }
+        unprocessed_items
+    }
+
+    /// Read the keys of a batch from the tables of a region, leaving keys unprocessed once the
+    /// response reaches [`Limits::max_batch_get_response_bytes`]
+    pub fn batch_get_item(
+        &self,
+        region: Region,
+        input: types::BatchGetItemInput,
+    ) -> Result<types::BatchGetItemOutput> {
+        let batch_size: usize = input
+            .request_items
+            .values()
+            .map(|request| request.keys.len())
+            .sum();
+
+        // tables are read in name order, and their keys in the order requested, so that the keys
+        // left unprocessed by the size limit are always the same
+        let mut request_items: Vec<_> = input.request_items.into_iter().collect();
+        request_items.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut output = types::BatchGetItemOutput::default();
+        let mut response_bytes = 0;
+        let mut full = false;
+        for (table_name, request) in request_items {
+            let Some(table) = self.get_active_table_in(region, &table_name) else {
+                tracing::warn!(%table_name, "could not find table");
+                continue;
+            };
+
+            let mut unprocessed = Vec::new();
+            for key in request.keys {
+                if full {
+                    unprocessed.push(key);
+                    continue;
+                }
+                if self.unprocessed_simulation.defer(batch_size) {
+                    tracing::debug!(%table_name, "simulating unprocessed key");
+                    unprocessed.push(key);
+                    continue;
+                }
+
+                let responses = output.responses.entry(table_name.clone()).or_default();
+                if let Some(item) = table.get_item(key.clone())? {
+                    let item = match request.attributes_to_get.as_deref() {
+                        Some(attributes) => table::legacy::project(item, attributes),
+                        None => item,
+                    };
+                    let item_size = table::size::item_size(&item);
+                    if response_bytes + item_size > self.limits.max_batch_get_response_bytes {
+                        tracing::debug!(%table_name, %response_bytes, "batch get response is full");
+                        full = true;
+                        unprocessed.push(key);
+                        continue;
                     }
+                    response_bytes += item_size;
+                    responses.push(item);
                 }
-                None => {
-                    tracing::warn!(%table_name, "could not find table");
-                    for req in put_request {
-                        unprocessed_items
-                            .entry(table_name.clone())
-                            .or_default()
-                            .push(req.clone());
+            }
+            if !unprocessed.is_empty() {
+                output.unprocessed_keys.insert(
+                    table_name,
+                    types::KeysAndAttributes {
+                        keys: unprocessed,
+                        ..request
+                    },
+                );
+            }
+        }
+        Ok(output)
+    }
+
+    /// Delete the expired items of every active table, returning how many were deleted
+    pub fn expire_items(&mut self) -> usize {
+        let now = self.providers.clock.now();
+        let mut expired = 0;
+        for account in self.per_account.values_mut() {
+            for tables in account.tables.values_mut() {
+                for table in tables {
+                    if table.status() == TableStatus::Active {
+                        expired += table.expire_items(now);
                     }
                 }
             }
         }
-        unprocessed_items
+        expired
     }
 
-    pub fn len(&self) -> usize {
-        let mut count = 0;
-        for account in self.per_account.values() {
-            count += account.tables.len();
+    /// Release the memory left unused by removed items in every table, returning roughly how
+    /// many bytes were reclaimed
+    pub fn compact(&mut self) -> usize {
+        self.remove_deleted_tables();
+        let mut reclaimed = 0;
+        for account in self.per_account.values_mut() {
+            for tables in account.tables.values_mut() {
+                for table in tables {
+                    reclaimed += table.compact();
+                }
+            }
         }
-        count
+        reclaimed
     }
 }
 
+/// Periodically delete expired items in the background, including those in namespaces
+pub fn schedule_expiry(
+    scheduler: &Scheduler,
+    manager: Arc<RwLock<TableManager>>,
+    namespaces: Arc<Namespaces>,
+    sweep_interval: Duration,
+) {
+    scheduler.every("time-to-live-sweep", sweep_interval, move || {
+        let mut expired = 0;
+        for manager in std::iter::once(Arc::clone(&manager)).chain(namespaces.managers()) {
+            if let Ok(mut manager) = manager.write() {
+                expired += manager.expire_items();
+            }
+        }
+        if expired > 0 {
+            tracing::debug!(%expired, "deleted expired items");
+        }
+        async {}
+    });
+}
+
+/// Periodically compact the tables in the background, including those in namespaces
+pub fn schedule_compaction(
+    scheduler: &Scheduler,
+    manager: Arc<RwLock<TableManager>>,
+    namespaces: Arc<Namespaces>,
+    metrics: Arc<Metrics>,
+    compaction_interval: Duration,
+) {
+    scheduler.every("compaction", compaction_interval, move || {
+        let mut reclaimed = 0;
+        for manager in std::iter::once(Arc::clone(&manager)).chain(namespaces.managers()) {
+            if let Ok(mut manager) = manager.write() {
+                reclaimed += manager.compact();
+            }
+        }
+        metrics.record_compaction(reclaimed);
+        if reclaimed > 0 {
+            tracing::debug!(%reclaimed, "compacted tables");
+        }
+        async {}
+    });
+}
+
 #[derive(Default)]
 pub struct TablesPerRegion {
     // map from region to table
     pub tables: HashMap<Region, Vec<table::Table>>,
 }
 
-impl TablesPerRegion {
-    fn remove(&mut self, table_name: &str) {
-        // wow inefficient...
-        let mut new = HashMap::new();
-        for (region, tables) in self.tables.iter() {
-            let new_tables: Vec<_> = tables
-                .iter()
-                .cloned()
-                .filter(|table| table.name != table_name)
-                .collect();
-            new.insert(region.clone(), new_tables);
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::table::TableOptions;
+
+    const REGIONS: [Region; 3] = [Region::UsEast1, Region::EuWest1, Region::ApNortheast1];
+
+    fn options(name: &str) -> TableOptions {
+        TableOptions {
+            name: name.to_string(),
+            partition_key: "pk".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create, copy, rename, restore and delete tables at random in several regions, checking
+    /// after every change that each table can be found under its own name, region and ARN
+    #[test]
+    fn random_table_changes_keep_tables_distinct() {
+        for seed in 0..20 {
+            let rng = fastrand::Rng::with_seed(seed);
+            let mut manager = TableManager::default();
+            // the names of the tables which should exist in each region
+            let mut expected: HashMap<Region, BTreeSet<String>> = HashMap::new();
+
+            for _ in 0..200 {
+                let region = REGIONS[rng.usize(..REGIONS.len())];
+                let name = format!("table-{}", rng.usize(..8));
+                let other = format!("table-{}", rng.usize(..8));
+                let names = expected.entry(region).or_default();
+                let exists = names.contains(&name);
+                match rng.usize(..5) {
+                    0 => {
+                        let created = manager.new_table_in(region, options(&name));
+                        assert_eq!(created.is_ok(), !exists, "seed {seed}: create {name}");
+                        names.insert(name);
+                    }
+                    1 => {
+                        manager.delete_table_in(region, &name).unwrap();
+                        names.remove(&name);
+                    }
+                    op @ (2 | 3) => {
+                        if !exists || names.contains(&other) {
+                            continue;
+                        }
+                        // tables are copied within the region new tables are created in
+                        manager.region = region;
+                        let rename = op == 3;
+                        assert!(manager.copy_table(&name, &other, rename).is_some());
+                        if rename {
+                            names.remove(&name);
+                        }
+                        names.insert(other);
+                    }
+                    _ => {
+                        // a restored table, which is added as already constructed
+                        let Some(source) = manager.get_table_in(region, &other) else {
+                            continue;
+                        };
+                        let restored = source.copy_as(&name);
+                        assert_eq!(manager.insert_table(restored).is_ok(), !exists);
+                        names.insert(name);
+                    }
+                }
+
+                manager.check_invariants().unwrap();
+                for (region, names) in &expected {
+                    let mut found = manager.table_names_in(*region);
+                    found.sort();
+                    assert!(
+                        found.iter().eq(names),
+                        "seed {seed}: tables {found:?} in {region}, expected {names:?}"
+                    );
+                    for name in names {
+                        let table = manager.get_table_in(*region, name).unwrap();
+                        assert_eq!(table.region(), *region);
+                        let by_arn = manager.get_table_by_arn(&table.arn).unwrap();
+                        assert_eq!(&by_arn.name, name);
+                    }
+                }
+            }
         }
-        self.tables = new;
+    }
+
+    #[test]
+    fn tables_are_not_aliased() {
+        let mut manager = TableManager::default();
+        manager
+            .new_table_in(Region::UsEast1, options("table"))
+            .unwrap();
+        let created = manager.new_table_in(Region::UsEast1, options("table"));
+        assert!(matches!(
+            created,
+            Err(crate::Error::Table(TableError::TableAlreadyExists(_)))
+        ));
+        // a table of the same name in another region is another table
+        let other = manager
+            .new_table_in(Region::EuWest1, options("table"))
+            .unwrap();
+        assert_eq!(
+            manager.get_table("table").unwrap().region(),
+            Region::UsEast1
+        );
+        assert_eq!(
+            manager.get_table_in(Region::EuWest1, "table").unwrap().arn,
+            other.arn
+        );
+        assert_eq!(manager.table_names_in(Region::EuWest1), vec!["table"]);
+
+        // an imported table is filed under its own account
+        let imported = table::Table::new(Region::EuWest1, "111111111111", options("imported"));
+        manager.insert_table(imported).unwrap();
+        assert!(manager.per_account.contains_key("111111111111"));
+        manager.check_invariants().unwrap();
+
+        // names only need to be unique within an account and region
+        let filed = |manager: &mut TableManager, region, account_id: &str| {
+            let table = table::Table::new(region, account_id, options("table"));
+            manager
+                .per_account
+                .entry(account_id.to_string())
+                .or_default()
+                .tables
+                .entry(region)
+                .or_default()
+                .push(table);
+        };
+        filed(
+            &mut manager,
+            Region::ApNortheast1,
+            crate::DEFAULT_ACCOUNT_ID,
+        );
+        filed(&mut manager, Region::UsEast1, "111111111111");
+        manager.check_invariants().unwrap();
+        filed(
+            &mut manager,
+            Region::ApNortheast1,
+            crate::DEFAULT_ACCOUNT_ID,
+        );
+        assert!(manager.check_invariants().is_err());
+    }
+
+    #[test]
+    fn tables_take_ids_and_creation_times_from_providers() {
+        use chrono::TimeZone;
+
+        let clock = Arc::new(crate::FixedClock::new(
+            chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        ));
+        let mut manager = TableManager {
+            providers: Providers {
+                clock: Arc::clone(&clock) as _,
+                ids: Arc::new(crate::SequentialIds::default()),
+            },
+            ..Default::default()
+        };
+
+        let created = manager.new_table(options("created")).unwrap();
+        assert_eq!(created.table_id, "00000000-0000-0000-0000-000000000001");
+        assert_eq!(created.created_at.timestamp(), 1_700_000_000);
+
+        clock.advance(chrono::Duration::seconds(60));
+        let copy = manager.copy_table("created", "copy", false).unwrap();
+        assert_eq!(copy.table_id, "00000000-0000-0000-0000-000000000002");
+        assert_eq!(copy.created_at.timestamp(), 1_700_000_060);
+
+        // namespaces share the providers of the manager they are created from
+        let mut namespaced = manager.without_tables();
+        let created = namespaced.new_table(options("created")).unwrap();
+        assert_eq!(created.table_id, "00000000-0000-0000-0000-000000000003");
+    }
+
+    #[test]
+    fn simulated_delays_follow_the_clock() {
+        let clock = Arc::new(crate::FixedClock::new(chrono::Utc::now()));
+        let delay = Duration::from_secs(10);
+        let mut manager = TableManager {
+            lifecycle: LifecycleSimulation {
+                creation_delay: delay,
+                deletion_delay: delay,
+                index_backfill_delay: delay,
+            },
+            providers: Providers {
+                clock: Arc::clone(&clock) as _,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        manager.new_table(options("table")).unwrap();
+        let status = |manager: &TableManager| manager.get_table("table").unwrap().status();
+        assert_eq!(status(&manager), TableStatus::Creating);
+        clock.advance(chrono::Duration::seconds(10));
+        assert_eq!(status(&manager), TableStatus::Active);
+        assert_eq!(manager.settles_at(), None);
+
+        // restore times are checked against the clock too
+        let table = manager.get_table_mut_in(Region::UsEast1, "table").unwrap();
+        table.set_point_in_time_recovery(true);
+        let now = crate::Clock::now(clock.as_ref());
+        assert!(table
+            .restore_to_point_in_time("late", now + chrono::Duration::seconds(1))
+            .is_err());
+        assert!(table.restore_to_point_in_time("restored", now).is_ok());
+
+        manager.delete_table_in(Region::UsEast1, "table").unwrap();
+        assert_eq!(status(&manager), TableStatus::Deleting);
+        clock.advance(chrono::Duration::seconds(10));
+        assert!(manager.get_table("table").is_none());
     }
 }
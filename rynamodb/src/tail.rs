@@ -0,0 +1,88 @@
+//! Live feed of the operations handled, streamed to clients of `/_rynamodb/tail` as server sent
+//! events
+//!
+//! ```text
+//! curl -N http://localhost:3050/_rynamodb/tail
+//! ```
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+
+use crate::AppState;
+
+/// operations buffered for each client before the oldest are dropped
+const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationEvent {
+    pub operation: String,
+    pub table_name: Option<String>,
+    pub duration_ms: f64,
+    /// HTTP status of the response
+    pub status: u16,
+    /// error type without the namespace, e.g. `ValidationException`, if the operation failed
+    pub error_type: Option<String>,
+    /// `amz-sdk-invocation-id` sent by the client, shared by every attempt of a call
+    pub invocation_id: Option<String>,
+    /// attempt number from the `amz-sdk-request` header, starting at 1
+    pub attempt: Option<u32>,
+    /// `x-rynamodb-test-id` sent by the client
+    pub test_id: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct Tail {
+    sender: broadcast::Sender<OperationEvent>,
+}
+
+impl Default for Tail {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Tail {
+    /// Whether any client is following the feed, so that events need not be built otherwise
+    pub fn is_followed(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn publish(&self, event: OperationEvent) {
+        // sending only fails if nobody is following
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Stream every operation handled from now on
+pub async fn tail(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = BroadcastStream::new(state.tail.sender.subscribe()).map(|event| {
+        Ok(match event {
+            Ok(event) => Event::default()
+                .event("operation")
+                .json_data(event)
+                .unwrap_or_else(|_| Event::default().comment("unserialisable operation")),
+            // tell slow clients that they missed some operations rather than disconnecting
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                Event::default().event("lagged").data(missed.to_string())
+            }
+        })
+    });
+    Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
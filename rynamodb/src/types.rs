@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use serde::{de::Unexpected, Deserialize, Serialize};
 use serde_dynamo::AttributeValue;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 pub struct AttributeDefinition {
     pub attribute_name: String,
@@ -37,9 +37,119 @@ pub struct CreateTableInput {
     pub table_name: String,
     pub attribute_definitions: Vec<AttributeDefinition>,
     pub key_schema: Vec<KeySchema>,
+    #[serde(rename = "SSESpecification")]
+    pub sse_specification: Option<SseSpecification>,
+    pub on_demand_throughput: Option<OnDemandThroughput>,
+    pub warm_throughput: Option<WarmThroughput>,
+    pub global_secondary_indexes: Option<Vec<GlobalSecondaryIndex>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct GlobalSecondaryIndex {
+    pub index_name: String,
+    pub key_schema: Vec<KeySchema>,
+    #[serde(default)]
+    pub projection: Projection,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Projection {
+    pub projection_type: Option<String>,
+    pub non_key_attributes: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct GlobalSecondaryIndexUpdate {
+    pub create: Option<GlobalSecondaryIndex>,
+    pub delete: Option<DeleteGlobalSecondaryIndexAction>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeleteGlobalSecondaryIndexAction {
+    pub index_name: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct GlobalSecondaryIndexDescription {
+    pub index_name: String,
+    pub key_schema: Vec<KeySchema>,
+    pub projection: Projection,
+    pub index_status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backfilling: Option<bool>,
+    pub index_arn: String,
+    pub item_count: usize,
+    pub index_size_bytes: usize,
+}
+
+/// Maximum request units for on-demand tables
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct OnDemandThroughput {
+    pub max_read_request_units: Option<i64>,
+    pub max_write_request_units: Option<i64>,
+}
+
+/// Requested pre-warmed throughput of a table
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct WarmThroughput {
+    pub read_units_per_second: Option<i64>,
+    pub write_units_per_second: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct TableWarmThroughputDescription {
+    pub read_units_per_second: Option<i64>,
+    pub write_units_per_second: Option<i64>,
+    pub status: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct SseSpecification {
+    pub enabled: Option<bool>,
+    #[serde(rename = "SSEType")]
+    pub sse_type: Option<String>,
+    #[serde(rename = "KMSMasterKeyId")]
+    pub kms_master_key_id: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct SseDescription {
+    pub status: String,
+    #[serde(rename = "SSEType")]
+    pub sse_type: String,
+    #[serde(rename = "KMSMasterKeyArn")]
+    pub kms_master_key_arn: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateTableInput {
+    pub table_name: String,
+    pub attribute_definitions: Option<Vec<AttributeDefinition>>,
+    #[serde(rename = "SSESpecification")]
+    pub sse_specification: Option<SseSpecification>,
+    pub on_demand_throughput: Option<OnDemandThroughput>,
+    pub warm_throughput: Option<WarmThroughput>,
+    pub global_secondary_index_updates: Option<Vec<GlobalSecondaryIndexUpdate>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateTableOutput {
+    pub table_description: TableDescription,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct KeySchema {
     pub attribute_name: String,
@@ -53,7 +163,7 @@ pub enum AttributeType {
     N,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum KeyType {
     HASH,
@@ -86,6 +196,11 @@ pub struct TableDescription {
     pub table_id: Option<String>,
     pub creation_date_time: Option<i64>,
     pub provisioned_throughput: Option<ProvisionedThroughputDescription>,
+    #[serde(rename = "SSEDescription")]
+    pub sse_description: Option<SseDescription>,
+    pub on_demand_throughput: Option<OnDemandThroughput>,
+    pub warm_throughput: Option<TableWarmThroughputDescription>,
+    pub global_secondary_indexes: Option<Vec<GlobalSecondaryIndexDescription>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -99,6 +214,47 @@ pub struct PutItemInput {
 #[serde(rename_all = "PascalCase")]
 pub struct PutItemOutput {}
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateItemInput {
+    pub table_name: String,
+    pub key: HashMap<String, AttributeValue>,
+    pub update_expression: Option<String>,
+    pub expression_attribute_names: Option<HashMap<String, String>>,
+    pub expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+    #[serde(default)]
+    pub return_values: ReturnValues,
+    pub condition_expression: Option<String>,
+    /// legacy alternative to `UpdateExpression`
+    pub attribute_updates: Option<HashMap<String, AttributeValueUpdate>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct AttributeValueUpdate {
+    pub value: Option<AttributeValue>,
+    pub action: Option<String>,
+}
+
+/// Attributes returned by a write, as they were before or after it
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReturnValues {
+    #[default]
+    None,
+    AllOld,
+    UpdatedOld,
+    AllNew,
+    UpdatedNew,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateItemOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<HashMap<String, AttributeValue>>,
+}
+
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct DescribeTableOutput {
@@ -111,54 +267,430 @@ pub struct QueryOutput {
     pub items: Vec<HashMap<String, AttributeValue>>,
     pub count: usize,
     pub scanned_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_evaluated_key: Option<HashMap<String, AttributeValue>>,
 }
 
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct DeleteTableOutput {}
 
+/// Timestamps are sent over the wire as fractional seconds since the epoch
+pub fn to_epoch_seconds(datetime: &DateTime<Utc>) -> f64 {
+    datetime.timestamp_millis() as f64 / 1000.0
+}
+
+pub fn from_epoch_seconds(seconds: f64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt((seconds * 1000.0).round() as i64)
+        .single()
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DescribeContinuousBackupsInput {
+    pub table_name: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DescribeContinuousBackupsOutput {
+    pub continuous_backups_description: ContinuousBackupsDescription,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateContinuousBackupsInput {
+    pub table_name: String,
+    pub point_in_time_recovery_specification: PointInTimeRecoverySpecification,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PointInTimeRecoverySpecification {
+    pub point_in_time_recovery_enabled: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateContinuousBackupsOutput {
+    pub continuous_backups_description: ContinuousBackupsDescription,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContinuousBackupsDescription {
+    pub continuous_backups_status: String,
+    pub point_in_time_recovery_description: PointInTimeRecoveryDescription,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PointInTimeRecoveryDescription {
+    pub point_in_time_recovery_status: String,
+    pub earliest_restorable_date_time: Option<f64>,
+    pub latest_restorable_date_time: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateTimeToLiveInput {
+    pub table_name: String,
+    pub time_to_live_specification: TimeToLiveSpecification,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TimeToLiveSpecification {
+    pub attribute_name: String,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateTimeToLiveOutput {
+    pub time_to_live_specification: TimeToLiveSpecification,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DescribeTimeToLiveInput {
+    pub table_name: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DescribeTimeToLiveOutput {
+    pub time_to_live_description: TimeToLiveDescription,
+}
+
 #[derive(Serialize, Debug)]
-#[serde(rename_all = "PascalCase", untagged)]
-pub enum Response {
-    CreateTable(CreateTableOutput),
-    PutItem(PutItemOutput),
-    DescribeTable(DescribeTableOutput),
-    Query(QueryOutput),
-    DeleteTable(DeleteTableOutput),
-    GetItem(GetItemOutput),
-    ListTables(ListTablesOutput),
-    BatchWriteItem(BatchWriteItemOutput),
+#[serde(rename_all = "PascalCase")]
+pub struct TimeToLiveDescription {
+    pub time_to_live_status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attribute_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DescribeTableReplicaAutoScalingInput {
+    pub table_name: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct DescribeTableReplicaAutoScalingOutput {
+    pub table_auto_scaling_description: TableAutoScalingDescription,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateTableReplicaAutoScalingInput {
+    pub table_name: String,
+    pub provisioned_write_capacity_auto_scaling_update: Option<AutoScalingSettingsUpdate>,
+    #[serde(default)]
+    pub global_secondary_index_updates: Vec<GlobalSecondaryIndexAutoScalingUpdate>,
+    #[serde(default)]
+    pub replica_updates: Vec<ReplicaAutoScalingUpdate>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateTableReplicaAutoScalingOutput {
+    pub table_auto_scaling_description: TableAutoScalingDescription,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct GlobalSecondaryIndexAutoScalingUpdate {
+    pub index_name: Option<String>,
+    pub provisioned_write_capacity_auto_scaling_update: Option<AutoScalingSettingsUpdate>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReplicaAutoScalingUpdate {
+    pub region_name: String,
+    #[serde(default)]
+    pub replica_global_secondary_index_updates: Vec<ReplicaGlobalSecondaryIndexAutoScalingUpdate>,
+    pub replica_provisioned_read_capacity_auto_scaling_update: Option<AutoScalingSettingsUpdate>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReplicaGlobalSecondaryIndexAutoScalingUpdate {
+    pub index_name: Option<String>,
+    pub provisioned_read_capacity_auto_scaling_update: Option<AutoScalingSettingsUpdate>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutoScalingSettingsUpdate {
+    pub minimum_units: Option<u64>,
+    pub maximum_units: Option<u64>,
+    pub auto_scaling_disabled: Option<bool>,
+    pub auto_scaling_role_arn: Option<String>,
+    pub scaling_policy_update: Option<AutoScalingPolicyUpdate>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutoScalingPolicyUpdate {
+    pub policy_name: Option<String>,
+    pub target_tracking_scaling_policy_configuration: TargetTrackingScalingPolicyConfiguration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct TargetTrackingScalingPolicyConfiguration {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_scale_in: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_in_cooldown: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_out_cooldown: Option<i64>,
+    /// target utilisation, as a percentage of the provisioned capacity
+    pub target_value: f64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct TableAutoScalingDescription {
+    pub table_name: String,
+    pub table_status: String,
+    pub replicas: Vec<ReplicaAutoScalingDescription>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReplicaAutoScalingDescription {
+    pub region_name: String,
+    pub replica_status: String,
+    pub global_secondary_indexes: Vec<ReplicaGlobalSecondaryIndexAutoScalingDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replica_provisioned_read_capacity_auto_scaling_settings:
+        Option<AutoScalingSettingsDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replica_provisioned_write_capacity_auto_scaling_settings:
+        Option<AutoScalingSettingsDescription>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct ReplicaGlobalSecondaryIndexAutoScalingDescription {
+    pub index_name: String,
+    pub index_status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provisioned_read_capacity_auto_scaling_settings: Option<AutoScalingSettingsDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provisioned_write_capacity_auto_scaling_settings: Option<AutoScalingSettingsDescription>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutoScalingSettingsDescription {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum_units: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum_units: Option<u64>,
+    pub auto_scaling_disabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_scaling_role_arn: Option<String>,
+    pub scaling_policies: Vec<AutoScalingPolicyDescription>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct AutoScalingPolicyDescription {
+    pub policy_name: String,
+    pub target_tracking_scaling_policy_configuration: TargetTrackingScalingPolicyConfiguration,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct RestoreTableToPointInTimeInput {
+    pub source_table_name: Option<String>,
+    pub source_table_arn: Option<String>,
+    pub target_table_name: String,
+    pub use_latest_restorable_time: Option<bool>,
+    pub restore_date_time: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct RestoreTableToPointInTimeOutput {
+    pub table_description: TableDescription,
 }
 
 #[derive(Serialize, Debug, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct ProvisionedThroughputDescription {
-    last_increase_date_time: Option<DateTime<Utc>>,
-    last_decrease_date_time: Option<DateTime<Utc>>,
+    /// epoch seconds, see [`to_epoch_seconds`]
+    last_increase_date_time: Option<f64>,
+    last_decrease_date_time: Option<f64>,
     number_of_decreases_today: Option<usize>,
     read_capacity_units: Option<u64>,
     write_capacity_units: Option<u64>,
 }
 
-impl Default for ProvisionedThroughputDescription {
-    fn default() -> Self {
+impl ProvisionedThroughputDescription {
+    pub fn with_capacity(read_capacity_units: u64, write_capacity_units: u64) -> Self {
         Self {
             number_of_decreases_today: Some(0),
-            read_capacity_units: Some(10),
-            write_capacity_units: Some(10),
+            read_capacity_units: Some(read_capacity_units),
+            write_capacity_units: Some(write_capacity_units),
             last_increase_date_time: None,
             last_decrease_date_time: None,
         }
     }
+
+    pub fn read_capacity_units(&self) -> Option<u64> {
+        self.read_capacity_units
+    }
+
+    pub fn write_capacity_units(&self) -> Option<u64> {
+        self.write_capacity_units
+    }
+
+    /// Change the provisioned capacity, recording when it last increased or decreased
+    pub fn set_capacity(
+        &mut self,
+        read_capacity_units: u64,
+        write_capacity_units: u64,
+        now: DateTime<Utc>,
+    ) {
+        let now = Some(to_epoch_seconds(&now));
+        let current = (
+            self.read_capacity_units.unwrap_or_default(),
+            self.write_capacity_units.unwrap_or_default(),
+        );
+        if read_capacity_units > current.0 || write_capacity_units > current.1 {
+            self.last_increase_date_time = now;
+        }
+        if read_capacity_units < current.0 || write_capacity_units < current.1 {
+            self.last_decrease_date_time = now;
+            self.number_of_decreases_today = Some(self.number_of_decreases_today.unwrap_or(0) + 1);
+        }
+        self.read_capacity_units = Some(read_capacity_units);
+        self.write_capacity_units = Some(write_capacity_units);
+    }
+}
+
+impl Default for ProvisionedThroughputDescription {
+    fn default() -> Self {
+        Self::with_capacity(10, 10)
+    }
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct QueryInput {
     pub table_name: String,
-    pub key_condition_expression: String,
+    pub index_name: Option<String>,
+    pub key_condition_expression: Option<String>,
     pub expression_attribute_names: Option<HashMap<String, String>>,
     pub expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+    pub filter_expression: Option<String>,
+    pub projection_expression: Option<String>,
+    pub limit: Option<usize>,
+    pub exclusive_start_key: Option<HashMap<String, AttributeValue>>,
+    pub scan_index_forward: Option<bool>,
+    /// reads are always consistent, but DynamoDB rejects consistent reads of global secondary
+    /// indexes
+    pub consistent_read: Option<bool>,
+    // legacy parameters
+    pub key_conditions: Option<HashMap<String, Condition>>,
+    pub query_filter: Option<HashMap<String, Condition>>,
+    pub attributes_to_get: Option<Vec<String>>,
+    pub conditional_operator: Option<ConditionalOperator>,
+}
+
+impl QueryInput {
+    /// Names of the legacy (non-expression) parameters present in the request
+    pub fn legacy_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[
+            ("AttributesToGet", self.attributes_to_get.is_some()),
+            ("KeyConditions", self.key_conditions.is_some()),
+            ("QueryFilter", self.query_filter.is_some()),
+            ("ConditionalOperator", self.conditional_operator.is_some()),
+        ])
+    }
+
+    /// Names of the expression parameters present in the request
+    pub fn expression_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[
+            ("ProjectionExpression", self.projection_expression.is_some()),
+            ("FilterExpression", self.filter_expression.is_some()),
+            (
+                "KeyConditionExpression",
+                self.key_condition_expression.is_some(),
+            ),
+        ])
+    }
+}
+
+fn present_parameters(parameters: &[(&'static str, bool)]) -> Vec<&'static str> {
+    parameters
+        .iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Condition used by the legacy `KeyConditions`, `QueryFilter` and `ScanFilter` parameters
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Condition {
+    pub comparison_operator: ComparisonOperator,
+    #[serde(default)]
+    pub attribute_value_list: Vec<AttributeValue>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ComparisonOperator {
+    Eq,
+    Ne,
+    Le,
+    Lt,
+    Ge,
+    Gt,
+    NotNull,
+    Null,
+    Contains,
+    NotContains,
+    BeginsWith,
+    In,
+    Between,
+}
+
+impl std::fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ComparisonOperator::Eq => "EQ",
+            ComparisonOperator::Ne => "NE",
+            ComparisonOperator::Le => "LE",
+            ComparisonOperator::Lt => "LT",
+            ComparisonOperator::Ge => "GE",
+            ComparisonOperator::Gt => "GT",
+            ComparisonOperator::NotNull => "NOT_NULL",
+            ComparisonOperator::Null => "NULL",
+            ComparisonOperator::Contains => "CONTAINS",
+            ComparisonOperator::NotContains => "NOT_CONTAINS",
+            ComparisonOperator::BeginsWith => "BEGINS_WITH",
+            ComparisonOperator::In => "IN",
+            ComparisonOperator::Between => "BETWEEN",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ConditionalOperator {
+    #[default]
+    And,
+    Or,
 }
 
 #[derive(Deserialize, Debug)]
@@ -166,6 +698,20 @@ pub struct QueryInput {
 pub struct GetItemInput {
     pub table_name: String,
     pub key: HashMap<String, AttributeValue>,
+    pub attributes_to_get: Option<Vec<String>>,
+    pub projection_expression: Option<String>,
+}
+
+impl GetItemInput {
+    /// Names of the legacy (non-expression) parameters present in the request
+    pub fn legacy_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[("AttributesToGet", self.attributes_to_get.is_some())])
+    }
+
+    /// Names of the expression parameters present in the request
+    pub fn expression_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[("ProjectionExpression", self.projection_expression.is_some())])
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -176,12 +722,17 @@ pub struct GetItemOutput {
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
-pub struct ListTablesInput {}
+pub struct ListTablesInput {
+    pub exclusive_start_table_name: Option<String>,
+    pub limit: Option<usize>,
+}
 
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub struct ListTablesOutput {
     pub table_names: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_evaluated_table_name: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -194,6 +745,35 @@ pub struct DeleteTableInput {
 #[serde(rename_all = "PascalCase")]
 pub struct ScanInput {
     pub table_name: String,
+    pub filter_expression: Option<String>,
+    pub projection_expression: Option<String>,
+    pub limit: Option<usize>,
+    pub exclusive_start_key: Option<HashMap<String, AttributeValue>>,
+    /// scans always read from a consistent snapshot, so this is only used to estimate their cost
+    pub consistent_read: Option<bool>,
+    // legacy parameters
+    pub scan_filter: Option<HashMap<String, Condition>>,
+    pub attributes_to_get: Option<Vec<String>>,
+    pub conditional_operator: Option<ConditionalOperator>,
+}
+
+impl ScanInput {
+    /// Names of the legacy (non-expression) parameters present in the request
+    pub fn legacy_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[
+            ("AttributesToGet", self.attributes_to_get.is_some()),
+            ("ScanFilter", self.scan_filter.is_some()),
+            ("ConditionalOperator", self.conditional_operator.is_some()),
+        ])
+    }
+
+    /// Names of the expression parameters present in the request
+    pub fn expression_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[
+            ("ProjectionExpression", self.projection_expression.is_some()),
+            ("FilterExpression", self.filter_expression.is_some()),
+        ])
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -228,6 +808,42 @@ pub struct BatchWriteInput {
     pub request_items: HashMap<String, Vec<BatchPutRequest>>,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct BatchGetItemInput {
+    pub request_items: HashMap<String, KeysAndAttributes>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct KeysAndAttributes {
+    pub keys: Vec<HashMap<String, AttributeValue>>,
+    pub consistent_read: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes_to_get: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projection_expression: Option<String>,
+}
+
+impl KeysAndAttributes {
+    /// Names of the legacy (non-expression) parameters present in the request
+    pub fn legacy_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[("AttributesToGet", self.attributes_to_get.is_some())])
+    }
+
+    /// Names of the expression parameters present in the request
+    pub fn expression_parameters(&self) -> Vec<&'static str> {
+        present_parameters(&[("ProjectionExpression", self.projection_expression.is_some())])
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct BatchGetItemOutput {
+    pub responses: HashMap<String, Vec<HashMap<String, AttributeValue>>>,
+    pub unprocessed_keys: HashMap<String, KeysAndAttributes>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,4 +871,47 @@ mod tests {
         "#;
         let _: BatchWriteInput = serde_json::from_str(input).unwrap();
     }
+
+    // the wire encoding of each type, which strict clients parse exactly
+    #[test]
+    fn attribute_value_encoding() {
+        let encoded = serde_json::json!({
+            "Item": {
+                "yes": {"BOOL": true},
+                "no": {"BOOL": false},
+                "nothing": {"NULL": true},
+                "n": {"N": "1.5"},
+                "ns": {"NS": ["1", "2"]},
+                "l": {"L": [{"BOOL": false}, {"NULL": true}, {"N": "0"}]},
+            }
+        });
+        let item: HashMap<String, AttributeValue> = [
+            ("yes".to_string(), AttributeValue::Bool(true)),
+            ("no".to_string(), AttributeValue::Bool(false)),
+            ("nothing".to_string(), AttributeValue::Null(true)),
+            ("n".to_string(), AttributeValue::N("1.5".to_string())),
+            (
+                "ns".to_string(),
+                AttributeValue::Ns(vec!["1".to_string(), "2".to_string()]),
+            ),
+            (
+                "l".to_string(),
+                AttributeValue::L(vec![
+                    AttributeValue::Bool(false),
+                    AttributeValue::Null(true),
+                    AttributeValue::N("0".to_string()),
+                ]),
+            ),
+        ]
+        .into();
+
+        let output = GetItemOutput {
+            item: Some(item.clone()),
+        };
+        assert_eq!(serde_json::to_value(&output).unwrap(), encoded);
+
+        let decoded: HashMap<String, AttributeValue> =
+            serde_json::from_value(encoded["Item"].clone()).unwrap();
+        assert_eq!(decoded, item);
+    }
 }
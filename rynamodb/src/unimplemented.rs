@@ -0,0 +1,169 @@
+//! Emulator gaps which clients depend on
+//!
+//! Requests relying on behaviour rynamodb does not implement, such as operations it does not
+//! support or key conditions it cannot evaluate, are recorded by the feature they needed and
+//! reported at `/_rynamodb/unimplemented`, so that a team can tell which gaps their test suite
+//! hits. By default the gaps are answered with the errors DynamoDB returns for invalid requests.
+//! In fail-fast mode they are answered with a `NotImplementedByRynamodb` error naming the
+//! feature instead, so that a test failing because of the emulator says so.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{errors::ErrorResponse, metrics::escape, OperationType};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnimplementedFeature {
+    pub feature: String,
+    /// number of requests which needed the feature
+    pub count: u64,
+    /// operations which needed the feature
+    pub operations: BTreeSet<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnimplementedReport {
+    pub fail_fast: bool,
+    /// features needed since the emulator started or the report was last cleared, by name
+    pub features: Vec<UnimplementedFeature>,
+}
+
+#[derive(Debug, Default)]
+pub struct Unimplemented {
+    fail_fast: bool,
+    features: Mutex<BTreeMap<String, UnimplementedFeature>>,
+}
+
+impl Unimplemented {
+    pub fn new(fail_fast: bool) -> Self {
+        Self {
+            fail_fast,
+            ..Default::default()
+        }
+    }
+
+    /// Record the feature an error was returned for, if the emulator does not implement it, and
+    /// convert the error into the one returned in the current mode
+    pub fn observe(&self, operation: Option<OperationType>, error: ErrorResponse) -> ErrorResponse {
+        let feature = match &error {
+            ErrorResponse::NotImplemented(operation) => format!("{operation} operation"),
+            ErrorResponse::NotImplementedByRynamodb(feature) => feature.clone(),
+            _ => return error,
+        };
+        self.record(operation, &feature);
+
+        match error {
+            ErrorResponse::NotImplemented(_) if self.fail_fast => {
+                ErrorResponse::NotImplementedByRynamodb(feature)
+            }
+            ErrorResponse::NotImplementedByRynamodb(_) if !self.fail_fast => {
+                ErrorResponse::ValidationError(format!("{feature} is not supported"))
+            }
+            error => error,
+        }
+    }
+
+    fn record(&self, operation: Option<OperationType>, feature: &str) {
+        tracing::warn!(feature, fail_fast = self.fail_fast, "unimplemented feature");
+        let now = Utc::now();
+        let mut features = self.features.lock().unwrap();
+        let entry = features
+            .entry(feature.to_string())
+            .or_insert_with(|| UnimplementedFeature {
+                feature: feature.to_string(),
+                count: 0,
+                operations: BTreeSet::new(),
+                first_seen: now,
+                last_seen: now,
+            });
+        entry.count += 1;
+        entry.last_seen = now;
+        if let Some(operation) = operation {
+            entry.operations.insert(format!("{operation:?}"));
+        }
+    }
+
+    pub fn report(&self) -> UnimplementedReport {
+        UnimplementedReport {
+            fail_fast: self.fail_fast,
+            features: self.features.lock().unwrap().values().cloned().collect(),
+        }
+    }
+
+    /// Forget the features recorded so far, e.g. at the start of a test run
+    pub fn clear(&self) {
+        self.features.lock().unwrap().clear();
+    }
+
+    /// Counts of the requests needing each feature in the Prometheus text exposition format,
+    /// served with the operation metrics
+    pub fn render(&self) -> String {
+        let name = "rynamodb_unimplemented_feature_requests_total";
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP {name} Requests needing a feature not implemented by rynamodb"
+        );
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for feature in self.features.lock().unwrap().values() {
+            let _ = writeln!(
+                out,
+                "{name}{{feature=\"{}\"}} {}",
+                escape(&feature.feature),
+                feature.count
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_features_and_converts_errors() {
+        let lenient = Unimplemented::new(false);
+        let error = lenient.observe(
+            Some(OperationType::Query),
+            ErrorResponse::NotImplementedByRynamodb("begins_with sort key conditions".to_string()),
+        );
+        assert!(matches!(error, ErrorResponse::ValidationError(_)));
+        let error = lenient.observe(None, ErrorResponse::NotImplemented("Foo".to_string()));
+        assert!(matches!(error, ErrorResponse::NotImplemented(_)));
+        let error = lenient.observe(None, ErrorResponse::ValidationError("bad".to_string()));
+        assert!(matches!(error, ErrorResponse::ValidationError(_)));
+
+        let report = lenient.report();
+        let features: Vec<_> = report.features.iter().map(|f| f.feature.as_str()).collect();
+        assert_eq!(
+            features,
+            ["Foo operation", "begins_with sort key conditions"]
+        );
+        assert_eq!(
+            report.features[1].operations,
+            BTreeSet::from(["Query".to_string()])
+        );
+        assert!(lenient.render().contains(
+            "rynamodb_unimplemented_feature_requests_total{feature=\"Foo operation\"} 1"
+        ));
+
+        let fail_fast = Unimplemented::new(true);
+        let error = fail_fast.observe(None, ErrorResponse::NotImplemented("Foo".to_string()));
+        assert!(
+            matches!(error, ErrorResponse::NotImplementedByRynamodb(feature) if feature == "Foo operation")
+        );
+        fail_fast.clear();
+        assert!(fail_fast.report().features.is_empty());
+    }
+}
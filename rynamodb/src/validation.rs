@@ -0,0 +1,246 @@
+//! Validation of the attribute values in request bodies
+//!
+//! serde rejects a malformed attribute value anywhere in a request with an error which does not
+//! say much about the problem. Requests which fail to parse are walked again here to find the
+//! offending attribute value, so that the error can name it like DynamoDB does.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_json::{Map, Value};
+
+use crate::errors::ErrorResponse;
+
+/// Request members holding a map of attribute names to attribute values
+const ATTRIBUTE_MAP_MEMBERS: &[&str] = &[
+    "Item",
+    "Key",
+    "ExpressionAttributeValues",
+    "ExclusiveStartKey",
+];
+
+/// Find the first malformed attribute value in a request
+pub fn check_attribute_values(body: &Value) -> Result<(), ErrorResponse> {
+    check_members(body, "")
+}
+
+fn check_members(value: &Value, path: &str) -> Result<(), ErrorResponse> {
+    match value {
+        Value::Object(members) => {
+            for (name, value) in members {
+                let path = member_path(path, name);
+                match name.as_str() {
+                    name if ATTRIBUTE_MAP_MEMBERS.contains(&name) => {
+                        check_attribute_map(value, &path)?
+                    }
+                    "Keys" => {
+                        for (i, value) in expect_list(value, &path)?.iter().enumerate() {
+                            check_attribute_map(value, &format!("{path}[{i}]"))?;
+                        }
+                    }
+                    "AttributeValueList" => {
+                        for (i, value) in expect_list(value, &path)?.iter().enumerate() {
+                            check_attribute_value(value, &format!("{path}[{i}]"))?;
+                        }
+                    }
+                    _ => check_members(value, &path)?,
+                }
+            }
+            Ok(())
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                check_members(value, &format!("{path}[{i}]"))?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_attribute_map(value: &Value, path: &str) -> Result<(), ErrorResponse> {
+    for (name, value) in expect_map(value, path)? {
+        check_attribute_value(value, &member_path(path, name))?;
+    }
+    Ok(())
+}
+
+fn check_attribute_value(value: &Value, path: &str) -> Result<(), ErrorResponse> {
+    let descriptors = expect_map(value, path)?;
+    match descriptors.len() {
+        0 => {
+            return Err(ErrorResponse::ValidationError(
+                "Supplied AttributeValue is empty, must contain exactly one of the supported datatypes"
+                    .to_string(),
+            ))
+        }
+        1 => {}
+        _ => {
+            return Err(ErrorResponse::ValidationError(
+                "Supplied AttributeValue has more than one datatypes set, must contain exactly one of the supported datatypes"
+                    .to_string(),
+            ))
+        }
+    }
+
+    let parent = path;
+    for (descriptor, value) in descriptors {
+        let path = member_path(parent, descriptor);
+        match descriptor.as_str() {
+            "S" | "N" => {
+                expect_string(value, &path)?;
+            }
+            "B" => expect_binary(value, &path)?,
+            "SS" | "NS" => {
+                for (i, value) in expect_list(value, &path)?.iter().enumerate() {
+                    expect_string(value, &format!("{path}[{i}]"))?;
+                }
+            }
+            "BS" => {
+                for (i, value) in expect_list(value, &path)?.iter().enumerate() {
+                    expect_binary(value, &format!("{path}[{i}]"))?;
+                }
+            }
+            "M" => check_attribute_map(value, &path)?,
+            "L" => {
+                for (i, value) in expect_list(value, &path)?.iter().enumerate() {
+                    check_attribute_value(value, &format!("{path}[{i}]"))?;
+                }
+            }
+            "NULL" | "BOOL" => {
+                if !value.is_boolean() {
+                    return Err(unexpected(value, "Boolean", &path));
+                }
+            }
+            _ => {
+                return Err(serialization_error(format!(
+                    "Unknown AttributeValue type {descriptor} at {parent}"
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn expect_map<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>, ErrorResponse> {
+    value
+        .as_object()
+        .ok_or_else(|| unexpected(value, "Map", path))
+}
+
+fn expect_list<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, ErrorResponse> {
+    value
+        .as_array()
+        .ok_or_else(|| unexpected(value, "List", path))
+}
+
+fn expect_string<'a>(value: &'a Value, path: &str) -> Result<&'a str, ErrorResponse> {
+    value
+        .as_str()
+        .ok_or_else(|| unexpected(value, "String", path))
+}
+
+fn expect_binary(value: &Value, path: &str) -> Result<(), ErrorResponse> {
+    let encoded = expect_string(value, path)?;
+    STANDARD
+        .decode(encoded)
+        .map(|_| ())
+        .map_err(|_| serialization_error(format!("Base64 encoded length is invalid at {path}")))
+}
+
+fn member_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+fn unexpected(value: &Value, expected: &str, path: &str) -> ErrorResponse {
+    // named after the JSON tokens reported by DynamoDB
+    let token = match value {
+        Value::Null => "VALUE_NULL",
+        Value::Bool(true) => "VALUE_TRUE",
+        Value::Bool(false) => "VALUE_FALSE",
+        Value::Number(_) => "NUMBER_VALUE",
+        Value::String(_) => "VALUE_STRING",
+        Value::Array(_) => "START_ARRAY",
+        Value::Object(_) => "START_OBJECT",
+    };
+    serialization_error(format!(
+        "{token} cannot be converted to {expected} at {path}"
+    ))
+}
+
+fn serialization_error(message: String) -> ErrorResponse {
+    ErrorResponse::SerializationError {
+        message: Some(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn message(body: Value) -> String {
+        match check_attribute_values(&body).unwrap_err() {
+            ErrorResponse::SerializationError {
+                message: Some(message),
+            }
+            | ErrorResponse::ValidationError(message) => message,
+            e => panic!("unexpected error {e:?}"),
+        }
+    }
+
+    #[test]
+    fn valid_attribute_values() {
+        let body = json!({
+            "TableName": "a",
+            "Item": {
+                "pk": {"S": "abc"},
+                "n": {"N": "1"},
+                "b": {"B": "YWJj"},
+                "m": {"M": {"nested": {"L": [{"BOOL": true}, {"NULL": true}]}}},
+                "ss": {"SS": ["a", "b"]},
+            },
+        });
+        assert!(check_attribute_values(&body).is_ok());
+    }
+
+    #[test]
+    fn unknown_type() {
+        let body = json!({"TableName": "a", "Item": {"pk": {"X": "abc"}}});
+        assert_eq!(message(body), "Unknown AttributeValue type X at Item.pk");
+    }
+
+    #[test]
+    fn wrong_json_type() {
+        let body = json!({"TableName": "a", "Key": {"pk": {"S": 1}}});
+        assert_eq!(
+            message(body),
+            "NUMBER_VALUE cannot be converted to String at Key.pk.S"
+        );
+    }
+
+    #[test]
+    fn nested_path() {
+        let body = json!({
+            "RequestItems": {
+                "a": [{"PutRequest": {"Item": {"m": {"M": {"l": {"L": [{"N": "1"}, {"N": []}]}}}}}}],
+            },
+        });
+        assert_eq!(
+            message(body),
+            "START_ARRAY cannot be converted to String at RequestItems.a[0].PutRequest.Item.m.M.l.L[1].N"
+        );
+    }
+
+    #[test]
+    fn empty_attribute_value() {
+        let body = json!({"TableName": "a", "Item": {"pk": {}}});
+        assert_eq!(
+            message(body),
+            "Supplied AttributeValue is empty, must contain exactly one of the supported datatypes"
+        );
+    }
+}
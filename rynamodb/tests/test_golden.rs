@@ -0,0 +1,170 @@
+//! Field by field comparison of responses against DynamoDB
+//!
+//! Each case in `golden/dynamodb.json` holds a response from DynamoDB, normalised to the default
+//! account and region of the emulator. The emulator's response to the same request must contain
+//! exactly the same fields, with the same names and values, so that output drift is caught as
+//! the types evolve:
+//!
+//! - `volatile` fields, such as generated ids and timestamps, must be present with the same JSON
+//!   type but may have any value
+//! - `knownMissing` fields are not returned by the emulator yet. The test fails once they are
+//!   returned, so that the list only ever shrinks.
+//!
+//! `null` values are treated as absent, as clients do. The order of fields is not compared, as it
+//! is not significant in JSON objects.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+static GOLDEN_RESPONSES: &str = include_str!("golden/dynamodb.json");
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GoldenCase {
+    id: String,
+    documentation: String,
+    /// requests sent before the request under test, whose responses are not compared
+    setup: Vec<GoldenRequest>,
+    request: GoldenRequest,
+    response: Value,
+    #[serde(default)]
+    volatile: Vec<String>,
+    #[serde(default)]
+    known_missing: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GoldenRequest {
+    target: String,
+    body: Value,
+}
+
+async fn send(client: &reqwest::Client, port: u16, request: &GoldenRequest) -> eyre::Result<Value> {
+    let response = client
+        .post(format!("http://127.0.0.1:{port}/"))
+        .header("x-amz-target", &request.target)
+        .header("content-type", "application/x-amz-json-1.0")
+        .json(&request.body)
+        .send()
+        .await?;
+    Ok(response.error_for_status()?.json().await?)
+}
+
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn compare(path: &str, expected: &Value, actual: &Value, case: &GoldenCase) -> Vec<String> {
+    if case.volatile.iter().any(|volatile| volatile == path) {
+        if json_type(expected) == json_type(actual) {
+            return Vec::new();
+        }
+        return vec![format!(
+            "{path}: expected a {}, got {actual}",
+            json_type(expected)
+        )];
+    }
+
+    let child = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            let mut problems = Vec::new();
+            for (key, expected) in expected {
+                let path = child(key);
+                let known_missing = case.known_missing.contains(&path);
+                match actual.get(key) {
+                    Some(_) if known_missing => problems.push(format!(
+                        "{path} is now returned, remove it from knownMissing"
+                    )),
+                    Some(actual) => problems.extend(compare(&path, expected, actual, case)),
+                    None if known_missing => {}
+                    None => problems.push(format!("{path}: missing")),
+                }
+            }
+            for key in actual.keys().filter(|key| !expected.contains_key(*key)) {
+                problems.push(format!("{}: not returned by DynamoDB", child(key)));
+            }
+            problems
+        }
+        (Value::Array(expected), Value::Array(actual)) if expected.len() == actual.len() => {
+            expected
+                .iter()
+                .zip(actual)
+                .enumerate()
+                .flat_map(|(i, (expected, actual))| {
+                    compare(&format!("{path}[{i}]"), expected, actual, case)
+                })
+                .collect()
+        }
+        (expected, actual) if expected == actual => Vec::new(),
+        (expected, actual) => vec![format!("{path}: expected {expected}, got {actual}")],
+    }
+}
+
+#[tokio::test]
+async fn golden_responses() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let cases: Vec<GoldenCase> =
+        serde_json::from_str(GOLDEN_RESPONSES).expect("parsing golden responses");
+
+    let mut failures = Vec::new();
+    for case in cases {
+        tracing::debug!(id = %case.id, documentation = %case.documentation, "comparing response");
+        // every case starts from an empty emulator
+        let router = rynamodb::router(Default::default());
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        rynamodb::test_run_server(router, |port| {
+            Box::new(Box::pin(async move {
+                let client = reqwest::Client::new();
+                for request in &case.setup {
+                    send(&client, port, request).await?;
+                }
+                let actual = without_nulls(send(&client, port, &case.request).await?);
+                let problems = compare("", &case.response, &actual, &case);
+                let _ = tx.send(
+                    problems
+                        .into_iter()
+                        .map(|problem| format!("{}: {problem}", case.id))
+                        .collect::<Vec<_>>(),
+                );
+                Ok(())
+            }))
+        })
+        .await
+        .unwrap();
+        failures.extend(rx.await.expect("case did not run"));
+    }
+
+    assert!(
+        failures.is_empty(),
+        "responses differ from DynamoDB:\n{}",
+        failures.join("\n")
+    );
+}
@@ -1,9 +1,22 @@
-use std::{collections::HashMap, future::Future, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use aws_sdk_dynamodb::{
     model::{
-        AttributeDefinition, AttributeValue, KeySchemaElement, KeyType, ProvisionedThroughput,
-        PutRequest, ScalarAttributeType, WriteRequest,
+        AttributeDefinition, AttributeValue, AutoScalingPolicyUpdate, AutoScalingSettingsUpdate,
+        AutoScalingTargetTrackingScalingPolicyConfigurationUpdate, ComparisonOperator, Condition,
+        CreateGlobalSecondaryIndexAction, GlobalSecondaryIndexUpdate, IndexStatus,
+        KeySchemaElement, KeyType, KeysAndAttributes, Projection, ProjectionType,
+        ProvisionedThroughput, PutRequest, ReplicaAutoScalingUpdate, ReturnValue,
+        ScalarAttributeType, SseSpecification, SseStatus, SseType, TimeToLiveSpecification,
+        TimeToLiveStatus, WriteRequest,
     },
     output::GetItemOutput,
     types::SdkError,
@@ -11,7 +24,10 @@ use aws_sdk_dynamodb::{
 };
 use axum::{
     async_trait,
-    http::{HeaderMap, HeaderName, HeaderValue},
+    body::Body,
+    http::{HeaderMap, HeaderName, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::IntoResponse,
 };
 use eyre::{Context, Result};
 use reqwest::header::CONTENT_TYPE;
@@ -192,7 +208,7 @@ async fn with_table<'a, F>(f: F) -> Result<()>
 where
     F: FnOnce(String, Client) -> Box<dyn Future<Output = Result<()>> + Unpin> + 'static,
 {
-    let router = rynamodb::router();
+    let router = rynamodb::router(Default::default());
     rynamodb::test_run_server(router, |port| {
         let table_name = format!("table-{}", uuid::Uuid::new_v4());
         Box::new(Box::pin(async move {
@@ -223,7 +239,7 @@ where
 async fn create_table_invalid_input() {
     test_init();
 
-    let router = rynamodb::router();
+    let router = rynamodb::router(Default::default());
     rynamodb::test_run_server(router, |port| {
         Box::new(Box::pin(async move {
             let url = if targeting_aws() {
@@ -262,7 +278,7 @@ async fn create_table_invalid_input() {
 async fn create_table() -> Result<()> {
     test_init();
 
-    let router = rynamodb::router();
+    let router = rynamodb::router(Default::default());
     rynamodb::test_run_server(router, |port| {
         Box::new(Box::pin(async move {
             let client = test_client(port).await;
@@ -351,7 +367,7 @@ async fn delete_table() {
 
     skip_aws_cloud!();
 
-    let router = rynamodb::router();
+    let router = rynamodb::router(Default::default());
     rynamodb::test_run_server(router, |port| {
         let table_names = vec![
             format!("table-{}", uuid::Uuid::new_v4()),
@@ -391,83 +407,180 @@ async fn delete_table() {
 }
 
 #[tokio::test]
-async fn batch_write() -> Result<()> {
+async fn operations_on_deleting_table() {
     test_init();
 
-    with_table(|table_name, client| {
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        lifecycle: rynamodb::LifecycleSimulation {
+            deletion_delay: Duration::from_secs(60),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
         Box::new(Box::pin(async move {
-            let write_request = WriteRequest::builder()
-                .put_request(
-                    PutRequest::builder()
-                        .item("pk", AttributeValue::S("abc".to_string()))
-                        .item("sk", AttributeValue::S("def".to_string()))
-                        .build(),
-                )
-                .build();
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            client.delete_table().table_name(&table_name).send().await?;
 
             let res = client
-                .batch_write_item()
-                .request_items(table_name, vec![write_request])
+                .describe_table()
+                .table_name(&table_name)
+                .send()
+                .await?;
+            assert_eq!(
+                res.table().and_then(|table| table.table_status()),
+                Some(&aws_sdk_dynamodb::model::TableStatus::Deleting)
+            );
+
+            let err = client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
                 .send()
                 .await
-                .wrap_err("inserting item")?;
+                .expect_err("write to a deleting table succeeded");
+            assert!(err.into_service_error().is_resource_not_found_exception());
 
-            let result = std::panic::catch_unwind(|| {
-                insta::assert_debug_snapshot!(res);
-            });
+            let err = client
+                .get_item()
+                .table_name(&table_name)
+                .key("pk", AttributeValue::S("abc".to_string()))
+                .key("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await
+                .expect_err("read from a deleting table succeeded");
+            assert!(err.into_service_error().is_resource_not_found_exception());
 
-            result.map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
+            // deleting again is not an error
+            client.delete_table().table_name(&table_name).send().await?;
+
+            Ok(())
         }))
     })
     .await
+    .unwrap();
 }
 
 #[tokio::test]
-async fn put_item() -> Result<()> {
+async fn configured_account_and_region() {
     test_init();
 
-    with_table(|table_name, client| {
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        account_id: "123456789012".to_string(),
+        region: rynamodb::Region::EuWest1,
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
         Box::new(Box::pin(async move {
-            let res = client
-                .put_item()
-                .table_name(table_name)
-                .item("pk", AttributeValue::S("abc".to_string()))
-                .item("sk", AttributeValue::S("def".to_string()))
-                .send()
-                .await
-                .wrap_err("inserting item")?;
+            let client = reqwest::Client::new();
+            let create_table = |table_name: &str, authorization: Option<&str>| {
+                let mut request = client
+                    .post(format!("http://localhost:{port}/"))
+                    .header("x-amz-target", "DynamoDB_20120810.CreateTable")
+                    .header("content-type", "application/x-amz-json-1.0")
+                    .json(&serde_json::json!({
+                        "TableName": table_name,
+                        "AttributeDefinitions": [{"AttributeName": "pk", "AttributeType": "S"}],
+                        "KeySchema": [{"AttributeName": "pk", "KeyType": "HASH"}],
+                        "BillingMode": "PAY_PER_REQUEST",
+                    }));
+                if let Some(authorization) = authorization {
+                    request = request.header("authorization", authorization);
+                }
+                async move {
+                    let body: serde_json::Value = request.send().await?.json().await?;
+                    Ok::<_, eyre::Report>(body["TableDescription"]["TableArn"].clone())
+                }
+            };
 
-            let result = std::panic::catch_unwind(|| {
-                insta::assert_debug_snapshot!(res);
-            });
+            // unsigned requests use the configured region
+            assert_eq!(
+                create_table("unsigned", None).await?,
+                "arn:aws:dynamodb:eu-west-1:123456789012:table/unsigned"
+            );
+            // signed requests use the region they were signed for
+            assert_eq!(
+                create_table(
+                    "signed",
+                    Some(
+                        "AWS4-HMAC-SHA256 \
+                        Credential=test/20240101/ap-northeast-1/dynamodb/aws4_request, \
+                        SignedHeaders=host, Signature=abc"
+                    )
+                )
+                .await?,
+                "arn:aws:dynamodb:ap-northeast-1:123456789012:table/signed"
+            );
+
+            // SDK clients sign every request, so the region they are configured for overrides
+            // the configured region
+            let sdk = test_client(port).await;
+            default_dynamodb_table("shared", &sdk).await?;
+            let res = sdk.describe_table().table_name("shared").send().await?;
+            assert_eq!(
+                res.table().and_then(|table| table.table_arn()),
+                Some("arn:aws:dynamodb:us-east-1:123456789012:table/shared")
+            );
+            // each region has its own tables
+            assert_eq!(
+                create_table("shared", None).await?,
+                "arn:aws:dynamodb:eu-west-1:123456789012:table/shared"
+            );
+            let res = sdk.list_tables().send().await?;
+            assert_eq!(res.table_names(), Some(&["shared".to_string()][..]));
 
-            result.map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
+            Ok(())
         }))
     })
     .await
+    .unwrap();
 }
 
 #[tokio::test]
-async fn list_tables() {
+async fn configured_table_defaults() {
     test_init();
 
-    with_table(|_table_name, client| {
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        table_defaults: rynamodb::TableDefaults {
+            read_capacity_units: 500,
+            write_capacity_units: 100,
+            kms_encryption: true,
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
         Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
             let res = client
-                .list_tables()
+                .describe_table()
+                .table_name(&table_name)
                 .send()
-                .await
-                .wrap_err("listing tables")?;
+                .await?;
+            let table = res.table().expect("table description");
+            let throughput = table
+                .provisioned_throughput()
+                .expect("provisioned throughput");
+            assert_eq!(throughput.read_capacity_units(), Some(500));
+            assert_eq!(throughput.write_capacity_units(), Some(100));
+            assert_eq!(
+                table.sse_description().and_then(|sse| sse.status()),
+                Some(&SseStatus::Enabled)
+            );
 
-            insta::with_settings!({ filters => vec![
-                // table name
-                (r"table-[a-z0-9]{8}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{12}", "[table-name]"),
-            ]}, {
-                std::panic::catch_unwind(|| {
-                    insta::assert_debug_snapshot!(res);
-                })
-                .map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
-            })
+            Ok(())
         }))
     })
     .await
@@ -475,137 +588,178 @@ async fn list_tables() {
 }
 
 #[tokio::test]
-async fn round_trip() {
+async fn delete_creating_table() {
     test_init();
 
-    // check that we can insert and fetch data from rynamodb
-    with_table(|table_name, client| {
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        lifecycle: rynamodb::LifecycleSimulation {
+            creation_delay: Duration::from_secs(60),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
         Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            // the table never becomes active, so do not wait for it
             client
-                .put_item()
+                .create_table()
                 .table_name(&table_name)
-                .item("pk", AttributeValue::S("abc".to_string()))
-                .item("sk", AttributeValue::S("def".to_string()))
+                .key_schema(
+                    KeySchemaElement::builder()
+                        .attribute_name("pk")
+                        .key_type(KeyType::Hash)
+                        .build(),
+                )
+                .attribute_definitions(
+                    AttributeDefinition::builder()
+                        .attribute_name("pk")
+                        .attribute_type(ScalarAttributeType::S)
+                        .build(),
+                )
                 .send()
-                .await
-                .wrap_err("inserting item")?;
+                .await?;
 
-            let res = client
-                .query()
+            // the table cannot be changed until it is active
+            let err = client
+                .update_time_to_live()
                 .table_name(&table_name)
-                .key_condition_expression("pk = :a AND sk = :b")
-                .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
-                .expression_attribute_values(":b", AttributeValue::S("def".to_string()))
+                .time_to_live_specification(
+                    TimeToLiveSpecification::builder()
+                        .enabled(true)
+                        .attribute_name("expires")
+                        .build(),
+                )
                 .send()
                 .await
-                .wrap_err("performing query")?;
-
-            // TODO: we have to sort the returned items somehow, or else the snapshotting won't
-            // work.
-
-            let expected_items = {
-                let mut h = HashMap::new();
-                h.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
-                h.insert("sk".to_string(), AttributeValue::S("def".to_string()));
-                h
-            };
-            let expected_output = aws_sdk_dynamodb::output::QueryOutput::builder()
-                .items(expected_items)
-                .count(1)
-                .scanned_count(1)
-                .build();
+                .expect_err("changing a creating table succeeded");
+            assert_eq!(
+                err.into_service_error().code(),
+                Some("ResourceInUseException")
+            );
+            let err = client
+                .update_continuous_backups()
+                .table_name(&table_name)
+                .point_in_time_recovery_specification(
+                    aws_sdk_dynamodb::model::PointInTimeRecoverySpecification::builder()
+                        .point_in_time_recovery_enabled(true)
+                        .build(),
+                )
+                .send()
+                .await
+                .expect_err("changing a creating table succeeded");
+            assert_eq!(
+                err.into_service_error().code(),
+                Some("ResourceInUseException")
+            );
 
-            assert_eq!(res, expected_output);
+            let err = client
+                .delete_table()
+                .table_name(&table_name)
+                .send()
+                .await
+                .expect_err("deleting a creating table succeeded");
+            assert!(err.into_service_error().is_resource_in_use_exception());
 
             Ok(())
-
-            //             let result = insta::with_settings!({ filters => vec![
-            //                 // table name
-            //                 (r"table-[a-z0-9]{8}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{12}", "[table-name]"),
-            //                 // region
-            //                 (r"(eu-west-2|us-east-1)", "[region]"),
-            //                 // account id
-            //                 (r"[0-9]{12}", "[account]"),
-            //                 // table id
-            //                 (r"[a-z0-9]{8}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{12}", "[table-id]"),
-            //                 // datetime seconds
-            //                 (r"seconds:\s*\d+", "[seconds]"),
-            //                 // datetime nanoseconds
-            //                 (r"subsecond_nanos:\s*\d+", "[nanos]"),
-            //             ] }, {
-            //                 std::panic::catch_unwind(|| {
-            //                     insta::assert_debug_snapshot!(res);
-            //                 })
-            //             });
-
-            // result.map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
         }))
     })
     .await
     .unwrap();
 }
 
-// TODO: sort the results so that they are stable
 #[tokio::test]
-#[ignore]
-async fn scan_table() {
+async fn barrier_waits_for_table_creation() {
     test_init();
 
-    with_table(|table_name, client| {
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        lifecycle: rynamodb::LifecycleSimulation {
+            creation_delay: Duration::from_millis(500),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
         Box::new(Box::pin(async move {
-            // add two items
+            let client = test_client(port).await;
             client
-                .put_item()
+                .create_table()
                 .table_name(&table_name)
-                .item("pk", AttributeValue::S("123".to_string()))
-                .item("sk", AttributeValue::S("456".to_string()))
-                .item("value", AttributeValue::S("789".to_string()))
+                .key_schema(
+                    KeySchemaElement::builder()
+                        .attribute_name("pk")
+                        .key_type(KeyType::Hash)
+                        .build(),
+                )
+                .attribute_definitions(
+                    AttributeDefinition::builder()
+                        .attribute_name("pk")
+                        .attribute_type(ScalarAttributeType::S)
+                        .build(),
+                )
                 .send()
-                .await
-                .wrap_err("inserting item")?;
+                .await?;
 
-            client
-                .put_item()
-                .table_name(&table_name)
-                .item("pk", AttributeValue::S("abc".to_string()))
-                .item("sk", AttributeValue::S("def".to_string()))
-                .item("value", AttributeValue::S("ghi".to_string()))
-                .send()
-                .await
-                .wrap_err("inserting item")?;
+            let barrier = |timeout_ms: u64| {
+                reqwest::Client::new()
+                    .post(format!(
+                        "http://localhost:{port}/_rynamodb/barrier?timeoutMs={timeout_ms}"
+                    ))
+                    .send()
+            };
+            let res = barrier(1).await?;
+            assert_eq!(res.status(), 500);
 
+            let res = barrier(10_000).await?;
+            assert!(res.status().is_success());
             let res = client
-                .scan()
+                .describe_table()
                 .table_name(&table_name)
                 .send()
-                .await
-                .wrap_err("scanning the table")?;
+                .await?;
+            assert_eq!(
+                res.table().and_then(|table| table.table_status()),
+                Some(&aws_sdk_dynamodb::model::TableStatus::Active)
+            );
 
-            let expected_items1 = {
-                let mut h = HashMap::new();
-                h.insert("pk".to_string(), AttributeValue::S("123".to_string()));
-                h.insert("sk".to_string(), AttributeValue::S("456".to_string()));
-                h.insert("value".to_string(), AttributeValue::S("789".to_string()));
-                h
-            };
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
 
-            let expected_items2 = {
-                let mut h = HashMap::new();
-                h.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
-                h.insert("sk".to_string(), AttributeValue::S("def".to_string()));
-                h.insert("value".to_string(), AttributeValue::S("ghi".to_string()));
-                h
-            };
+#[tokio::test]
+async fn connection_faults() {
+    test_init();
 
-            // TODO: stable sort
-            let expected_output = aws_sdk_dynamodb::output::ScanOutput::builder()
-                .items(expected_items1)
-                .items(expected_items2)
-                .count(2)
-                .scanned_count(2)
-                .build();
+    skip_aws_cloud!();
 
-            assert_eq!(res, expected_output);
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        connection_faults: rynamodb::ConnectionFaults {
+            probability: 1.0,
+            stall_duration: Duration::from_millis(100),
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            client
+                .list_tables()
+                .send()
+                .await
+                .expect_err("faulted connection succeeded");
+
+            // admin endpoints are not faulted
+            let health = reqwest::get(format!("http://localhost:{port}/_health")).await?;
+            assert_eq!(health.text().await?, "ok");
 
             Ok(())
         }))
@@ -614,19 +768,2493 @@ async fn scan_table() {
     .unwrap();
 }
 
-#[derive(PartialEq, Debug)]
-struct SortableItem {
-    name: String,
-    value: AttributeValue,
-}
+#[tokio::test]
+async fn update_settings() {
+    test_init();
 
-impl Eq for SortableItem {}
+    skip_aws_cloud!();
 
-impl PartialOrd for SortableItem {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.name.partial_cmp(&other.name)
-    }
-}
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            let http = reqwest::Client::new();
+            let url = format!("http://localhost:{port}/_rynamodb/config");
+            let update = |body: serde_json::Value| http.put(&url).json(&body).send();
+
+            let res = update(serde_json::json!({
+                "connectionFaultProbability": 1.0,
+                "connectionFaultStallMs": 10,
+            }))
+            .await?;
+            assert!(res.status().is_success());
+            client
+                .list_tables()
+                .send()
+                .await
+                .expect_err("faulted connection succeeded");
+
+            let res = update(serde_json::json!({"connectionFaultProbability": 0.0})).await?;
+            let settings: serde_json::Value = res.json().await?;
+            assert_eq!(settings["connectionFaultProbability"], 0.0);
+            assert_eq!(settings["connectionFaultStallMs"], 10);
+            client.list_tables().send().await?;
+
+            let res = update(serde_json::json!({"unprocessedProbability": 2.0})).await?;
+            assert_eq!(res.status(), 400);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn unimplemented_operation() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            let err = client
+                .delete_item()
+                .table_name(&table_name)
+                .key("pk", AttributeValue::S("abc".to_string()))
+                .send()
+                .await
+                .expect_err("unimplemented operation succeeded")
+                .into_service_error();
+            assert_eq!(err.code(), Some("UnknownOperationException"));
+            assert!(err
+                .message()
+                .unwrap_or_default()
+                .contains("DeleteItem is not implemented by rynamodb"));
+
+            let report: serde_json::Value =
+                reqwest::get(format!("http://localhost:{port}/_rynamodb/operations"))
+                    .await?
+                    .json()
+                    .await?;
+            let listed = |key: &str, operation: &str| {
+                report[key]
+                    .as_array()
+                    .unwrap()
+                    .contains(&serde_json::Value::from(operation))
+            };
+            assert!(listed("implemented", "PutItem"));
+            assert!(listed("notImplemented", "DeleteItem"));
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fail_fast_unimplemented_features() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        fail_fast: true,
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("abc".to_string()))
+                .send()
+                .await?;
+
+            let err = client
+                .update_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "abc")))
+                .update_expression("ADD counter :one")
+                .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+                .send()
+                .await
+                .expect_err("unimplemented update action succeeded")
+                .into_service_error();
+            assert_eq!(err.code(), Some("NotImplementedByRynamodb"));
+            assert!(err
+                .message()
+                .unwrap_or_default()
+                .contains("ADD actions in update expressions is not implemented by rynamodb"));
+
+            let err = client
+                .delete_item()
+                .table_name(&table_name)
+                .key("pk", AttributeValue::S("abc".to_string()))
+                .send()
+                .await
+                .expect_err("unimplemented operation succeeded")
+                .into_service_error();
+            assert_eq!(err.code(), Some("NotImplementedByRynamodb"));
+
+            let url = format!("http://localhost:{port}/_rynamodb/unimplemented");
+            let report: serde_json::Value = reqwest::get(&url).await?.json().await?;
+            assert_eq!(report["failFast"], true);
+            let features: Vec<_> = report["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|feature| feature["feature"].as_str().unwrap())
+                .collect();
+            assert_eq!(
+                features,
+                ["ADD actions in update expressions", "DeleteItem operation"]
+            );
+            assert_eq!(report["features"][0]["operations"][0], "UpdateItem");
+
+            let metrics = reqwest::get(format!("http://localhost:{port}/_rynamodb/metrics"))
+                .await?
+                .text()
+                .await?;
+            assert!(metrics.contains(
+                "rynamodb_unimplemented_feature_requests_total{feature=\"DeleteItem operation\"} 1"
+            ));
+
+            reqwest::Client::new().delete(&url).send().await?;
+            let report: serde_json::Value = reqwest::get(&url).await?.json().await?;
+            assert_eq!(report["features"].as_array().unwrap().len(), 0);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn namespaces() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = reqwest::Client::new();
+            let send = |namespace: Option<&str>, operation: &str, body: serde_json::Value| {
+                let mut request = client
+                    .post(format!("http://localhost:{port}/"))
+                    .header("x-amz-target", format!("DynamoDB_20120810.{operation}"))
+                    .header("content-type", "application/x-amz-json-1.0")
+                    .json(&body);
+                if let Some(namespace) = namespace {
+                    request = request.header("x-rynamodb-namespace", namespace);
+                }
+                async move {
+                    let response = request.send().await?;
+                    Ok::<_, eyre::Report>((
+                        response.status(),
+                        response.json::<serde_json::Value>().await?,
+                    ))
+                }
+            };
+            let create_table = serde_json::json!({
+                "TableName": "shared",
+                "AttributeDefinitions": [{"AttributeName": "pk", "AttributeType": "S"}],
+                "KeySchema": [{"AttributeName": "pk", "KeyType": "HASH"}],
+                "BillingMode": "PAY_PER_REQUEST",
+            });
+
+            // the same table name can be created in each namespace
+            for namespace in ["first", "second"] {
+                let (status, _) =
+                    send(Some(namespace), "CreateTable", create_table.clone()).await?;
+                assert_eq!(status, 200);
+            }
+            let (status, _) = send(
+                Some("first"),
+                "PutItem",
+                serde_json::json!({"TableName": "shared", "Item": {"pk": {"S": "abc"}}}),
+            )
+            .await?;
+            assert_eq!(status, 200);
+
+            let scan = serde_json::json!({"TableName": "shared"});
+            let (_, first) = send(Some("first"), "Scan", scan.clone()).await?;
+            assert_eq!(first["Count"], 1);
+            let (_, second) = send(Some("second"), "Scan", scan.clone()).await?;
+            assert_eq!(second["Count"], 0);
+
+            // requests without a namespace do not see the namespaced tables
+            let (_, tables) = send(None, "ListTables", serde_json::json!({})).await?;
+            assert_eq!(tables["TableNames"], serde_json::json!([]));
+            let (status, _) = send(None, "Scan", scan).await?;
+            assert_eq!(status, 400);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn table_limits() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        limits: rynamodb::Limits {
+            max_tables: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&format!("table-{}", uuid::Uuid::new_v4()), &client).await?;
+
+            let err = default_dynamodb_table(&format!("table-{}", uuid::Uuid::new_v4()), &client)
+                .await
+                .expect_err("table limit was not enforced");
+            let err = err
+                .downcast::<SdkError<aws_sdk_dynamodb::error::CreateTableError>>()
+                .expect("unexpected error type");
+            assert!(err.into_service_error().is_limit_exceeded_exception());
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn shrunk_request_limits() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        limits: rynamodb::Limits {
+            max_item_bytes: 1024,
+            max_page_items: Some(3),
+            max_expression_bytes: 16,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            let err = client
+                .put_item()
+                .table_name(&table_name)
+                .set_item(Some(key("abc", "big")))
+                .item("payload", AttributeValue::S("x".repeat(1024)))
+                .send()
+                .await
+                .expect_err("oversized item was accepted")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+
+            let expected: Vec<String> = (0..7).map(|i| format!("{i:02}")).collect();
+            for sk in &expected {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .set_item(Some(key("abc", sk)))
+                    .send()
+                    .await?;
+            }
+            let mut seen = Vec::new();
+            let mut page_sizes = Vec::new();
+            let mut exclusive_start_key = None;
+            loop {
+                let res = client
+                    .query()
+                    .table_name(&table_name)
+                    .key_condition_expression("pk = :a")
+                    .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                    .set_exclusive_start_key(exclusive_start_key.take())
+                    .send()
+                    .await?;
+                let items = res.items().unwrap_or_default();
+                page_sizes.push(items.len());
+                seen.extend(items.iter().map(|item| item["sk"].as_s().unwrap().clone()));
+                match res.last_evaluated_key() {
+                    Some(key) => exclusive_start_key = Some(key.clone()),
+                    None => break,
+                }
+            }
+            assert_eq!(seen, expected);
+            assert_eq!(page_sizes, vec![3, 3, 1]);
+
+            // scans are paged the same way, and a lower `Limit` still applies
+            let mut seen = Vec::new();
+            let mut page_sizes = Vec::new();
+            let mut exclusive_start_key = None;
+            loop {
+                let res = client
+                    .scan()
+                    .table_name(&table_name)
+                    .limit(2)
+                    .set_exclusive_start_key(exclusive_start_key.take())
+                    .send()
+                    .await?;
+                let items = res.items().unwrap_or_default();
+                page_sizes.push(items.len());
+                seen.extend(items.iter().map(|item| item["sk"].as_s().unwrap().clone()));
+                match res.last_evaluated_key() {
+                    Some(key) => exclusive_start_key = Some(key.clone()),
+                    None => break,
+                }
+            }
+            assert_eq!(seen, expected);
+            assert_eq!(page_sizes, vec![2, 2, 2, 1]);
+
+            let err = client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :a AND sk > :b")
+                .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                .expression_attribute_values(":b", AttributeValue::S("00".to_string()))
+                .send()
+                .await
+                .expect_err("long expression was accepted")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn signed_pagination_tokens() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        let other_table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            default_dynamodb_table(&other_table_name, &client).await?;
+            for sk in ["a", "b", "c"] {
+                for table_name in [&table_name, &other_table_name] {
+                    client
+                        .put_item()
+                        .table_name(table_name)
+                        .set_item(Some(key("abc", sk)))
+                        .send()
+                        .await?;
+                }
+            }
+
+            let res = client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :a")
+                .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                .limit(1)
+                .send()
+                .await?;
+            let token = res.last_evaluated_key().expect("no token").clone();
+            // the token does not give away the key it was made from
+            assert!(!token.contains_key("sk"));
+            let res = client
+                .scan()
+                .table_name(&table_name)
+                .limit(1)
+                .send()
+                .await?;
+            let scan_token = res.last_evaluated_key().expect("no token").clone();
+
+            // tokens continue from where the page ended
+            let res = client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :a")
+                .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                .set_exclusive_start_key(Some(token.clone()))
+                .send()
+                .await?;
+            let sort_keys: Vec<_> = res
+                .items()
+                .unwrap_or_default()
+                .iter()
+                .map(|item| item["sk"].as_s().unwrap().as_str())
+                .collect();
+            assert_eq!(sort_keys, vec!["b", "c"]);
+
+            let mut tampered = token.clone();
+            for value in tampered.values_mut() {
+                let mut bytes = value.as_b().unwrap().clone().into_inner();
+                let last = bytes.len() - 1;
+                bytes[last] ^= 1;
+                *value = AttributeValue::B(aws_sdk_dynamodb::types::Blob::new(bytes));
+            }
+            for (start_table_name, start_key) in [
+                (&table_name, tampered.clone()),
+                (&other_table_name, token.clone()),
+            ] {
+                let err = client
+                    .query()
+                    .table_name(start_table_name)
+                    .key_condition_expression("pk = :a")
+                    .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                    .set_exclusive_start_key(Some(start_key))
+                    .send()
+                    .await
+                    .expect_err("invalid token was accepted")
+                    .into_service_error();
+                assert_eq!(err.code(), Some("ValidationException"));
+            }
+            for (start_table_name, start_key) in [
+                (&table_name, tampered),
+                (&other_table_name, scan_token.clone()),
+            ] {
+                let err = client
+                    .scan()
+                    .table_name(start_table_name)
+                    .set_exclusive_start_key(Some(start_key))
+                    .send()
+                    .await
+                    .expect_err("invalid token was accepted")
+                    .into_service_error();
+                assert_eq!(err.code(), Some("ValidationException"));
+            }
+
+            // table names are paged in order
+            let mut names = Vec::new();
+            let mut exclusive_start_table_name = None;
+            loop {
+                let res = client
+                    .list_tables()
+                    .limit(1)
+                    .set_exclusive_start_table_name(exclusive_start_table_name.take())
+                    .send()
+                    .await?;
+                names.extend(res.table_names().unwrap_or_default().iter().cloned());
+                match res.last_evaluated_table_name() {
+                    Some(token) => {
+                        assert!(!token.contains("table-"));
+                        exclusive_start_table_name = Some(token.to_string());
+                    }
+                    None => break,
+                }
+            }
+            let mut expected = vec![table_name.clone(), other_table_name.clone()];
+            expected.sort();
+            assert_eq!(names, expected);
+
+            let err = client
+                .list_tables()
+                .exclusive_start_table_name("rynamodb:not-a-token")
+                .send()
+                .await
+                .expect_err("invalid token was accepted")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn slow_operation_log() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        slow_operations: rynamodb::SlowOperationThresholds {
+            scanned_count: Some(2),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            for sk in ["a", "b"] {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()))
+                    .send()
+                    .await?;
+            }
+
+            // below the threshold
+            client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :a AND sk = :b")
+                .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                .expression_attribute_values(":b", AttributeValue::S("a".to_string()))
+                .send()
+                .await?;
+
+            client.scan().table_name(&table_name).send().await?;
+
+            let entries: serde_json::Value =
+                reqwest::get(format!("http://localhost:{port}/_rynamodb/slow-operations"))
+                    .await?
+                    .json()
+                    .await?;
+            let entries = entries.as_array().expect("entries should be a list");
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0]["operation"], "Scan");
+            assert_eq!(entries[0]["tableName"], table_name.as_str());
+            assert_eq!(entries[0]["scannedCount"], 2);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn error_log() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            let result = client
+                .describe_table()
+                .table_name("missing-table")
+                .send()
+                .await;
+            assert!(result.is_err());
+
+            let summary: serde_json::Value =
+                reqwest::get(format!("http://localhost:{port}/_rynamodb/errors"))
+                    .await?
+                    .json()
+                    .await?;
+            assert_eq!(summary["counts"]["ResourceNotFoundException"], 1);
+            let recent = summary["recent"]
+                .as_array()
+                .expect("recent errors should be a list");
+            assert_eq!(recent.len(), 1);
+            assert_eq!(recent[0]["operation"], "DescribeTable");
+            assert_eq!(recent[0]["tableName"], "missing-table");
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn edge_routing() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        edge: rynamodb::EdgeRouting {
+            path_prefix: Some("/dynamodb/".to_string()),
+            host_routing: true,
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = create_client(Some(&format!("http://127.0.0.1:{port}/dynamodb"))).await;
+            let tables = client.list_tables().send().await?;
+            assert!(tables.table_names().unwrap_or_default().is_empty());
+
+            let health = reqwest::get(format!("http://127.0.0.1:{port}/dynamodb/_health")).await?;
+            assert_eq!(health.status(), 200);
+            let unprefixed = reqwest::get(format!("http://127.0.0.1:{port}/_health")).await?;
+            assert_eq!(unprefixed.status(), 404);
+
+            let other_service = reqwest::Client::new()
+                .get(format!("http://127.0.0.1:{port}/dynamodb/_health"))
+                .header("Host", "s3.localhost.localstack.cloud:4566")
+                .send()
+                .await?;
+            assert_eq!(other_service.status(), 404);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn operation_tail() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let mut tail = reqwest::get(format!("http://localhost:{port}/_rynamodb/tail")).await?;
+            assert_eq!(tail.headers()["content-type"], "text/event-stream");
+
+            let client = test_client(port).await;
+            let result = client
+                .describe_table()
+                .table_name("missing-table")
+                .send()
+                .await;
+            assert!(result.is_err());
+
+            let chunk = tail.chunk().await?.expect("tail ended");
+            let chunk = String::from_utf8_lossy(&chunk);
+            let data = chunk
+                .lines()
+                .find_map(|line| line.strip_prefix("data:"))
+                .expect("event without data");
+            let event: serde_json::Value = serde_json::from_str(data)?;
+            assert_eq!(event["operation"], "DescribeTable");
+            assert_eq!(event["tableName"], "missing-table");
+            assert_eq!(event["status"], 400);
+            assert_eq!(event["errorType"], "ResourceNotFoundException");
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn added_index_backfill() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        lifecycle: rynamodb::LifecycleSimulation {
+            index_backfill_delay: Duration::from_secs(60),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            let put = |sk: &'static str| {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()))
+                    .item("category", AttributeValue::S("books".to_string()))
+                    .send()
+            };
+            put("before").await?;
+
+            client
+                .update_table()
+                .table_name(&table_name)
+                .attribute_definitions(
+                    AttributeDefinition::builder()
+                        .attribute_name("category")
+                        .attribute_type(ScalarAttributeType::S)
+                        .build(),
+                )
+                .global_secondary_index_updates(
+                    GlobalSecondaryIndexUpdate::builder()
+                        .create(
+                            CreateGlobalSecondaryIndexAction::builder()
+                                .index_name("by-category")
+                                .key_schema(
+                                    KeySchemaElement::builder()
+                                        .attribute_name("category")
+                                        .key_type(KeyType::Hash)
+                                        .build(),
+                                )
+                                .projection(
+                                    Projection::builder()
+                                        .projection_type(ProjectionType::All)
+                                        .build(),
+                                )
+                                .build(),
+                        )
+                        .build(),
+                )
+                .send()
+                .await?;
+
+            let res = client
+                .describe_table()
+                .table_name(&table_name)
+                .send()
+                .await?;
+            let index = &res
+                .table()
+                .and_then(|table| table.global_secondary_indexes())
+                .expect("no indexes")[0];
+            assert_eq!(index.index_status(), Some(&IndexStatus::Creating));
+            assert_eq!(index.backfilling(), Some(true));
+
+            put("after").await?;
+
+            let res = client
+                .query()
+                .table_name(&table_name)
+                .index_name("by-category")
+                .key_condition_expression("category = :c")
+                .expression_attribute_values(":c", AttributeValue::S("books".to_string()))
+                .send()
+                .await?;
+            let items = res.items().unwrap_or_default();
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0]["sk"].as_s().unwrap(), "after");
+
+            // global secondary indexes are only eventually consistent
+            let err = client
+                .query()
+                .table_name(&table_name)
+                .index_name("by-category")
+                .key_condition_expression("category = :c")
+                .expression_attribute_values(":c", AttributeValue::S("books".to_string()))
+                .consistent_read(true)
+                .send()
+                .await
+                .expect_err("consistent read of an index succeeded")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+            assert_eq!(
+                err.message(),
+                Some("Consistent reads are not supported on global secondary indexes")
+            );
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn copy_and_rename_table() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        let copy_name = format!("table-{}", uuid::Uuid::new_v4());
+        let renamed_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await?;
+
+            let http = reqwest::Client::new();
+            let admin_url = |name: &str, action: &str| {
+                format!("http://localhost:{port}/_rynamodb/tables/{name}/{action}")
+            };
+
+            let res = http
+                .post(admin_url(&table_name, "copy"))
+                .json(&serde_json::json!({ "targetTableName": copy_name }))
+                .send()
+                .await?;
+            assert!(res.status().is_success());
+
+            let res = http
+                .post(admin_url(&copy_name, "rename"))
+                .json(&serde_json::json!({ "targetTableName": renamed_name }))
+                .send()
+                .await?;
+            assert!(res.status().is_success());
+
+            // the target must not already exist
+            let res = http
+                .post(admin_url(&table_name, "copy"))
+                .json(&serde_json::json!({ "targetTableName": renamed_name }))
+                .send()
+                .await?;
+            assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+            let item = client
+                .get_item()
+                .table_name(&renamed_name)
+                .key("pk", AttributeValue::S("abc".to_string()))
+                .key("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await?;
+            assert!(item.item().is_some());
+
+            let err = client
+                .describe_table()
+                .table_name(&copy_name)
+                .send()
+                .await
+                .expect_err("renamed table still exists");
+            assert!(err.into_service_error().is_resource_not_found_exception());
+
+            // the source table is unaffected
+            client
+                .describe_table()
+                .table_name(&table_name)
+                .send()
+                .await?;
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn table_attribute_statistics() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            for sk in ["a", "b"] {
+                let mut request = client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()));
+                if sk == "a" {
+                    request = request.item("value", AttributeValue::N("1".to_string()));
+                }
+                request.send().await?;
+            }
+
+            let stats: serde_json::Value = reqwest::get(format!(
+                "http://localhost:{port}/_rynamodb/tables/{table_name}/attributes?sampleSize=10"
+            ))
+            .await?
+            .json()
+            .await?;
+            assert_eq!(stats["sampledItems"], 2);
+            assert_eq!(stats["totalItems"], 2);
+            let attributes = stats["attributes"]
+                .as_array()
+                .expect("attributes should be a list");
+            let names: Vec<_> = attributes.iter().map(|a| a["name"].clone()).collect();
+            assert_eq!(names, ["pk", "sk", "value"]);
+            assert_eq!(attributes[2]["fillRate"], 0.5);
+            assert_eq!(attributes[2]["types"]["N"], 1);
+            assert_eq!(stats["writtenItemSizes"]["count"], 2);
+            assert_eq!(stats["writtenItemSizes"]["buckets"][0]["count"], 2);
+
+            let res = reqwest::get(format!(
+                "http://localhost:{port}/_rynamodb/tables/missing/attributes"
+            ))
+            .await?;
+            assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn csv_export() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            for (pk, sk) in [("def", "b"), ("abc", "b"), ("abc", "a")] {
+                let mut request = client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S(pk.to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()));
+                if sk == "a" {
+                    request = request.item(
+                        "tags",
+                        AttributeValue::L(vec![AttributeValue::S("x,y".to_string())]),
+                    );
+                }
+                request.send().await?;
+            }
+
+            let res = reqwest::get(format!(
+                "http://localhost:{port}/_rynamodb/tables/{table_name}/csv"
+            ))
+            .await?;
+            assert_eq!(res.headers()["content-type"], "text/csv; charset=utf-8");
+            assert_eq!(
+                res.text().await?,
+                "pk,sk,tags\r\n\
+                abc,a,\"{\"\"L\"\":[{\"\"S\"\":\"\"x,y\"\"}]}\"\r\n\
+                abc,b,\r\n\
+                def,b,\r\n"
+            );
+
+            let res = reqwest::Client::new()
+                .post(format!("http://localhost:{port}/_rynamodb/csv/Query"))
+                .json(&serde_json::json!({
+                    "TableName": table_name,
+                    "KeyConditionExpression": "pk = :pk AND sk > :sk",
+                    "ExpressionAttributeValues": {
+                        ":pk": {"S": "abc"},
+                        ":sk": {"S": "a"},
+                    },
+                }))
+                .send()
+                .await?;
+            assert_eq!(res.text().await?, "pk,sk\r\nabc,b\r\n");
+
+            let res = reqwest::get(format!(
+                "http://localhost:{port}/_rynamodb/tables/missing/csv"
+            ))
+            .await?;
+            assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn patch_item_over_admin_api() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            client
+                .put_item()
+                .table_name(&table_name)
+                .set_item(Some(key("abc", "a")))
+                .item("status", AttributeValue::S("pending".to_string()))
+                .item("notes", AttributeValue::S("fragile".to_string()))
+                .send()
+                .await?;
+
+            let url = format!("http://localhost:{port}/_rynamodb/tables/{table_name}/items");
+            let http = reqwest::Client::new();
+            let res = http
+                .patch(&url)
+                .json(&serde_json::json!({
+                    "key": {"pk": {"S": "abc"}, "sk": {"S": "a"}},
+                    "patch": {"status": {"N": "3"}, "notes": null},
+                }))
+                .send()
+                .await?;
+            assert_eq!(res.status(), reqwest::StatusCode::OK);
+
+            let item = client
+                .get_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "a")))
+                .send()
+                .await?
+                .item
+                .expect("patched item should exist");
+            assert_eq!(item["status"], AttributeValue::N("3".to_string()));
+            assert!(!item.contains_key("notes"));
+
+            // key attributes cannot be patched
+            let res = http
+                .patch(&url)
+                .json(&serde_json::json!({
+                    "key": {"pk": {"S": "abc"}, "sk": {"S": "a"}},
+                    "patch": {"sk": {"S": "b"}},
+                }))
+                .send()
+                .await?;
+            assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn create_existing_table() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    for idempotent_create_table in [false, true] {
+        let router = rynamodb::router(rynamodb::RynamodbConfig {
+            idempotent_create_table,
+            ..Default::default()
+        });
+        rynamodb::test_run_server(router, |port| {
+            let table_name = format!("table-{}", uuid::Uuid::new_v4());
+            Box::new(Box::pin(async move {
+                let client = test_client(port).await;
+                default_dynamodb_table(&table_name, &client).await?;
+
+                let res = default_dynamodb_table(&table_name, &client).await;
+                assert_eq!(res.is_ok(), idempotent_create_table);
+
+                // a different schema is always an error
+                let err = client
+                    .create_table()
+                    .table_name(&table_name)
+                    .key_schema(
+                        KeySchemaElement::builder()
+                            .attribute_name("pk")
+                            .key_type(KeyType::Hash)
+                            .build(),
+                    )
+                    .attribute_definitions(
+                        AttributeDefinition::builder()
+                            .attribute_name("pk")
+                            .attribute_type(ScalarAttributeType::S)
+                            .build(),
+                    )
+                    .send()
+                    .await
+                    .expect_err("creating a table with a different schema succeeded");
+                assert!(err.into_service_error().is_resource_in_use_exception());
+
+                Ok(())
+            }))
+        })
+        .await
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn time_to_live_sweep() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            client
+                .update_time_to_live()
+                .table_name(&table_name)
+                .time_to_live_specification(
+                    TimeToLiveSpecification::builder()
+                        .attribute_name("expires")
+                        .enabled(true)
+                        .build(),
+                )
+                .send()
+                .await?;
+            let description = client
+                .describe_time_to_live()
+                .table_name(&table_name)
+                .send()
+                .await?
+                .time_to_live_description
+                .expect("time to live description");
+            assert_eq!(
+                description.time_to_live_status,
+                Some(TimeToLiveStatus::Enabled)
+            );
+            assert_eq!(description.attribute_name.as_deref(), Some("expires"));
+
+            let now = chrono::Utc::now().timestamp();
+            for (sk, expires) in [
+                ("expired", AttributeValue::N((now - 60).to_string())),
+                ("live", AttributeValue::N((now + 3600).to_string())),
+                ("string", AttributeValue::S((now - 60).to_string())),
+                (
+                    "ancient",
+                    AttributeValue::N((now - 6 * 365 * 24 * 3600).to_string()),
+                ),
+            ] {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()))
+                    .item("expires", expires)
+                    .send()
+                    .await?;
+            }
+
+            let sweep: serde_json::Value = reqwest::Client::new()
+                .post(format!("http://localhost:{port}/_rynamodb/ttl/sweep"))
+                .send()
+                .await?
+                .json()
+                .await?;
+            assert_eq!(sweep["expiredItems"], 1);
+
+            let res = client.scan().table_name(&table_name).send().await?;
+            let mut remaining: Vec<_> = res
+                .items()
+                .unwrap_or_default()
+                .iter()
+                .map(|item| item["sk"].as_s().unwrap().clone())
+                .collect();
+            remaining.sort();
+            assert_eq!(remaining, ["ancient", "live", "string"]);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn table_encryption_settings() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+
+            client
+                .create_table()
+                .table_name(&table_name)
+                .key_schema(
+                    KeySchemaElement::builder()
+                        .attribute_name("pk")
+                        .key_type(KeyType::Hash)
+                        .build(),
+                )
+                .attribute_definitions(
+                    AttributeDefinition::builder()
+                        .attribute_name("pk")
+                        .attribute_type(ScalarAttributeType::S)
+                        .build(),
+                )
+                .sse_specification(SseSpecification::builder().enabled(true).build())
+                .send()
+                .await?;
+
+            let res = client
+                .describe_table()
+                .table_name(&table_name)
+                .send()
+                .await?;
+            let sse_description = res
+                .table()
+                .and_then(|table| table.sse_description())
+                .expect("no sse description");
+            assert_eq!(sse_description.status(), Some(&SseStatus::Enabled));
+            assert_eq!(sse_description.sse_type(), Some(&SseType::Kms));
+            assert!(sse_description
+                .kms_master_key_arn()
+                .expect("no kms key arn")
+                .starts_with("arn:aws:kms:"));
+
+            let res = client
+                .update_table()
+                .table_name(&table_name)
+                .sse_specification(SseSpecification::builder().enabled(false).build())
+                .send()
+                .await?;
+            assert!(res
+                .table_description()
+                .expect("no table description")
+                .sse_description()
+                .is_none());
+
+            client.delete_table().table_name(&table_name).send().await?;
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn batch_write() -> Result<()> {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            let write_request = WriteRequest::builder()
+                .put_request(
+                    PutRequest::builder()
+                        .item("pk", AttributeValue::S("abc".to_string()))
+                        .item("sk", AttributeValue::S("def".to_string()))
+                        .build(),
+                )
+                .build();
+
+            let res = client
+                .batch_write_item()
+                .request_items(table_name, vec![write_request])
+                .send()
+                .await
+                .wrap_err("inserting item")?;
+
+            let result = std::panic::catch_unwind(|| {
+                insta::assert_debug_snapshot!(res);
+            });
+
+            result.map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
+        }))
+    })
+    .await
+}
+
+fn key(pk: &str, sk: &str) -> HashMap<String, AttributeValue> {
+    let mut h = HashMap::new();
+    h.insert("pk".to_string(), AttributeValue::S(pk.to_string()));
+    h.insert("sk".to_string(), AttributeValue::S(sk.to_string()));
+    h
+}
+
+fn put_request(pk: &str, sk: &str) -> WriteRequest {
+    WriteRequest::builder()
+        .put_request(
+            PutRequest::builder()
+                .item("pk", AttributeValue::S(pk.to_string()))
+                .item("sk", AttributeValue::S(sk.to_string()))
+                .build(),
+        )
+        .build()
+}
+
+#[tokio::test]
+async fn batch_get() {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            client
+                .batch_write_item()
+                .request_items(
+                    &table_name,
+                    vec![put_request("abc", "def"), put_request("abc", "ghi")],
+                )
+                .send()
+                .await
+                .wrap_err("inserting items")?;
+
+            let res = client
+                .batch_get_item()
+                .request_items(
+                    &table_name,
+                    KeysAndAttributes::builder()
+                        .keys(key("abc", "def"))
+                        .keys(key("abc", "ghi"))
+                        .keys(key("abc", "missing"))
+                        .build(),
+                )
+                .send()
+                .await
+                .wrap_err("fetching items")?;
+
+            let mut items = res
+                .responses()
+                .and_then(|responses| responses.get(&table_name))
+                .cloned()
+                .expect("no responses for table");
+            items.sort_by_key(|item| format!("{:?}", item.get("sk")));
+            assert_eq!(items, vec![key("abc", "def"), key("abc", "ghi")]);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn batch_get_limits() {
+    test_init();
+
+    // DynamoDB does not choose which keys are left unprocessed in a fixed order
+    skip_aws_cloud!();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            // 41 of these items fit in the 16MB response limit
+            let payload = "x".repeat(400_000);
+            let sort_keys: Vec<_> = (0..45).map(|i| format!("{i:02}")).collect();
+            for sk in &sort_keys {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .set_item(Some(key("abc", sk)))
+                    .item("payload", AttributeValue::S(payload.clone()))
+                    .send()
+                    .await
+                    .wrap_err("inserting item")?;
+            }
+
+            let request = sort_keys
+                .iter()
+                .fold(KeysAndAttributes::builder(), |request, sk| {
+                    request.keys(key("abc", sk))
+                })
+                .build();
+            let res = client
+                .batch_get_item()
+                .request_items(&table_name, request)
+                .send()
+                .await
+                .wrap_err("fetching items")?;
+            let returned = res
+                .responses()
+                .and_then(|responses| responses.get(&table_name))
+                .expect("no responses for table");
+            assert_eq!(returned.len(), 41);
+            let unprocessed_keys = res
+                .unprocessed_keys()
+                .and_then(|keys| keys.get(&table_name))
+                .and_then(|keys| keys.keys())
+                .expect("no unprocessed keys");
+            let expected: Vec<_> = sort_keys[41..].iter().map(|sk| key("abc", sk)).collect();
+            assert_eq!(unprocessed_keys, expected.as_slice());
+
+            let request = (0..101)
+                .fold(KeysAndAttributes::builder(), |request, i| {
+                    request.keys(key("abc", &i.to_string()))
+                })
+                .build();
+            let err = client
+                .batch_get_item()
+                .request_items(&table_name, request)
+                .send()
+                .await
+                .expect_err("too many keys were accepted")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn batch_unprocessed_simulation() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        unprocessed_simulation: rynamodb::UnprocessedSimulation {
+            probability: 1.0,
+            min_batch_size: 2,
+        },
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            // batches smaller than the threshold are always processed
+            let res = client
+                .batch_write_item()
+                .request_items(&table_name, vec![put_request("abc", "def")])
+                .send()
+                .await?;
+            assert!(res
+                .unprocessed_items()
+                .map(|items| items.is_empty())
+                .unwrap_or(true));
+
+            let res = client
+                .batch_write_item()
+                .request_items(
+                    &table_name,
+                    vec![put_request("abc", "ghi"), put_request("abc", "jkl")],
+                )
+                .send()
+                .await?;
+            let unprocessed_items = res
+                .unprocessed_items()
+                .and_then(|items| items.get(&table_name))
+                .expect("no unprocessed items");
+            assert_eq!(unprocessed_items.len(), 2);
+
+            let res = client
+                .batch_get_item()
+                .request_items(
+                    &table_name,
+                    KeysAndAttributes::builder()
+                        .keys(key("abc", "def"))
+                        .keys(key("abc", "ghi"))
+                        .build(),
+                )
+                .send()
+                .await?;
+            let unprocessed_keys = res
+                .unprocessed_keys()
+                .and_then(|keys| keys.get(&table_name))
+                .and_then(|keys| keys.keys())
+                .expect("no unprocessed keys");
+            assert_eq!(unprocessed_keys.len(), 2);
+
+            client.delete_table().table_name(&table_name).send().await?;
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn builder_hooks() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let operations = Arc::new(Mutex::new(Vec::new()));
+
+    let router = {
+        let requests = Arc::clone(&requests);
+        let operations = Arc::clone(&operations);
+        rynamodb::Rynamodb::builder()
+            .layer(axum::middleware::from_fn(
+                move |req: Request<Body>, next: Next<Body>| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    next.run(req)
+                },
+            ))
+            .on_operation(move |request| {
+                operations.lock().unwrap().push(request.operation);
+                None
+            })
+            .on_operation(|request| {
+                (request.operation == rynamodb::OperationType::PutItem).then(|| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        axum::Json(serde_json::json!({
+                            "__type": "com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException",
+                            "message": "injected fault",
+                        })),
+                    )
+                        .into_response()
+                })
+            })
+            .build_router()
+    };
+
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            let err = client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await
+                .expect_err("fault was not injected");
+            assert!(err
+                .into_service_error()
+                .is_conditional_check_failed_exception());
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        *operations.lock().unwrap(),
+        vec![
+            rynamodb::OperationType::CreateTable,
+            rynamodb::OperationType::DescribeTable,
+            rynamodb::OperationType::PutItem
+        ]
+    );
+}
+
+#[tokio::test]
+async fn operation_observers() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let puts = Arc::new(Mutex::new(Vec::new()));
+    let queries = Arc::new(AtomicUsize::new(0));
+
+    let table_name = format!("table-{}", uuid::Uuid::new_v4());
+    let router = {
+        let puts = Arc::clone(&puts);
+        let queries = Arc::clone(&queries);
+        let table_name = table_name.clone();
+        rynamodb::Rynamodb::builder()
+            .on_put_item(move |table, item| {
+                puts.lock()
+                    .unwrap()
+                    .push((table.to_string(), item.get("sk").cloned()));
+            })
+            .on_query(move |table, _input| {
+                if table == table_name {
+                    queries.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build_router()
+    };
+
+    let name = table_name.clone();
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&name, &client).await?;
+
+            client
+                .put_item()
+                .table_name(&name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await?;
+
+            for _ in 0..3 {
+                client
+                    .query()
+                    .table_name(&name)
+                    .key_condition_expression("pk = :a")
+                    .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                    .send()
+                    .await?;
+            }
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(queries.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        *puts.lock().unwrap(),
+        vec![(
+            table_name,
+            Some(serde_dynamo::AttributeValue::S("def".to_string()))
+        )]
+    );
+}
+
+#[tokio::test]
+async fn legacy_scan_parameters() {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            for (sk, count) in [("a", "1"), ("b", "2"), ("c", "3")] {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()))
+                    .item("count", AttributeValue::N(count.to_string()))
+                    .send()
+                    .await?;
+            }
+
+            let res = client
+                .scan()
+                .table_name(&table_name)
+                .scan_filter(
+                    "count",
+                    Condition::builder()
+                        .comparison_operator(ComparisonOperator::Ge)
+                        .attribute_value_list(AttributeValue::N("2".to_string()))
+                        .build(),
+                )
+                .attributes_to_get("sk")
+                .send()
+                .await?;
+
+            assert_eq!(res.count(), 2);
+            assert_eq!(res.scanned_count(), 3);
+            let mut sort_keys: Vec<_> = res
+                .items()
+                .unwrap_or_default()
+                .iter()
+                .map(|item| {
+                    assert_eq!(item.len(), 1);
+                    item["sk"].as_s().unwrap().clone()
+                })
+                .collect();
+            sort_keys.sort();
+            assert_eq!(sort_keys, vec!["b".to_string(), "c".to_string()]);
+
+            let res = client
+                .scan()
+                .table_name(&table_name)
+                .attributes_to_get("sk")
+                .filter_expression("sk = :a")
+                .expression_attribute_values(":a", AttributeValue::S("a".to_string()))
+                .send()
+                .await;
+            let body = res.to_json_value().await.expect("no error body");
+            assert_eq!(
+                body["message"],
+                "Can not use both expression and non-expression parameters in the same request: \
+                Non-expression parameters: {AttributesToGet} Expression parameters: {FilterExpression}"
+            );
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn legacy_get_item_parameters() {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .item("count", AttributeValue::N("1".to_string()))
+                .send()
+                .await?;
+
+            let res = client
+                .get_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .attributes_to_get("count")
+                .attributes_to_get("missing")
+                .send()
+                .await?;
+            let item = res.item().expect("item not found");
+            assert_eq!(item.len(), 1);
+            assert_eq!(item["count"].as_n().unwrap(), "1");
+
+            let res = client
+                .batch_get_item()
+                .request_items(
+                    &table_name,
+                    KeysAndAttributes::builder()
+                        .keys(key("abc", "def"))
+                        .attributes_to_get("sk")
+                        .build(),
+                )
+                .send()
+                .await?;
+            let items = &res.responses().unwrap()[&table_name];
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].len(), 1);
+            assert_eq!(items[0]["sk"].as_s().unwrap(), "def");
+
+            let res = client
+                .get_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .attributes_to_get("count")
+                .projection_expression("sk")
+                .send()
+                .await;
+            let body = res.to_json_value().await.expect("no error body");
+            assert_eq!(
+                body["message"],
+                "Can not use both expression and non-expression parameters in the same request: \
+                Non-expression parameters: {AttributesToGet} Expression parameters: {ProjectionExpression}"
+            );
+
+            // projection expressions on their own are not applied, so they are reported rather
+            // than answered with the whole item
+            let res = client
+                .get_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .projection_expression("sk")
+                .send()
+                .await;
+            let body = res.to_json_value().await.expect("no error body");
+            assert_eq!(
+                body["message"],
+                "ProjectionExpression in GetItem is not supported"
+            );
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn put_item() -> Result<()> {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            let res = client
+                .put_item()
+                .table_name(table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await
+                .wrap_err("inserting item")?;
+
+            let result = std::panic::catch_unwind(|| {
+                insta::assert_debug_snapshot!(res);
+            });
+
+            result.map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
+        }))
+    })
+    .await
+}
+
+#[tokio::test]
+async fn put_item_duplicate_set_elements() -> Result<()> {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            let err = client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .item(
+                    "numbers",
+                    AttributeValue::Ns(vec!["1".to_string(), "1.0".to_string()]),
+                )
+                .send()
+                .await
+                .expect_err("writing a set with duplicate elements succeeded");
+            let err = err.into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+            assert_eq!(
+                err.message(),
+                Some("One or more parameter values were invalid: Input collection [1, 1.0] contains duplicates.")
+            );
+
+            let res = client
+                .get_item()
+                .table_name(&table_name)
+                .key("pk", AttributeValue::S("abc".to_string()))
+                .key("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await?;
+            assert!(res.item().is_none());
+
+            Ok(())
+        }))
+    })
+    .await
+}
+
+#[tokio::test]
+async fn update_item() -> Result<()> {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            // updating an item which does not exist creates it
+            client
+                .update_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .update_expression("SET visits = :one, tags = :tags, note = :note")
+                .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+                .expression_attribute_values(
+                    ":tags",
+                    AttributeValue::L(vec![AttributeValue::S("a".to_string())]),
+                )
+                .expression_attribute_values(":note", AttributeValue::S("x".to_string()))
+                .send()
+                .await
+                .wrap_err("creating item")?;
+
+            let res = client
+                .update_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .update_expression(
+                    "SET visits = visits + :one, #tags = list_append(#tags, :more) REMOVE note",
+                )
+                .expression_attribute_names("#tags", "tags")
+                .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+                .expression_attribute_values(
+                    ":more",
+                    AttributeValue::L(vec![AttributeValue::S("b".to_string())]),
+                )
+                .return_values(ReturnValue::AllNew)
+                .send()
+                .await
+                .wrap_err("updating item")?;
+            let mut expected = key("abc", "def");
+            expected.insert("visits".to_string(), AttributeValue::N("2".to_string()));
+            expected.insert(
+                "tags".to_string(),
+                AttributeValue::L(vec![
+                    AttributeValue::S("a".to_string()),
+                    AttributeValue::S("b".to_string()),
+                ]),
+            );
+            assert_eq!(res.attributes(), Some(&expected));
+
+            let res = client
+                .get_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .send()
+                .await?;
+            assert_eq!(res.item(), Some(&expected));
+
+            let err = client
+                .update_item()
+                .table_name(&table_name)
+                .set_key(Some(key("abc", "def")))
+                .update_expression("SET sk = :v")
+                .expression_attribute_values(":v", AttributeValue::S("other".to_string()))
+                .send()
+                .await
+                .expect_err("updating a key attribute succeeded")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+
+            Ok(())
+        }))
+    })
+    .await
+}
+
+#[tokio::test]
+async fn list_tables() {
+    test_init();
+
+    with_table(|_table_name, client| {
+        Box::new(Box::pin(async move {
+            let res = client
+                .list_tables()
+                .send()
+                .await
+                .wrap_err("listing tables")?;
+
+            insta::with_settings!({ filters => vec![
+                // table name
+                (r"table-[a-z0-9]{8}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{12}", "[table-name]"),
+            ]}, {
+                std::panic::catch_unwind(|| {
+                    insta::assert_debug_snapshot!(res);
+                })
+                .map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
+            })
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn round_trip() {
+    test_init();
+
+    // check that we can insert and fetch data from rynamodb
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .send()
+                .await
+                .wrap_err("inserting item")?;
+
+            let res = client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :a AND sk = :b")
+                .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                .expression_attribute_values(":b", AttributeValue::S("def".to_string()))
+                .send()
+                .await
+                .wrap_err("performing query")?;
+
+            // TODO: we have to sort the returned items somehow, or else the snapshotting won't
+            // work.
+
+            let expected_items = {
+                let mut h = HashMap::new();
+                h.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+                h.insert("sk".to_string(), AttributeValue::S("def".to_string()));
+                h
+            };
+            let expected_output = aws_sdk_dynamodb::output::QueryOutput::builder()
+                .items(expected_items)
+                .count(1)
+                .scanned_count(1)
+                .build();
+
+            assert_eq!(res, expected_output);
+
+            Ok(())
+
+            //             let result = insta::with_settings!({ filters => vec![
+            //                 // table name
+            //                 (r"table-[a-z0-9]{8}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{12}", "[table-name]"),
+            //                 // region
+            //                 (r"(eu-west-2|us-east-1)", "[region]"),
+            //                 // account id
+            //                 (r"[0-9]{12}", "[account]"),
+            //                 // table id
+            //                 (r"[a-z0-9]{8}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{4}-[a-z0-9]{12}", "[table-id]"),
+            //                 // datetime seconds
+            //                 (r"seconds:\s*\d+", "[seconds]"),
+            //                 // datetime nanoseconds
+            //                 (r"subsecond_nanos:\s*\d+", "[nanos]"),
+            //             ] }, {
+            //                 std::panic::catch_unwind(|| {
+            //                     insta::assert_debug_snapshot!(res);
+            //                 })
+            //             });
+
+            // result.map_err(|e| eyre::eyre!("snapshot did not match: {e:?}"))
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn query_pagination_with_interleaved_writes() {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            let put = |sk: &'static str| {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.to_string()))
+                    .send()
+            };
+            for sk in ["e", "a", "c"] {
+                put(sk).await.wrap_err("inserting item")?;
+            }
+
+            let mut seen = Vec::new();
+            let mut exclusive_start_key = None;
+            for page in 0.. {
+                let res = client
+                    .query()
+                    .table_name(&table_name)
+                    .key_condition_expression("pk = :a")
+                    .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                    .limit(2)
+                    .set_exclusive_start_key(exclusive_start_key.take())
+                    .send()
+                    .await
+                    .wrap_err("performing query")?;
+                for item in res.items().unwrap_or_default() {
+                    seen.push(item["sk"].as_s().unwrap().clone());
+                }
+
+                // writes between pages, before and after the current position
+                if page == 0 {
+                    put("b").await.wrap_err("inserting item")?;
+                    put("d").await.wrap_err("inserting item")?;
+                }
+
+                match res.last_evaluated_key() {
+                    Some(key) => exclusive_start_key = Some(key.clone()),
+                    None => break,
+                }
+            }
+
+            assert_eq!(seen, vec!["a", "c", "d", "e"]);
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn query_pagination_with_page_jitter() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        page_jitter: Some(rynamodb::PageJitter {
+            min_page_size: 1,
+            max_page_size: 4,
+        }),
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            let expected: Vec<String> = (0..30).map(|i| format!("{i:02}")).collect();
+            for sk in &expected {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S(sk.clone()))
+                    .send()
+                    .await?;
+            }
+
+            let mut seen = Vec::new();
+            let mut page_sizes = Vec::new();
+            let mut exclusive_start_key = None;
+            loop {
+                let res = client
+                    .query()
+                    .table_name(&table_name)
+                    .key_condition_expression("pk = :a")
+                    .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                    .limit(3)
+                    .set_exclusive_start_key(exclusive_start_key.take())
+                    .send()
+                    .await?;
+                let items = res.items().unwrap_or_default();
+                page_sizes.push(items.len());
+                seen.extend(items.iter().map(|item| item["sk"].as_s().unwrap().clone()));
+                match res.last_evaluated_key() {
+                    Some(key) => exclusive_start_key = Some(key.clone()),
+                    None => break,
+                }
+            }
+
+            assert_eq!(seen, expected);
+            assert!(page_sizes.iter().all(|size| *size <= 3));
+            assert!(page_sizes.iter().any(|size| *size < 3));
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn scan_shuffle() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    // scan the same items, written in different orders, from two emulators with the same seed
+    let orders = Arc::new(Mutex::new(Vec::new()));
+    for reverse in [false, true] {
+        let router = rynamodb::router(rynamodb::RynamodbConfig {
+            scan_shuffle_seed: Some(42),
+            ..Default::default()
+        });
+        let orders = Arc::clone(&orders);
+        rynamodb::test_run_server(router, |port| {
+            Box::new(Box::pin(async move {
+                let client = test_client(port).await;
+                default_dynamodb_table("shuffled", &client).await?;
+                let mut partition_keys: Vec<String> = (0..20).map(|i| format!("{i:02}")).collect();
+                if reverse {
+                    partition_keys.reverse();
+                }
+                for pk in &partition_keys {
+                    client
+                        .put_item()
+                        .table_name("shuffled")
+                        .set_item(Some(key(pk, "a")))
+                        .send()
+                        .await?;
+                }
+
+                let res = client.scan().table_name("shuffled").send().await?;
+                let order: Vec<String> = res
+                    .items()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|item| item["pk"].as_s().unwrap().clone())
+                    .collect();
+                orders.lock().unwrap().push(order);
+                Ok(())
+            }))
+        })
+        .await
+        .unwrap();
+    }
+
+    let orders = orders.lock().unwrap();
+    assert_eq!(orders[0], orders[1]);
+    let mut sorted = orders[0].clone();
+    sorted.sort();
+    assert_eq!(sorted.len(), 20);
+    assert_ne!(sorted, orders[0]);
+}
+
+#[tokio::test]
+async fn dax_caches_reads() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        dax: Some(Default::default()),
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = &test_client(port).await;
+            let table_name = &table_name;
+            default_dynamodb_table(table_name, client).await?;
+            let put = |value: &str| {
+                client
+                    .put_item()
+                    .table_name(table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S("def".to_string()))
+                    .item("value", AttributeValue::S(value.to_string()))
+                    .send()
+            };
+            let get = |consistent: bool| async move {
+                let res = client
+                    .get_item()
+                    .table_name(table_name)
+                    .key("pk", AttributeValue::S("abc".to_string()))
+                    .key("sk", AttributeValue::S("def".to_string()))
+                    .consistent_read(consistent)
+                    .send()
+                    .await?;
+                let value = res.item().unwrap()["value"].as_s().unwrap().clone();
+                Ok::<_, aws_sdk_dynamodb::Error>(value)
+            };
+            let query = || async move {
+                let res = client
+                    .query()
+                    .table_name(table_name)
+                    .key_condition_expression("pk = :a")
+                    .expression_attribute_values(":a", AttributeValue::S("abc".to_string()))
+                    .send()
+                    .await?;
+                let value = res.items().unwrap()[0]["value"].as_s().unwrap().clone();
+                Ok::<_, aws_sdk_dynamodb::Error>(value)
+            };
+
+            put("first").await?;
+            assert_eq!(get(false).await?, "first");
+            assert_eq!(query().await?, "first");
+            put("second").await?;
+            // the written item is evicted, but the query result is stale until it expires
+            assert_eq!(get(false).await?, "second");
+            assert_eq!(get(false).await?, "second");
+            assert_eq!(query().await?, "first");
+            assert_eq!(get(true).await?, "second");
+
+            let url = format!("http://localhost:{port}/_rynamodb/dax");
+            let report: serde_json::Value = reqwest::get(&url).await?.json().await?;
+            assert_eq!(report["itemCache"]["hits"], 1);
+            assert_eq!(report["itemCache"]["misses"], 2);
+            assert_eq!(report["queryCache"]["hits"], 1);
+
+            reqwest::Client::new().delete(&url).send().await?;
+            let report: serde_json::Value = reqwest::get(&url).await?.json().await?;
+            assert_eq!(report["itemCache"]["entries"], 0);
+            assert_eq!(query().await?, "second");
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn replica_auto_scaling_settings() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+
+            let policy = AutoScalingPolicyUpdate::builder()
+                .target_tracking_scaling_policy_configuration(
+                    AutoScalingTargetTrackingScalingPolicyConfigurationUpdate::builder()
+                        .target_value(70.0)
+                        .build(),
+                )
+                .build();
+            client
+                .update_table_replica_auto_scaling()
+                .table_name(&table_name)
+                .provisioned_write_capacity_auto_scaling_update(
+                    AutoScalingSettingsUpdate::builder()
+                        .minimum_units(20)
+                        .maximum_units(100)
+                        .scaling_policy_update(policy)
+                        .build(),
+                )
+                .replica_updates(
+                    ReplicaAutoScalingUpdate::builder()
+                        .region_name("us-east-1")
+                        .replica_provisioned_read_capacity_auto_scaling_update(
+                            AutoScalingSettingsUpdate::builder()
+                                .minimum_units(1)
+                                .maximum_units(5)
+                                .build(),
+                        )
+                        .build(),
+                )
+                .send()
+                .await?;
+
+            let res = client
+                .describe_table_replica_auto_scaling()
+                .table_name(&table_name)
+                .send()
+                .await?;
+            let description = res.table_auto_scaling_description().unwrap();
+            let replicas = description.replicas().unwrap();
+            assert_eq!(replicas.len(), 1);
+            assert_eq!(replicas[0].region_name(), Some("us-east-1"));
+            let write = replicas[0]
+                .replica_provisioned_write_capacity_auto_scaling_settings()
+                .unwrap();
+            assert_eq!(write.minimum_units(), Some(20));
+            assert_eq!(write.maximum_units(), Some(100));
+            let policies = write.scaling_policies().unwrap();
+            assert_eq!(
+                policies[0].policy_name(),
+                Some(format!("DynamoDBWriteCapacityUtilization:table/{table_name}").as_str())
+            );
+
+            // the provisioned capacity is moved into the registered ranges
+            let table = client
+                .describe_table()
+                .table_name(&table_name)
+                .send()
+                .await?;
+            let throughput = table.table().unwrap().provisioned_throughput().unwrap();
+            assert_eq!(throughput.read_capacity_units(), Some(5));
+            assert_eq!(throughput.write_capacity_units(), Some(20));
+
+            let err = client
+                .update_table_replica_auto_scaling()
+                .table_name(&table_name)
+                .provisioned_write_capacity_auto_scaling_update(
+                    AutoScalingSettingsUpdate::builder()
+                        .minimum_units(200)
+                        .build(),
+                )
+                .send()
+                .await
+                .expect_err("minimum above the maximum was accepted")
+                .into_service_error();
+            assert_eq!(err.code(), Some("ValidationException"));
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn background_jobs_reported() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(rynamodb::RynamodbConfig {
+        time_to_live: rynamodb::TimeToLiveSimulation {
+            sweep_interval: Some(Duration::from_millis(10)),
+        },
+        compaction_interval: Some(Duration::from_secs(3600)),
+        ..Default::default()
+    });
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let jobs: serde_json::Value =
+                reqwest::get(format!("http://localhost:{port}/_rynamodb/jobs"))
+                    .await?
+                    .json()
+                    .await?;
+            let job = |name: &str| {
+                jobs.as_array()
+                    .unwrap()
+                    .iter()
+                    .find(|job| job["name"] == name)
+                    .cloned()
+                    .unwrap()
+            };
+            assert!(job("time-to-live-sweep")["runs"].as_u64().unwrap() > 1);
+            assert_eq!(job("compaction")["periodMs"], 3_600_000);
+            assert_eq!(job("compaction")["runs"], 1);
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+// TODO: sort the results so that they are stable
+#[tokio::test]
+#[ignore]
+async fn scan_table() {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            // add two items
+            client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("123".to_string()))
+                .item("sk", AttributeValue::S("456".to_string()))
+                .item("value", AttributeValue::S("789".to_string()))
+                .send()
+                .await
+                .wrap_err("inserting item")?;
+
+            client
+                .put_item()
+                .table_name(&table_name)
+                .item("pk", AttributeValue::S("abc".to_string()))
+                .item("sk", AttributeValue::S("def".to_string()))
+                .item("value", AttributeValue::S("ghi".to_string()))
+                .send()
+                .await
+                .wrap_err("inserting item")?;
+
+            let res = client
+                .scan()
+                .table_name(&table_name)
+                .send()
+                .await
+                .wrap_err("scanning the table")?;
+
+            let expected_items1 = {
+                let mut h = HashMap::new();
+                h.insert("pk".to_string(), AttributeValue::S("123".to_string()));
+                h.insert("sk".to_string(), AttributeValue::S("456".to_string()));
+                h.insert("value".to_string(), AttributeValue::S("789".to_string()));
+                h
+            };
+
+            let expected_items2 = {
+                let mut h = HashMap::new();
+                h.insert("pk".to_string(), AttributeValue::S("abc".to_string()));
+                h.insert("sk".to_string(), AttributeValue::S("def".to_string()));
+                h.insert("value".to_string(), AttributeValue::S("ghi".to_string()));
+                h
+            };
+
+            // TODO: stable sort
+            let expected_output = aws_sdk_dynamodb::output::ScanOutput::builder()
+                .items(expected_items1)
+                .items(expected_items2)
+                .count(2)
+                .scanned_count(2)
+                .build();
+
+            assert_eq!(res, expected_output);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[derive(PartialEq, Debug)]
+struct SortableItem {
+    name: String,
+    value: AttributeValue,
+}
+
+impl Eq for SortableItem {}
+
+impl PartialOrd for SortableItem {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.name.partial_cmp(&other.name)
+    }
+}
 
 impl Ord for SortableItem {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
@@ -689,6 +3317,133 @@ async fn get_item() {
     .unwrap();
 }
 
+#[tokio::test]
+async fn query_begins_with() {
+    test_init();
+
+    with_table(|table_name, client| {
+        Box::new(Box::pin(async move {
+            for sk in ["order#1", "order#2", "invoice#1"] {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .set_item(Some(key("abc", sk)))
+                    .send()
+                    .await?;
+            }
+
+            let res = client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                .expression_attribute_values(":pk", AttributeValue::S("abc".to_string()))
+                .expression_attribute_values(":prefix", AttributeValue::S("order#".to_string()))
+                .send()
+                .await?;
+            let sort_keys: Vec<_> = res
+                .items()
+                .unwrap_or_default()
+                .iter()
+                .map(|item| item["sk"].as_s().unwrap().as_str())
+                .collect();
+            assert_eq!(sort_keys, ["order#1", "order#2"]);
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn numeric_keys() -> Result<()> {
+    test_init();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            let mut request = client.create_table().table_name(&table_name);
+            for (name, key_type) in [("pk", KeyType::Hash), ("sk", KeyType::Range)] {
+                request = request
+                    .attribute_definitions(
+                        AttributeDefinition::builder()
+                            .attribute_name(name)
+                            .attribute_type(ScalarAttributeType::N)
+                            .build(),
+                    )
+                    .key_schema(
+                        KeySchemaElement::builder()
+                            .attribute_name(name)
+                            .key_type(key_type)
+                            .build(),
+                    );
+            }
+            request
+                .provisioned_throughput(
+                    ProvisionedThroughput::builder()
+                        .read_capacity_units(10)
+                        .write_capacity_units(10)
+                        .build(),
+                )
+                .send()
+                .await?;
+            wait_for_table_creation(&table_name, &client).await?;
+
+            let n = |n: &str| AttributeValue::N(n.to_string());
+            for sk in [
+                "2",
+                "10",
+                "-3",
+                "12345678901234567890",
+                "12345678901234567891",
+            ] {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", n("1"))
+                    .item("sk", n(sk))
+                    .send()
+                    .await?;
+            }
+
+            // numbers are looked up by value
+            let item = client
+                .get_item()
+                .table_name(&table_name)
+                .key("pk", n("1.0"))
+                .key("sk", n("1E1"))
+                .send()
+                .await?
+                .item
+                .expect("item should be found by an equal number");
+            assert_eq!(item["sk"], n("10"));
+
+            // and sorted numerically rather than as strings
+            let items = client
+                .query()
+                .table_name(&table_name)
+                .key_condition_expression("pk = :pk AND sk < :sk")
+                .expression_attribute_values(":pk", n("1"))
+                .expression_attribute_values(":sk", n("12345678901234567891"))
+                .send()
+                .await?
+                .items
+                .unwrap_or_default();
+            let sort_keys: Vec<_> = items.iter().map(|item| item["sk"].clone()).collect();
+            assert_eq!(
+                sort_keys,
+                [n("-3"), n("2"), n("10"), n("12345678901234567890")]
+            );
+
+            client.delete_table().table_name(&table_name).send().await?;
+            Ok(())
+        }))
+    })
+    .await
+}
+
 // tables
 
 // test describing a non-existent table
@@ -696,7 +3451,7 @@ async fn get_item() {
 async fn describe_nonexistent_table() {
     test_init();
 
-    let router = rynamodb::router();
+    let router = rynamodb::router(Default::default());
     rynamodb::test_run_server(router, |port| {
         Box::new(Box::pin(async move {
             let client = test_client(port).await;
@@ -784,3 +3539,173 @@ async fn create_client(endpoint_url: Option<&str>) -> aws_sdk_dynamodb::Client {
         }
     }
 }
+
+#[tokio::test]
+async fn test_id_echoed() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = reqwest::Client::new();
+            let call = |operation: &str| {
+                client
+                    .post(format!("http://localhost:{port}/"))
+                    .header("x-amz-target", format!("DynamoDB_20120810.{operation}"))
+                    .header("content-type", "application/x-amz-json-1.0")
+                    .header("x-rynamodb-test-id", "suite/case-1")
+                    .body("{}")
+                    .send()
+            };
+
+            let response = call("ListTables").await?;
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()["x-rynamodb-test-id"], "suite/case-1");
+
+            // also echoed when the operation cannot be handled
+            let response = call("NotAnOperation").await?;
+            assert_eq!(response.status(), 400);
+            assert_eq!(response.headers()["x-rynamodb-test-id"], "suite/case-1");
+
+            let response = client
+                .post(format!("http://localhost:{port}/"))
+                .header("x-amz-target", "DynamoDB_20120810.ListTables")
+                .body("{}")
+                .send()
+                .await?;
+            assert!(response.headers().get("x-rynamodb-test-id").is_none());
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn table_events() {
+    test_init();
+
+    skip_aws_cloud!();
+
+    let (router, tables) = rynamodb::Rynamodb::builder().build();
+    let mut events = tables.subscribe();
+    rynamodb::test_run_server(router, |port| {
+        let table_name = format!("table-{}", uuid::Uuid::new_v4());
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table(&table_name, &client).await?;
+            for value in ["first", "second"] {
+                client
+                    .put_item()
+                    .table_name(&table_name)
+                    .item("pk", AttributeValue::S("abc".to_string()))
+                    .item("sk", AttributeValue::S("def".to_string()))
+                    .item("value", AttributeValue::S(value.to_string()))
+                    .send()
+                    .await?;
+            }
+            client
+                .batch_write_item()
+                .request_items(&table_name, vec![put_request("abc", "ghi")])
+                .send()
+                .await?;
+            client.delete_table().table_name(&table_name).send().await?;
+
+            let s = |s: &str| serde_dynamo::AttributeValue::S(s.to_string());
+            let item = |sk: &str, value: Option<&str>| {
+                let mut item: HashMap<String, serde_dynamo::AttributeValue> =
+                    [("pk".to_string(), s("abc")), ("sk".to_string(), s(sk))].into();
+                if let Some(value) = value {
+                    item.insert("value".to_string(), s(value));
+                }
+                item
+            };
+            let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+            assert_eq!(
+                received,
+                vec![
+                    rynamodb::TableEvent::TableCreated {
+                        table_name: table_name.clone(),
+                        region: rynamodb::Region::UsEast1,
+                    },
+                    rynamodb::TableEvent::ItemPut {
+                        table_name: table_name.clone(),
+                        item: item("def", Some("first")),
+                        old_item: None,
+                    },
+                    rynamodb::TableEvent::ItemPut {
+                        table_name: table_name.clone(),
+                        item: item("def", Some("second")),
+                        old_item: Some(item("def", Some("first"))),
+                    },
+                    rynamodb::TableEvent::ItemPut {
+                        table_name: table_name.clone(),
+                        item: item("ghi", None),
+                        old_item: None,
+                    },
+                    rynamodb::TableEvent::TableDeleted {
+                        table_name: table_name.clone(),
+                        region: rynamodb::Region::UsEast1,
+                    },
+                ]
+            );
+
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn fixed_clock_and_ids() {
+    use chrono::TimeZone;
+
+    test_init();
+
+    skip_aws_cloud!();
+
+    let created_at = chrono::Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    let clock = Arc::new(rynamodb::FixedClock::new(created_at));
+    let (router, tables) = rynamodb::Rynamodb::builder()
+        .clock(Arc::clone(&clock) as _)
+        .id_provider(Arc::new(rynamodb::SequentialIds::default()))
+        .build();
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = test_client(port).await;
+            default_dynamodb_table("first", &client).await?;
+            clock.advance(chrono::Duration::hours(1));
+            default_dynamodb_table("second", &client).await?;
+
+            for (table_name, created_at) in [
+                ("first", created_at),
+                ("second", created_at + chrono::Duration::hours(1)),
+            ] {
+                let res = client
+                    .describe_table()
+                    .table_name(table_name)
+                    .send()
+                    .await?;
+                let table = res.table().unwrap();
+                assert_eq!(
+                    table.creation_date_time().map(|at| at.secs()),
+                    Some(created_at.timestamp())
+                );
+                // request ids are drawn from the same sequence as table ids
+                let table_id = table.table_id().unwrap();
+                assert!(table_id.starts_with("00000000-0000-0000-0000-"));
+                assert_eq!(
+                    tables.with_table(table_name, |table| table.table_id.clone()),
+                    Some(table_id.to_string())
+                );
+            }
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,112 @@
+//! Wire format tests for the `awsJson1_0` protocol
+//!
+//! The test vectors in `protocol/awsjson1_0.json` follow the layout of the Smithy protocol
+//! compliance tests: each case is a raw HTTP request and the response expected from the server.
+//! They are sent directly to the router without an AWS SDK, so that changes to the wire format
+//! are caught even if the SDK would tolerate them.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+static TEST_VECTORS: &str = include_str!("protocol/awsjson1_0.json");
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestCase {
+    id: String,
+    documentation: String,
+    request: TestRequest,
+    response: TestResponse,
+}
+
+#[derive(Deserialize, Debug)]
+struct TestRequest {
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TestResponse {
+    code: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// headers which must be present, with any value
+    #[serde(default)]
+    require_headers: Vec<String>,
+    body: serde_json::Value,
+}
+
+async fn run_case(client: &reqwest::Client, port: u16, case: &TestCase) -> Result<(), String> {
+    let mut request = client
+        .post(format!("http://127.0.0.1:{port}/"))
+        .body(case.request.body.clone());
+    for (name, value) in &case.request.headers {
+        request = request.header(name, value);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("sending request: {e}"))?;
+
+    if response.status().as_u16() != case.response.code {
+        return Err(format!(
+            "expected status {}, got {}",
+            case.response.code,
+            response.status()
+        ));
+    }
+
+    // header names are case insensitive
+    for (name, expected) in &case.response.headers {
+        let value = response.headers().get(name).and_then(|v| v.to_str().ok());
+        if value != Some(expected.as_str()) {
+            return Err(format!("expected header {name}: {expected}, got {value:?}"));
+        }
+    }
+    for name in &case.response.require_headers {
+        if !response.headers().contains_key(name) {
+            return Err(format!("missing required header {name}"));
+        }
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("response body is not JSON: {e}"))?;
+    if body != case.response.body {
+        return Err(format!("expected body {}, got {body}", case.response.body));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn aws_json_1_0_protocol() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let cases: Vec<TestCase> =
+        serde_json::from_str(TEST_VECTORS).expect("parsing protocol test vectors");
+
+    let router = rynamodb::router(Default::default());
+    rynamodb::test_run_server(router, |port| {
+        Box::new(Box::pin(async move {
+            let client = reqwest::Client::new();
+            let mut failures = Vec::new();
+            for case in &cases {
+                tracing::debug!(id = %case.id, documentation = %case.documentation, "running protocol test");
+                if let Err(e) = run_case(&client, port, case).await {
+                    failures.push(format!("{}: {e}", case.id));
+                }
+            }
+
+            if !failures.is_empty() {
+                eyre::bail!("protocol tests failed:\n{}", failures.join("\n"));
+            }
+            Ok(())
+        }))
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,10 @@
+module github.com/simonrw/rynamodb/sdk-tests/go
+
+go 1.21
+
+require (
+	github.com/aws/aws-sdk-go-v2 v1.24.0
+	github.com/aws/aws-sdk-go-v2/config v1.26.1
+	github.com/aws/aws-sdk-go-v2/service/dynamodb v1.26.6
+	github.com/google/uuid v1.5.0
+)
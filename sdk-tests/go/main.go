@@ -0,0 +1,124 @@
+// Round trip of every attribute type through aws-sdk-go-v2, see sdk-tests/README.md
+package main
+
+import (
+	"context"
+	"fmt"
+	"os"
+	"reflect"
+	"slices"
+	"time"
+
+	"github.com/aws/aws-sdk-go-v2/aws"
+	"github.com/aws/aws-sdk-go-v2/config"
+	"github.com/aws/aws-sdk-go-v2/service/dynamodb"
+	"github.com/aws/aws-sdk-go-v2/service/dynamodb/types"
+	"github.com/google/uuid"
+)
+
+type item = map[string]types.AttributeValue
+
+func check(condition bool, format string, args ...any) {
+	if !condition {
+		fmt.Fprintf(os.Stderr, "FAIL: "+format+"\n", args...)
+		os.Exit(1)
+	}
+}
+
+func must[T any](value T, err error) T {
+	check(err == nil, "%v", err)
+	return value
+}
+
+func key(sk string) item {
+	return item{
+		"pk": &types.AttributeValueMemberS{Value: "partition"},
+		"sk": &types.AttributeValueMemberN{Value: sk},
+	}
+}
+
+func main() {
+	ctx := context.Background()
+	cfg := must(config.LoadDefaultConfig(ctx))
+	client := dynamodb.NewFromConfig(cfg, func(o *dynamodb.Options) {
+		o.BaseEndpoint = aws.String(os.Getenv("ENDPOINT_URL"))
+	})
+	tableName := "sdk-matrix-go-" + uuid.NewString()
+
+	must(client.CreateTable(ctx, &dynamodb.CreateTableInput{
+		TableName: aws.String(tableName),
+		AttributeDefinitions: []types.AttributeDefinition{
+			{AttributeName: aws.String("pk"), AttributeType: types.ScalarAttributeTypeS},
+			{AttributeName: aws.String("sk"), AttributeType: types.ScalarAttributeTypeN},
+		},
+		KeySchema: []types.KeySchemaElement{
+			{AttributeName: aws.String("pk"), KeyType: types.KeyTypeHash},
+			{AttributeName: aws.String("sk"), KeyType: types.KeyTypeRange},
+		},
+		BillingMode: types.BillingModePayPerRequest,
+	}))
+	waiter := dynamodb.NewTableExistsWaiter(client)
+	check(waiter.Wait(ctx, &dynamodb.DescribeTableInput{TableName: aws.String(tableName)}, 30*time.Second) == nil, "table was not created")
+	tables := must(client.ListTables(ctx, &dynamodb.ListTablesInput{}))
+	check(slices.Contains(tables.TableNames, tableName), "table is not listed")
+
+	putItem := key("1")
+	putItem["string"] = &types.AttributeValueMemberS{Value: "value"}
+	putItem["number"] = &types.AttributeValueMemberN{Value: "1.50"}
+	putItem["binary"] = &types.AttributeValueMemberB{Value: []byte("\x00\x01binary")}
+	putItem["yes"] = &types.AttributeValueMemberBOOL{Value: true}
+	putItem["no"] = &types.AttributeValueMemberBOOL{Value: false}
+	putItem["nothing"] = &types.AttributeValueMemberNULL{Value: true}
+	putItem["strings"] = &types.AttributeValueMemberSS{Value: []string{"a"}}
+	putItem["numbers"] = &types.AttributeValueMemberNS{Value: []string{"2"}}
+	putItem["binaries"] = &types.AttributeValueMemberBS{Value: [][]byte{{0xff}}}
+	putItem["list"] = &types.AttributeValueMemberL{Value: []types.AttributeValue{
+		&types.AttributeValueMemberS{Value: "a"},
+		&types.AttributeValueMemberN{Value: "2"},
+		&types.AttributeValueMemberBOOL{Value: false},
+	}}
+	putItem["map"] = &types.AttributeValueMemberM{Value: item{
+		"nested": &types.AttributeValueMemberM{Value: item{
+			"n": &types.AttributeValueMemberN{Value: "-3"},
+		}},
+	}}
+	must(client.PutItem(ctx, &dynamodb.PutItemInput{TableName: aws.String(tableName), Item: putItem}))
+
+	// numbers are returned in canonical form
+	expected := item{}
+	for name, value := range putItem {
+		expected[name] = value
+	}
+	expected["number"] = &types.AttributeValueMemberN{Value: "1.5"}
+	got := must(client.GetItem(ctx, &dynamodb.GetItemInput{TableName: aws.String(tableName), Key: key("1")}))
+	check(reflect.DeepEqual(got.Item, expected), "get item returned %#v", got.Item)
+
+	query := must(client.Query(ctx, &dynamodb.QueryInput{
+		TableName:              aws.String(tableName),
+		KeyConditionExpression: aws.String("pk = :pk AND sk > :sk"),
+		ExpressionAttributeValues: item{
+			":pk": &types.AttributeValueMemberS{Value: "partition"},
+			":sk": &types.AttributeValueMemberN{Value: "0"},
+		},
+	}))
+	check(query.Count == 1, "query returned %d items", query.Count)
+
+	must(client.BatchWriteItem(ctx, &dynamodb.BatchWriteItemInput{
+		RequestItems: map[string][]types.WriteRequest{tableName: {
+			{PutRequest: &types.PutRequest{Item: key("2")}},
+			{PutRequest: &types.PutRequest{Item: key("3")}},
+		}},
+	}))
+	batch := must(client.BatchGetItem(ctx, &dynamodb.BatchGetItemInput{
+		RequestItems: map[string]types.KeysAndAttributes{tableName: {
+			Keys: []item{key("1"), key("2"), key("3")},
+		}},
+	}))
+	check(len(batch.Responses[tableName]) == 3, "batch get did not return every item")
+
+	scan := must(client.Scan(ctx, &dynamodb.ScanInput{TableName: aws.String(tableName)}))
+	check(scan.Count == 3, "scan returned %d items", scan.Count)
+
+	must(client.DeleteTable(ctx, &dynamodb.DeleteTableInput{TableName: aws.String(tableName)}))
+	fmt.Println("ok")
+}
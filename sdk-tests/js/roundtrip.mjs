@@ -0,0 +1,94 @@
+// Round trip of every attribute type through aws-sdk-js v3, see sdk-tests/README.md
+
+import { randomUUID } from "node:crypto";
+import { isDeepStrictEqual } from "node:util";
+import {
+  BatchGetItemCommand,
+  BatchWriteItemCommand,
+  CreateTableCommand,
+  DeleteTableCommand,
+  DynamoDBClient,
+  GetItemCommand,
+  ListTablesCommand,
+  PutItemCommand,
+  QueryCommand,
+  ScanCommand,
+  waitUntilTableExists,
+} from "@aws-sdk/client-dynamodb";
+
+const client = new DynamoDBClient({ endpoint: process.env.ENDPOINT_URL });
+const tableName = `sdk-matrix-js-${randomUUID()}`;
+
+const item = {
+  pk: { S: "partition" },
+  sk: { N: "1" },
+  string: { S: "value" },
+  number: { N: "1.50" },
+  binary: { B: new Uint8Array([0, 1, 98, 105, 110, 97, 114, 121]) },
+  yes: { BOOL: true },
+  no: { BOOL: false },
+  nothing: { NULL: true },
+  strings: { SS: ["a"] },
+  numbers: { NS: ["2"] },
+  binaries: { BS: [new Uint8Array([255])] },
+  list: { L: [{ S: "a" }, { N: "2" }, { BOOL: false }] },
+  map: { M: { nested: { M: { n: { N: "-3" } } } } },
+};
+// numbers are returned in canonical form
+const expected = { ...item, number: { N: "1.5" } };
+
+function check(condition, message) {
+  if (!condition) {
+    console.error(`FAIL: ${message}`);
+    process.exit(1);
+  }
+}
+
+const key = (sk) => ({ pk: { S: "partition" }, sk: { N: String(sk) } });
+
+await client.send(
+  new CreateTableCommand({
+    TableName: tableName,
+    AttributeDefinitions: [
+      { AttributeName: "pk", AttributeType: "S" },
+      { AttributeName: "sk", AttributeType: "N" },
+    ],
+    KeySchema: [
+      { AttributeName: "pk", KeyType: "HASH" },
+      { AttributeName: "sk", KeyType: "RANGE" },
+    ],
+    BillingMode: "PAY_PER_REQUEST",
+  }),
+);
+await waitUntilTableExists({ client, maxWaitTime: 30 }, { TableName: tableName });
+const tables = await client.send(new ListTablesCommand({}));
+check(tables.TableNames.includes(tableName), "table is not listed");
+
+await client.send(new PutItemCommand({ TableName: tableName, Item: item }));
+const got = await client.send(new GetItemCommand({ TableName: tableName, Key: key(1) }));
+check(isDeepStrictEqual(got.Item, expected), `get item returned ${JSON.stringify(got.Item)}`);
+
+const query = await client.send(
+  new QueryCommand({
+    TableName: tableName,
+    KeyConditionExpression: "pk = :pk AND sk > :sk",
+    ExpressionAttributeValues: { ":pk": { S: "partition" }, ":sk": { N: "0" } },
+  }),
+);
+check(query.Count === 1, `query returned ${query.Count} items`);
+
+await client.send(
+  new BatchWriteItemCommand({
+    RequestItems: { [tableName]: [2, 3].map((sk) => ({ PutRequest: { Item: key(sk) } })) },
+  }),
+);
+const batch = await client.send(
+  new BatchGetItemCommand({ RequestItems: { [tableName]: { Keys: [1, 2, 3].map(key) } } }),
+);
+check(batch.Responses[tableName].length === 3, "batch get did not return every item");
+
+const scan = await client.send(new ScanCommand({ TableName: tableName }));
+check(scan.Count === 3, `scan returned ${scan.Count} items`);
+
+await client.send(new DeleteTableCommand({ TableName: tableName }));
+console.log("ok");
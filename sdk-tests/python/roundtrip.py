@@ -0,0 +1,82 @@
+"""Round trip of every attribute type through boto3, see sdk-tests/README.md"""
+
+import os
+import uuid
+
+import boto3
+
+client = boto3.client("dynamodb", endpoint_url=os.environ["ENDPOINT_URL"])
+table_name = f"sdk-matrix-boto3-{uuid.uuid4()}"
+
+ITEM = {
+    "pk": {"S": "partition"},
+    "sk": {"N": "1"},
+    "string": {"S": "value"},
+    "number": {"N": "1.50"},
+    "binary": {"B": b"\x00\x01binary"},
+    "yes": {"BOOL": True},
+    "no": {"BOOL": False},
+    "nothing": {"NULL": True},
+    "strings": {"SS": ["a"]},
+    "numbers": {"NS": ["2"]},
+    "binaries": {"BS": [b"\xff"]},
+    "list": {"L": [{"S": "a"}, {"N": "2"}, {"BOOL": False}]},
+    "map": {"M": {"nested": {"M": {"n": {"N": "-3"}}}}},
+}
+# numbers are returned in canonical form
+EXPECTED = {**ITEM, "number": {"N": "1.5"}}
+
+
+def check(condition, message):
+    if not condition:
+        raise SystemExit(f"FAIL: {message}")
+
+
+client.create_table(
+    TableName=table_name,
+    AttributeDefinitions=[
+        {"AttributeName": "pk", "AttributeType": "S"},
+        {"AttributeName": "sk", "AttributeType": "N"},
+    ],
+    KeySchema=[
+        {"AttributeName": "pk", "KeyType": "HASH"},
+        {"AttributeName": "sk", "KeyType": "RANGE"},
+    ],
+    BillingMode="PAY_PER_REQUEST",
+)
+client.get_waiter("table_exists").wait(TableName=table_name)
+check(table_name in client.list_tables()["TableNames"], "table is not listed")
+
+client.put_item(TableName=table_name, Item=ITEM)
+item = client.get_item(TableName=table_name, Key={"pk": ITEM["pk"], "sk": ITEM["sk"]})["Item"]
+check(item == EXPECTED, f"get item returned {item}")
+
+query = client.query(
+    TableName=table_name,
+    KeyConditionExpression="pk = :pk AND sk > :sk",
+    ExpressionAttributeValues={":pk": {"S": "partition"}, ":sk": {"N": "0"}},
+)
+check(query["Count"] == 1, f"query returned {query['Count']} items")
+
+client.batch_write_item(
+    RequestItems={
+        table_name: [
+            {"PutRequest": {"Item": {"pk": {"S": "partition"}, "sk": {"N": str(sk)}}}}
+            for sk in (2, 3)
+        ]
+    }
+)
+batch = client.batch_get_item(
+    RequestItems={
+        table_name: {
+            "Keys": [{"pk": {"S": "partition"}, "sk": {"N": str(sk)}} for sk in (1, 2, 3)]
+        }
+    }
+)
+check(len(batch["Responses"][table_name]) == 3, "batch get did not return every item")
+
+scan = client.scan(TableName=table_name)
+check(scan["Count"] == 3, f"scan returned {scan['Count']} items")
+
+client.delete_table(TableName=table_name)
+print("ok")
@@ -0,0 +1,208 @@
+//! Development tasks, run with `cargo xtask <task>`
+//!
+//! - `sdk-matrix`: run the canned scripts in `sdk-tests/` with the AWS SDKs for other languages
+//!   against rynamodb, each in its own docker container, and report which SDKs are compatible
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitCode, Stdio},
+    time::{Duration, Instant},
+};
+
+/// port the server is started on, unless an endpoint is given
+const DEFAULT_PORT: u16 = 3051;
+
+/// longest time to wait for the server to start
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Sdk {
+    name: &'static str,
+    /// directory in `sdk-tests/` holding the script
+    directory: &'static str,
+    image: &'static str,
+    command: &'static str,
+}
+
+const SDKS: &[Sdk] = &[
+    Sdk {
+        name: "boto3",
+        directory: "python",
+        image: "python:3.11-slim",
+        command: "pip install --quiet --disable-pip-version-check boto3 && python roundtrip.py",
+    },
+    Sdk {
+        name: "aws-sdk-js",
+        directory: "js",
+        image: "node:20-slim",
+        command: "npm install --silent --no-audit --no-fund && node roundtrip.mjs",
+    },
+    Sdk {
+        name: "aws-sdk-go",
+        directory: "go",
+        image: "golang:1.21",
+        command: "go mod tidy && go run .",
+    },
+];
+
+const USAGE: &str = "\
+usage: cargo xtask sdk-matrix [--sdk NAME]... [--endpoint URL]
+
+    --sdk NAME       only run the scripts of this SDK: boto3, aws-sdk-js or aws-sdk-go
+    --endpoint URL   test an emulator which is already running, rather than building and
+                     starting one";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let result = match args.next().as_deref() {
+        Some("sdk-matrix") => parse_matrix_args(args).and_then(sdk_matrix),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MatrixArgs {
+    sdks: Vec<String>,
+    endpoint: Option<String>,
+}
+
+fn parse_matrix_args(mut args: impl Iterator<Item = String>) -> Result<MatrixArgs, String> {
+    let mut parsed = MatrixArgs::default();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("{arg} needs a value\n\n{USAGE}"))
+        };
+        match arg.as_str() {
+            "--sdk" => parsed.sdks.push(value()?),
+            "--endpoint" => parsed.endpoint = Some(value()?),
+            _ => return Err(format!("unknown argument {arg}\n\n{USAGE}")),
+        }
+    }
+    if let Some(unknown) = parsed
+        .sdks
+        .iter()
+        .find(|name| !SDKS.iter().any(|sdk| sdk.name == name.as_str()))
+    {
+        return Err(format!("unknown SDK {unknown}\n\n{USAGE}"));
+    }
+    Ok(parsed)
+}
+
+/// Run the SDK scripts, returning whether they all passed
+fn sdk_matrix(args: MatrixArgs) -> Result<bool, String> {
+    let root = workspace_root();
+    let (endpoint, server) = match args.endpoint {
+        Some(endpoint) => (endpoint, None),
+        None => {
+            let server = Server::start(&root, DEFAULT_PORT)?;
+            (format!("http://127.0.0.1:{DEFAULT_PORT}"), Some(server))
+        }
+    };
+
+    let mut results = Vec::new();
+    for sdk in SDKS
+        .iter()
+        .filter(|sdk| args.sdks.is_empty() || args.sdks.iter().any(|name| name == sdk.name))
+    {
+        eprintln!("running {} scripts", sdk.name);
+        let start = Instant::now();
+        let output = Command::new("docker")
+            .args(["run", "--rm", "--network", "host"])
+            .args(["-e", &format!("ENDPOINT_URL={endpoint}")])
+            .args(["-e", "AWS_ACCESS_KEY_ID=test"])
+            .args(["-e", "AWS_SECRET_ACCESS_KEY=test"])
+            .args(["-e", "AWS_REGION=us-east-1"])
+            .args(["-e", "AWS_DEFAULT_REGION=us-east-1"])
+            .arg("-v")
+            .arg(format!(
+                "{}:/work",
+                root.join("sdk-tests").join(sdk.directory).display()
+            ))
+            .args(["-w", "/work", sdk.image, "sh", "-c", sdk.command])
+            .output()
+            .map_err(|e| format!("could not run docker: {e}"))?;
+        let passed = output.status.success();
+        if !passed {
+            eprintln!("{} failed:", sdk.name);
+            eprintln!("{}", String::from_utf8_lossy(&output.stdout));
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+        results.push((sdk.name, passed, start.elapsed()));
+    }
+    drop(server);
+
+    println!("| SDK | result | duration |");
+    println!("| --- | --- | --- |");
+    for (name, passed, duration) in &results {
+        let result = if *passed { "pass" } else { "FAIL" };
+        println!("| {name} | {result} | {:.1}s |", duration.as_secs_f64());
+    }
+    Ok(results.iter().all(|(_, passed, _)| *passed))
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in the workspace")
+        .to_path_buf()
+}
+
+/// Emulator started for the tests, stopped when dropped
+struct Server(Child);
+
+impl Server {
+    fn start(root: &Path, port: u16) -> Result<Self, String> {
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--package", "rynamodb"])
+            .current_dir(root)
+            .status()
+            .map_err(|e| format!("could not build rynamodb: {e}"))?;
+        if !status.success() {
+            return Err("could not build rynamodb".to_string());
+        }
+
+        let child = Command::new(root.join("target").join("debug").join("rynamodb"))
+            .args(["--port", &port.to_string()])
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start rynamodb: {e}"))?;
+        let server = Self(child);
+
+        let start = Instant::now();
+        while !is_healthy(port) {
+            if start.elapsed() > STARTUP_TIMEOUT {
+                return Err("rynamodb did not start".to_string());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        Ok(server)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn is_healthy(port: u16) -> bool {
+    let Ok(mut stream) = TcpStream::connect(("127.0.0.1", port)) else {
+        return false;
+    };
+    let request = "GET /_health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let mut response = String::new();
+    stream.write_all(request.as_bytes()).is_ok()
+        && stream.read_to_string(&mut response).is_ok()
+        && response.starts_with("HTTP/1.1 200")
+}